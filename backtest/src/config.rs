@@ -0,0 +1,87 @@
+use serde::Deserialize;
+
+use drift_vault::state::VaultState;
+
+// synthetic market + vault params (vault params mean the same as in VaultState)
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct BacktestConfig {
+    pub market_index: u64,
+    pub initial_collateral: f64, // USDC 
+
+    // synthetic AMM 
+    pub sqrt_k: f64, // base asset 
+    pub funding_period: i64,
+    pub fee_numerator: u128,
+    pub fee_denominator: u128,
+    // fees the clearing house can pay funding from (funding is capped past it)
+    pub fee_pool: f64, // USDC 
+    // net base asset position of the other traders (funding imbalance)
+    pub other_base_asset_amount: f64,
+    // the clearing house's oracle validity guard rail (price / twap > ratio => too volatile)
+    pub too_volatile_ratio: i128,
+
+    // vault 
+    pub rebalance_interval: i64,
+    pub funding_window: i64,
+    pub min_leverage: u128,
+    pub max_leverage: u128,
+    pub max_volatility: u128,
+    pub funding_history_length: u64,
+    pub funding_history_decay: u128,
+    pub funding_history_full_size_rate: u128,
+    pub max_market_share: u128,
+    pub idle_buffer_numerator: u128,
+    pub idle_buffer_denominator: u128,
+    pub max_twap_staleness: i64,
+    pub flatten_on_funding_pause: bool,
+}
+
+impl Default for BacktestConfig {
+    fn default() -> Self {
+        BacktestConfig {
+            market_index: 0,
+            initial_collateral: 10_000.,
+            sqrt_k: 1_000_000.,
+            funding_period: 3600,
+            fee_numerator: 10,
+            fee_denominator: 10_000,
+            fee_pool: 100_000.,
+            other_base_asset_amount: 0.,
+            too_volatile_ratio: 5,
+            rebalance_interval: 3600,
+            funding_window: 0,
+            min_leverage: 0,
+            max_leverage: 0,
+            max_volatility: 0,
+            funding_history_length: 0,
+            funding_history_decay: 0,
+            funding_history_full_size_rate: 0,
+            max_market_share: 0,
+            idle_buffer_numerator: 0,
+            idle_buffer_denominator: 0,
+            max_twap_staleness: 0,
+            flatten_on_funding_pause: false,
+        }
+    }
+}
+
+impl BacktestConfig {
+    pub fn vault_state(&self) -> VaultState {
+        VaultState {
+            funding_window: self.funding_window,
+            min_leverage: self.min_leverage,
+            max_leverage: self.max_leverage,
+            max_volatility: self.max_volatility,
+            funding_history_length: self.funding_history_length,
+            funding_history_decay: self.funding_history_decay,
+            funding_history_full_size_rate: self.funding_history_full_size_rate,
+            max_market_share: self.max_market_share,
+            idle_buffer_numerator: self.idle_buffer_numerator,
+            idle_buffer_denominator: self.idle_buffer_denominator,
+            max_twap_staleness: self.max_twap_staleness,
+            flatten_on_funding_pause: self.flatten_on_funding_pause,
+            ..VaultState::default()
+        }
+    }
+}
@@ -0,0 +1,441 @@
+use std::cmp::max;
+
+use clearing_house::state::market::{Markets, OraclePriceData};
+use clearing_house::state::state::{FeeStructure, ValidityGuardRails};
+use clearing_house::state::user::{User, UserPositions};
+use clearing_house::state::history::funding_rate::FundingRateRecord;
+use clearing_house::controller::amm::move_to_price;
+use clearing_house::controller::position::{self, PositionDirection};
+use clearing_house::math::amm::{update_mark_twap, update_oracle_price_twap};
+use clearing_house::math::collateral::calculate_updated_collateral;
+use clearing_house::math::fees::calculate_fee_for_trade;
+use clearing_house::math::funding::{calculate_funding_payment, calculate_funding_rate_long_short};
+use clearing_house::math::position::calculate_base_asset_value_and_pnl;
+use clearing_house::math::casting::cast_to_i128;
+use clearing_house::math::constants::{
+    AMM_RESERVE_PRECISION, AMM_TO_QUOTE_PRECISION_RATIO_I128, MARK_PRICE_PRECISION,
+    MARK_PRICE_TIMES_AMM_TO_QUOTE_PRECISION_RATIO, PEG_PRECISION,
+};
+
+use drift_vault::state::{VaultState, Position};
+use drift_vault::funding::calculate_next_funding_ts;
+use drift_vault::instructions::calculate_idle_buffer_target;
+use drift_vault::strategy::{
+    get_strategy, get_stale_market_indexes, calculate_trades, calculate_rebalance_targets,
+    calculate_collateral_liabilities, StrategySnapshot,
+};
+use drift_vault::twap::{calculate_fresh_twaps, validate_twap_staleness};
+use drift_vault_client::calculate_predicted_funding_rate;
+
+use crate::config::BacktestConfig;
+use crate::error::BacktestError;
+use crate::report::{BacktestReport, EquityPoint, quote_to_usd, usd_to_quote};
+use crate::series::PriceRow;
+
+// the vault's drift user trading against a synthetic market which
+// follows the series (mark price is moved to the row's mark price)
+pub struct Backtest {
+    config: BacktestConfig,
+    vault_state: VaultState,
+    fee_structure: FeeStructure,
+    markets: Box<Markets>,
+    user: User,
+    user_positions: Box<UserPositions>,
+    // the vault ATA (idle buffer)
+    idle_amount: u128,
+    // newest first
+    funding_rate_records: Vec<FundingRateRecord>,
+    last_rebalance_ts: Option<i64>,
+
+    funding_pnl: i128,
+    fees: u128,
+    slippage: u128,
+    traded_notional: u128,
+    trades: u64,
+    funding_updates: u64,
+    stale_twap_holds: u64,
+    funding_pause_freezes: u64,
+    peak_equity: i128,
+    max_drawdown: i128,
+    max_drawdown_pct: f64,
+    equity_curve: Vec<EquityPoint>,
+}
+
+pub fn run_backtest(
+    config: &BacktestConfig,
+    rows: &[PriceRow],
+) -> Result<BacktestReport, BacktestError> {
+    let first_row = rows.first().ok_or_else(|| BacktestError::InvalidSeries("no rows".to_string()))?;
+    let mut backtest = Backtest::new(config, first_row)?;
+    for row in rows.iter() {
+        backtest.step(row)?;
+    }
+    backtest.report()
+}
+
+fn price_to_mark_precision(price: f64) -> u128 {
+    (price * MARK_PRICE_PRECISION as f64) as u128
+}
+
+impl Backtest {
+    pub fn new(
+        config: &BacktestConfig,
+        first_row: &PriceRow,
+    ) -> Result<Self, BacktestError> {
+        let market_index = config.market_index;
+        let mark_price = price_to_mark_precision(first_row.mark_price);
+        let oracle_price = cast_to_i128(price_to_mark_precision(first_row.oracle_price))?;
+
+        let mut markets = Box::new(Markets::default());
+        let market = markets.get_market_mut(market_index);
+        market.initialized = true;
+        market.base_asset_amount = (config.other_base_asset_amount * AMM_RESERVE_PRECISION as f64) as i128;
+        market.base_asset_amount_long = market.base_asset_amount.max(0);
+        market.base_asset_amount_short = market.base_asset_amount.min(0);
+        let amm = &mut market.amm;
+        let sqrt_k = (config.sqrt_k * AMM_RESERVE_PRECISION as f64) as u128;
+        amm.sqrt_k = sqrt_k;
+        amm.base_asset_reserve = sqrt_k;
+        amm.quote_asset_reserve = sqrt_k;
+        amm.peg_multiplier = max(1, (first_row.mark_price * PEG_PRECISION as f64) as u128);
+        amm.funding_period = config.funding_period;
+        amm.last_funding_rate_ts = first_row.ts;
+        amm.last_mark_price_twap = mark_price;
+        amm.last_mark_price_twap_ts = first_row.ts;
+        amm.last_oracle_price_twap = oracle_price;
+        amm.last_oracle_price_twap_ts = first_row.ts;
+        amm.last_oracle_price = oracle_price;
+        amm.total_fee_minus_distributions = usd_to_quote(config.fee_pool);
+        move_to_price(amm, mark_price)?;
+
+        let user = User {
+            collateral: usd_to_quote(config.initial_collateral),
+            ..User::default()
+        };
+        let mut user_positions = Box::new(UserPositions::default());
+        user_positions.positions[0].market_index = market_index;
+
+        let initial_equity = cast_to_i128(user.collateral)?;
+        Ok(Backtest {
+            config: config.clone(),
+            vault_state: config.vault_state(),
+            fee_structure: FeeStructure {
+                fee_numerator: config.fee_numerator,
+                fee_denominator: config.fee_denominator,
+                ..FeeStructure::default()
+            },
+            markets,
+            user,
+            user_positions,
+            idle_amount: 0,
+            funding_rate_records: vec![],
+            last_rebalance_ts: None,
+            funding_pnl: 0,
+            fees: 0,
+            slippage: 0,
+            traded_notional: 0,
+            trades: 0,
+            funding_updates: 0,
+            stale_twap_holds: 0,
+            funding_pause_freezes: 0,
+            peak_equity: initial_equity,
+            max_drawdown: 0,
+            max_drawdown_pct: 0.,
+            equity_curve: vec![],
+        })
+    }
+
+    pub fn step(&mut self, row: &PriceRow) -> Result<(), BacktestError> {
+        let now = row.ts;
+        let oracle_price = cast_to_i128(price_to_mark_precision(row.oracle_price))?;
+
+        // the series is sampled like a trade / crank touching the market each row 
+        // (=> a gap in the series = twaps nobody refreshed in between)
+        let amm = &mut self.markets.get_market_mut(self.config.market_index).amm;
+        let twaps_stale = validate_twap_staleness(amm, now, self.vault_state.max_twap_staleness).is_err();
+        move_to_price(amm, price_to_mark_precision(row.mark_price))?;
+        amm.last_oracle_price = oracle_price;
+        update_oracle_price_twap(amm, now, oracle_price)?;
+        update_mark_twap(amm, now, None)?;
+
+        // the clearing house doesnt update funding while it's paused
+        if !row.funding_paused && now >= calculate_next_funding_ts(amm)? {
+            self.update_funding(now, oracle_price, row.funding_rate)?;
+        }
+
+        let rebalance_due = match self.last_rebalance_ts {
+            None => true,
+            Some(last_rebalance_ts) => now - last_rebalance_ts >= self.config.rebalance_interval,
+        };
+        if rebalance_due {
+            let oracle_price_data = OraclePriceData {
+                price: oracle_price,
+                confidence: price_to_mark_precision(row.oracle_confidence),
+                delay: 0,
+                has_sufficient_number_of_data_points: true,
+            };
+            self.rebalance(now, &oracle_price_data, row.funding_paused, twaps_stale)?;
+            self.last_rebalance_ts = Some(now);
+        }
+
+        self.record_equity(now)
+    }
+
+    // mirrors controller::funding::update_funding_rate + settle_funding_payment
+    fn update_funding(
+        &mut self,
+        now: i64,
+        oracle_price: i128,
+        recorded_funding_rate: Option<i128>,
+    ) -> Result<(), BacktestError> {
+        let market_index = self.config.market_index;
+        let market = self.markets.get_market_mut(market_index);
+        let twaps = calculate_fresh_twaps(market_index, &market.amm, oracle_price, now)?;
+        let funding_rate = match recorded_funding_rate {
+            Some(funding_rate) => funding_rate,
+            None => calculate_predicted_funding_rate(&market.amm, &twaps)?,
+        };
+
+        let (funding_rate_long, funding_rate_short) = calculate_funding_rate_long_short(market, funding_rate)?;
+        let amm = &mut market.amm;
+        amm.cumulative_funding_rate_long += funding_rate_long;
+        amm.cumulative_funding_rate_short += funding_rate_short;
+        amm.last_funding_rate = funding_rate;
+        amm.last_funding_rate_ts = now;
+        amm.net_revenue_since_last_funding = 0;
+
+        let record_id = self.funding_rate_records.first().map_or(1, |record| record.record_id + 1);
+        self.funding_rate_records.insert(0, FundingRateRecord {
+            ts: now,
+            record_id,
+            market_index,
+            funding_rate,
+            cumulative_funding_rate_long: amm.cumulative_funding_rate_long,
+            cumulative_funding_rate_short: amm.cumulative_funding_rate_short,
+            oracle_price_twap: twaps.oracle_price_twap,
+            mark_price_twap: twaps.mark_price_twap,
+        });
+        self.funding_rate_records.truncate(max(1, self.config.funding_history_length as usize));
+        self.funding_updates += 1;
+
+        // settle the vault's funding payment
+        for market_position in self.user_positions.positions.iter_mut() {
+            if market_position.base_asset_amount == 0 {
+                continue;
+            }
+            let amm = &self.markets.get_market(market_position.market_index).amm;
+            let amm_cumulative_funding_rate = if market_position.base_asset_amount > 0 {
+                amm.cumulative_funding_rate_long
+            } else {
+                amm.cumulative_funding_rate_short
+            };
+            let funding_payment = calculate_funding_payment(amm_cumulative_funding_rate, market_position)?
+                / AMM_TO_QUOTE_PRECISION_RATIO_I128;
+            self.user.collateral = calculate_updated_collateral(self.user.collateral, funding_payment)?;
+            market_position.last_cumulative_funding_rate = amm_cumulative_funding_rate;
+            self.funding_pnl += funding_payment;
+        }
+        Ok(())
+    }
+
+    // mirrors UpdatePosition::rebalance (pauses => prepare_rebalance) w/ the program's own 
+    // decision (calculate_rebalance_targets) + the clearing house's open / close position 
+    fn rebalance(
+        &mut self,
+        now: i64,
+        oracle_price_data: &OraclePriceData,
+        funding_paused: bool,
+        twaps_stale: bool,
+    ) -> Result<(), BacktestError> {
+        // funding paused + not flattening => frozen, nothing happens
+        if funding_paused && !self.vault_state.flatten_on_funding_pause {
+            self.funding_pause_freezes += 1;
+            return Ok(());
+        }
+        // stale twaps => update_position fails (+ reverts the idle buffer move)
+        if !funding_paused && twaps_stale {
+            self.stale_twap_holds += 1;
+            return Ok(());
+        }
+
+        // 1. idle buffer
+        self.rebalance_idle_buffer();
+
+        // 2. targets
+        let market_index = self.config.market_index;
+        let strategy = get_strategy(&self.vault_state, market_index);
+        let mut twaps = vec![];
+        if !funding_paused {
+            for strategy_market_index in strategy.get_market_indexes() {
+                let amm = &self.markets.get_market(strategy_market_index).amm;
+                twaps.push(calculate_fresh_twaps(strategy_market_index, amm, oracle_price_data.price, now)?);
+            }
+        }
+        let snapshot = StrategySnapshot {
+            markets: &self.markets,
+            user: &self.user,
+            user_positions: &self.user_positions,
+            vault_state: &self.vault_state,
+            now,
+            funding_rate_records: &self.funding_rate_records,
+            twaps: &twaps,
+        };
+        let validity_guard_rails = ValidityGuardRails {
+            too_volatile_ratio: self.config.too_volatile_ratio,
+            ..ValidityGuardRails::default()
+        };
+        // one market => one oracle
+        let targets = match calculate_rebalance_targets(
+            &*strategy,
+            &snapshot,
+            funding_paused,
+            &validity_guard_rails,
+            |_| Ok(*oracle_price_data),
+        )? {
+            Some(targets) => targets,
+            None => return Ok(()),
+        };
+
+        // 3. closes + 4. trades
+        for stale_market_index in get_stale_market_indexes(&targets, &self.user_positions) {
+            self.close_position(stale_market_index, now)?;
+        }
+        for trade in calculate_trades(&targets, &self.user_positions, &self.markets) {
+            self.open_position(trade.market_index, trade.direction, trade.amount, now)?;
+        }
+        Ok(())
+    }
+
+    // mirrors UpdatePosition::rebalance_idle_buffer: drift collateral <=> the vault ATA 
+    fn rebalance_idle_buffer(&mut self) {
+        let [collateral_amount, ..] = calculate_collateral_liabilities(&self.user, &self.user_positions, &self.markets);
+        let idle_target = calculate_idle_buffer_target(collateral_amount + self.idle_amount, &self.vault_state);
+        let total_amount = self.user.collateral + self.idle_amount;
+        self.user.collateral = total_amount.saturating_sub(idle_target);
+        self.idle_amount = total_amount - self.user.collateral;
+    }
+
+    fn close_position(
+        &mut self,
+        market_index: u64,
+        now: i64,
+    ) -> Result<(), BacktestError> {
+        let position_index = self.get_position_index(market_index)?;
+        let market = self.markets.get_market_mut(market_index);
+        let mark_price = market.amm.mark_price()?;
+        let market_position = &mut self.user_positions.positions[position_index];
+        let (quote_asset_amount, base_asset_amount, _) = 
+            position::close(&mut self.user, market, market_position, now, None, Some(mark_price))?;
+        self.record_trade(quote_asset_amount, base_asset_amount, mark_price, market_index)
+    }
+
+    fn open_position(
+        &mut self,
+        market_index: u64,
+        direction: Position,
+        quote_asset_amount: u128,
+        now: i64,
+    ) -> Result<(), BacktestError> {
+        let direction = match direction {
+            Position::Long => PositionDirection::Long,
+            Position::Short => PositionDirection::Short,
+            Position::None => return Ok(()),
+        };
+        let position_index = self.get_position_index(market_index)?;
+        let market = self.markets.get_market_mut(market_index);
+        let mark_price = market.amm.mark_price()?;
+        let market_position = &mut self.user_positions.positions[position_index];
+
+        let is_increase = market_position.base_asset_amount == 0
+            || (market_position.base_asset_amount > 0) == (direction == PositionDirection::Long);
+        let base_asset_amount = match is_increase {
+            true => position::increase(direction, quote_asset_amount, market, market_position, now, Some(mark_price))?,
+            false => position::reduce(direction, quote_asset_amount, &mut self.user, market, market_position, now, Some(mark_price))?,
+        };
+        self.record_trade(quote_asset_amount, base_asset_amount, mark_price, market_index)
+    }
+
+    // charges the clearing house's fee + tracks the slippage vs the pre-trade mark price
+    fn record_trade(
+        &mut self,
+        quote_asset_amount: u128,
+        base_asset_amount: i128,
+        mark_price: u128,
+        market_index: u64,
+    ) -> Result<(), BacktestError> {
+        if quote_asset_amount == 0 {
+            return Ok(());
+        }
+        let (user_fee, ..) = calculate_fee_for_trade(quote_asset_amount, &self.fee_structure, None, &None)?;
+        self.user.collateral = self.user.collateral.saturating_sub(user_fee);
+        let amm = &mut self.markets.get_market_mut(market_index).amm;
+        amm.total_fee += user_fee;
+        amm.total_fee_minus_distributions += user_fee;
+
+        let mark_value = base_asset_amount.unsigned_abs() * mark_price / MARK_PRICE_TIMES_AMM_TO_QUOTE_PRECISION_RATIO;
+        self.slippage += max(quote_asset_amount, mark_value) - quote_asset_amount.min(mark_value);
+        self.fees += user_fee;
+        self.traded_notional += quote_asset_amount;
+        self.trades += 1;
+        Ok(())
+    }
+
+    fn get_position_index(&self, market_index: u64) -> Result<usize, BacktestError> {
+        self.user_positions
+            .positions
+            .iter()
+            .position(|market_position| market_position.market_index == market_index)
+            .ok_or_else(|| BacktestError::InvalidSeries(format!("no position slot for market {}", market_index)))
+    }
+
+    // collateral + unrealized pnl (amm valuation) + idle buffer
+    pub fn calculate_equity(&self) -> Result<i128, BacktestError> {
+        let mut equity = cast_to_i128(self.user.collateral + self.idle_amount)?;
+        for market_position in self.user_positions.positions.iter() {
+            if market_position.base_asset_amount == 0 {
+                continue;
+            }
+            let amm = &self.markets.get_market(market_position.market_index).amm;
+            let (_, unrealized_pnl) = calculate_base_asset_value_and_pnl(market_position, amm)?;
+            equity += unrealized_pnl;
+        }
+        Ok(equity)
+    }
+
+    fn record_equity(&mut self, now: i64) -> Result<(), BacktestError> {
+        let equity = self.calculate_equity()?;
+        self.peak_equity = max(self.peak_equity, equity);
+        let drawdown = self.peak_equity - equity;
+        if drawdown > self.max_drawdown {
+            self.max_drawdown = drawdown;
+            self.max_drawdown_pct = drawdown as f64 / self.peak_equity as f64 * 100.;
+        }
+        self.equity_curve.push(EquityPoint { ts: now, equity: quote_to_usd(equity) });
+        Ok(())
+    }
+
+    pub fn report(&self) -> Result<BacktestReport, BacktestError> {
+        let total_pnl = self.calculate_equity()? - cast_to_i128(usd_to_quote(self.config.initial_collateral))?;
+        let fees = cast_to_i128(self.fees)?;
+        let slippage = cast_to_i128(self.slippage)?;
+        let price_pnl = total_pnl - self.funding_pnl + fees + slippage;
+
+        Ok(BacktestReport {
+            total_pnl: quote_to_usd(total_pnl),
+            funding_pnl: quote_to_usd(self.funding_pnl),
+            price_pnl: quote_to_usd(price_pnl),
+            fees: quote_to_usd(fees),
+            slippage: quote_to_usd(slippage),
+            max_drawdown: quote_to_usd(self.max_drawdown),
+            max_drawdown_pct: self.max_drawdown_pct,
+            traded_notional: quote_to_usd(cast_to_i128(self.traded_notional)?),
+            turnover: quote_to_usd(cast_to_i128(self.traded_notional)?) / self.config.initial_collateral,
+            trades: self.trades,
+            funding_updates: self.funding_updates,
+            stale_twap_holds: self.stale_twap_holds,
+            funding_pause_freezes: self.funding_pause_freezes,
+            idle_amount: quote_to_usd(cast_to_i128(self.idle_amount)?),
+            equity_curve: self.equity_curve.clone(),
+        })
+    }
+}
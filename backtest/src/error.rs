@@ -0,0 +1,25 @@
+use anchor_lang::prelude::ProgramError;
+use clearing_house::error::ErrorCode;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum BacktestError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("csv error: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("clearing house error: {0:?}")]
+    ClearingHouse(ErrorCode),
+    #[error("program error: {0}")]
+    Program(#[from] ProgramError),
+    #[error("invalid series: {0}")]
+    InvalidSeries(String),
+}
+
+impl From<ErrorCode> for BacktestError {
+    fn from(error: ErrorCode) -> Self {
+        BacktestError::ClearingHouse(error)
+    }
+}
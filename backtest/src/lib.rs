@@ -0,0 +1,14 @@
+// backtests the vault's funding capture strategy by replaying historical 
+// funding rate / oracle / mark price series through the clearing house's own 
+// math on a synthetic AMM 
+pub mod error;
+pub mod series;
+pub mod config;
+pub mod engine;
+pub mod report;
+
+pub use error::*;
+pub use series::*;
+pub use config::*;
+pub use engine::*;
+pub use report::*;
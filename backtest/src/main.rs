@@ -0,0 +1,26 @@
+use std::fs::File;
+use std::path::Path;
+use std::process::exit;
+
+use drift_vault_backtest::{load_series, run_backtest, BacktestConfig, BacktestError};
+
+// drift-vault-backtest <config.json> <series.csv|series.json>
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 3 {
+        eprintln!("usage: drift-vault-backtest <config.json> <series.csv|series.json>");
+        exit(1);
+    }
+    if let Err(error) = run(&args[1], &args[2]) {
+        eprintln!("error: {}", error);
+        exit(1);
+    }
+}
+
+fn run(config_path: &str, series_path: &str) -> Result<(), BacktestError> {
+    let config: BacktestConfig = serde_json::from_reader(File::open(config_path)?)?;
+    let rows = load_series(Path::new(series_path))?;
+    let report = run_backtest(&config, &rows)?;
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
+}
@@ -0,0 +1,39 @@
+use serde::Serialize;
+
+use clearing_house::math::constants::QUOTE_PRECISION;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct EquityPoint {
+    pub ts: i64,
+    pub equity: f64,
+}
+
+// amounts in USDC: total = funding + price - fees - slippage 
+#[derive(Clone, Debug, Serialize)]
+pub struct BacktestReport {
+    pub total_pnl: f64,
+    pub funding_pnl: f64,
+    pub price_pnl: f64,
+    pub fees: f64,
+    pub slippage: f64,
+    pub max_drawdown: f64,
+    pub max_drawdown_pct: f64,
+    pub traded_notional: f64,
+    pub turnover: f64, // traded notional / initial collateral 
+    pub trades: u64,
+    pub funding_updates: u64,
+    // rebalances skipped: twaps older than max_twap_staleness (update_position fails) + 
+    // frozen by a funding pause (flatten_on_funding_pause = false)
+    pub stale_twap_holds: u64,
+    pub funding_pause_freezes: u64,
+    pub idle_amount: f64, // the idle buffer at the end 
+    pub equity_curve: Vec<EquityPoint>,
+}
+
+pub fn quote_to_usd(amount: i128) -> f64 {
+    amount as f64 / QUOTE_PRECISION as f64
+}
+
+pub fn usd_to_quote(amount: f64) -> u128 {
+    (amount * QUOTE_PRECISION as f64) as u128
+}
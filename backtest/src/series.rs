@@ -0,0 +1,49 @@
+use std::fs::File;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::error::BacktestError;
+
+// one point of the historical series (prices in USD) 
+#[derive(Clone, Debug, Deserialize)]
+pub struct PriceRow {
+    pub ts: i64,
+    pub oracle_price: f64,
+    pub mark_price: f64,
+    // recorded funding rate (same units as FundingRateRecord::funding_rate), 
+    // None => computed from the twaps like update_funding_rate 
+    #[serde(default)]
+    pub funding_rate: Option<i128>,
+    // oracle confidence interval (USD, feeds volatility sizing)
+    #[serde(default)]
+    pub oracle_confidence: f64,
+    // clearing house funding paused: no funding update, the vault freezes or goes flat
+    #[serde(default)]
+    pub funding_paused: bool,
+}
+
+// .csv (header: ts,oracle_price,mark_price[,funding_rate,oracle_confidence,funding_paused]) 
+// or .json (array of rows)
+pub fn load_series(path: &Path) -> Result<Vec<PriceRow>, BacktestError> {
+    let mut rows: Vec<PriceRow> = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("csv") => csv::Reader::from_path(path)?
+            .deserialize()
+            .collect::<Result<_, _>>()?,
+        Some("json") => serde_json::from_reader(File::open(path)?)?,
+        _ => return Err(BacktestError::InvalidSeries(format!("{} isnt .csv or .json", path.display()))),
+    };
+    validate_series(&mut rows)?;
+    Ok(rows)
+}
+
+pub fn validate_series(rows: &mut Vec<PriceRow>) -> Result<(), BacktestError> {
+    rows.sort_by_key(|row| row.ts);
+    if rows.is_empty() {
+        return Err(BacktestError::InvalidSeries("no rows".to_string()));
+    }
+    if let Some(row) = rows.iter().find(|row| row.oracle_price <= 0. || row.mark_price <= 0.) {
+        return Err(BacktestError::InvalidSeries(format!("non positive price at {}", row.ts)));
+    }
+    Ok(())
+}
@@ -0,0 +1,174 @@
+// a small hand-worked series: mark 1.00 < oracle 1.01 the whole time (=> long), 
+// a recorded funding rate of -0.01 / base per period (shorts pay longs), 
+// funding every hour + a rebalance every 2h on a $100m deep amm 
+use drift_vault_backtest::{Backtest, BacktestConfig, PriceRow};
+
+// 0.01 usd / base in FundingRateRecord units (MARK_PRICE_PRECISION * FUNDING_PAYMENT_PRECISION)
+const FUNDING_RATE: i128 = -1_000_000_000_000;
+
+fn config() -> BacktestConfig {
+    BacktestConfig {
+        initial_collateral: 10_000.,
+        sqrt_k: 100_000_000.,
+        funding_period: 3600,
+        fee_numerator: 10, // 0.1%
+        fee_denominator: 10_000,
+        rebalance_interval: 7200,
+        ..BacktestConfig::default()
+    }
+}
+
+fn series() -> Vec<PriceRow> {
+    (0..=6)
+        .map(|i| PriceRow {
+            ts: i * 1800,
+            oracle_price: 1.01,
+            mark_price: 1.00,
+            funding_rate: Some(FUNDING_RATE),
+            oracle_confidence: 0.,
+            funding_paused: false,
+        })
+        .collect()
+}
+
+fn assert_close(actual: f64, expected: f64) {
+    assert!((actual - expected).abs() < 1e-3, "{} != {}", actual, expected);
+}
+
+#[test]
+fn rebalances_and_pays_funding_on_schedule() {
+    let rows = series();
+    let mut backtest = Backtest::new(&config(), &rows[0]).unwrap();
+
+    // (trades, funding updates) after each row: funding on the hour, 
+    // rebalances at 0 (open the long) + 2h (top up w/ the funding received)
+    let expected = [(1, 0), (1, 0), (1, 1), (1, 1), (2, 2), (2, 2), (2, 3)];
+    for (row, (trades, funding_updates)) in rows.iter().zip(expected.iter()) {
+        backtest.step(row).unwrap();
+        let report = backtest.report().unwrap();
+        assert_eq!((report.trades, report.funding_updates), (*trades, *funding_updates), "at {}", row.ts);
+    }
+}
+
+#[test]
+fn splits_pnl_into_funding_fees_and_slippage() {
+    let report = drift_vault_backtest::run_backtest(&config(), &series()).unwrap();
+    let k = 100_000_000.;
+
+    // 0h: $10k long => 10_000 * k / (k + 10_000) = 9999.0001 base, $10 fee 
+    let base_0 = 10_000. * k / (k + 10_000.);
+    // 1h + 2h: 0.01 * 9999.0001 = $99.99 funding each 
+    let funding_0 = 0.01 * base_0;
+    // 2h: collateral + pnl - position value = 9990 + 2 * 99.99 - 10_000 = $189.98 top up (pnl cancels out)
+    let top_up = 9_990. + 2. * funding_0 - 10_000.;
+    let base_1 = top_up * k / (k + top_up);
+    // 3h: 0.01 * 10188.9797 base
+    let funding_1 = 0.01 * (base_0 + base_1);
+
+    assert_close(report.funding_pnl, 2. * funding_0 + funding_1);
+    assert_close(report.funding_pnl, 301.8698);
+    assert_close(report.fees, 10. + top_up * 0.001);
+    assert_close(report.fees, 10.19);
+    assert_close(report.traded_notional, 10_000. + top_up);
+    assert_close(report.turnover, (10_000. + top_up) / 10_000.);
+    // quote paid vs base bought at the 1.00 mark
+    assert_close(report.slippage, (10_000. - base_0) + (top_up - base_1));
+    assert_close(report.total_pnl, report.funding_pnl + report.price_pnl - report.fees - report.slippage);
+
+    // worst point = right after the first trade: the fee + selling 9999.0001 base back into the amm
+    let exit_value = base_0 * k / (k + base_0);
+    assert_close(report.max_drawdown, 10. + 10_000. - exit_value);
+    assert_eq!(report.equity_curve.len(), 7);
+    assert!(report.equity_curve.last().unwrap().equity > 10_200.);
+}
+
+#[test]
+fn keeps_the_idle_buffer_out_of_the_position() {
+    let config = BacktestConfig {
+        idle_buffer_numerator: 1, // 10% 
+        idle_buffer_denominator: 10,
+        ..config()
+    };
+    let rows = series();
+    let mut backtest = Backtest::new(&config, &rows[0]).unwrap();
+    backtest.step(&rows[0]).unwrap();
+
+    // $1k stays in the vault ATA, the long is sized on the $9k left in drift
+    let report = backtest.report().unwrap();
+    assert_close(report.idle_amount, 1_000.);
+    assert_close(report.traded_notional, 9_000.);
+    assert_close(report.fees, 9.);
+}
+
+#[test]
+fn freezes_or_goes_flat_on_a_funding_pause() {
+    // funding paused from 2h on: the 2h funding update + rebalance never happen
+    let rows: Vec<PriceRow> = series()
+        .into_iter()
+        .map(|row| PriceRow { funding_paused: row.ts >= 7200, ..row })
+        .collect();
+    let k = 100_000_000.;
+    let base_0 = 10_000. * k / (k + 10_000.);
+
+    // frozen: the long is held (no top up)
+    let report = drift_vault_backtest::run_backtest(&config(), &rows).unwrap();
+    assert_eq!((report.trades, report.funding_updates, report.funding_pause_freezes), (1, 1, 1));
+    assert_close(report.funding_pnl, 0.01 * base_0);
+
+    // flatten_on_funding_pause: the long is closed at 2h
+    let config = BacktestConfig {
+        flatten_on_funding_pause: true,
+        ..config()
+    };
+    let report = drift_vault_backtest::run_backtest(&config, &rows).unwrap();
+    assert_eq!((report.trades, report.funding_updates, report.funding_pause_freezes), (2, 1, 0));
+    assert_close(report.funding_pnl, 0.01 * base_0);
+}
+
+#[test]
+fn holds_the_position_on_stale_twaps() {
+    let config = BacktestConfig {
+        max_twap_staleness: 3600,
+        ..config()
+    };
+    // nothing between 0.5h and 2h => the twaps are 1.5h old at the 2h rebalance
+    let rows: Vec<PriceRow> = series()
+        .into_iter()
+        .map(|row| PriceRow { ts: if row.ts > 1800 { row.ts + 3600 } else { row.ts }, ..row })
+        .collect();
+    let mut backtest = Backtest::new(&config, &rows[0]).unwrap();
+
+    // (trades, stale twap holds) after each row: held at 2h, topped up at 4h (fresh twaps again)
+    let expected = [(1, 0), (1, 0), (1, 1), (1, 1), (1, 1), (1, 1), (2, 1)];
+    for (row, (trades, stale_twap_holds)) in rows.iter().zip(expected.iter()) {
+        backtest.step(row).unwrap();
+        let report = backtest.report().unwrap();
+        assert_eq!((report.trades, report.stale_twap_holds), (*trades, *stale_twap_holds), "at {}", row.ts);
+    }
+}
+
+#[test]
+fn sizes_down_on_the_oracle_confidence() {
+    let config = BacktestConfig {
+        max_leverage: 10_000, // 1x 
+        max_volatility: 5_000, // 50% 
+        ..config()
+    };
+    let row = PriceRow {
+        ts: 0,
+        oracle_price: 1.,
+        mark_price: 0.99,
+        funding_rate: None,
+        oracle_confidence: 0.,
+        funding_paused: false,
+    };
+
+    // no twap gap + no confidence => 1x
+    let report = drift_vault_backtest::run_backtest(&config, &[row.clone()]).unwrap();
+    assert_close(report.traded_notional, 10_000.);
+
+    // +-$0.25 on a $1 oracle = 25% volatility => half of max_leverage 
+    let row = PriceRow { oracle_confidence: 0.25, ..row };
+    let report = drift_vault_backtest::run_backtest(&config, &[row]).unwrap();
+    assert_close(report.traded_notional, 5_000.);
+}
@@ -0,0 +1,142 @@
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::instruction::Instruction;
+use clap::Subcommand;
+use solana_sdk::signature::Signer;
+
+use crate::commands::parse_strategy;
+use crate::context::CliContext;
+use crate::error::CliError;
+
+// the program's admin instructions (same args + precisions as lib.rs)
+#[derive(Subcommand)]
+pub enum AdminCommand {
+    /// Caps on total vault collateral, per depositor collateral + the vault's share of a market side (0 = no cap)
+    DepositCaps {
+        max_vault_collateral: u128,
+        max_depositor_amount: u64,
+        #[clap(long, default_value_t = 0)]
+        max_market_share: u128,
+    },
+    /// Only holders of this token can deposit (11111111111111111111111111111111 = anyone)
+    WhitelistMint { whitelist_mint: Pubkey },
+    /// Min holding period + early withdrawal fee (numerator = 0 => rejected)
+    Lockup {
+        min_holding_period: i64,
+        early_withdrawal_fee_numerator: u128,
+        early_withdrawal_fee_denominator: u128,
+    },
+    /// % of collateral kept idle in the vault
+    IdleBuffer { idle_buffer_numerator: u128, idle_buffer_denominator: u128 },
+    /// Oracle offset limit orders instead of market orders
+    ExecutionMode {
+        #[clap(long)]
+        use_limit_orders: bool,
+        #[clap(long, default_value_t = 0)]
+        limit_order_oracle_offset: u128,
+        #[clap(long, default_value_t = 0)]
+        max_order_age: i64,
+    },
+    /// Stop loss / take profit % from the entry price (numerator = 0 => off)
+    TriggerOrders {
+        stop_loss_numerator: u128,
+        stop_loss_denominator: u128,
+        take_profit_numerator: u128,
+        take_profit_denominator: u128,
+    },
+    /// Only trade within this many seconds before the next funding update (0 = off)
+    FundingWindow { funding_window: i64 },
+    /// Switch the strategy (funding-twap | funding-spread)
+    Strategy {
+        strategy: String,
+        /// market_index:beta (BETA_PRECISION), repeat for each spread market
+        #[clap(long = "spread-market")]
+        spread_markets: Vec<String>,
+    },
+    /// Volatility sizing (LEVERAGE / VOLATILITY_PRECISION, max_leverage = 0 => off)
+    VolatilitySizing { min_leverage: u128, max_leverage: u128, max_volatility: u128 },
+    /// Funding history signal (length = 0 => off)
+    FundingHistory {
+        funding_history_length: u64,
+        funding_history_decay: u128,
+        funding_history_full_size_rate: u128,
+    },
+    /// Max twap age in seconds (0 = off)
+    TwapStaleness { max_twap_staleness: i64 },
+    /// Close the positions when the clearing house pauses funding
+    FundingPauseMode {
+        #[clap(long)]
+        flatten: bool,
+    },
+}
+
+pub fn run(
+    ctx: &CliContext,
+    command: &AdminCommand,
+) -> Result<(), CliError> {
+    ctx.send("admin", build_instructions(ctx, command)?)
+}
+
+pub fn build_instructions(
+    ctx: &CliContext,
+    command: &AdminCommand,
+) -> Result<Vec<Instruction>, CliError> {
+    let admin = ctx.payer.pubkey();
+    let state = ctx.get_state()?;
+    let instructions = ctx.get_vault_instructions(&state);
+
+    let instruction = match command {
+        AdminCommand::DepositCaps { max_vault_collateral, max_depositor_amount, max_market_share } => 
+            instructions.update_deposit_caps(&admin, *max_vault_collateral, *max_depositor_amount, *max_market_share),
+        AdminCommand::WhitelistMint { whitelist_mint } => 
+            instructions.update_whitelist_mint(&admin, *whitelist_mint),
+        AdminCommand::Lockup { 
+            min_holding_period, 
+            early_withdrawal_fee_numerator, 
+            early_withdrawal_fee_denominator,
+        } => instructions.update_lockup(
+            &admin, 
+            *min_holding_period, 
+            *early_withdrawal_fee_numerator, 
+            *early_withdrawal_fee_denominator,
+        ),
+        AdminCommand::IdleBuffer { idle_buffer_numerator, idle_buffer_denominator } => 
+            instructions.update_idle_buffer(&admin, *idle_buffer_numerator, *idle_buffer_denominator),
+        AdminCommand::ExecutionMode { use_limit_orders, limit_order_oracle_offset, max_order_age } => 
+            instructions.update_execution_mode(&admin, *use_limit_orders, *limit_order_oracle_offset, *max_order_age),
+        AdminCommand::TriggerOrders { 
+            stop_loss_numerator, 
+            stop_loss_denominator, 
+            take_profit_numerator, 
+            take_profit_denominator,
+        } => instructions.update_trigger_orders(
+            &admin, 
+            *stop_loss_numerator, 
+            *stop_loss_denominator, 
+            *take_profit_numerator, 
+            *take_profit_denominator,
+        ),
+        AdminCommand::FundingWindow { funding_window } => 
+            instructions.update_funding_window(&admin, *funding_window),
+        AdminCommand::Strategy { strategy, spread_markets } => {
+            let (strategy, strategy_params) = parse_strategy(strategy, spread_markets)?;
+            instructions.update_strategy(&admin, strategy, strategy_params)
+        }
+        AdminCommand::VolatilitySizing { min_leverage, max_leverage, max_volatility } => 
+            instructions.update_volatility_sizing(&admin, *min_leverage, *max_leverage, *max_volatility),
+        AdminCommand::FundingHistory { 
+            funding_history_length, 
+            funding_history_decay, 
+            funding_history_full_size_rate,
+        } => instructions.update_funding_history(
+            &admin, 
+            *funding_history_length, 
+            *funding_history_decay, 
+            *funding_history_full_size_rate,
+        ),
+        AdminCommand::TwapStaleness { max_twap_staleness } => 
+            instructions.update_twap_staleness(&admin, *max_twap_staleness),
+        AdminCommand::FundingPauseMode { flatten } => 
+            instructions.update_funding_pause_mode(&admin, *flatten),
+    };
+    Ok(vec![instruction])
+}
@@ -0,0 +1,66 @@
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::instruction::Instruction;
+use solana_sdk::signature::Signer;
+use spl_associated_token_account::{create_associated_token_account, get_associated_token_address};
+
+use drift_vault_client::DeployAccounts;
+
+use crate::context::CliContext;
+use crate::error::CliError;
+
+// creates the vault token ATA + depositor state on the first deposit, 
+// passes the whitelist token when the vault has a whitelist 
+// (deploy = the market to deploy into, the program skips it for vaults w/ orders)
+pub fn run(
+    ctx: &CliContext,
+    amount: u64,
+    deploy: Option<u64>,
+    max_slippage: u128,
+) -> Result<(), CliError> {
+    ctx.send("deposit", build_instructions(ctx, amount, deploy, max_slippage)?)
+}
+
+pub fn build_instructions(
+    ctx: &CliContext,
+    amount: u64,
+    deploy: Option<u64>,
+    max_slippage: u128,
+) -> Result<Vec<Instruction>, CliError> {
+    let owner = ctx.payer.pubkey();
+    let vault_state = ctx.get_vault_state()?;
+    let state = ctx.get_state()?;
+    let vault_instructions = ctx.get_vault_instructions(&state);
+    let vault_mint = ctx.pdas.vault_mint.0;
+    let user_collateral_ata = get_associated_token_address(&owner, &state.collateral_mint);
+    let user_vault_ata = get_associated_token_address(&owner, &vault_mint);
+
+    let mut instructions = vec![];
+    if ctx.get_optional_account_data(&user_vault_ata)?.is_none() {
+        instructions.push(create_associated_token_account(&owner, &owner, &vault_mint));
+    }
+    if ctx.get_optional_account_data(&ctx.pdas.depositor(&owner).0)?.is_none() {
+        instructions.push(vault_instructions.initialize_depositor(&owner));
+    }
+
+    let whitelist_token = match vault_state.whitelist_mint == Pubkey::default() {
+        true => None,
+        false => Some(get_associated_token_address(&owner, &vault_state.whitelist_mint)),
+    };
+    let deploy = match deploy {
+        Some(market_index) => {
+            let (oracle, other_oracles) = ctx.get_oracles(&state, &vault_state, market_index)?;
+            Some(DeployAccounts { market_index, max_slippage, oracle, other_oracles })
+        }
+        None => None,
+    };
+    instructions.push(vault_instructions.deposit(
+        &owner,
+        &user_collateral_ata,
+        &user_vault_ata,
+        amount,
+        whitelist_token.as_ref(),
+        &ctx.get_position_oracles(&state)?,
+        deploy.as_ref(),
+    ));
+    Ok(instructions)
+}
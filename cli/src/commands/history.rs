@@ -0,0 +1,105 @@
+use serde::Serialize;
+
+use clearing_house::state::history::funding_payment::FundingPaymentRecord;
+use clearing_house::state::history::funding_rate::FundingRateRecord;
+
+use drift_vault::funding::read_funding_rate_records;
+use drift_vault_client::read_funding_payment_records;
+
+use crate::context::CliContext;
+use crate::error::CliError;
+use crate::output::{format_quote, print_output, Render};
+
+// newest first
+#[derive(Serialize)]
+pub struct HistoryOutput {
+    pub market_index: u64,
+    pub funding_rates: Vec<FundingRateOutput>,
+    // the vault's funding payments (all markets)
+    pub funding_payments: Vec<FundingPaymentOutput>,
+}
+
+#[derive(Serialize)]
+pub struct FundingRateOutput {
+    pub ts: i64,
+    pub funding_rate: i128,
+    pub mark_price_twap: u128,
+    pub oracle_price_twap: i128,
+}
+
+#[derive(Serialize)]
+pub struct FundingPaymentOutput {
+    pub ts: i64,
+    pub market_index: u64,
+    pub funding_payment: i128, // QUOTE_PRECISION 
+    pub base_asset_amount: i128,
+}
+
+impl From<&FundingRateRecord> for FundingRateOutput {
+    fn from(record: &FundingRateRecord) -> Self {
+        FundingRateOutput {
+            ts: record.ts,
+            funding_rate: record.funding_rate,
+            mark_price_twap: record.mark_price_twap,
+            oracle_price_twap: record.oracle_price_twap,
+        }
+    }
+}
+
+impl From<&FundingPaymentRecord> for FundingPaymentOutput {
+    fn from(record: &FundingPaymentRecord) -> Self {
+        FundingPaymentOutput {
+            ts: record.ts,
+            market_index: record.market_index,
+            // recorded in AMM_RESERVE_PRECISION
+            funding_payment: record.funding_payment / clearing_house::math::constants::AMM_TO_QUOTE_PRECISION_RATIO_I128,
+            base_asset_amount: record.base_asset_amount,
+        }
+    }
+}
+
+pub fn run(
+    ctx: &CliContext,
+    market_index: u64,
+    limit: usize,
+) -> Result<(), CliError> {
+    let state = ctx.get_state()?;
+    let funding_rate_records = read_funding_rate_records(
+        &ctx.get_account_data(&state.funding_rate_history)?,
+        market_index,
+        limit,
+    )?;
+    let funding_payment_records = read_funding_payment_records(
+        &ctx.get_account_data(&state.funding_payment_history)?,
+        &ctx.pdas.user.0,
+        limit,
+    )?;
+
+    let output = HistoryOutput {
+        market_index,
+        funding_rates: funding_rate_records.iter().map(|record| record.into()).collect(),
+        funding_payments: funding_payment_records.iter().map(|record| record.into()).collect(),
+    };
+    print_output(&output, ctx.json);
+    Ok(())
+}
+
+impl Render for HistoryOutput {
+    fn render(&self) -> String {
+        let mut lines = vec![format!("funding rates (market {}):", self.market_index)];
+        for record in self.funding_rates.iter() {
+            lines.push(format!(
+                "    {}: rate {} mark twap {} oracle twap {}",
+                record.ts, record.funding_rate, record.mark_price_twap, record.oracle_price_twap,
+            ));
+        }
+        lines.push("vault funding payments:".to_string());
+        for record in self.funding_payments.iter() {
+            lines.push(format!(
+                "    {}: market {} base {} paid {}",
+                record.ts, record.market_index, record.base_asset_amount, format_quote(record.funding_payment),
+            ));
+        }
+        lines.join("\n")
+    }
+}
@@ -0,0 +1,25 @@
+use anchor_lang::solana_program::instruction::Instruction;
+use solana_sdk::signature::Signer;
+
+use crate::commands::parse_strategy;
+use crate::context::CliContext;
+use crate::error::CliError;
+
+pub fn run(
+    ctx: &CliContext,
+    strategy: &str,
+    spread_markets: &[String],
+) -> Result<(), CliError> {
+    ctx.send("init", build_instructions(ctx, strategy, spread_markets)?)
+}
+
+pub fn build_instructions(
+    ctx: &CliContext,
+    strategy: &str,
+    spread_markets: &[String],
+) -> Result<Vec<Instruction>, CliError> {
+    let (strategy, strategy_params) = parse_strategy(strategy, spread_markets)?;
+    let state = ctx.get_state()?;
+    let instructions = ctx.get_vault_instructions(&state);
+    Ok(vec![instructions.initialize_vault(&ctx.payer.pubkey(), strategy, strategy_params)])
+}
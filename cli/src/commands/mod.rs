@@ -0,0 +1,44 @@
+use drift_vault::state::{StrategyKind, StrategyParams, MAX_SPREAD_MARKETS};
+use drift_vault::strategy::validate_strategy;
+
+use crate::error::CliError;
+
+// the commands which send: build_instructions (reads through the ctx's AccountSource) 
+// + run (sends them or prints them on --dry-run)
+pub mod init;
+pub mod deposit;
+pub mod withdraw;
+pub mod unlock;
+pub mod rebalance;
+pub mod status;
+pub mod history;
+pub mod admin;
+
+// funding-twap | funding-spread w/ spread markets as market_index:beta (BETA_PRECISION)
+pub fn parse_strategy(
+    strategy: &str,
+    spread_markets: &[String],
+) -> Result<(StrategyKind, StrategyParams), CliError> {
+    let strategy = match strategy {
+        "funding-twap" => StrategyKind::FundingTwap,
+        "funding-spread" => StrategyKind::FundingSpread,
+        _ => return Err(CliError::InvalidArgument(format!("unknown strategy {}", strategy))),
+    };
+    if spread_markets.len() > MAX_SPREAD_MARKETS {
+        return Err(CliError::InvalidArgument(format!("at most {} spread markets", MAX_SPREAD_MARKETS)));
+    }
+
+    let mut strategy_params = StrategyParams::default();
+    for (i, spread_market) in spread_markets.iter().enumerate() {
+        let (market_index, beta) = spread_market
+            .split_once(':')
+            .and_then(|(market_index, beta)| Some((market_index.parse().ok()?, beta.parse().ok()?)))
+            .ok_or_else(|| CliError::InvalidArgument(format!("spread market {} isnt market_index:beta", spread_market)))?;
+        strategy_params.spread_market_indexes[i] = market_index;
+        strategy_params.spread_market_betas[i] = beta;
+    }
+
+    validate_strategy(strategy, &strategy_params)
+        .map_err(|error| CliError::InvalidArgument(format!("{:?}", error)))?;
+    Ok((strategy, strategy_params))
+}
@@ -0,0 +1,34 @@
+use anchor_lang::solana_program::instruction::Instruction;
+
+use clearing_house::state::order_state::OrderState;
+use drift_vault_client::deserialize_account;
+
+use crate::context::CliContext;
+use crate::error::CliError;
+
+// update_position (update_position_with_orders for vaults w/ orders)
+pub fn run(
+    ctx: &CliContext,
+    market_index: u64,
+) -> Result<(), CliError> {
+    ctx.send("rebalance", build_instructions(ctx, market_index)?)
+}
+
+pub fn build_instructions(
+    ctx: &CliContext,
+    market_index: u64,
+) -> Result<Vec<Instruction>, CliError> {
+    let vault_state = ctx.get_vault_state()?;
+    let state = ctx.get_state()?;
+    let vault_instructions = ctx.get_vault_instructions(&state);
+    let (oracle, other_oracles) = ctx.get_oracles(&state, &vault_state, market_index)?;
+
+    let instruction = match vault_state.uses_orders() {
+        true => {
+            let order_state: OrderState = deserialize_account(&ctx.get_account_data(&state.order_state)?)?;
+            vault_instructions.update_position_with_orders(market_index, &oracle, &order_state.order_history)
+        }
+        false => vault_instructions.update_position(market_index, &oracle, &other_oracles),
+    };
+    Ok(vec![instruction])
+}
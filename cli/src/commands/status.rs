@@ -0,0 +1,240 @@
+use anchor_spl::token::Mint;
+use serde::Serialize;
+
+use clearing_house::math::constants::{FUNDING_PAYMENT_PRECISION, MARGIN_PRECISION, MARK_PRICE_PRECISION};
+
+use drift_vault::funding::{calculate_funding_rate_ewma, calculate_next_funding_ts, read_funding_rate_records};
+use drift_vault::state::Position;
+use drift_vault::twap::calculate_fresh_twaps;
+use drift_vault_client::{
+    calculate_predicted_funding_rate, calculate_vault_nav, deserialize_account, get_oracle_price_data,
+    get_vault_market_indexes, VaultAccountsData,
+};
+
+use crate::context::CliContext;
+use crate::error::CliError;
+use crate::output::{format_quote, print_output, Render};
+
+// amounts in QUOTE_PRECISION, funding rates like FundingRateRecord::funding_rate
+#[derive(Serialize)]
+pub struct StatusOutput {
+    pub market_index: u64,
+    pub strategy: String,
+    pub exchange_paused: bool,
+    pub funding_paused: bool,
+    pub nav: u128,
+    pub nav_per_share: u128,
+    pub share_supply: u64,
+    pub amm_collateral: u128,
+    pub oracle_collateral: u128,
+    pub idle_amount: u128,
+    pub liabilities: u128,
+    pub margin_ratio: Option<u128>, // MARGIN_PRECISION, None = no positions
+    pub positions: Vec<PositionOutput>,
+    pub funding_signals: Vec<FundingSignalOutput>,
+    // what a rebalance would do right now
+    pub pending_closes: Vec<u64>,
+    pub pending_trades: Vec<TradeOutput>,
+}
+
+#[derive(Serialize)]
+pub struct PositionOutput {
+    pub market_index: u64,
+    pub base_asset_amount: i128,
+    pub base_asset_value: u128,
+    pub unrealized_pnl: i128,
+    pub oracle_unrealized_pnl: i128,
+    pub pending_funding: i128,
+}
+
+#[derive(Serialize)]
+pub struct FundingSignalOutput {
+    pub market_index: u64,
+    pub last_funding_rate: i128,
+    pub predicted_funding_rate: i128, // w/ the twaps extended to now
+    pub funding_rate_ewma: Option<i128>, // funding history signal (None = off)
+    pub next_funding_ts: i64,
+}
+
+#[derive(Serialize)]
+pub struct TradeOutput {
+    pub market_index: u64,
+    pub direction: String,
+    pub amount: u128,
+    pub is_reduce: bool,
+}
+
+pub fn run(
+    ctx: &CliContext,
+    market_index: u64,
+) -> Result<(), CliError> {
+    let (now, slot) = ctx.get_clock()?;
+    let pdas = &ctx.pdas;
+    let vault_state_data = ctx.get_account_data(&pdas.vault_state.0)?;
+    let vault_mint_data = ctx.get_account_data(&pdas.vault_mint.0)?;
+    let vault_state = ctx.get_vault_state()?;
+    let state = ctx.get_state()?;
+    let markets = ctx.get_markets(&state)?;
+    let user_positions = ctx.get_user_positions()?;
+
+    let vault_market_indexes = get_vault_market_indexes(&vault_state, market_index, &user_positions);
+    let mut oracle_data = vec![];
+    for &vault_market_index in vault_market_indexes.iter() {
+        let oracle = markets.get_market(vault_market_index).amm.oracle;
+        oracle_data.push((oracle, ctx.get_account_data(&oracle)?));
+    }
+    let oracles: Vec<_> = oracle_data.iter().map(|(oracle, data)| (*oracle, data.as_slice())).collect();
+    let funding_rate_history = match vault_state.funding_history_length {
+        0 => None,
+        _ => Some(ctx.get_account_data(&state.funding_rate_history)?),
+    };
+
+    let accounts = VaultAccountsData {
+        vault_state: &vault_state_data,
+        vault_mint: &vault_mint_data,
+        vault_collateral: &ctx.get_account_data(&pdas.vault_collateral.0)?,
+        state: &ctx.get_account_data(&ctx.clearing_house_state)?,
+        user: &ctx.get_account_data(&pdas.user.0)?,
+        user_positions: &ctx.get_account_data(&pdas.user_positions.0)?,
+        markets: &ctx.get_account_data(&state.markets)?,
+        oracles: &oracles,
+        funding_rate_history: funding_rate_history.as_deref(),
+    };
+    let nav = calculate_vault_nav(&accounts, market_index, now, slot)?;
+    let vault_mint: Mint = deserialize_account(&vault_mint_data)?;
+
+    let mut funding_signals = vec![];
+    for &vault_market_index in vault_market_indexes.iter() {
+        let amm = &markets.get_market(vault_market_index).amm;
+        let oracle_price_data = get_oracle_price_data(amm, &oracles, slot)?;
+        let twaps = calculate_fresh_twaps(vault_market_index, amm, oracle_price_data.price, now)?;
+        let funding_rate_ewma = match &funding_rate_history {
+            Some(funding_rate_history) => {
+                let records = read_funding_rate_records(
+                    funding_rate_history,
+                    vault_market_index,
+                    vault_state.funding_history_length as usize,
+                )?;
+                calculate_funding_rate_ewma(&records, vault_state.funding_history_decay)?
+            }
+            None => None,
+        };
+        funding_signals.push(FundingSignalOutput {
+            market_index: vault_market_index,
+            last_funding_rate: amm.last_funding_rate,
+            predicted_funding_rate: calculate_predicted_funding_rate(amm, &twaps)?,
+            funding_rate_ewma,
+            next_funding_ts: calculate_next_funding_ts(amm)?,
+        });
+    }
+
+    let output = StatusOutput {
+        market_index,
+        strategy: format!("{:?}", vault_state.strategy),
+        exchange_paused: state.exchange_paused,
+        funding_paused: state.funding_paused,
+        nav: nav.nav,
+        nav_per_share: nav.nav_per_share,
+        share_supply: vault_mint.supply,
+        amm_collateral: nav.amm_collateral,
+        oracle_collateral: nav.oracle_collateral,
+        idle_amount: nav.idle_amount,
+        liabilities: nav.liabilities,
+        margin_ratio: match nav.margin_ratio {
+            u128::MAX => None,
+            margin_ratio => Some(margin_ratio),
+        },
+        positions: nav.exposures
+            .iter()
+            .map(|exposure| PositionOutput {
+                market_index: exposure.market_index,
+                base_asset_amount: exposure.base_asset_amount,
+                base_asset_value: exposure.base_asset_value,
+                unrealized_pnl: exposure.unrealized_pnl,
+                oracle_unrealized_pnl: exposure.oracle_unrealized_pnl,
+                pending_funding: exposure.pending_funding,
+            })
+            .collect(),
+        funding_signals,
+        pending_closes: nav.closes,
+        pending_trades: nav.trades
+            .iter()
+            .map(|trade| TradeOutput {
+                market_index: trade.market_index,
+                direction: match trade.direction {
+                    Position::Long => "long".to_string(),
+                    _ => "short".to_string(),
+                },
+                amount: trade.amount,
+                is_reduce: trade.is_reduce,
+            })
+            .collect(),
+    };
+    print_output(&output, ctx.json);
+    Ok(())
+}
+
+// quote per base asset per funding period
+fn format_funding_rate(funding_rate: i128) -> String {
+    format!("{:.6}", funding_rate as f64 / (MARK_PRICE_PRECISION * FUNDING_PAYMENT_PRECISION) as f64)
+}
+
+impl Render for StatusOutput {
+    fn render(&self) -> String {
+        let mut lines = vec![
+            format!("strategy: {} (market {})", self.strategy, self.market_index),
+            format!("exchange paused: {}, funding paused: {}", self.exchange_paused, self.funding_paused),
+            format!("nav: {} ({} / share, {} shares)", 
+                format_quote(self.nav as i128), 
+                format_quote(self.nav_per_share as i128), 
+                self.share_supply,
+            ),
+            format!("collateral: {} amm / {} oracle valuation, {} idle", 
+                format_quote(self.amm_collateral as i128), 
+                format_quote(self.oracle_collateral as i128), 
+                format_quote(self.idle_amount as i128),
+            ),
+            format!("margin ratio: {}", match self.margin_ratio {
+                Some(margin_ratio) => format!("{:.2}%", margin_ratio as f64 / MARGIN_PRECISION as f64 * 100.),
+                None => "- (no positions)".to_string(),
+            }),
+            "positions:".to_string(),
+        ];
+        for position in self.positions.iter() {
+            lines.push(format!(
+                "    market {}: base {} value {} pnl {} (oracle {}) pending funding {}",
+                position.market_index,
+                position.base_asset_amount,
+                format_quote(position.base_asset_value as i128),
+                format_quote(position.unrealized_pnl),
+                format_quote(position.oracle_unrealized_pnl),
+                format_quote(position.pending_funding),
+            ));
+        }
+        lines.push("funding:".to_string());
+        for signal in self.funding_signals.iter() {
+            lines.push(format!(
+                "    market {}: last {} predicted {} ewma {} next update at {}",
+                signal.market_index,
+                format_funding_rate(signal.last_funding_rate),
+                format_funding_rate(signal.predicted_funding_rate),
+                signal.funding_rate_ewma.map_or("-".to_string(), format_funding_rate),
+                signal.next_funding_ts,
+            ));
+        }
+        lines.push("rebalance would:".to_string());
+        for market_index in self.pending_closes.iter() {
+            lines.push(format!("    close market {}", market_index));
+        }
+        for trade in self.pending_trades.iter() {
+            lines.push(format!(
+                "    {} {} in market {} ({})",
+                trade.direction,
+                format_quote(trade.amount as i128),
+                trade.market_index,
+                if trade.is_reduce { "reduce" } else { "increase" },
+            ));
+        }
+        lines.join("\n")
+    }
+}
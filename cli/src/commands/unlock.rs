@@ -0,0 +1,20 @@
+use anchor_lang::solana_program::instruction::Instruction;
+use solana_sdk::signature::Signer;
+use spl_associated_token_account::get_associated_token_address;
+
+use crate::context::CliContext;
+use crate::error::CliError;
+
+pub fn run(ctx: &CliContext) -> Result<(), CliError> {
+    ctx.send("unlock", build_instructions(ctx)?)
+}
+
+pub fn build_instructions(ctx: &CliContext) -> Result<Vec<Instruction>, CliError> {
+    let owner = ctx.payer.pubkey();
+    let state = ctx.get_state()?;
+    let instruction = ctx.get_vault_instructions(&state).unlock_shares(
+        &owner,
+        &get_associated_token_address(&owner, &ctx.pdas.vault_mint.0),
+    );
+    Ok(vec![instruction])
+}
@@ -0,0 +1,35 @@
+use anchor_lang::solana_program::instruction::Instruction;
+use solana_sdk::signature::Signer;
+use spl_associated_token_account::get_associated_token_address;
+
+use crate::context::CliContext;
+use crate::error::CliError;
+
+pub fn run(
+    ctx: &CliContext,
+    burn_amount: u128,
+    market_index: u64,
+) -> Result<(), CliError> {
+    ctx.send("withdraw", build_instructions(ctx, burn_amount, market_index)?)
+}
+
+pub fn build_instructions(
+    ctx: &CliContext,
+    burn_amount: u128,
+    market_index: u64,
+) -> Result<Vec<Instruction>, CliError> {
+    let owner = ctx.payer.pubkey();
+    let vault_state = ctx.get_vault_state()?;
+    let state = ctx.get_state()?;
+    let (oracle, other_oracles) = ctx.get_oracles(&state, &vault_state, market_index)?;
+    let instruction = ctx.get_vault_instructions(&state).withdraw(
+        &owner,
+        &get_associated_token_address(&owner, &state.collateral_mint),
+        &get_associated_token_address(&owner, &ctx.pdas.vault_mint.0),
+        burn_amount,
+        market_index,
+        &oracle,
+        &other_oracles,
+    );
+    Ok(vec![instruction])
+}
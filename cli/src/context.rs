@@ -0,0 +1,172 @@
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::instruction::Instruction;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::signature::{read_keypair_file, Keypair, Signer};
+use solana_sdk::transaction::Transaction;
+
+use clearing_house::state::market::Markets;
+use clearing_house::state::state::State;
+use clearing_house::state::user::UserPositions;
+
+use drift_vault::state::VaultState;
+use drift_vault_client::{
+    deserialize_account, deserialize_zero_copy_account, get_clearing_house_state_address,
+    get_vault_market_indexes, ClearingHouseAccounts, VaultInstructions, VaultPdas,
+};
+
+use crate::error::CliError;
+use crate::output::{print_output, SendOutput};
+use crate::source::{AccountSource, RpcAccountSource};
+
+// rpc + signer + output settings every command gets 
+pub struct CliContext {
+    pub client: RpcClient, // sends the txs 
+    pub accounts: Box<dyn AccountSource>,
+    pub payer: Keypair,
+    pub pdas: VaultPdas,
+    pub clearing_house_state: Pubkey,
+    pub dry_run: bool,
+    pub json: bool,
+}
+
+impl CliContext {
+    pub fn new(
+        url: &str,
+        keypair_path: &str,
+        program_id: &Pubkey,
+        clearing_house_program_id: &Pubkey,
+        dry_run: bool,
+        json: bool,
+    ) -> Result<Self, CliError> {
+        let keypair_path = match (keypair_path.strip_prefix("~/"), std::env::var("HOME")) {
+            (Some(path), Ok(home)) => format!("{}/{}", home, path),
+            _ => keypair_path.to_string(),
+        };
+        let payer = read_keypair_file(&keypair_path)
+            .map_err(|error| CliError::InvalidArgument(format!("cant read keypair {}: {}", keypair_path, error)))?;
+        Ok(CliContext::with_accounts(
+            url,
+            Box::new(RpcAccountSource::new(url)),
+            payer,
+            program_id,
+            clearing_house_program_id,
+            dry_run,
+            json,
+        ))
+    }
+
+    // reads the accounts from `accounts` instead of the rpc 
+    pub fn with_accounts(
+        url: &str,
+        accounts: Box<dyn AccountSource>,
+        payer: Keypair,
+        program_id: &Pubkey,
+        clearing_house_program_id: &Pubkey,
+        dry_run: bool,
+        json: bool,
+    ) -> Self {
+        CliContext {
+            client: RpcClient::new_with_commitment(url.to_string(), CommitmentConfig::confirmed()),
+            accounts,
+            payer,
+            pdas: VaultPdas::derive(program_id, clearing_house_program_id),
+            clearing_house_state: get_clearing_house_state_address(clearing_house_program_id).0,
+            dry_run,
+            json,
+        }
+    }
+
+    pub fn get_account_data(&self, address: &Pubkey) -> Result<Vec<u8>, CliError> {
+        self.get_optional_account_data(address)?.ok_or(CliError::MissingAccount(*address))
+    }
+
+    pub fn get_optional_account_data(&self, address: &Pubkey) -> Result<Option<Vec<u8>>, CliError> {
+        self.accounts.get_optional_account_data(address)
+    }
+
+    // (unix timestamp, slot)
+    pub fn get_clock(&self) -> Result<(i64, u64), CliError> {
+        self.accounts.get_clock()
+    }
+
+    pub fn get_vault_state(&self) -> Result<VaultState, CliError> {
+        Ok(deserialize_account(&self.get_account_data(&self.pdas.vault_state.0)?)?)
+    }
+
+    pub fn get_state(&self) -> Result<State, CliError> {
+        Ok(deserialize_account(&self.get_account_data(&self.clearing_house_state)?)?)
+    }
+
+    pub fn get_markets(&self, state: &State) -> Result<Box<Markets>, CliError> {
+        Ok(Box::new(deserialize_zero_copy_account(&self.get_account_data(&state.markets)?)?))
+    }
+
+    pub fn get_user_positions(&self) -> Result<UserPositions, CliError> {
+        Ok(deserialize_zero_copy_account(&self.get_account_data(&self.pdas.user_positions.0)?)?)
+    }
+
+    pub fn get_vault_instructions(&self, state: &State) -> VaultInstructions {
+        VaultInstructions::new(
+            self.pdas,
+            ClearingHouseAccounts::from_state(self.clearing_house_state, state),
+        )
+    }
+
+    // (market_index's oracle, the oracles of the vault's other markets)
+    pub fn get_oracles(
+        &self,
+        state: &State,
+        vault_state: &VaultState,
+        market_index: u64,
+    ) -> Result<(Pubkey, Vec<Pubkey>), CliError> {
+        let markets = self.get_markets(state)?;
+        let user_positions = self.get_user_positions()?;
+        let other_oracles = get_vault_market_indexes(vault_state, market_index, &user_positions)
+            .into_iter()
+            .filter(|&vault_market_index| vault_market_index != market_index)
+            .map(|vault_market_index| markets.get_market(vault_market_index).amm.oracle)
+            .collect();
+        Ok((markets.get_market(market_index).amm.oracle, other_oracles))
+    }
+
+    // oracles of the markets the vault has a position in (share pricing)
+    pub fn get_position_oracles(&self, state: &State) -> Result<Vec<Pubkey>, CliError> {
+        let markets = self.get_markets(state)?;
+        let user_positions = self.get_user_positions()?;
+        Ok(user_positions
+            .positions
+            .iter()
+            .filter(|market_position| market_position.base_asset_amount != 0)
+            .map(|market_position| markets.get_market(market_position.market_index).amm.oracle)
+            .collect())
+    }
+
+    // signs w/ the keypair + sends (or prints the instructions on --dry-run)
+    pub fn send(&self, command: &str, instructions: Vec<Instruction>) -> Result<(), CliError> {
+        let output = match self.dry_run {
+            true => SendOutput {
+                command: command.to_string(),
+                signature: None,
+                instructions: Some(instructions.iter().map(|instruction| instruction.into()).collect()),
+            },
+            false => {
+                let blockhash = self.client.get_latest_blockhash()?;
+                let transaction = Transaction::new_signed_with_payer(
+                    &instructions,
+                    Some(&self.payer.pubkey()),
+                    &[&self.payer],
+                    blockhash,
+                );
+                let signature = self.client.send_and_confirm_transaction(&transaction)?;
+                SendOutput {
+                    command: command.to_string(),
+                    signature: Some(signature.to_string()),
+                    instructions: None,
+                }
+            }
+        };
+        print_output(&output, self.json);
+        Ok(())
+    }
+}
@@ -0,0 +1,25 @@
+use anchor_lang::prelude::{ProgramError, Pubkey};
+use clearing_house::error::ErrorCode;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum CliError {
+    #[error("rpc error: {0}")]
+    Rpc(#[from] solana_client::client_error::ClientError),
+    #[error("program error: {0}")]
+    Program(#[from] ProgramError),
+    #[error("clearing house error: {0:?}")]
+    ClearingHouse(ErrorCode),
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("account {0} not found")]
+    MissingAccount(Pubkey),
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+}
+
+impl From<ErrorCode> for CliError {
+    fn from(error: ErrorCode) -> Self {
+        CliError::ClearingHouse(error)
+    }
+}
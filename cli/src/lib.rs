@@ -0,0 +1,12 @@
+// operator cli for the vault: each command reads the accounts through an 
+// AccountSource (rpc or in-memory) + builds its instructions w/ the client crate
+pub mod error;
+pub mod output;
+pub mod source;
+pub mod context;
+pub mod commands;
+
+pub use error::*;
+pub use output::*;
+pub use source::*;
+pub use context::*;
@@ -0,0 +1,113 @@
+use std::process::exit;
+
+use anchor_lang::prelude::Pubkey;
+use clap::{Parser, Subcommand};
+
+use drift_vault_cli::commands;
+use drift_vault_cli::commands::admin::AdminCommand;
+use drift_vault_cli::{CliContext, CliError};
+
+/// Operate the drift vault (amounts in the token's base units / the program's precisions)
+#[derive(Parser)]
+#[clap(name = "drift-vault")]
+struct Opts {
+    #[clap(long, short = 'u', default_value = "http://localhost:8899")]
+    url: String,
+    /// Signer + fee payer (admin for `admin`, owner for `deposit` / `withdraw`)
+    #[clap(long, short = 'k', default_value = "~/.config/solana/id.json")]
+    keypair: String,
+    #[clap(long, default_value_t = drift_vault::ID)]
+    program_id: Pubkey,
+    #[clap(long, default_value_t = clearing_house::ID)]
+    clearing_house_program_id: Pubkey,
+    /// Print the instructions instead of sending them
+    #[clap(long)]
+    dry_run: bool,
+    /// JSON output
+    #[clap(long)]
+    json: bool,
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Initialize the vault (funding-twap | funding-spread)
+    Init {
+        #[clap(long, default_value = "funding-twap")]
+        strategy: String,
+        /// market_index:beta (BETA_PRECISION), repeat for each spread market
+        #[clap(long = "spread-market")]
+        spread_markets: Vec<String>,
+    },
+    /// Deposit collateral (creates the vault token account + depositor state if needed)
+    Deposit {
+        amount: u64,
+        /// Deploy the deposit into this market in the same instruction (update_position)
+        #[clap(long)]
+        deploy: Option<u64>,
+        /// Worst deploy fill vs the oracle (SLIPPAGE_PRECISION, > 0)
+        #[clap(long, default_value_t = 100)]
+        max_slippage: u128,
+    },
+    /// Burn vault tokens for collateral
+    Withdraw {
+        burn_amount: u128,
+        #[clap(long, default_value_t = 0)]
+        market_index: u64,
+    },
+    /// Move the vault tokens minted within the lockup to the vault token account (once it ends)
+    Unlock,
+    /// Send update_position (update_position_with_orders for vaults w/ orders)
+    Rebalance {
+        #[clap(long, default_value_t = 0)]
+        market_index: u64,
+    },
+    /// NAV, share price, positions, margin ratio, funding signal + what a rebalance would do
+    Status {
+        #[clap(long, default_value_t = 0)]
+        market_index: u64,
+    },
+    /// The market's funding rates + the vault's funding payments
+    History {
+        #[clap(long, default_value_t = 0)]
+        market_index: u64,
+        #[clap(long, default_value_t = 24)]
+        limit: usize,
+    },
+    /// Update the vault's params
+    #[clap(subcommand)]
+    Admin(AdminCommand),
+}
+
+fn main() {
+    let opts = Opts::parse();
+    if let Err(error) = run(&opts) {
+        match opts.json {
+            true => println!("{}", serde_json::json!({ "error": error.to_string() })),
+            false => eprintln!("error: {}", error),
+        }
+        exit(1);
+    }
+}
+
+fn run(opts: &Opts) -> Result<(), CliError> {
+    let ctx = CliContext::new(
+        &opts.url,
+        &opts.keypair,
+        &opts.program_id,
+        &opts.clearing_house_program_id,
+        opts.dry_run,
+        opts.json,
+    )?;
+    match &opts.command {
+        Command::Init { strategy, spread_markets } => commands::init::run(&ctx, strategy, spread_markets),
+        Command::Deposit { amount, deploy, max_slippage } => commands::deposit::run(&ctx, *amount, *deploy, *max_slippage),
+        Command::Withdraw { burn_amount, market_index } => commands::withdraw::run(&ctx, *burn_amount, *market_index),
+        Command::Unlock => commands::unlock::run(&ctx),
+        Command::Rebalance { market_index } => commands::rebalance::run(&ctx, *market_index),
+        Command::Status { market_index } => commands::status::run(&ctx, *market_index),
+        Command::History { market_index, limit } => commands::history::run(&ctx, *market_index, *limit),
+        Command::Admin(command) => commands::admin::run(&ctx, command),
+    }
+}
@@ -0,0 +1,84 @@
+use anchor_lang::solana_program::instruction::Instruction;
+use serde::Serialize;
+
+// what a command prints: json (--json) or text 
+pub trait Render: Serialize {
+    fn render(&self) -> String;
+}
+
+pub fn print_output<T: Render>(value: &T, json: bool) {
+    match json {
+        true => println!("{}", serde_json::to_string_pretty(value).unwrap()),
+        false => println!("{}", value.render()),
+    }
+}
+
+#[derive(Serialize)]
+pub struct AccountOutput {
+    pub pubkey: String,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+#[derive(Serialize)]
+pub struct InstructionOutput {
+    pub program_id: String,
+    pub accounts: Vec<AccountOutput>,
+    pub data: String, // base64 
+}
+
+impl From<&Instruction> for InstructionOutput {
+    fn from(instruction: &Instruction) -> Self {
+        InstructionOutput {
+            program_id: instruction.program_id.to_string(),
+            accounts: instruction.accounts
+                .iter()
+                .map(|account| AccountOutput {
+                    pubkey: account.pubkey.to_string(),
+                    is_signer: account.is_signer,
+                    is_writable: account.is_writable,
+                })
+                .collect(),
+            data: base64::encode(&instruction.data),
+        }
+    }
+}
+
+// result of a command which sends a tx (dry run => the instructions instead)
+#[derive(Serialize)]
+pub struct SendOutput {
+    pub command: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<Vec<InstructionOutput>>,
+}
+
+impl Render for SendOutput {
+    fn render(&self) -> String {
+        match (&self.signature, &self.instructions) {
+            (Some(signature), _) => format!("{}: {}", self.command, signature),
+            (None, Some(instructions)) => {
+                let mut lines = vec![format!("{} (dry run): {} instructions", self.command, instructions.len())];
+                for (i, instruction) in instructions.iter().enumerate() {
+                    lines.push(format!("#{} program {}", i, instruction.program_id));
+                    for account in instruction.accounts.iter() {
+                        lines.push(format!(
+                            "    {} {}{}",
+                            account.pubkey,
+                            if account.is_writable { "w" } else { "-" },
+                            if account.is_signer { "s" } else { "-" },
+                        ));
+                    }
+                    lines.push(format!("    data {}", instruction.data));
+                }
+                lines.join("\n")
+            }
+            (None, None) => format!("{}: nothing to send", self.command),
+        }
+    }
+}
+
+pub fn format_quote(amount: i128) -> String {
+    format!("{:.6}", amount as f64 / clearing_house::math::constants::QUOTE_PRECISION as f64)
+}
@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::{clock::Clock, sysvar};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::account::from_account;
+use solana_sdk::commitment_config::CommitmentConfig;
+
+use crate::error::CliError;
+
+// where the commands read accounts from
+pub trait AccountSource {
+    // (unix timestamp, slot)
+    fn get_clock(&self) -> Result<(i64, u64), CliError>;
+
+    // None = account doesnt exist
+    fn get_optional_account_data(&self, address: &Pubkey) -> Result<Option<Vec<u8>>, CliError>;
+}
+
+pub struct RpcAccountSource {
+    client: RpcClient,
+}
+
+impl RpcAccountSource {
+    pub fn new(url: &str) -> Self {
+        RpcAccountSource {
+            client: RpcClient::new_with_commitment(url.to_string(), CommitmentConfig::confirmed()),
+        }
+    }
+}
+
+impl AccountSource for RpcAccountSource {
+    fn get_clock(&self) -> Result<(i64, u64), CliError> {
+        let account = self.client.get_account(&sysvar::clock::ID)?;
+        let clock: Clock = from_account(&account).ok_or(CliError::MissingAccount(sysvar::clock::ID))?;
+        Ok((clock.unix_timestamp, clock.slot))
+    }
+
+    fn get_optional_account_data(&self, address: &Pubkey) -> Result<Option<Vec<u8>>, CliError> {
+        let account = self.client
+            .get_account_with_commitment(address, CommitmentConfig::confirmed())?
+            .value;
+        Ok(account.map(|account| account.data))
+    }
+}
+
+// serves fixed accounts, for tests w/o a network
+#[derive(Default)]
+pub struct MemoryAccountSource {
+    pub now: i64,
+    pub slot: u64,
+    pub accounts: HashMap<Pubkey, Vec<u8>>,
+}
+
+impl AccountSource for MemoryAccountSource {
+    fn get_clock(&self) -> Result<(i64, u64), CliError> {
+        Ok((self.now, self.slot))
+    }
+
+    fn get_optional_account_data(&self, address: &Pubkey) -> Result<Option<Vec<u8>>, CliError> {
+        Ok(self.accounts.get(address).cloned())
+    }
+}
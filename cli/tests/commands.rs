@@ -0,0 +1,280 @@
+// the instructions each sending command builds (what --dry-run prints) vs the client's
+// builders, on in-memory accounts: a vault trading market 0 w/ a position in market 1
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::{AccountSerialize, Discriminator};
+use solana_sdk::signature::{Keypair, Signer};
+use spl_associated_token_account::{create_associated_token_account, get_associated_token_address};
+
+use clearing_house::state::market::Markets;
+use clearing_house::state::order_state::OrderState;
+use clearing_house::state::state::State;
+use clearing_house::state::user::{MarketPosition, UserPositions};
+
+use drift_vault::state::{DepositorState, StrategyKind, StrategyParams, VaultState};
+use drift_vault_cli::commands;
+use drift_vault_cli::commands::admin::AdminCommand;
+use drift_vault_cli::{CliContext, MemoryAccountSource};
+use drift_vault_client::{
+    get_clearing_house_state_address, ClearingHouseAccounts, DeployAccounts, VaultInstructions, VaultPdas,
+};
+
+struct Fixture {
+    state: State,
+    oracles: [Pubkey; 2],
+    order_history: Pubkey,
+    source: MemoryAccountSource,
+}
+
+fn serialize_account<T: AccountSerialize>(account: &T) -> Vec<u8> {
+    let mut data = vec![];
+    account.try_serialize(&mut data).unwrap();
+    data
+}
+
+fn serialize_zero_copy_account<T: Discriminator + bytemuck::Pod>(account: &T) -> Vec<u8> {
+    let mut data = T::discriminator().to_vec();
+    data.extend_from_slice(bytemuck::bytes_of(account));
+    data
+}
+
+fn fixture(vault_state: VaultState) -> Fixture {
+    let pdas = VaultPdas::derive(&drift_vault::ID, &clearing_house::ID);
+    let state = State {
+        markets: Pubkey::new_unique(),
+        collateral_mint: Pubkey::new_unique(),
+        collateral_vault: Pubkey::new_unique(),
+        collateral_vault_authority: Pubkey::new_unique(),
+        insurance_vault: Pubkey::new_unique(),
+        insurance_vault_authority: Pubkey::new_unique(),
+        deposit_history: Pubkey::new_unique(),
+        trade_history: Pubkey::new_unique(),
+        funding_payment_history: Pubkey::new_unique(),
+        funding_rate_history: Pubkey::new_unique(),
+        order_state: Pubkey::new_unique(),
+        ..State::default()
+    };
+    let oracles = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let mut markets = Box::new(Markets::default());
+    for (market_index, oracle) in oracles.iter().enumerate() {
+        markets.markets[market_index].initialized = true;
+        markets.markets[market_index].amm.oracle = *oracle;
+    }
+    let mut user_positions = UserPositions { user: pdas.user.0, ..UserPositions::default() };
+    user_positions.positions[0] = MarketPosition {
+        market_index: 1,
+        base_asset_amount: -1,
+        ..MarketPosition::default()
+    };
+    let order_history = Pubkey::new_unique();
+    let order_state = OrderState { order_history, ..OrderState::default() };
+
+    let mut source = MemoryAccountSource::default();
+    source.accounts.insert(pdas.vault_state.0, serialize_account(&vault_state));
+    source.accounts.insert(get_clearing_house_state_address(&clearing_house::ID).0, serialize_account(&state));
+    source.accounts.insert(state.markets, serialize_zero_copy_account(&*markets));
+    source.accounts.insert(pdas.user_positions.0, serialize_zero_copy_account(&user_positions));
+    source.accounts.insert(state.order_state, serialize_account(&order_state));
+    Fixture { state, oracles, order_history, source }
+}
+
+// --dry-run
+fn cli_context(source: MemoryAccountSource, payer: Keypair) -> CliContext {
+    CliContext::with_accounts(
+        "http://localhost:8899",
+        Box::new(source),
+        payer,
+        &drift_vault::ID,
+        &clearing_house::ID,
+        true,
+        false,
+    )
+}
+
+fn vault_instructions(state: &State) -> VaultInstructions {
+    VaultInstructions::new(
+        VaultPdas::derive(&drift_vault::ID, &clearing_house::ID),
+        ClearingHouseAccounts::from_state(get_clearing_house_state_address(&clearing_house::ID).0, state),
+    )
+}
+
+#[test]
+fn init_builds_initialize_vault_w_the_parsed_strategy() {
+    let Fixture { state, source, .. } = fixture(VaultState::default());
+    let ctx = cli_context(source, Keypair::new());
+
+    let spread_markets = vec!["0:10000".to_string(), "1:5000".to_string()];
+    let mut strategy_params = StrategyParams::default();
+    strategy_params.spread_market_indexes[..2].copy_from_slice(&[0, 1]);
+    strategy_params.spread_market_betas[..2].copy_from_slice(&[10_000, 5_000]);
+    assert_eq!(
+        commands::init::build_instructions(&ctx, "funding-spread", &spread_markets).unwrap(),
+        vec![vault_instructions(&state).initialize_vault(&ctx.payer.pubkey(), StrategyKind::FundingSpread, strategy_params)],
+    );
+
+    // a single leg isnt a spread
+    assert!(commands::init::build_instructions(&ctx, "funding-spread", &spread_markets[..1]).is_err());
+    assert!(commands::init::build_instructions(&ctx, "funding-carry", &[]).is_err());
+}
+
+#[test]
+fn deposit_creates_the_missing_accounts_then_deposits() {
+    let payer = Keypair::new();
+    let owner = payer.pubkey();
+    let Fixture { state, oracles, source, .. } = fixture(VaultState::default());
+    let vault = vault_instructions(&state);
+    let vault_mint = vault.pdas.vault_mint.0;
+    let user_collateral_ata = get_associated_token_address(&owner, &state.collateral_mint);
+    let user_vault_ata = get_associated_token_address(&owner, &vault_mint);
+    let ctx = cli_context(source, payer);
+
+    let deploy = DeployAccounts { market_index: 0, max_slippage: 100, oracle: oracles[0], other_oracles: vec![oracles[1]] };
+    assert_eq!(
+        commands::deposit::build_instructions(&ctx, 5_000_000, Some(0), 100).unwrap(),
+        vec![
+            create_associated_token_account(&owner, &owner, &vault_mint),
+            vault.initialize_depositor(&owner),
+            vault.deposit(&owner, &user_collateral_ata, &user_vault_ata, 5_000_000, None, &[oracles[1]], Some(&deploy)),
+        ],
+    );
+}
+
+#[test]
+fn deposit_passes_the_whitelist_token_once_the_accounts_exist() {
+    let payer = Keypair::new();
+    let owner = payer.pubkey();
+    let whitelist_mint = Pubkey::new_unique();
+    let Fixture { state, oracles, mut source, .. } = fixture(VaultState { whitelist_mint, ..VaultState::default() });
+    let vault = vault_instructions(&state);
+    let user_collateral_ata = get_associated_token_address(&owner, &state.collateral_mint);
+    let user_vault_ata = get_associated_token_address(&owner, &vault.pdas.vault_mint.0);
+    source.accounts.insert(user_vault_ata, vec![]);
+    source.accounts.insert(vault.pdas.depositor(&owner).0, serialize_account(&DepositorState::default()));
+    let ctx = cli_context(source, payer);
+
+    let whitelist_token = get_associated_token_address(&owner, &whitelist_mint);
+    assert_eq!(
+        commands::deposit::build_instructions(&ctx, 5_000_000, None, 100).unwrap(),
+        vec![vault.deposit(&owner, &user_collateral_ata, &user_vault_ata, 5_000_000, Some(&whitelist_token), &[oracles[1]], None)],
+    );
+}
+
+#[test]
+fn withdraw_passes_the_market_oracle_and_the_position_oracles() {
+    let payer = Keypair::new();
+    let owner = payer.pubkey();
+    let Fixture { state, oracles, source, .. } = fixture(VaultState::default());
+    let vault = vault_instructions(&state);
+    let ctx = cli_context(source, payer);
+
+    assert_eq!(
+        commands::withdraw::build_instructions(&ctx, 1_000_000, 0).unwrap(),
+        vec![vault.withdraw(
+            &owner,
+            &get_associated_token_address(&owner, &state.collateral_mint),
+            &get_associated_token_address(&owner, &vault.pdas.vault_mint.0),
+            1_000_000,
+            0,
+            &oracles[0],
+            &[oracles[1]],
+        )],
+    );
+}
+
+#[test]
+fn unlock_builds_unlock_shares() {
+    let payer = Keypair::new();
+    let owner = payer.pubkey();
+    let Fixture { state, source, .. } = fixture(VaultState::default());
+    let vault = vault_instructions(&state);
+    let ctx = cli_context(source, payer);
+
+    assert_eq!(
+        commands::unlock::build_instructions(&ctx).unwrap(),
+        vec![vault.unlock_shares(&owner, &get_associated_token_address(&owner, &vault.pdas.vault_mint.0))],
+    );
+}
+
+#[test]
+fn rebalance_builds_update_position_or_its_orders_variant() {
+    let Fixture { state, oracles, source, .. } = fixture(VaultState::default());
+    let ctx = cli_context(source, Keypair::new());
+    assert_eq!(
+        commands::rebalance::build_instructions(&ctx, 0).unwrap(),
+        vec![vault_instructions(&state).update_position(0, &oracles[0], &[oracles[1]])],
+    );
+
+    let Fixture { state, oracles, order_history, source } = fixture(VaultState { use_limit_orders: true, ..VaultState::default() });
+    let ctx = cli_context(source, Keypair::new());
+    assert_eq!(
+        commands::rebalance::build_instructions(&ctx, 0).unwrap(),
+        vec![vault_instructions(&state).update_position_with_orders(0, &oracles[0], &order_history)],
+    );
+}
+
+#[test]
+fn admin_builds_each_update_instruction() {
+    let payer = Keypair::new();
+    let admin = payer.pubkey();
+    let Fixture { state, source, .. } = fixture(VaultState::default());
+    let vault = vault_instructions(&state);
+    let ctx = cli_context(source, payer);
+    let whitelist_mint = Pubkey::new_unique();
+    let mut strategy_params = StrategyParams::default();
+    strategy_params.spread_market_indexes[..2].copy_from_slice(&[2, 3]);
+    strategy_params.spread_market_betas[..2].copy_from_slice(&[10_000, 10_000]);
+
+    let cases: Vec<(AdminCommand, Instruction)> = vec![
+        (
+            AdminCommand::DepositCaps { max_vault_collateral: 1, max_depositor_amount: 2, max_market_share: 3 },
+            vault.update_deposit_caps(&admin, 1, 2, 3),
+        ),
+        (
+            AdminCommand::WhitelistMint { whitelist_mint },
+            vault.update_whitelist_mint(&admin, whitelist_mint),
+        ),
+        (
+            AdminCommand::Lockup { min_holding_period: 86_400, early_withdrawal_fee_numerator: 1, early_withdrawal_fee_denominator: 100 },
+            vault.update_lockup(&admin, 86_400, 1, 100),
+        ),
+        (
+            AdminCommand::IdleBuffer { idle_buffer_numerator: 1, idle_buffer_denominator: 10 },
+            vault.update_idle_buffer(&admin, 1, 10),
+        ),
+        (
+            AdminCommand::ExecutionMode { use_limit_orders: true, limit_order_oracle_offset: 5, max_order_age: 60 },
+            vault.update_execution_mode(&admin, true, 5, 60),
+        ),
+        (
+            AdminCommand::TriggerOrders { stop_loss_numerator: 1, stop_loss_denominator: 10, take_profit_numerator: 2, take_profit_denominator: 10 },
+            vault.update_trigger_orders(&admin, 1, 10, 2, 10),
+        ),
+        (
+            AdminCommand::FundingWindow { funding_window: 600 },
+            vault.update_funding_window(&admin, 600),
+        ),
+        (
+            AdminCommand::Strategy { strategy: "funding-spread".to_string(), spread_markets: vec!["2:10000".to_string(), "3:10000".to_string()] },
+            vault.update_strategy(&admin, StrategyKind::FundingSpread, strategy_params),
+        ),
+        (
+            AdminCommand::VolatilitySizing { min_leverage: 5_000, max_leverage: 10_000, max_volatility: 0 },
+            vault.update_volatility_sizing(&admin, 5_000, 10_000, 0),
+        ),
+        (
+            AdminCommand::FundingHistory { funding_history_length: 24, funding_history_decay: 9_000, funding_history_full_size_rate: 0 },
+            vault.update_funding_history(&admin, 24, 9_000, 0),
+        ),
+        (
+            AdminCommand::TwapStaleness { max_twap_staleness: 600 },
+            vault.update_twap_staleness(&admin, 600),
+        ),
+        (
+            AdminCommand::FundingPauseMode { flatten: true },
+            vault.update_funding_pause_mode(&admin, true),
+        ),
+    ];
+    for (command, expected) in cases {
+        assert_eq!(commands::admin::build_instructions(&ctx, &command).unwrap(), vec![expected]);
+    }
+}
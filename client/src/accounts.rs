@@ -0,0 +1,63 @@
+use anchor_lang::prelude::*;
+use anchor_lang::{AccountDeserialize, Discriminator};
+
+use clearing_house::state::state::State;
+
+// anchor (borsh) accounts: VaultState, DepositorState, State, User, OrderState ...
+pub fn deserialize_account<T: AccountDeserialize>(
+    data: &[u8],
+) -> std::result::Result<T, ProgramError> {
+    T::try_deserialize(&mut &data[..])
+}
+
+// zero copy accounts: Markets, UserPositions, UserOrders ...
+pub fn deserialize_zero_copy_account<T: Discriminator + bytemuck::Pod>(
+    data: &[u8],
+) -> std::result::Result<T, ProgramError> {
+    let account_end = 8 + std::mem::size_of::<T>();
+    if data.len() < account_end || data[..8] != T::discriminator() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    bytemuck::try_from_bytes::<T>(&data[8..account_end])
+        .map(|account| *account)
+        .map_err(|_| ProgramError::InvalidAccountData)
+}
+
+// clearing house accounts the vault's instructions need (stored in its State)
+#[derive(Clone, Copy, Debug)]
+pub struct ClearingHouseAccounts {
+    pub state: Pubkey,
+    pub markets: Pubkey,
+    pub collateral_mint: Pubkey,
+    pub collateral_vault: Pubkey,
+    pub collateral_vault_authority: Pubkey,
+    pub insurance_vault: Pubkey,
+    pub insurance_vault_authority: Pubkey,
+    pub deposit_history: Pubkey,
+    pub trade_history: Pubkey,
+    pub funding_payment_history: Pubkey,
+    pub funding_rate_history: Pubkey,
+    pub order_state: Pubkey,
+}
+
+impl ClearingHouseAccounts {
+    pub fn from_state(
+        state_address: Pubkey, 
+        state: &State,
+    ) -> Self {
+        ClearingHouseAccounts {
+            state: state_address,
+            markets: state.markets,
+            collateral_mint: state.collateral_mint,
+            collateral_vault: state.collateral_vault,
+            collateral_vault_authority: state.collateral_vault_authority,
+            insurance_vault: state.insurance_vault,
+            insurance_vault_authority: state.insurance_vault_authority,
+            deposit_history: state.deposit_history,
+            trade_history: state.trade_history,
+            funding_payment_history: state.funding_payment_history,
+            funding_rate_history: state.funding_rate_history,
+            order_state: state.order_state,
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::InstructionData;
+
+use drift_vault::accounts as vault_accounts;
+use drift_vault::instruction as vault_instruction;
+use drift_vault::{StrategyKind, StrategyParams};
+
+use crate::instructions::VaultInstructions;
+
+// admin instructions (args mean the same as in the program's lib.rs)
+impl VaultInstructions {
+    pub fn update_deposit_caps(
+        &self, 
+        admin: &Pubkey,
+        max_vault_collateral: u128,
+        max_depositor_amount: u64,
+        max_market_share: u128,
+    ) -> Instruction {
+        self.admin_instruction(admin, vault_instruction::UpdateDepositCaps {
+            max_vault_collateral,
+            max_depositor_amount,
+            max_market_share,
+        })
+    }
+
+    pub fn update_whitelist_mint(
+        &self, 
+        admin: &Pubkey,
+        whitelist_mint: Pubkey,
+    ) -> Instruction {
+        self.admin_instruction(admin, vault_instruction::UpdateWhitelistMint { whitelist_mint })
+    }
+
+    pub fn update_lockup(
+        &self, 
+        admin: &Pubkey,
+        min_holding_period: i64,
+        early_withdrawal_fee_numerator: u128,
+        early_withdrawal_fee_denominator: u128,
+    ) -> Instruction {
+        self.admin_instruction(admin, vault_instruction::UpdateLockup {
+            min_holding_period,
+            early_withdrawal_fee_numerator,
+            early_withdrawal_fee_denominator,
+        })
+    }
+
+    pub fn update_idle_buffer(
+        &self, 
+        admin: &Pubkey,
+        idle_buffer_numerator: u128,
+        idle_buffer_denominator: u128,
+    ) -> Instruction {
+        self.admin_instruction(admin, vault_instruction::UpdateIdleBuffer {
+            idle_buffer_numerator,
+            idle_buffer_denominator,
+        })
+    }
+
+    pub fn update_execution_mode(
+        &self, 
+        admin: &Pubkey,
+        use_limit_orders: bool,
+        limit_order_oracle_offset: u128,
+        max_order_age: i64,
+    ) -> Instruction {
+        self.admin_instruction(admin, vault_instruction::UpdateExecutionMode {
+            use_limit_orders,
+            limit_order_oracle_offset,
+            max_order_age,
+        })
+    }
+
+    pub fn update_trigger_orders(
+        &self, 
+        admin: &Pubkey,
+        stop_loss_numerator: u128,
+        stop_loss_denominator: u128,
+        take_profit_numerator: u128,
+        take_profit_denominator: u128,
+    ) -> Instruction {
+        self.admin_instruction(admin, vault_instruction::UpdateTriggerOrders {
+            stop_loss_numerator,
+            stop_loss_denominator,
+            take_profit_numerator,
+            take_profit_denominator,
+        })
+    }
+
+    pub fn update_funding_window(
+        &self, 
+        admin: &Pubkey,
+        funding_window: i64,
+    ) -> Instruction {
+        self.admin_instruction(admin, vault_instruction::UpdateFundingWindow { funding_window })
+    }
+
+    pub fn update_strategy(
+        &self, 
+        admin: &Pubkey,
+        strategy: StrategyKind,
+        strategy_params: StrategyParams,
+    ) -> Instruction {
+        self.admin_instruction(admin, vault_instruction::UpdateStrategy { strategy, strategy_params })
+    }
+
+    pub fn update_volatility_sizing(
+        &self, 
+        admin: &Pubkey,
+        min_leverage: u128,
+        max_leverage: u128,
+        max_volatility: u128,
+    ) -> Instruction {
+        self.admin_instruction(admin, vault_instruction::UpdateVolatilitySizing {
+            min_leverage,
+            max_leverage,
+            max_volatility,
+        })
+    }
+
+    pub fn update_funding_history(
+        &self, 
+        admin: &Pubkey,
+        funding_history_length: u64,
+        funding_history_decay: u128,
+        funding_history_full_size_rate: u128,
+    ) -> Instruction {
+        self.admin_instruction(admin, vault_instruction::UpdateFundingHistory {
+            funding_history_length,
+            funding_history_decay,
+            funding_history_full_size_rate,
+        })
+    }
+
+    pub fn update_twap_staleness(
+        &self, 
+        admin: &Pubkey,
+        max_twap_staleness: i64,
+    ) -> Instruction {
+        self.admin_instruction(admin, vault_instruction::UpdateTwapStaleness { max_twap_staleness })
+    }
+
+    pub fn update_funding_pause_mode(
+        &self, 
+        admin: &Pubkey,
+        flatten_on_funding_pause: bool,
+    ) -> Instruction {
+        self.admin_instruction(admin, vault_instruction::UpdateFundingPauseMode { flatten_on_funding_pause })
+    }
+
+    fn admin_instruction(
+        &self, 
+        admin: &Pubkey,
+        data: impl InstructionData,
+    ) -> Instruction {
+        let accounts = vault_accounts::AdminUpdateVault {
+            admin: *admin,
+            vault_state: self.pdas.vault_state.0,
+        };
+        self.instruction(accounts.to_account_metas(None), data)
+    }
+}
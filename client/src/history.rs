@@ -0,0 +1,67 @@
+use std::convert::TryInto;
+
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+
+use clearing_house::state::history::funding_payment::{FundingPaymentHistory, FundingPaymentRecord};
+
+const FUNDING_PAYMENT_HISTORY_LENGTH: u64 = 1024;
+const FUNDING_PAYMENT_RECORD_SIZE: usize = 184;
+const FUNDING_PAYMENT_RECORDS_OFFSET: usize = 16; // discriminator + head 
+
+// same as drift_vault::funding::read_funding_rate_records but for a user's funding 
+// payments: [discriminator][head: u64][FundingPaymentRecord; 1024], newest first 
+pub fn read_funding_payment_records(
+    data: &[u8],
+    user: &Pubkey,
+    max_records: usize,
+) -> std::result::Result<Vec<FundingPaymentRecord>, ProgramError> {
+    let records_end = FUNDING_PAYMENT_RECORDS_OFFSET + 
+        FUNDING_PAYMENT_HISTORY_LENGTH as usize * FUNDING_PAYMENT_RECORD_SIZE;
+    if data.len() < records_end || data[..8] != FundingPaymentHistory::discriminator() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let head = u64::from_le_bytes(data[8..16].try_into().unwrap());
+
+    let mut records = vec![];
+    for i in 1..=FUNDING_PAYMENT_HISTORY_LENGTH {
+        if records.len() == max_records {
+            break;
+        }
+        let index = (head + FUNDING_PAYMENT_HISTORY_LENGTH - i) % FUNDING_PAYMENT_HISTORY_LENGTH;
+        let start = FUNDING_PAYMENT_RECORDS_OFFSET + index as usize * FUNDING_PAYMENT_RECORD_SIZE;
+        let record = parse_funding_payment_record(&data[start..start + FUNDING_PAYMENT_RECORD_SIZE]);
+
+        let record_id = record.record_id;
+        if record_id == 0 {
+            break;
+        }
+        let record_user = record.user;
+        if record_user == *user {
+            records.push(record);
+        }
+    }
+
+    Ok(records)
+}
+
+fn parse_funding_payment_record(data: &[u8]) -> FundingPaymentRecord {
+    let read_u64 = |start: usize| u64::from_le_bytes(data[start..start + 8].try_into().unwrap());
+    let read_u128 = |start: usize| u128::from_le_bytes(data[start..start + 16].try_into().unwrap());
+    let read_i128 = |start: usize| i128::from_le_bytes(data[start..start + 16].try_into().unwrap());
+    let read_pubkey = |start: usize| Pubkey::new(&data[start..start + 32]);
+
+    FundingPaymentRecord {
+        ts: read_u64(0) as i64,
+        record_id: read_u128(8),
+        user_authority: read_pubkey(24),
+        user: read_pubkey(56),
+        market_index: read_u64(88),
+        funding_payment: read_i128(96),
+        base_asset_amount: read_i128(112),
+        user_last_cumulative_funding: read_i128(128),
+        user_last_funding_rate_ts: read_u64(144) as i64,
+        amm_cumulative_funding_long: read_i128(152),
+        amm_cumulative_funding_short: read_i128(168),
+    }
+}
@@ -0,0 +1,341 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{instruction::Instruction, system_program, sysvar};
+use anchor_lang::InstructionData;
+
+use drift_vault::accounts as vault_accounts;
+use drift_vault::instruction as vault_instruction;
+use drift_vault::{DeployParams, DepositOptionalAccounts, StrategyKind, StrategyParams};
+
+use crate::pda::VaultPdas;
+use crate::accounts::ClearingHouseAccounts;
+
+// builds the vault's instructions w/ the right account lists + nonces
+#[derive(Clone, Copy, Debug)]
+pub struct VaultInstructions {
+    pub pdas: VaultPdas,
+    pub clearing_house: ClearingHouseAccounts,
+}
+
+// deploys a deposit in the same instruction: market_index + max_slippage (vs the oracle, 
+// in SLIPPAGE_PRECISION) are passed to the program, oracle = market_index's oracle, 
+// other_oracles = the strategy's other markets
+#[derive(Clone, Debug)]
+pub struct DeployAccounts {
+    pub market_index: u64,
+    pub max_slippage: u128,
+    pub oracle: Pubkey,
+    pub other_oracles: Vec<Pubkey>,
+}
+
+impl VaultInstructions {
+    pub fn new(
+        pdas: VaultPdas, 
+        clearing_house: ClearingHouseAccounts,
+    ) -> Self {
+        VaultInstructions { pdas, clearing_house }
+    }
+
+    pub fn initialize_vault(
+        &self, 
+        payer: &Pubkey,
+        strategy: StrategyKind,
+        strategy_params: StrategyParams,
+    ) -> Instruction {
+        let pdas = &self.pdas;
+        let accounts = vault_accounts::InitializeVault {
+            payer: *payer,
+            authority: pdas.authority.0,
+            user: pdas.user.0,
+            user_positions: pdas.user_positions.0,
+            user_orders: pdas.user_orders.0,
+            state: self.clearing_house.state,
+            vault_mint: pdas.vault_mint.0,
+            vault_state: pdas.vault_state.0,
+            vault_collateral: pdas.vault_collateral.0,
+            collateral_mint: self.clearing_house.collateral_mint,
+            rent: sysvar::rent::ID,
+            system_program: system_program::ID,
+            token_program: anchor_spl::token::ID,
+            clearing_house_program: pdas.clearing_house_program_id,
+        };
+        let data = vault_instruction::InitializeVault {
+            user_nonce: pdas.user.1,
+            authority_nonce: pdas.authority.1,
+            user_positions_nonce: pdas.user_positions.1,
+            user_orders_nonce: pdas.user_orders.1,
+            strategy,
+            strategy_params,
+        };
+        self.instruction(accounts.to_account_metas(None), data)
+    }
+
+    pub fn initialize_depositor(
+        &self, 
+        owner: &Pubkey,
+    ) -> Instruction {
+        let accounts = vault_accounts::InitializeDepositor {
+            owner: *owner,
+            depositor_state: self.pdas.depositor(owner).0,
+            locked_shares: self.pdas.locked_shares(owner).0,
+            vault_mint: self.pdas.vault_mint.0,
+            authority: self.pdas.authority.0,
+            rent: sysvar::rent::ID,
+            system_program: system_program::ID,
+            token_program: anchor_spl::token::ID,
+        };
+        self.instruction(accounts.to_account_metas(None), vault_instruction::InitializeDepositor {})
+    }
+
+    // remaining accounts: [whitelist token] [update_position accounts + other oracles] [oracles]
+    // (oracles = oracles of the vault's positions (share pricing))
+    #[allow(clippy::too_many_arguments)]
+    pub fn deposit(
+        &self, 
+        owner: &Pubkey,
+        user_collateral_ata: &Pubkey,
+        user_vault_ata: &Pubkey,
+        deposit_amount: u64,
+        whitelist_token: Option<&Pubkey>,
+        oracles: &[Pubkey],
+        deploy: Option<&DeployAccounts>,
+    ) -> Instruction {
+        let pdas = &self.pdas;
+        let clearing_house = &self.clearing_house;
+        let accounts = vault_accounts::Deposit {
+            owner: *owner,
+            vault_collateral_ata: pdas.vault_collateral.0,
+            user_collateral_ata: *user_collateral_ata,
+            user_vault_ata: *user_vault_ata,
+            locked_shares: pdas.locked_shares(owner).0,
+            vault_state: pdas.vault_state.0,
+            vault_mint: pdas.vault_mint.0,
+            depositor_state: pdas.depositor(owner).0,
+            authority: pdas.authority.0,
+            user_positions: pdas.user_positions.0,
+            user: pdas.user.0,
+            state: clearing_house.state,
+            collateral_vault: clearing_house.collateral_vault,
+            markets: clearing_house.markets,
+            funding_payment_history: clearing_house.funding_payment_history,
+            deposit_history: clearing_house.deposit_history,
+            clearing_house_program: pdas.clearing_house_program_id,
+            token_program: anchor_spl::token::ID,
+        };
+        let mut account_metas = accounts.to_account_metas(None);
+        if let Some(whitelist_token) = whitelist_token {
+            account_metas.push(AccountMeta::new_readonly(*whitelist_token, false));
+        }
+        if let Some(deploy) = deploy {
+            account_metas.extend(self.update_position_accounts(&deploy.oracle).to_account_metas(None));
+            account_metas.extend(oracle_metas(&deploy.other_oracles));
+        }
+        account_metas.extend(oracle_metas(oracles));
+
+        let data = vault_instruction::Deposit {
+            deposit_amount,
+            authority_nonce: pdas.authority.1,
+            optional_accounts: DepositOptionalAccounts {
+                whitelist_token: whitelist_token.is_some(),
+                deploy: deploy.map(|deploy| DeployParams {
+                    market_index: deploy.market_index,
+                    max_slippage: deploy.max_slippage,
+                }),
+            },
+        };
+        self.instruction(account_metas, data)
+    }
+
+    // other_oracles = oracles of the vault's positions in other markets (share pricing)
+    #[allow(clippy::too_many_arguments)]
+    pub fn withdraw(
+        &self, 
+        owner: &Pubkey,
+        user_collateral_ata: &Pubkey,
+        user_vault_ata: &Pubkey,
+        burn_amount: u128,
+        market_index: u64,
+        oracle: &Pubkey,
+        other_oracles: &[Pubkey],
+    ) -> Instruction {
+        let accounts = vault_accounts::Withdraw {
+            owner: *owner,
+            user_collateral_ata: *user_collateral_ata,
+            user_vault_ata: *user_vault_ata,
+            locked_shares: self.pdas.locked_shares(owner).0,
+            vault_mint: self.pdas.vault_mint.0,
+            depositor_state: self.pdas.depositor(owner).0,
+            update_position: self.update_position_accounts(oracle),
+        };
+        let mut account_metas = accounts.to_account_metas(None);
+        account_metas.extend(oracle_metas(other_oracles));
+
+        let data = vault_instruction::Withdraw {
+            burn_amount,
+            market_index,
+            authority_nonce: self.pdas.authority.1,
+        };
+        self.instruction(account_metas, data)
+    }
+
+    // vault tokens minted within the lockup => user_vault_ata (once it ends)
+    pub fn unlock_shares(
+        &self, 
+        owner: &Pubkey,
+        user_vault_ata: &Pubkey,
+    ) -> Instruction {
+        let accounts = vault_accounts::UnlockShares {
+            owner: *owner,
+            user_vault_ata: *user_vault_ata,
+            locked_shares: self.pdas.locked_shares(owner).0,
+            vault_state: self.pdas.vault_state.0,
+            depositor_state: self.pdas.depositor(owner).0,
+            authority: self.pdas.authority.0,
+            token_program: anchor_spl::token::ID,
+        };
+        let data = vault_instruction::UnlockShares {
+            authority_nonce: self.pdas.authority.1,
+        };
+        self.instruction(accounts.to_account_metas(None), data)
+    }
+
+    // other_oracles = oracles of the strategy's other markets
+    pub fn update_position(
+        &self, 
+        market_index: u64,
+        oracle: &Pubkey,
+        other_oracles: &[Pubkey],
+    ) -> Instruction {
+        let mut account_metas = self.update_position_accounts(oracle).to_account_metas(None);
+        account_metas.extend(oracle_metas(other_oracles));
+
+        let data = vault_instruction::UpdatePosition {
+            market_index,
+            authority_nonce: self.pdas.authority.1,
+        };
+        self.instruction(account_metas, data)
+    }
+
+    // order_history = the clearing house OrderState's order_history
+    pub fn update_position_with_orders(
+        &self, 
+        market_index: u64,
+        oracle: &Pubkey,
+        order_history: &Pubkey,
+    ) -> Instruction {
+        let data = vault_instruction::UpdatePositionWithOrders {
+            market_index,
+            authority_nonce: self.pdas.authority.1,
+        };
+        self.instruction(self.update_position_with_orders_accounts(oracle, order_history), data)
+    }
+
+    pub fn refresh_orders(
+        &self, 
+        market_index: u64,
+        oracle: &Pubkey,
+        order_history: &Pubkey,
+    ) -> Instruction {
+        let data = vault_instruction::RefreshOrders {
+            market_index,
+            authority_nonce: self.pdas.authority.1,
+        };
+        self.instruction(self.update_position_with_orders_accounts(oracle, order_history), data)
+    }
+
+    // ** clearing house cranks (permissionless) 
+    pub fn update_funding_rate(
+        &self, 
+        market_index: u64,
+        oracle: &Pubkey,
+    ) -> Instruction {
+        let clearing_house = &self.clearing_house;
+        let accounts = clearing_house::accounts::UpdateFundingRate {
+            state: clearing_house.state,
+            markets: clearing_house.markets,
+            oracle: *oracle,
+            funding_rate_history: clearing_house.funding_rate_history,
+        };
+        Instruction {
+            program_id: self.pdas.clearing_house_program_id,
+            accounts: accounts.to_account_metas(None),
+            data: clearing_house::instruction::UpdateFundingRate { market_index }.data(),
+        }
+    }
+
+    // settles the vault user's funding payments 
+    pub fn settle_funding_payment(&self) -> Instruction {
+        let clearing_house = &self.clearing_house;
+        let accounts = clearing_house::accounts::SettleFunding {
+            state: clearing_house.state,
+            user: self.pdas.user.0,
+            markets: clearing_house.markets,
+            user_positions: self.pdas.user_positions.0,
+            funding_payment_history: clearing_house.funding_payment_history,
+        };
+        Instruction {
+            program_id: self.pdas.clearing_house_program_id,
+            accounts: accounts.to_account_metas(None),
+            data: clearing_house::instruction::SettleFundingPayment {}.data(),
+        }
+    }
+
+    fn update_position_with_orders_accounts(
+        &self, 
+        oracle: &Pubkey,
+        order_history: &Pubkey,
+    ) -> Vec<AccountMeta> {
+        vault_accounts::UpdatePositionWithOrders {
+            update_position: self.update_position_accounts(oracle),
+            order_state: self.clearing_house.order_state,
+            user_orders: self.pdas.user_orders.0,
+            order_history: *order_history,
+        }.to_account_metas(None)
+    }
+
+    pub fn update_position_accounts(
+        &self, 
+        oracle: &Pubkey,
+    ) -> vault_accounts::UpdatePosition {
+        let pdas = &self.pdas;
+        let clearing_house = &self.clearing_house;
+        vault_accounts::UpdatePosition {
+            authority: pdas.authority.0,
+            user_positions: pdas.user_positions.0,
+            vault_state: pdas.vault_state.0,
+            vault_collateral_ata: pdas.vault_collateral.0,
+            state: clearing_house.state,
+            user: pdas.user.0,
+            markets: clearing_house.markets,
+            trade_history: clearing_house.trade_history,
+            funding_payment_history: clearing_house.funding_payment_history,
+            funding_rate_history: clearing_house.funding_rate_history,
+            oracle: *oracle,
+            collateral_vault: clearing_house.collateral_vault,
+            collateral_vault_authority: clearing_house.collateral_vault_authority,
+            deposit_history: clearing_house.deposit_history,
+            insurance_vault: clearing_house.insurance_vault,
+            insurance_vault_authority: clearing_house.insurance_vault_authority,
+            clearing_house_program: pdas.clearing_house_program_id,
+            token_program: anchor_spl::token::ID,
+        }
+    }
+
+    pub(crate) fn instruction(
+        &self, 
+        accounts: Vec<AccountMeta>, 
+        data: impl InstructionData,
+    ) -> Instruction {
+        Instruction {
+            program_id: self.pdas.program_id,
+            accounts,
+            data: data.data(),
+        }
+    }
+}
+
+fn oracle_metas(oracles: &[Pubkey]) -> Vec<AccountMeta> {
+    oracles
+        .iter()
+        .map(|oracle| AccountMeta::new_readonly(*oracle, false))
+        .collect()
+}
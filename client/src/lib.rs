@@ -0,0 +1,14 @@
+// client side of the drift vault: PDAs, instruction builders + account decoding 
+// (the rust equivalent of the ts in tests/ + ts/)
+pub mod pda;
+pub mod accounts;
+pub mod instructions;
+pub mod admin;
+pub mod nav;
+pub mod history;
+
+pub use pda::*;
+pub use accounts::*;
+pub use instructions::*;
+pub use nav::*;
+pub use history::*;
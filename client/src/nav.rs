@@ -0,0 +1,299 @@
+use std::cmp::{max, min};
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Mint, TokenAccount};
+
+use clearing_house::state::market::{Markets, AMM, OraclePriceData};
+use clearing_house::state::state::State;
+use clearing_house::state::user::{User, UserPositions};
+use clearing_house::math::position::{
+    calculate_base_asset_value_and_pnl, 
+    calculate_base_asset_value_and_pnl_with_oracle_price,
+};
+use clearing_house::math::funding::calculate_funding_payment;
+use clearing_house::math::casting::cast_to_i128;
+use clearing_house::math::constants::{
+    QUOTE_PRECISION, MARGIN_PRECISION, FUNDING_PAYMENT_PRECISION, ONE_HOUR, TWENTYFOUR_HOUR,
+};
+
+use drift_vault::state::VaultState;
+use drift_vault::error::VaultErrorCode;
+use drift_vault::funding::read_funding_rate_records;
+use drift_vault::instructions::calculate_idle_buffer_target;
+use drift_vault::strategy::{
+    get_strategy, StrategySnapshot, Trade, 
+    calculate_collateral_liabilities, calculate_collateral_with_oracle_prices, 
+    calculate_trades, get_stale_market_indexes, calculate_rebalance_targets,
+};
+use drift_vault::twap::{MarketTwaps, calculate_fresh_twaps, validate_twap_staleness};
+
+use crate::accounts::{deserialize_account, deserialize_zero_copy_account};
+
+// raw account data of the vault at one point in time 
+pub struct VaultAccountsData<'a> {
+    pub vault_state: &'a [u8],
+    pub vault_mint: &'a [u8],
+    pub vault_collateral: &'a [u8], // idle buffer 
+    pub state: &'a [u8],
+    pub user: &'a [u8],
+    pub user_positions: &'a [u8],
+    pub markets: &'a [u8],
+    // (oracle address, data) of the vault's markets 
+    pub oracles: &'a [(Pubkey, &'a [u8])],
+    // only needed w/ the funding history signal on 
+    pub funding_rate_history: Option<&'a [u8]>,
+}
+
+#[derive(Clone, Debug)]
+pub struct MarketExposure {
+    pub market_index: u64,
+    pub base_asset_amount: i128,
+    pub base_asset_value: u128,
+    pub unrealized_pnl: i128, // amm valuation 
+    pub oracle_unrealized_pnl: i128,
+    pub pending_funding: i128, // unsettled funding payment (+ = received)
+    pub predicted_funding_rate: i128, // next funding update w/ the twaps extended to now 
+}
+
+#[derive(Clone, Debug)]
+pub struct VaultNav {
+    // lower of the amm / oracle valuation + idle buffer (what withdraw prices with)
+    pub nav: u128,
+    // higher of the amm / oracle valuation + idle buffer (what deposit prices with)
+    pub deposit_nav: u128,
+    pub nav_per_share: u128, // QUOTE_PRECISION 
+    pub amm_collateral: u128,
+    pub oracle_collateral: u128,
+    pub idle_amount: u128,
+    pub liabilities: u128,
+    // (collateral + unrealized pnl) / position value like the clearing house's 
+    // liquidation check (MARGIN_PRECISION, u128::MAX = no positions)
+    pub margin_ratio: u128,
+    pub exposures: Vec<MarketExposure>,
+    // what update_position(market_index) would do right now: 
+    // close the positions in these markets, then make the trades 
+    pub closes: Vec<u64>,
+    pub trades: Vec<Trade>,
+}
+
+pub fn calculate_vault_nav(
+    accounts: &VaultAccountsData,
+    market_index: u64,
+    now: i64,
+    slot: u64,
+) -> std::result::Result<VaultNav, ProgramError> {
+    let vault_state: VaultState = deserialize_account(accounts.vault_state)?;
+    let vault_mint: Mint = deserialize_account(accounts.vault_mint)?;
+    let vault_collateral: TokenAccount = deserialize_account(accounts.vault_collateral)?;
+    let state: State = deserialize_account(accounts.state)?;
+    let user: User = deserialize_account(accounts.user)?;
+    let user_positions: UserPositions = deserialize_zero_copy_account(accounts.user_positions)?;
+    let markets: Box<Markets> = Box::new(deserialize_zero_copy_account(accounts.markets)?);
+
+    // 1. nav (same as withdraw / deposit)
+    let [amm_collateral, liabilities] = calculate_collateral_liabilities(&user, &user_positions, &markets);
+    let mut oracle_prices = vec![];
+    let mut exposures = vec![];
+    let mut unrealized_pnl_amount: i128 = 0;
+    for market_position in user_positions.positions.iter() {
+        if market_position.base_asset_amount == 0 {
+            continue;
+        }
+        let position_market_index = market_position.market_index;
+        let amm = &markets.get_market(position_market_index).amm;
+        let oracle_price_data = get_oracle_price_data(amm, accounts.oracles, slot)?;
+        oracle_prices.push((position_market_index, oracle_price_data.price));
+
+        let (base_asset_value, unrealized_pnl) = calculate_base_asset_value_and_pnl(market_position, amm)?;
+        unrealized_pnl_amount += unrealized_pnl;
+        let (_, oracle_unrealized_pnl) = 
+            calculate_base_asset_value_and_pnl_with_oracle_price(market_position, oracle_price_data.price)?;
+
+        let amm_cumulative_funding_rate = if market_position.base_asset_amount > 0 {
+            amm.cumulative_funding_rate_long
+        } else {
+            amm.cumulative_funding_rate_short
+        };
+        let pending_funding = calculate_funding_payment(amm_cumulative_funding_rate, market_position)?;
+
+        let twaps = calculate_fresh_twaps(position_market_index, amm, oracle_price_data.price, now)?;
+        exposures.push(MarketExposure {
+            market_index: position_market_index,
+            base_asset_amount: market_position.base_asset_amount,
+            base_asset_value,
+            unrealized_pnl,
+            oracle_unrealized_pnl,
+            pending_funding,
+            predicted_funding_rate: calculate_predicted_funding_rate(amm, &twaps)?,
+        });
+    }
+    let oracle_collateral = calculate_collateral_with_oracle_prices(&user, &user_positions, &oracle_prices)?;
+
+    let margin_ratio = match liabilities {
+        0 => u128::MAX, 
+        _ => (cast_to_i128(user.collateral)? + unrealized_pnl_amount).max(0) as u128 * MARGIN_PRECISION / liabilities,
+    };
+
+    let idle_amount = vault_collateral.amount as u128;
+    let nav = min(amm_collateral, oracle_collateral) + idle_amount;
+    let deposit_nav = max(amm_collateral, oracle_collateral) + idle_amount;
+    let nav_per_share = match vault_mint.supply {
+        0 => 0, 
+        supply => nav
+            .checked_mul(QUOTE_PRECISION).unwrap()
+            .checked_div(supply as u128).unwrap(),
+    };
+
+    // 2. the trades update_position would make 
+    let (closes, trades) = calculate_update_position_trades(
+        &vault_state, &state, user, &user_positions, &markets, 
+        idle_amount, accounts, market_index, now, slot,
+    )?;
+
+    Ok(VaultNav {
+        nav,
+        deposit_nav,
+        nav_per_share,
+        amm_collateral,
+        oracle_collateral,
+        idle_amount,
+        liabilities,
+        margin_ratio,
+        exposures,
+        closes,
+        trades,
+    })
+}
+
+// mirrors UpdatePosition::prepare_rebalance w/o the CPIs 
+#[allow(clippy::too_many_arguments)]
+fn calculate_update_position_trades(
+    vault_state: &VaultState,
+    state: &State,
+    mut user: User,
+    user_positions: &UserPositions,
+    markets: &Markets,
+    idle_amount: u128,
+    accounts: &VaultAccountsData,
+    market_index: u64,
+    now: i64,
+    slot: u64,
+) -> std::result::Result<(Vec<u64>, Vec<Trade>), ProgramError> {
+    // update_position reverts while the exchange is paused 
+    if state.exchange_paused {
+        return Ok((vec![], vec![]));
+    }
+    if state.funding_paused && !vault_state.flatten_on_funding_pause {
+        return Ok((vec![], vec![]));
+    }
+
+    // the idle buffer is rebalanced first (moves collateral in / out of drift)
+    if vault_state.idle_buffer_numerator > 0 {
+        let [collateral_amount, ..] = calculate_collateral_liabilities(&user, user_positions, markets);
+        let idle_target = calculate_idle_buffer_target(collateral_amount + idle_amount, vault_state);
+        user.collateral = (user.collateral + idle_amount).saturating_sub(idle_target);
+    }
+
+    let strategy = get_strategy(vault_state, market_index);
+    let funding_rate_records = match (state.funding_paused, vault_state.funding_history_length) {
+        (true, _) | (_, 0) => vec![], 
+        (false, funding_history_length) => read_funding_rate_records(
+            accounts.funding_rate_history.ok_or(ProgramError::NotEnoughAccountKeys)?, 
+            market_index, 
+            funding_history_length as usize,
+        )?,
+    };
+    let mut twaps = vec![];
+    if !state.funding_paused {
+        for strategy_market_index in strategy.get_market_indexes() {
+            let market = markets.get_market(strategy_market_index);
+            if !market.initialized {
+                continue;
+            }
+            validate_twap_staleness(&market.amm, now, vault_state.max_twap_staleness)?;
+            let oracle_price_data = get_oracle_price_data(&market.amm, accounts.oracles, slot)?;
+            twaps.push(calculate_fresh_twaps(strategy_market_index, &market.amm, oracle_price_data.price, now)?);
+        }
+    }
+
+    let snapshot = StrategySnapshot {
+        markets, 
+        user: &user, 
+        user_positions, 
+        vault_state, 
+        now, 
+        funding_rate_records: &funding_rate_records, 
+        twaps: &twaps, 
+    };
+    let targets = match calculate_rebalance_targets(
+        &*strategy, 
+        &snapshot, 
+        state.funding_paused, 
+        &state.oracle_guard_rails.validity, 
+        |target_market_index| get_oracle_price_data(&markets.get_market(target_market_index).amm, accounts.oracles, slot),
+    )? {
+        Some(targets) => targets, 
+        None => return Ok((vec![], vec![])),
+    };
+
+    Ok((
+        get_stale_market_indexes(&targets, user_positions), 
+        calculate_trades(&targets, user_positions, markets),
+    ))
+}
+
+// the strategy's markets + the markets the vault has a position in 
+// (the oracles update_position / withdraw need)
+pub fn get_vault_market_indexes(
+    vault_state: &VaultState,
+    market_index: u64,
+    user_positions: &UserPositions,
+) -> Vec<u64> {
+    let mut market_indexes = get_strategy(vault_state, market_index).get_market_indexes();
+    for market_position in user_positions.positions.iter() {
+        let position_market_index = market_position.market_index;
+        if market_position.base_asset_amount != 0 && !market_indexes.contains(&position_market_index) {
+            market_indexes.push(position_market_index);
+        }
+    }
+    market_indexes
+}
+
+// mirrors controller::funding::update_funding_rate (w/ the twaps extended to now)
+pub fn calculate_predicted_funding_rate(
+    amm: &AMM,
+    twaps: &MarketTwaps,
+) -> std::result::Result<i128, ProgramError> {
+    let period_adjustment = TWENTYFOUR_HOUR / max(ONE_HOUR, amm.funding_period);
+    let price_spread = cast_to_i128(twaps.mark_price_twap)?
+        .checked_sub(twaps.oracle_price_twap)
+        .ok_or(ProgramError::InvalidArgument)?;
+
+    // clamped to 3% like the clearing house 
+    let max_price_spread = twaps.oracle_price_twap / 33;
+    let clamped_price_spread = max(-max_price_spread, min(price_spread, max_price_spread));
+
+    Ok(clamped_price_spread
+        .checked_mul(FUNDING_PAYMENT_PRECISION as i128)
+        .ok_or(ProgramError::InvalidArgument)?
+        / period_adjustment as i128)
+}
+
+// parse an oracle account w/ the market's own oracle source (pyth / switchboard)
+pub fn get_oracle_price_data(
+    amm: &AMM,
+    oracles: &[(Pubkey, &[u8])],
+    slot: u64,
+) -> std::result::Result<OraclePriceData, ProgramError> {
+    let oracle_key = amm.oracle;
+    let (key, data) = oracles
+        .iter()
+        .find(|(key, _)| *key == oracle_key)
+        .ok_or(VaultErrorCode::OracleNotFound)?;
+
+    let mut lamports = 0;
+    let mut data = data.to_vec();
+    let owner = Pubkey::default();
+    let oracle = AccountInfo::new(key, false, false, &mut lamports, &mut data, &owner, false, 0);
+    Ok(amm.get_oracle_price(&oracle, slot)?)
+}
@@ -0,0 +1,77 @@
+use anchor_lang::prelude::Pubkey;
+
+// vault PDAs (+ their bump nonces which the instructions take as args)
+#[derive(Clone, Copy, Debug)]
+pub struct VaultPdas {
+    pub program_id: Pubkey,
+    pub clearing_house_program_id: Pubkey,
+
+    pub authority: (Pubkey, u8),
+    pub user_positions: (Pubkey, u8),
+    pub vault_mint: (Pubkey, u8),
+    pub vault_state: (Pubkey, u8),
+    pub vault_collateral: (Pubkey, u8),
+
+    // clearing house PDAs of the vault's drift account 
+    pub user: (Pubkey, u8),
+    pub user_orders: (Pubkey, u8),
+}
+
+impl VaultPdas {
+    pub fn derive(
+        program_id: &Pubkey, 
+        clearing_house_program_id: &Pubkey,
+    ) -> Self {
+        let authority = find_vault_address(b"authority", program_id);
+        let user = get_user_address(&authority.0, clearing_house_program_id);
+        VaultPdas {
+            program_id: *program_id,
+            clearing_house_program_id: *clearing_house_program_id,
+            authority,
+            user_positions: find_vault_address(b"user_positions", program_id),
+            vault_mint: find_vault_address(b"vault_mint", program_id),
+            vault_state: find_vault_address(b"vault_state", program_id),
+            vault_collateral: find_vault_address(b"vault_collateral", program_id),
+            user,
+            user_orders: get_user_orders_address(&user.0, clearing_house_program_id),
+        }
+    }
+
+    pub fn depositor(&self, owner: &Pubkey) -> (Pubkey, u8) {
+        get_depositor_address(owner, &self.program_id)
+    }
+
+    pub fn locked_shares(&self, owner: &Pubkey) -> (Pubkey, u8) {
+        get_locked_shares_address(owner, &self.program_id)
+    }
+}
+
+fn find_vault_address(seed: &[u8], program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[seed], program_id)
+}
+
+pub fn get_depositor_address(owner: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"depositor", owner.as_ref()], program_id)
+}
+
+// holds the owner's vault tokens minted within the lockup 
+pub fn get_locked_shares_address(owner: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"locked_shares", owner.as_ref()], program_id)
+}
+
+// ** clearing house PDAs (mirrors sdk/src/addresses.ts)
+pub fn get_clearing_house_state_address(clearing_house_program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"clearing_house"], clearing_house_program_id)
+}
+
+pub fn get_order_state_address(clearing_house_program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"order_state"], clearing_house_program_id)
+}
+
+pub fn get_user_address(authority: &Pubkey, clearing_house_program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"user", authority.as_ref()], clearing_house_program_id)
+}
+
+pub fn get_user_orders_address(user: &Pubkey, clearing_house_program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"user_orders", user.as_ref()], clearing_house_program_id)
+}
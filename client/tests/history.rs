@@ -0,0 +1,85 @@
+// reading FundingPaymentHistory w/ the clearing house's own (packed) layout
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+
+use clearing_house::state::history::funding_payment::{FundingPaymentHistory, FundingPaymentRecord};
+use drift_vault_client::read_funding_payment_records;
+
+const HISTORY_LENGTH: usize = 1024;
+
+// every field distinct + non zero so a wrong offset cant go unnoticed
+fn record(record_id: u128, user: Pubkey) -> FundingPaymentRecord {
+    FundingPaymentRecord {
+        ts: 1_650_000_000 + record_id as i64,
+        record_id,
+        user_authority: Pubkey::new_unique(),
+        user,
+        market_index: 3,
+        funding_payment: -12_345_678_901_234,
+        base_asset_amount: 98_765_432_109_876,
+        user_last_cumulative_funding: -555_555_555_555,
+        user_last_funding_rate_ts: 1_649_999_999,
+        amm_cumulative_funding_long: 777_777_777_777,
+        amm_cumulative_funding_short: -888_888_888_888,
+    }
+}
+
+// account data as the clearing house writes it: records appended from head
+fn history_data(records: &[FundingPaymentRecord], head: usize) -> Vec<u8> {
+    let mut history = vec![FundingPaymentRecord::default(); HISTORY_LENGTH];
+    for (i, record) in records.iter().enumerate() {
+        history[(head + HISTORY_LENGTH - records.len() + i) % HISTORY_LENGTH] = *record;
+    }
+    let mut data = FundingPaymentHistory::discriminator().to_vec();
+    data.extend_from_slice(&(head as u64).to_le_bytes());
+    for record in history.iter() {
+        data.extend_from_slice(bytemuck::bytes_of(record));
+    }
+    assert_eq!(data.len(), 8 + std::mem::size_of::<FundingPaymentHistory>());
+    data
+}
+
+#[test]
+fn round_trips_every_field() {
+    let user = Pubkey::new_unique();
+    let expected = record(1, user);
+    let data = history_data(&[expected], 1);
+
+    let records = read_funding_payment_records(&data, &user, 10).unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(bytemuck::bytes_of(&records[0]), bytemuck::bytes_of(&expected));
+}
+
+#[test]
+fn reads_the_users_records_newest_first() {
+    let user = Pubkey::new_unique();
+    let other_user = Pubkey::new_unique();
+    let records: Vec<FundingPaymentRecord> = (1..=5)
+        .map(|record_id| record(record_id, if record_id % 2 == 0 { other_user } else { user }))
+        .collect();
+
+    // the ring buffer wraps around: head = 2 => records at 1021 .. 1023, 0, 1
+    let data = history_data(&records, 2);
+    let record_ids = |max_records| -> Vec<u128> {
+        read_funding_payment_records(&data, &user, max_records)
+            .unwrap()
+            .iter()
+            .map(|record| record.record_id)
+            .collect()
+    };
+    assert_eq!(record_ids(10), vec![5, 3, 1]);
+    assert_eq!(record_ids(2), vec![5, 3]);
+    assert!(read_funding_payment_records(&data, &Pubkey::new_unique(), 10).unwrap().is_empty());
+}
+
+#[test]
+fn rejects_other_accounts() {
+    let data = history_data(&[], 0);
+    let user = Pubkey::new_unique();
+    assert!(read_funding_payment_records(&data, &user, 10).unwrap().is_empty());
+    assert!(read_funding_payment_records(&data[..data.len() - 1], &user, 10).is_err());
+
+    let mut data = data;
+    data[0] ^= 1;
+    assert!(read_funding_payment_records(&data, &user, 10).is_err());
+}
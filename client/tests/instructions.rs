@@ -0,0 +1,176 @@
+// account lists + data of the instruction builders: the fixed accounts of each 
+// ix, then its remaining accounts in the order the program reads them
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hash;
+use anchor_lang::solana_program::instruction::Instruction;
+
+use drift_vault::instruction as vault_instruction;
+use drift_vault_client::{ClearingHouseAccounts, DeployAccounts, VaultInstructions, VaultPdas};
+
+fn vault_instructions() -> VaultInstructions {
+    let pdas = VaultPdas::derive(&drift_vault::ID, &clearing_house::ID);
+    let clearing_house = ClearingHouseAccounts {
+        state: Pubkey::new_unique(),
+        markets: Pubkey::new_unique(),
+        collateral_mint: Pubkey::new_unique(),
+        collateral_vault: Pubkey::new_unique(),
+        collateral_vault_authority: Pubkey::new_unique(),
+        insurance_vault: Pubkey::new_unique(),
+        insurance_vault_authority: Pubkey::new_unique(),
+        deposit_history: Pubkey::new_unique(),
+        trade_history: Pubkey::new_unique(),
+        funding_payment_history: Pubkey::new_unique(),
+        funding_rate_history: Pubkey::new_unique(),
+        order_state: Pubkey::new_unique(),
+    };
+    VaultInstructions::new(pdas, clearing_house)
+}
+
+// anchor's sighash + the borsh args
+fn decode<T: AnchorDeserialize>(ix: &Instruction, name: &str) -> T {
+    let sighash = &hash(format!("global:{}", name).as_bytes()).to_bytes()[..8];
+    assert_eq!(&ix.data[..8], sighash);
+    T::try_from_slice(&ix.data[8..]).unwrap()
+}
+
+fn readonly(keys: &[Pubkey]) -> Vec<AccountMeta> {
+    keys.iter().map(|key| AccountMeta::new_readonly(*key, false)).collect()
+}
+
+#[test]
+fn builds_deposit_w_its_optional_accounts_in_order() {
+    let vault = vault_instructions();
+    let pdas = vault.pdas;
+    let owner = Pubkey::new_unique();
+    let (user_collateral_ata, user_vault_ata) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let whitelist_token = Pubkey::new_unique();
+    let oracles = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let deploy = DeployAccounts {
+        market_index: 1,
+        max_slippage: 100,
+        oracle: Pubkey::new_unique(),
+        other_oracles: vec![Pubkey::new_unique()],
+    };
+
+    let ix = vault.deposit(
+        &owner, &user_collateral_ata, &user_vault_ata, 5_000_000, Some(&whitelist_token), &oracles, Some(&deploy),
+    );
+    assert_eq!(ix.program_id, drift_vault::ID);
+    assert_eq!(ix.accounts[0], AccountMeta::new_readonly(owner, true));
+    assert_eq!(ix.accounts[1], AccountMeta::new(pdas.vault_collateral.0, false));
+    assert_eq!(ix.accounts[2], AccountMeta::new(user_collateral_ata, false));
+    assert_eq!(ix.accounts[3], AccountMeta::new(user_vault_ata, false));
+    assert_eq!(ix.accounts[4], AccountMeta::new(pdas.locked_shares(&owner).0, false));
+    assert_eq!(ix.accounts.iter().filter(|meta| meta.is_signer).count(), 1);
+
+    // [whitelist token] [update_position accounts + other oracles] [oracles]
+    let update_position = vault.update_position_accounts(&deploy.oracle).to_account_metas(None);
+    let mut remaining_accounts = readonly(&[whitelist_token]);
+    remaining_accounts.extend(update_position);
+    remaining_accounts.extend(readonly(&deploy.other_oracles));
+    remaining_accounts.extend(readonly(&oracles));
+    let fixed_accounts = ix.accounts.len() - remaining_accounts.len();
+    assert_eq!(fixed_accounts, 18);
+    assert_eq!(ix.accounts[fixed_accounts..], remaining_accounts[..]);
+
+    let data: vault_instruction::Deposit = decode(&ix, "deposit");
+    assert_eq!(data.deposit_amount, 5_000_000);
+    assert_eq!(data.authority_nonce, pdas.authority.1);
+    assert!(data.optional_accounts.whitelist_token);
+    let deploy_params = data.optional_accounts.deploy.unwrap();
+    assert_eq!((deploy_params.market_index, deploy_params.max_slippage), (1, 100));
+
+    // w/o the optional accounts only the oracles follow
+    let ix = vault.deposit(&owner, &user_collateral_ata, &user_vault_ata, 1, None, &oracles, None);
+    assert_eq!(ix.accounts[fixed_accounts..], readonly(&oracles)[..]);
+    let data: vault_instruction::Deposit = decode(&ix, "deposit");
+    assert!(!data.optional_accounts.whitelist_token);
+    assert!(data.optional_accounts.deploy.is_none());
+}
+
+#[test]
+fn builds_withdraw_w_the_update_position_accounts() {
+    let vault = vault_instructions();
+    let pdas = vault.pdas;
+    let owner = Pubkey::new_unique();
+    let (user_collateral_ata, user_vault_ata) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let oracle = Pubkey::new_unique();
+    let other_oracles = [Pubkey::new_unique()];
+
+    let ix = vault.withdraw(&owner, &user_collateral_ata, &user_vault_ata, 7, 1, &oracle, &other_oracles);
+    let mut accounts = vec![
+        AccountMeta::new_readonly(owner, true),
+        AccountMeta::new(user_collateral_ata, false),
+        AccountMeta::new(user_vault_ata, false),
+        AccountMeta::new(pdas.locked_shares(&owner).0, false),
+        AccountMeta::new(pdas.vault_mint.0, false),
+        AccountMeta::new(pdas.depositor(&owner).0, false),
+    ];
+    accounts.extend(vault.update_position_accounts(&oracle).to_account_metas(None));
+    accounts.extend(readonly(&other_oracles));
+    assert_eq!(ix.accounts, accounts);
+
+    let data: vault_instruction::Withdraw = decode(&ix, "withdraw");
+    assert_eq!((data.burn_amount, data.market_index), (7, 1));
+    assert_eq!(data.authority_nonce, pdas.authority.1);
+}
+
+#[test]
+fn builds_update_position_w_the_strategys_other_oracles() {
+    let vault = vault_instructions();
+    let oracle = Pubkey::new_unique();
+    let other_oracles = [Pubkey::new_unique(), Pubkey::new_unique()];
+
+    let ix = vault.update_position(2, &oracle, &other_oracles);
+    let mut accounts = vault.update_position_accounts(&oracle).to_account_metas(None);
+    accounts.extend(readonly(&other_oracles));
+    assert_eq!(ix.accounts, accounts);
+    assert!(ix.accounts.iter().all(|meta| !meta.is_signer));
+    assert!(ix.accounts.iter().any(|meta| meta.pubkey == oracle && !meta.is_writable));
+
+    let data: vault_instruction::UpdatePosition = decode(&ix, "update_position");
+    assert_eq!(data.market_index, 2);
+    assert_eq!(data.authority_nonce, vault.pdas.authority.1);
+}
+
+#[test]
+fn builds_unlock_shares_for_the_owners_escrow() {
+    let vault = vault_instructions();
+    let pdas = vault.pdas;
+    let owner = Pubkey::new_unique();
+    let user_vault_ata = Pubkey::new_unique();
+
+    let ix = vault.unlock_shares(&owner, &user_vault_ata);
+    let keys: Vec<Pubkey> = ix.accounts.iter().map(|meta| meta.pubkey).collect();
+    assert_eq!(keys, vec![
+        owner,
+        user_vault_ata,
+        pdas.locked_shares(&owner).0,
+        pdas.vault_state.0,
+        pdas.depositor(&owner).0,
+        pdas.authority.0,
+        anchor_spl::token::ID,
+    ]);
+    assert!(ix.accounts[0].is_signer);
+
+    let data: vault_instruction::UnlockShares = decode(&ix, "unlock_shares");
+    assert_eq!(data.authority_nonce, pdas.authority.1);
+}
+
+#[test]
+fn builds_the_clearing_house_cranks() {
+    let vault = vault_instructions();
+    let oracle = Pubkey::new_unique();
+
+    let ix = vault.update_funding_rate(3, &oracle);
+    assert_eq!(ix.program_id, clearing_house::ID);
+    let data: clearing_house::instruction::UpdateFundingRate = decode(&ix, "update_funding_rate");
+    assert_eq!(data.market_index, 3);
+    assert!(ix.accounts.iter().any(|meta| meta.pubkey == oracle));
+
+    let ix = vault.settle_funding_payment();
+    assert_eq!(ix.program_id, clearing_house::ID);
+    let _: clearing_house::instruction::SettleFundingPayment = decode(&ix, "settle_funding_payment");
+    assert!(ix.accounts.iter().any(|meta| meta.pubkey == vault.pdas.user.0 && meta.is_writable));
+    assert!(ix.accounts.iter().any(|meta| meta.pubkey == vault.pdas.user_positions.0 && meta.is_writable));
+}
@@ -0,0 +1,120 @@
+// the offline nav / trade / funding predictions vs what the programs then do 
+// on the same in-memory clearing house + vault
+use drift_vault::state::Position;
+use drift_vault_client::read_funding_payment_records;
+use drift_vault_test_utils::{
+    usdc, PositionDirection, StrategyKind, StrategyParams, TestExchange, DEFAULT_SQRT_K, ONE_HOUR,
+};
+
+const SOL: u64 = 0;
+
+// mark = oracle = 1, FundingTwap vault on SOL, alice deposited 1000
+async fn build_exchange() -> TestExchange {
+    let mut exchange = TestExchange::builder()
+        .market(SOL, 1.0, DEFAULT_SQRT_K, ONE_HOUR)
+        .user("trader", usdc(10_000))
+        .vault(StrategyKind::FundingTwap, StrategyParams::default())
+        .depositor("alice", usdc(1_000))
+        .build()
+        .await;
+    exchange.deposit("alice", usdc(1_000)).await.unwrap();
+    exchange
+}
+
+// oracle > mark for a funding period => longs are paid
+async fn crank_oracle_above_mark(exchange: &mut TestExchange) {
+    exchange.set_oracle_price(SOL, 1.04).await;
+    exchange.warp_to_next_funding(SOL).await;
+    exchange.update_funding_rate(SOL).await.unwrap();
+}
+
+#[tokio::test]
+async fn prices_withdrawals_like_the_program() {
+    let mut exchange = build_exchange().await;
+    let alice_usdc = exchange.depositor("alice").usdc;
+    let admin = exchange.admin();
+
+    // half idle so the refund below is paid straight from the idle buffer (w/o unwinds)
+    let ix = exchange.vault().update_idle_buffer(&admin, 1, 2);
+    exchange.process(&[ix], &[]).await.unwrap();
+    crank_oracle_above_mark(&mut exchange).await;
+    exchange.update_position(SOL).await.unwrap();
+    assert!(exchange.get_vault_base_asset_amount(SOL).await > 0);
+
+    // the amm + oracle valuations drift apart
+    exchange.set_oracle_price(SOL, 0.97).await;
+    exchange.open_position("trader", SOL, PositionDirection::Short, usdc(3_000)).await.unwrap();
+
+    let nav = exchange.get_vault_nav(SOL).await;
+    let supply = exchange.get_vault_token_supply().await as u128;
+    assert_ne!(nav.amm_collateral, nav.oracle_collateral);
+    assert_eq!(nav.nav, nav.amm_collateral.min(nav.oracle_collateral) + nav.idle_amount);
+    assert_eq!(nav.deposit_nav, nav.amm_collateral.max(nav.oracle_collateral) + nav.idle_amount);
+    assert_eq!(nav.idle_amount, exchange.get_vault_idle_amount().await as u128);
+    assert_eq!(nav.nav_per_share, nav.nav * 1_000_000 / supply);
+    assert_eq!(nav.exposures.len(), 1);
+    assert!(nav.exposures[0].base_asset_amount > 0);
+
+    let burn_amount = usdc(100) as u128;
+    let refund = burn_amount * nav.nav / supply;
+    assert!(refund < nav.idle_amount);
+    exchange.withdraw("alice", usdc(100), SOL).await.unwrap();
+    assert_eq!(exchange.get_token_balance(&alice_usdc).await as u128, refund);
+}
+
+#[tokio::test]
+async fn predicts_the_trades_of_update_position() {
+    let mut exchange = build_exchange().await;
+
+    // mark = oracle => nothing to do
+    let nav = exchange.get_vault_nav(SOL).await;
+    assert!(nav.closes.is_empty());
+    assert!(nav.trades.is_empty());
+    assert!(nav.exposures.is_empty());
+
+    crank_oracle_above_mark(&mut exchange).await;
+    let nav = exchange.get_vault_nav(SOL).await;
+    assert!(nav.closes.is_empty());
+    assert_eq!(nav.trades.len(), 1);
+    assert_eq!(nav.trades[0].market_index, SOL);
+    assert_eq!(nav.trades[0].direction, Position::Long);
+    assert!(!nav.trades[0].is_reduce);
+
+    exchange.update_position(SOL).await.unwrap();
+    assert!(exchange.get_vault_base_asset_amount(SOL).await > 0);
+}
+
+#[tokio::test]
+async fn predicts_the_funding_rate_and_payment() {
+    let mut exchange = build_exchange().await;
+    crank_oracle_above_mark(&mut exchange).await;
+    exchange.update_position(SOL).await.unwrap();
+
+    // the next update w/ the twaps extended to now = what the clearing house records
+    exchange.warp_to_next_funding(SOL).await;
+    let nav = exchange.get_vault_nav(SOL).await;
+    assert_eq!(nav.exposures[0].pending_funding, 0);
+    let predicted_funding_rate = nav.exposures[0].predicted_funding_rate;
+    exchange.update_funding_rate(SOL).await.unwrap();
+    let last_funding_rate = exchange.get_markets().await.get_market(SOL).amm.last_funding_rate;
+    assert_eq!(predicted_funding_rate, last_funding_rate);
+    assert!(last_funding_rate < 0);
+
+    // the unsettled payment = the FundingPaymentRecord settling it
+    let nav = exchange.get_vault_nav(SOL).await;
+    let exposure = nav.exposures[0].clone();
+    assert!(exposure.pending_funding > 0);
+    exchange.settle_vault_funding_payment().await.unwrap();
+
+    let vault_user = exchange.vault().pdas.user.0;
+    let funding_payment_history = exchange.get_state().await.funding_payment_history;
+    let data = exchange.get_account_data(&funding_payment_history).await;
+    let records = read_funding_payment_records(&data, &vault_user, 10).unwrap();
+    assert_eq!(records.len(), 1);
+    let record = records[0];
+    let (market_index, funding_payment, base_asset_amount) = 
+        (record.market_index, record.funding_payment, record.base_asset_amount);
+    assert_eq!(market_index, SOL);
+    assert_eq!(funding_payment, exposure.pending_funding);
+    assert_eq!(base_asset_amount, exposure.base_asset_amount);
+}
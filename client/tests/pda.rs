@@ -0,0 +1,84 @@
+// the client's PDAs = the accounts the programs create (init checks their seeds)
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Mint, TokenAccount};
+use solana_sdk::signature::Signer;
+
+use clearing_house::state::user::{User, UserPositions};
+use drift_vault::state::{DepositorState, VaultState};
+use drift_vault_client::{
+    deserialize_account, deserialize_zero_copy_account, get_clearing_house_state_address, get_order_state_address,
+    VaultPdas,
+};
+use drift_vault_test_utils::{usdc, StrategyKind, StrategyParams, TestExchange, DEFAULT_SQRT_K, ONE_HOUR};
+
+#[test]
+fn derives_the_nonces_the_instructions_take() {
+    let pdas = VaultPdas::derive(&drift_vault::ID, &clearing_house::ID);
+    let owner = Pubkey::new_unique();
+    let vault_pdas = [
+        (pdas.authority, b"authority".to_vec()),
+        (pdas.user_positions, b"user_positions".to_vec()),
+        (pdas.vault_mint, b"vault_mint".to_vec()),
+        (pdas.vault_state, b"vault_state".to_vec()),
+        (pdas.vault_collateral, b"vault_collateral".to_vec()),
+    ];
+    for ((address, nonce), seed) in vault_pdas.iter() {
+        let pda = Pubkey::create_program_address(&[seed, &[*nonce]], &drift_vault::ID).unwrap();
+        assert_eq!(pda, *address);
+    }
+
+    let (depositor, nonce) = pdas.depositor(&owner);
+    let pda = Pubkey::create_program_address(&[b"depositor", owner.as_ref(), &[nonce]], &drift_vault::ID).unwrap();
+    assert_eq!(pda, depositor);
+    let (locked_shares, nonce) = pdas.locked_shares(&owner);
+    let pda = Pubkey::create_program_address(&[b"locked_shares", owner.as_ref(), &[nonce]], &drift_vault::ID).unwrap();
+    assert_eq!(pda, locked_shares);
+    assert_ne!(pdas.depositor(&Pubkey::new_unique()).0, depositor);
+    assert_ne!(pdas.locked_shares(&Pubkey::new_unique()).0, locked_shares);
+
+    // the vault's drift account is owned by the vault authority
+    let (user, nonce) = pdas.user;
+    let pda = Pubkey::create_program_address(&[b"user", pdas.authority.0.as_ref(), &[nonce]], &clearing_house::ID).unwrap();
+    assert_eq!(pda, user);
+    let (user_orders, nonce) = pdas.user_orders;
+    let pda = Pubkey::create_program_address(&[b"user_orders", user.as_ref(), &[nonce]], &clearing_house::ID).unwrap();
+    assert_eq!(pda, user_orders);
+}
+
+#[tokio::test]
+async fn matches_the_accounts_the_programs_create() {
+    let mut exchange = TestExchange::builder()
+        .market(0, 1.0, DEFAULT_SQRT_K, ONE_HOUR)
+        .vault(StrategyKind::FundingTwap, StrategyParams::default())
+        .depositor("alice", usdc(1_000))
+        .build()
+        .await;
+    let pdas = exchange.vault().pdas;
+    let clearing_house = exchange.vault().clearing_house;
+    assert_eq!(clearing_house.state, get_clearing_house_state_address(&clearing_house::ID).0);
+    assert_eq!(clearing_house.order_state, get_order_state_address(&clearing_house::ID).0);
+
+    let authority = pdas.authority.0;
+    let _: VaultState = deserialize_account(&exchange.get_account_data(&pdas.vault_state.0).await).unwrap();
+    let user: User = deserialize_account(&exchange.get_account_data(&pdas.user.0).await).unwrap();
+    assert_eq!(user.authority, authority);
+    assert_eq!(user.positions, pdas.user_positions.0);
+    let user_positions: UserPositions =
+        deserialize_zero_copy_account(&exchange.get_account_data(&pdas.user_positions.0).await).unwrap();
+    assert_eq!(user_positions.user, pdas.user.0);
+    let vault_mint: Mint = deserialize_account(&exchange.get_account_data(&pdas.vault_mint.0).await).unwrap();
+    assert_eq!(Option::<Pubkey>::from(vault_mint.mint_authority), Some(authority));
+    let vault_collateral: TokenAccount =
+        deserialize_account(&exchange.get_account_data(&pdas.vault_collateral.0).await).unwrap();
+    assert_eq!(vault_collateral.owner, authority);
+    assert_eq!(vault_collateral.mint, clearing_house.collateral_mint);
+
+    let owner = exchange.depositor("alice").owner.pubkey();
+    let depositor_state: DepositorState =
+        deserialize_account(&exchange.get_account_data(&pdas.depositor(&owner).0).await).unwrap();
+    assert_eq!(depositor_state.deposited_amount, 0);
+    let locked_shares: TokenAccount =
+        deserialize_account(&exchange.get_account_data(&pdas.locked_shares(&owner).0).await).unwrap();
+    assert_eq!(locked_shares.owner, authority);
+    assert_eq!(locked_shares.mint, pdas.vault_mint.0);
+}
@@ -0,0 +1,64 @@
+// two trades in the same direction against the same market: the bigger one
+// never gets a better price (mark moves further, more slippage) + a round trip
+// never pays the trader more than they put in
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use clearing_house::controller::amm::{swap_base_asset, swap_quote_asset, SwapDirection};
+use clearing_house::math::position::swap_direction_to_close_position;
+use clearing_house::math::slippage::calculate_slippage;
+
+use drift_vault_fuzz::{log_scale, to_quote, MarketInput, MIN_TRADE};
+
+#[derive(arbitrary::Arbitrary, Debug)]
+struct Input {
+    market: MarketInput,
+    is_long: bool,
+    quote_amount: u32,
+    // the bigger trade = quote_amount + extra
+    extra: u32,
+}
+
+fuzz_target!(|input: Input| {
+    let market = input.market.to_market();
+    let direction = match input.is_long {
+        true => SwapDirection::Add,
+        false => SwapDirection::Remove,
+    };
+    let small = to_quote(log_scale(input.quote_amount, MIN_TRADE, input.market.max_trade()));
+    let big = small + to_quote(log_scale(input.extra, MIN_TRADE, input.market.max_trade()));
+
+    let mark_before = market.amm.mark_price().unwrap();
+    let trade = |quote_asset_amount: u128| {
+        let mut amm = market.amm;
+        let base_asset_amount = swap_quote_asset(&mut amm, quote_asset_amount, direction, 0, None).unwrap();
+        let mark_after = amm.mark_price().unwrap();
+        let slippage = calculate_slippage(quote_asset_amount, base_asset_amount.unsigned_abs(), mark_before as i128).unwrap();
+        (amm, base_asset_amount, mark_after, slippage)
+    };
+    let (small_amm, small_base, small_mark, small_slippage) = trade(small);
+    let (_, big_base, big_mark, big_slippage) = trade(big);
+
+    assert!(small_base.unsigned_abs() <= big_base.unsigned_abs(), "{} > {}", small_base, big_base);
+    // average price rounding: 1ppm of the mark
+    let tolerance = (mark_before / 1_000_000) as i128 + 1;
+    match direction {
+        SwapDirection::Add => {
+            assert!(small_mark <= big_mark, "mark: {} > {}", small_mark, big_mark);
+            assert!(small_slippage <= big_slippage + tolerance, "slippage: {} > {}", small_slippage, big_slippage);
+        }
+        SwapDirection::Remove => {
+            assert!(small_mark >= big_mark, "mark: {} < {}", small_mark, big_mark);
+            assert!(small_slippage + tolerance >= big_slippage, "slippage: {} < {}", small_slippage, big_slippage);
+        }
+    }
+
+    // close the small trade at the new mark
+    let mut amm = small_amm;
+    let close_direction = swap_direction_to_close_position(small_base);
+    let quote_asset_amount = swap_base_asset(&mut amm, small_base.unsigned_abs(), close_direction, 0, None).unwrap();
+    match direction {
+        SwapDirection::Add => assert!(quote_asset_amount <= small, "long round trip: {} > {}", quote_asset_amount, small),
+        SwapDirection::Remove => assert!(quote_asset_amount >= small, "short round trip: {} < {}", quote_asset_amount, small),
+    }
+});
@@ -0,0 +1,81 @@
+// trade fees w/ the default discount token tiers: the fee never exceeds the trade,
+// a bigger trade never pays less + a bigger discount token balance never pays more
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use spl_token::state::Account as TokenAccount;
+
+use clearing_house::math::constants::*;
+use clearing_house::math::fees::calculate_fee_for_trade;
+use clearing_house::state::state::{DiscountTokenTier, DiscountTokenTiers, FeeStructure};
+
+use drift_vault_fuzz::{log_scale, to_quote, MAX_DEPTH, MAX_TRADE_DEPTH_RATIO, MIN_TRADE};
+
+#[derive(arbitrary::Arbitrary, Debug)]
+struct Input {
+    // 0 - 1%
+    fee_numerator: u8,
+    quote_amount: u32,
+    extra_quote_amount: u32,
+    // discount token balances (None = no token account)
+    token_balance: Option<u64>,
+    extra_token_balance: u64,
+}
+
+fn tier(minimum_balance: u64, discount_numerator: u128, discount_denominator: u128) -> DiscountTokenTier {
+    DiscountTokenTier { minimum_balance, discount_numerator, discount_denominator }
+}
+
+fn fee_structure(fee_numerator: u8) -> FeeStructure {
+    FeeStructure {
+        fee_numerator: fee_numerator as u128 % 101,
+        fee_denominator: DEFAULT_FEE_DENOMINATOR,
+        discount_token_tiers: DiscountTokenTiers {
+            first_tier: tier(
+                DEFAULT_DISCOUNT_TOKEN_FIRST_TIER_MINIMUM_BALANCE,
+                DEFAULT_DISCOUNT_TOKEN_FIRST_TIER_DISCOUNT_NUMERATOR,
+                DEFAULT_DISCOUNT_TOKEN_FIRST_TIER_DISCOUNT_DENOMINATOR,
+            ),
+            second_tier: tier(
+                DEFAULT_DISCOUNT_TOKEN_SECOND_TIER_MINIMUM_BALANCE,
+                DEFAULT_DISCOUNT_TOKEN_SECOND_TIER_DISCOUNT_NUMERATOR,
+                DEFAULT_DISCOUNT_TOKEN_SECOND_TIER_DISCOUNT_DENOMINATOR,
+            ),
+            third_tier: tier(
+                DEFAULT_DISCOUNT_TOKEN_THIRD_TIER_MINIMUM_BALANCE,
+                DEFAULT_DISCOUNT_TOKEN_THIRD_TIER_DISCOUNT_NUMERATOR,
+                DEFAULT_DISCOUNT_TOKEN_THIRD_TIER_DISCOUNT_DENOMINATOR,
+            ),
+            fourth_tier: tier(
+                DEFAULT_DISCOUNT_TOKEN_FOURTH_TIER_MINIMUM_BALANCE,
+                DEFAULT_DISCOUNT_TOKEN_FOURTH_TIER_DISCOUNT_NUMERATOR,
+                DEFAULT_DISCOUNT_TOKEN_FOURTH_TIER_DISCOUNT_DENOMINATOR,
+            ),
+        },
+        ..FeeStructure::default()
+    }
+}
+
+fuzz_target!(|input: Input| {
+    let fee_structure = fee_structure(input.fee_numerator);
+    let max_trade = MAX_DEPTH * MAX_TRADE_DEPTH_RATIO;
+    let small = to_quote(log_scale(input.quote_amount, MIN_TRADE, max_trade));
+    let big = small + to_quote(log_scale(input.extra_quote_amount, MIN_TRADE, max_trade));
+    let token = |balance: Option<u64>| balance.map(|amount| TokenAccount { amount, ..TokenAccount::default() });
+
+    // (user fee, fee to the market) w/o a referrer
+    let fee = |quote_asset_amount: u128, token_balance: Option<u64>| {
+        let (user_fee, fee_to_market, ..) =
+            calculate_fee_for_trade(quote_asset_amount, &fee_structure, token(token_balance), &None).unwrap();
+        assert_eq!(user_fee, fee_to_market);
+        user_fee
+    };
+
+    let small_fee = fee(small, input.token_balance);
+    let big_fee = fee(big, input.token_balance);
+    assert!(small_fee <= small, "fee {} > trade {}", small_fee, small);
+    assert!(small_fee <= big_fee, "fee: {} (trade {}) > {} (trade {})", small_fee, small, big_fee, big);
+
+    let more_tokens = input.token_balance.unwrap_or(0).saturating_add(input.extra_token_balance);
+    let discounted_fee = fee(small, Some(more_tokens));
+    assert!(discounted_fee <= small_fee, "fee: {} ({} tokens) > {}", discounted_fee, more_tokens, small_fee);
+});
@@ -0,0 +1,74 @@
+// funding payments: the side the rate says pays, pays (a long and a short of the
+// same size settle to opposite amounts) + capping the receiving side never takes
+// the fee pool below its lower bound or caps the paying side
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use clearing_house::error::ErrorCode;
+use clearing_house::math::constants::FUNDING_PAYMENT_PRECISION;
+use clearing_house::math::funding::{calculate_funding_payment, calculate_funding_rate_long_short};
+use clearing_house::math::repeg::total_fee_lower_bound;
+use clearing_house::state::user::MarketPosition;
+
+use drift_vault_fuzz::{signed_scale, AccountInput, MarketInput, PositionInput};
+
+#[derive(arbitrary::Arbitrary, Debug)]
+struct Input {
+    position: PositionInput,
+    // mark - oracle twap spread: -3% .. +3% (the clearing house's clamp)
+    price_spread: i32,
+    // cumulative funding since the position was opened: -100% .. +100% of the price
+    cumulative_funding: i32,
+}
+
+// update_funding_rate w/ a 1 hour funding period
+fn funding_rate(market: &MarketInput, price_spread: i32) -> i128 {
+    let price_spread = signed_scale(price_spread, 0.03) * market.mark_price() as f64;
+    price_spread as i128 * FUNDING_PAYMENT_PRECISION as i128 / 24
+}
+
+fuzz_target!(|input: Input| {
+    let position = input.position;
+
+    // settle_funding_payment
+    let account = AccountInput { collateral: 0, positions: vec![position] }.to_account();
+    let base_asset_amount = account.user_positions.positions[0].base_asset_amount.abs();
+    let cumulative_funding_rate =
+        (signed_scale(input.cumulative_funding, 1.) * (position.market.mark_price() * FUNDING_PAYMENT_PRECISION) as f64) as i128;
+    let payment = |base_asset_amount: i128| {
+        let market_position = MarketPosition { base_asset_amount, ..MarketPosition::default() };
+        calculate_funding_payment(cumulative_funding_rate, &market_position).unwrap()
+    };
+    let long_payment = payment(base_asset_amount);
+    let short_payment = payment(-base_asset_amount);
+    assert_eq!(long_payment, -short_payment);
+    // payment > 0 = received: longs pay when the rate went up
+    if cumulative_funding_rate > 0 {
+        assert!(long_payment <= 0, "long received {} at rate {}", long_payment, cumulative_funding_rate);
+    } else {
+        assert!(long_payment >= 0, "long paid {} at rate {}", long_payment, cumulative_funding_rate);
+    }
+
+    // update_funding_rate
+    let mut market = position.market.to_market();
+    let funding_rate = funding_rate(&position.market, input.price_spread);
+    match calculate_funding_rate_long_short(&mut market, funding_rate) {
+        Ok((funding_rate_long, funding_rate_short)) => {
+            if funding_rate > 0 {
+                assert_eq!(funding_rate_long, funding_rate);
+            }
+            if funding_rate < 0 {
+                assert_eq!(funding_rate_short, funding_rate);
+            }
+            let total_fee_minus_distributions = market.amm.total_fee_minus_distributions;
+            let lower_bound = total_fee_lower_bound(&market).unwrap();
+            assert!(
+                total_fee_minus_distributions >= lower_bound,
+                "fee pool {} < lower bound {}", total_fee_minus_distributions, lower_bound
+            );
+        }
+        // the fee pool cant cover the imbalance (expected)
+        Err(ErrorCode::InvalidFundingProfitability) => {}
+        Err(error) => panic!("{:?}", error),
+    }
+});
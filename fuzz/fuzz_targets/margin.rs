@@ -0,0 +1,53 @@
+// margin on an account w/ up to 5 positions: more collateral never means less
+// free collateral, closing a position never does either + free collateral > 0
+// always meets the initial margin requirement
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use std::cell::{Ref, RefCell, RefMut};
+
+use clearing_house::math::margin::{calculate_free_collateral, meets_initial_margin_requirement};
+use clearing_house::state::user::User;
+
+use drift_vault_fuzz::{log_scale, to_quote, AccountInput, MAX_COLLATERAL, MIN_TRADE};
+
+#[derive(arbitrary::Arbitrary, Debug)]
+struct Input {
+    account: AccountInput,
+    extra_collateral: u32,
+    market_to_close: u8,
+}
+
+fuzz_target!(|input: Input| {
+    let account = input.account.to_account();
+    let richer_user = User {
+        collateral: account.user.collateral + to_quote(log_scale(input.extra_collateral, MIN_TRADE, MAX_COLLATERAL)),
+        ..account.user
+    };
+    let markets = RefCell::new(account.markets);
+    let user_positions = RefCell::new(account.user_positions);
+
+    let free_collateral = |user: &User, market_to_close: Option<u64>| {
+        let mut user_positions = user_positions.borrow_mut();
+        calculate_free_collateral(user, &mut user_positions, &markets.borrow(), market_to_close).unwrap().0
+    };
+    let meets_initial_margin = |user: &User| {
+        let user_positions = RefMut::map(user_positions.borrow_mut(), |user_positions| &mut **user_positions);
+        let markets = Ref::map(markets.borrow(), |markets| &**markets);
+        meets_initial_margin_requirement(user, &user_positions, &markets).unwrap()
+    };
+
+    let free = free_collateral(&account.user, None);
+    let richer_free = free_collateral(&richer_user, None);
+    assert!(free <= richer_free, "free collateral: {} > {} w/ more collateral", free, richer_free);
+
+    let market_to_close = (input.market_to_close % 5) as u64;
+    let closed_free = free_collateral(&account.user, Some(market_to_close));
+    assert!(free <= closed_free, "free collateral: {} > {} w/ market {} closed", free, closed_free, market_to_close);
+
+    if free > 0 {
+        assert!(meets_initial_margin(&account.user), "free collateral {} but below initial margin", free);
+    }
+    if meets_initial_margin(&account.user) {
+        assert!(meets_initial_margin(&richer_user));
+    }
+});
@@ -0,0 +1,59 @@
+// an open position valued at two marks / two oracle prices: a long's pnl never
+// falls as the price rises (a short's never rises)
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use clearing_house::controller::amm::move_to_price;
+use clearing_house::math::position::{
+    calculate_base_asset_value_and_pnl, calculate_base_asset_value_and_pnl_with_oracle_price,
+};
+use clearing_house::state::user::MarketPosition;
+
+use drift_vault_fuzz::{signed_scale, to_mark_price, AccountInput, PositionInput};
+
+#[derive(arbitrary::Arbitrary, Debug)]
+struct Input {
+    position: PositionInput,
+    // the other price: -50% .. +50% of the peg
+    other_mark_offset: i32,
+}
+
+fuzz_target!(|input: Input| {
+    let position = input.position;
+    let account = AccountInput { collateral: 0, positions: vec![position] }.to_account();
+    let market_position: MarketPosition = account.user_positions.positions[0];
+    let amm = account.markets.get_market(0).amm;
+
+    let other_price = to_mark_price(position.market.peg_price() * (1. + signed_scale(input.other_mark_offset, 0.5)));
+    let (low_price, high_price) = match position.exit_mark_price() <= other_price {
+        true => (position.exit_mark_price(), other_price),
+        false => (other_price, position.exit_mark_price()),
+    };
+    let is_long = market_position.base_asset_amount > 0;
+    // move_to_price rounding: 1 quote unit
+    let assert_monotone = |low_pnl: i128, high_pnl: i128, valuation: &str| match is_long {
+        true => assert!(low_pnl <= high_pnl + 1, "{} long pnl: {} > {}", valuation, low_pnl, high_pnl),
+        false => assert!(low_pnl + 1 >= high_pnl, "{} short pnl: {} < {}", valuation, low_pnl, high_pnl),
+    };
+
+    // at the amm's mark
+    let pnl_at_mark = |price: u128| {
+        let mut amm = amm;
+        move_to_price(&mut amm, price).unwrap();
+        calculate_base_asset_value_and_pnl(&market_position, &amm).unwrap().1
+    };
+    assert_monotone(pnl_at_mark(low_price), pnl_at_mark(high_price), "amm");
+
+    // at the oracle price
+    let pnl_at_oracle =
+        |price: u128| calculate_base_asset_value_and_pnl_with_oracle_price(&market_position, price as i128).unwrap().1;
+    assert_monotone(pnl_at_oracle(low_price), pnl_at_oracle(high_price), "oracle");
+
+    // a long loses at most what it paid, a short gains at most what it sold for
+    let (_, pnl) = calculate_base_asset_value_and_pnl(&market_position, &amm).unwrap();
+    let quote_asset_amount = market_position.quote_asset_amount as i128;
+    match is_long {
+        true => assert!(pnl >= -quote_asset_amount, "long pnl: {} < -{}", pnl, quote_asset_amount),
+        false => assert!(pnl <= quote_asset_amount, "short pnl: {} > {}", pnl, quote_asset_amount),
+    }
+});
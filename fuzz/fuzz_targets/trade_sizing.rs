@@ -0,0 +1,158 @@
+// the vault's position sizing: FundingTwap's target never shrinks w/ more collateral,
+// calculate_trades gets exactly to the targets (reductions first), volatility
+// leverage stays within [min, max] + the funding rate ewma within the rates
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use clearing_house::math::constants::{FUNDING_PAYMENT_PRECISION, MARK_PRICE_PRECISION};
+use clearing_house::state::history::funding_rate::FundingRateRecord;
+use clearing_house::state::market::OraclePriceData;
+use clearing_house::state::state::ValidityGuardRails;
+use clearing_house::state::user::User;
+
+use drift_vault::funding::calculate_funding_rate_ewma;
+use drift_vault::state::{Position, VaultState};
+use drift_vault::strategy::{
+    calculate_position_value, calculate_trades, get_stale_market_indexes, FundingTwapStrategy, Strategy,
+    StrategySnapshot,
+};
+use drift_vault::twap::MarketTwaps;
+use drift_vault::volatility::calculate_volatility_leverage;
+
+use drift_vault_fuzz::{log_scale, signed_scale, to_mark_price, to_quote, AccountInput, MAX_COLLATERAL, MAX_PRICE, MIN_TRADE};
+
+#[derive(arbitrary::Arbitrary, Debug)]
+struct Input {
+    account: AccountInput,
+    extra_collateral: u32,
+    // oracle twap vs the peg: -50% .. +50%
+    oracle_twap_offset: i32,
+    // oracle confidence: 0 - 10% of the price
+    oracle_confidence: u16,
+    // leverage: 0 - 5x
+    leverage: (u16, u16),
+    max_volatility: u16,
+    // funding rate history (newest first): -3% .. +3% of the price / 24
+    funding_rates: Vec<i32>,
+    funding_history_decay: u16,
+}
+
+const MARKET_INDEX: u64 = 0;
+
+fuzz_target!(|input: Input| {
+    if input.account.positions.is_empty() {
+        return;
+    }
+    let account = input.account.to_account();
+    let market_input = input.account.positions[0].market;
+    let market = account.markets.get_market(MARKET_INDEX);
+    let mark_price = market.amm.mark_price().unwrap();
+    let oracle_price_twap = to_mark_price(market_input.peg_price() * (1. + signed_scale(input.oracle_twap_offset, 0.5)));
+    let vault_state = VaultState::default();
+
+    // FundingTwap (w/o the funding window / history signal)
+    let twaps = [MarketTwaps {
+        market_index: MARKET_INDEX,
+        mark_price_twap: mark_price,
+        oracle_price_twap: oracle_price_twap as i128,
+    }];
+    let strategy = FundingTwapStrategy { market_index: MARKET_INDEX };
+    let get_targets = |user: &User| {
+        let snapshot = StrategySnapshot {
+            markets: &account.markets,
+            user,
+            user_positions: &account.user_positions,
+            vault_state: &vault_state,
+            now: 0,
+            funding_rate_records: &[],
+            twaps: &twaps,
+        };
+        strategy.get_target_positions(&snapshot).unwrap()
+    };
+    let richer_user = User {
+        collateral: account.user.collateral + to_quote(log_scale(input.extra_collateral, MIN_TRADE, MAX_COLLATERAL)),
+        ..account.user
+    };
+    let targets = match (get_targets(&account.user), get_targets(&richer_user)) {
+        (Some(targets), Some(richer_targets)) => {
+            assert_eq!(targets.len(), 1);
+            assert_eq!(richer_targets.len(), 1);
+            assert!(targets[0].direction == richer_targets[0].direction);
+            assert!(
+                targets[0].value <= richer_targets[0].value,
+                "target: {} > {} w/ more collateral", targets[0].value, richer_targets[0].value
+            );
+            targets
+        }
+        // mark twap == oracle twap
+        (None, None) => return,
+        _ => panic!("more collateral changed the funding direction"),
+    };
+
+    // calculate_trades
+    let trades = calculate_trades(&targets, &account.user_positions, &account.markets);
+    assert!(trades.len() <= targets.len());
+    let first_increase = trades.iter().position(|trade| !trade.is_reduce).unwrap_or(trades.len());
+    assert!(trades[first_increase..].iter().all(|trade| !trade.is_reduce), "reductions first: {:?}", trades);
+    let stale_market_indexes = get_stale_market_indexes(&targets, &account.user_positions);
+    for trade in trades.iter() {
+        assert!(trade.amount > 0);
+        let target = targets.iter().find(|target| target.market_index == trade.market_index).unwrap();
+        let current_value = match stale_market_indexes.contains(&trade.market_index) {
+            true => 0,
+            false => calculate_position_value(&account.user_positions, &account.markets, trade.market_index),
+        };
+        match trade.is_reduce {
+            true => {
+                assert!(trade.direction != target.direction);
+                assert_eq!(current_value - trade.amount, target.value);
+            }
+            false => {
+                assert!(trade.direction == target.direction);
+                assert_eq!(current_value + trade.amount, target.value);
+            }
+        }
+    }
+
+    // volatility sizing
+    let (min_leverage, max_leverage) = (input.leverage.0 as u128 % 50_001, input.leverage.1 as u128 % 50_001);
+    let vault_state = VaultState {
+        min_leverage: min_leverage.min(max_leverage),
+        max_leverage: min_leverage.max(max_leverage),
+        max_volatility: input.max_volatility as u128,
+        ..VaultState::default()
+    };
+    let mut amm = market.amm;
+    amm.last_oracle_price_twap = oracle_price_twap as i128;
+    let oracle_price_data = OraclePriceData {
+        price: amm.last_oracle_price,
+        confidence: amm.last_oracle_price.unsigned_abs() * (input.oracle_confidence as u128 % 1_001) / 10_000,
+        ..OraclePriceData::default()
+    };
+    let guard_rails = ValidityGuardRails { too_volatile_ratio: 5, ..ValidityGuardRails::default() };
+    let leverage = calculate_volatility_leverage(&amm, &oracle_price_data, &vault_state, &guard_rails).unwrap();
+    assert!(
+        vault_state.min_leverage <= leverage && leverage <= vault_state.max_leverage,
+        "leverage {} outside [{}, {}]", leverage, vault_state.min_leverage, vault_state.max_leverage
+    );
+
+    // funding history signal
+    let max_funding_rate = MAX_PRICE * (MARK_PRICE_PRECISION * FUNDING_PAYMENT_PRECISION) as f64 / 24.;
+    let records: Vec<FundingRateRecord> = input
+        .funding_rates
+        .iter()
+        .map(|funding_rate| FundingRateRecord {
+            funding_rate: signed_scale(*funding_rate, 0.03 * max_funding_rate) as i128,
+            ..FundingRateRecord::default()
+        })
+        .collect();
+    let decay = input.funding_history_decay as u128 % 10_001;
+    match calculate_funding_rate_ewma(&records, decay).unwrap() {
+        Some(ewma) => {
+            let rates = records.iter().map(|record| record.funding_rate);
+            let (min, max) = (rates.clone().min().unwrap(), rates.max().unwrap());
+            assert!(min <= ewma && ewma <= max, "ewma {} outside [{}, {}]", ewma, min, max);
+        }
+        None => assert!(records.is_empty()),
+    }
+});
@@ -0,0 +1,79 @@
+// the vault's share pricing: the amm + oracle valuations of the drift user never
+// panic / overflow + refunds stay within the nav, grow w/ the shares burnt, early
+// withdrawals never get more + a deposit withdrawn right away never gets more back
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use drift_vault::instructions::deposit::compute_mint_amount;
+use drift_vault::instructions::withdraw::compute_refund_amount;
+use drift_vault::state::VaultState;
+use drift_vault::strategy::{calculate_collateral_liabilities, calculate_collateral_with_oracle_prices};
+
+use drift_vault_fuzz::{log_scale, signed_scale, to_mark_price, to_quote, AccountInput, MAX_COLLATERAL, MAX_POSITIONS, MIN_TRADE};
+
+#[derive(arbitrary::Arbitrary, Debug)]
+struct Input {
+    account: AccountInput,
+    // oracle vs the peg for each position's market: -50% .. +50% (missing = at the mark)
+    oracle_offsets: Vec<i32>,
+    // vault collateral ATA
+    idle: u32,
+    // vault token supply + how much of it is burnt
+    supply: u32,
+    burn: u32,
+    extra_burn: u32,
+    // 0 - 5%
+    early_withdrawal_fee: u16,
+}
+
+fuzz_target!(|input: Input| {
+    let account = input.account.to_account();
+    let oracle_prices: Vec<(u64, i128)> = input
+        .account
+        .positions
+        .iter()
+        .take(MAX_POSITIONS)
+        .enumerate()
+        .map(|(market_index, position)| {
+            let oracle_price = match input.oracle_offsets.get(market_index) {
+                Some(offset) => to_mark_price(position.market.peg_price() * (1. + signed_scale(*offset, 0.5))),
+                None => position.exit_mark_price(),
+            };
+            (market_index as u64, oracle_price as i128)
+        })
+        .collect();
+
+    // get_share_collateral
+    let [amm_collateral, _] = calculate_collateral_liabilities(&account.user, &account.user_positions, &account.markets);
+    let oracle_collateral =
+        calculate_collateral_with_oracle_prices(&account.user, &account.user_positions, &oracle_prices).unwrap();
+    let idle = to_quote(log_scale(input.idle, MIN_TRADE, MAX_COLLATERAL));
+    let nav = amm_collateral.min(oracle_collateral) + idle;
+    let deposit_nav = amm_collateral.max(oracle_collateral) + idle;
+
+    // compute_refund_amount
+    let supply = to_quote(log_scale(input.supply, MIN_TRADE, MAX_COLLATERAL)) as u64;
+    let small_burn = 1 + input.burn as u64 % supply;
+    let big_burn = (small_burn + input.extra_burn as u64).min(supply);
+    let vault_state = VaultState {
+        total_amount_minted: supply,
+        early_withdrawal_fee_numerator: input.early_withdrawal_fee as u128 % 501,
+        early_withdrawal_fee_denominator: 10_000,
+        ..VaultState::default()
+    };
+    let refund = |burn: u64, early: bool| compute_refund_amount(burn as u128, nav, &vault_state, early) as u128;
+
+    assert!(refund(big_burn, false) <= nav, "refund {} > nav {}", refund(big_burn, false), nav);
+    assert!(refund(small_burn, false) <= refund(big_burn, false));
+    assert!(refund(small_burn, true) <= refund(small_burn, false));
+    assert!(refund(small_burn, true) <= refund(big_burn, true));
+    // the last depositor out gets everything
+    assert_eq!(refund(supply, false), nav);
+
+    // compute_mint_amount: deposit + withdraw in the same slot is never a profit
+    let deposit = to_quote(log_scale(input.burn, MIN_TRADE, MAX_COLLATERAL)) as u64;
+    let minted = compute_mint_amount(deposit, deposit_nav, &vault_state);
+    let vault_state = VaultState { total_amount_minted: supply + minted, ..vault_state };
+    let refund = compute_refund_amount(minted as u128, nav + deposit as u128, &vault_state, false);
+    assert!(refund <= deposit, "deposit {} => refund {}", deposit, refund);
+});
@@ -0,0 +1,186 @@
+// fuzzer bytes => markets, positions + vault users in the ranges real drift markets
+// see, shared by the fuzz targets (fuzz_targets/), see the README for how to run them
+use std::cmp::max;
+
+use arbitrary::Arbitrary;
+
+use clearing_house::controller::amm::{move_to_price, swap_quote_asset, SwapDirection};
+use clearing_house::math::constants::{
+    AMM_RESERVE_PRECISION, MARK_PRICE_PRECISION, PEG_PRECISION, QUOTE_PRECISION,
+};
+use clearing_house::state::market::{Market, Markets, AMM};
+use clearing_house::state::user::{MarketPosition, User, UserPositions};
+
+// $0.001 (memes) .. $100k (btc)
+pub const MIN_PRICE: f64 = 0.001;
+pub const MAX_PRICE: f64 = 100_000.;
+// usdc notional of the amm reserves (how much it takes to move the price)
+pub const MIN_DEPTH: f64 = 100_000.;
+pub const MAX_DEPTH: f64 = 1_000_000_000.;
+// trades / positions are at most this share of the depth
+pub const MAX_TRADE_DEPTH_RATIO: f64 = 0.1;
+pub const MIN_TRADE: f64 = 1.;
+pub const MAX_COLLATERAL: f64 = 100_000_000.;
+// UserPositions holds 5 positions
+pub const MAX_POSITIONS: usize = 5;
+
+// value in [min, max] on a log scale (0 => min, u32::MAX => max)
+pub fn log_scale(value: u32, min: f64, max: f64) -> f64 {
+    min * (max / min).powf(value as f64 / u32::MAX as f64)
+}
+
+// value in [-max, max] (linear)
+pub fn signed_scale(value: i32, max: f64) -> f64 {
+    value as f64 / i32::MAX as f64 * max
+}
+
+pub fn to_mark_price(price: f64) -> u128 {
+    (price * MARK_PRICE_PRECISION as f64) as u128
+}
+
+pub fn to_quote(amount: f64) -> u128 {
+    (amount * QUOTE_PRECISION as f64) as u128
+}
+
+#[derive(Arbitrary, Clone, Copy, Debug)]
+pub struct MarketInput {
+    pub peg_price: u32,
+    // mark vs peg: -50% .. +50%
+    pub mark_offset: i32,
+    pub depth: u32,
+    // the other traders' open interest (share of the depth)
+    pub base_asset_amount_long: u16,
+    pub base_asset_amount_short: u16,
+    pub total_fee: u32,
+    pub margin_ratio_initial: u16,
+}
+
+impl MarketInput {
+    pub fn peg_price(&self) -> f64 {
+        log_scale(self.peg_price, MIN_PRICE, MAX_PRICE)
+    }
+
+    pub fn mark_price(&self) -> u128 {
+        to_mark_price(self.peg_price() * (1. + signed_scale(self.mark_offset, 0.5)))
+    }
+
+    // max usdc notional of one trade / position
+    pub fn max_trade(&self) -> f64 {
+        log_scale(self.depth, MIN_DEPTH, MAX_DEPTH) * MAX_TRADE_DEPTH_RATIO
+    }
+
+    // like the backtest's synthetic market: base = quote reserves at the peg, moved to the mark
+    pub fn to_market(&self) -> Market {
+        let peg_price = self.peg_price();
+        let sqrt_k = log_scale(self.depth, MIN_DEPTH, MAX_DEPTH) / peg_price;
+        let open_interest = |share: u16| {
+            (sqrt_k * MAX_TRADE_DEPTH_RATIO * share as f64 / u16::MAX as f64 * AMM_RESERVE_PRECISION as f64) as i128
+        };
+
+        let mark_price = self.mark_price();
+        let sqrt_k = max(1, (sqrt_k * AMM_RESERVE_PRECISION as f64) as u128);
+        let total_fee = to_quote(log_scale(self.total_fee, MIN_TRADE, 10_000_000.));
+        let mut amm = AMM {
+            sqrt_k,
+            base_asset_reserve: sqrt_k,
+            quote_asset_reserve: sqrt_k,
+            peg_multiplier: max(1, (peg_price * PEG_PRECISION as f64) as u128),
+            funding_period: 3600,
+            last_mark_price_twap: mark_price,
+            last_oracle_price_twap: mark_price as i128,
+            last_oracle_price: mark_price as i128,
+            total_fee,
+            total_fee_minus_distributions: total_fee,
+            ..AMM::default()
+        };
+        move_to_price(&mut amm, mark_price).unwrap();
+
+        let base_asset_amount_long = open_interest(self.base_asset_amount_long);
+        let base_asset_amount_short = -open_interest(self.base_asset_amount_short);
+        // 1x .. 20x
+        let margin_ratio_initial = max(500, self.margin_ratio_initial as u32 % 10_001);
+        Market {
+            initialized: true,
+            base_asset_amount_long,
+            base_asset_amount_short,
+            base_asset_amount: base_asset_amount_long + base_asset_amount_short,
+            amm,
+            margin_ratio_initial,
+            margin_ratio_partial: margin_ratio_initial / 2,
+            margin_ratio_maintenance: margin_ratio_initial / 4,
+            ..Market::default()
+        }
+    }
+}
+
+#[derive(Arbitrary, Clone, Copy, Debug)]
+pub struct PositionInput {
+    pub market: MarketInput,
+    pub is_long: bool,
+    pub quote_amount: u32,
+    // where the mark goes after the position is opened: -50% .. +50%
+    pub exit_mark_offset: i32,
+}
+
+impl PositionInput {
+    pub fn direction(&self) -> SwapDirection {
+        match self.is_long {
+            true => SwapDirection::Add,
+            false => SwapDirection::Remove,
+        }
+    }
+
+    pub fn quote_amount(&self) -> u128 {
+        to_quote(log_scale(self.quote_amount, MIN_TRADE, self.market.max_trade()))
+    }
+
+    pub fn exit_mark_price(&self) -> u128 {
+        to_mark_price(self.market.peg_price() * (1. + signed_scale(self.exit_mark_offset, 0.5)))
+    }
+}
+
+// a user w/ positions opened against the amm of their market (market index = slot)
+// whose marks then moved, like the vault's drift user
+#[derive(Arbitrary, Clone, Debug)]
+pub struct AccountInput {
+    pub collateral: u32,
+    pub positions: Vec<PositionInput>,
+}
+
+pub struct Account {
+    pub markets: Box<Markets>,
+    pub user: User,
+    pub user_positions: Box<UserPositions>,
+}
+
+impl AccountInput {
+    pub fn collateral(&self) -> u128 {
+        to_quote(log_scale(self.collateral, MIN_TRADE, MAX_COLLATERAL))
+    }
+
+    // (the swaps are in range => an error here is a finding too)
+    pub fn to_account(&self) -> Account {
+        let mut markets = Box::new(Markets::default());
+        let mut user_positions = Box::new(UserPositions::default());
+        for (market_index, input) in self.positions.iter().take(MAX_POSITIONS).enumerate() {
+            let market = markets.get_market_mut(market_index as u64);
+            *market = input.market.to_market();
+
+            let quote_asset_amount = input.quote_amount();
+            let base_asset_amount = swap_quote_asset(&mut market.amm, quote_asset_amount, input.direction(), 0, None).unwrap();
+            move_to_price(&mut market.amm, input.exit_mark_price()).unwrap();
+
+            user_positions.positions[market_index] = MarketPosition {
+                market_index: market_index as u64,
+                base_asset_amount,
+                quote_asset_amount,
+                ..MarketPosition::default()
+            };
+        }
+        let user = User {
+            collateral: self.collateral(),
+            ..User::default()
+        };
+        Account { markets, user, user_positions }
+    }
+}
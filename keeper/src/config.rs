@@ -0,0 +1,74 @@
+use std::fs::File;
+use std::path::Path;
+use std::str::FromStr;
+
+use anchor_lang::prelude::Pubkey;
+use serde::Deserialize;
+
+use crate::decision::Action;
+use crate::error::KeeperError;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct KeeperConfig {
+    pub rpc_url: String,
+    pub keypair_path: String, // pays for the cranks 
+    pub program_id: String,
+    pub clearing_house_program_id: String,
+    // the market_index update_position is called with 
+    pub market_index: u64,
+
+    pub poll_interval: u64, // seconds 
+    // skip rebalances which trade less than this (QUOTE_PRECISION), closes always go through 
+    pub min_trade_value: u128,
+    // min seconds between two sends of the same action on the same market 
+    pub update_position_cooldown: i64,
+    pub refresh_orders_cooldown: i64,
+    pub funding_crank_cooldown: i64,
+    pub settle_funding_cooldown: i64,
+    // log the decisions w/o sending anything 
+    pub dry_run: bool,
+}
+
+impl Default for KeeperConfig {
+    fn default() -> Self {
+        KeeperConfig {
+            rpc_url: "http://localhost:8899".to_string(),
+            keypair_path: "~/.config/solana/id.json".to_string(),
+            program_id: drift_vault::ID.to_string(),
+            clearing_house_program_id: clearing_house::ID.to_string(),
+            market_index: 0,
+            poll_interval: 10,
+            min_trade_value: 0,
+            update_position_cooldown: 60,
+            refresh_orders_cooldown: 60,
+            funding_crank_cooldown: 30,
+            settle_funding_cooldown: 3600,
+            dry_run: false,
+        }
+    }
+}
+
+impl KeeperConfig {
+    pub fn load(path: &Path) -> Result<Self, KeeperError> {
+        Ok(serde_json::from_reader(File::open(path)?)?)
+    }
+
+    // (vault program id, clearing house program id)
+    pub fn program_ids(&self) -> Result<(Pubkey, Pubkey), KeeperError> {
+        Ok((parse_pubkey(&self.program_id)?, parse_pubkey(&self.clearing_house_program_id)?))
+    }
+
+    pub fn get_cooldown(&self, action: Action) -> i64 {
+        match action {
+            Action::UpdatePosition | Action::UpdatePositionWithOrders => self.update_position_cooldown,
+            Action::RefreshOrders => self.refresh_orders_cooldown,
+            Action::UpdateFundingRate => self.funding_crank_cooldown,
+            Action::SettleFundingPayment => self.settle_funding_cooldown,
+        }
+    }
+}
+
+fn parse_pubkey(key: &str) -> Result<Pubkey, KeeperError> {
+    Pubkey::from_str(key).map_err(|_| KeeperError::InvalidConfig(format!("invalid pubkey {}", key)))
+}
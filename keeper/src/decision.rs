@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::instruction::Instruction;
+use serde::Serialize;
+
+use clearing_house::state::market::Markets;
+use clearing_house::state::order_state::OrderState;
+use clearing_house::state::state::State;
+use clearing_house::state::user::UserPositions;
+use clearing_house::state::user_orders::{OrderStatus, OrderType, UserOrders};
+
+use drift_vault::error::VaultErrorCode;
+use drift_vault::state::VaultState;
+use drift_vault::funding::calculate_next_funding_ts;
+use drift_vault_client::{
+    calculate_vault_nav, deserialize_account, deserialize_zero_copy_account, get_vault_market_indexes,
+    ClearingHouseAccounts, VaultAccountsData, VaultInstructions,
+};
+
+use crate::config::KeeperConfig;
+use crate::error::KeeperError;
+use crate::snapshot::{AccountSnapshot, KeeperAddresses};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    // clearing house crank
+    UpdateFundingRate,
+    // settle the vault user's funding payments
+    SettleFundingPayment,
+    UpdatePosition,
+    // update_position for vaults w/ limit / trigger orders
+    UpdatePositionWithOrders,
+    RefreshOrders,
+}
+
+// send = false => the action was due but held back (reason says why)
+#[derive(Clone, Debug, Serialize)]
+pub struct Decision {
+    pub ts: i64,
+    pub action: Action,
+    pub market_index: u64,
+    pub send: bool,
+    pub reason: String,
+}
+
+// when each action was last sent per market
+#[derive(Clone, Debug, Default)]
+pub struct Cooldowns {
+    last_sent: HashMap<(Action, u64), i64>,
+}
+
+impl Cooldowns {
+    pub fn record(&mut self, decision: &Decision) {
+        self.last_sent.insert((decision.action, decision.market_index), decision.ts);
+    }
+
+    pub fn decide(
+        &self,
+        config: &KeeperConfig,
+        now: i64,
+        action: Action,
+        market_index: u64,
+        reason: String,
+    ) -> Decision {
+        let ready_ts = self.last_sent
+            .get(&(action, market_index))
+            .map_or(now, |last_sent_ts| last_sent_ts + config.get_cooldown(action));
+        match now >= ready_ts {
+            true => Decision { ts: now, action, market_index, send: true, reason },
+            false => Decision {
+                ts: now,
+                action,
+                market_index,
+                send: false,
+                reason: format!("{} (cooldown for {}s)", reason, ready_ts - now),
+            },
+        }
+    }
+}
+
+fn hold(now: i64, action: Action, market_index: u64, reason: &str) -> Decision {
+    Decision { ts: now, action, market_index, send: false, reason: reason.to_string() }
+}
+
+// pure: what to crank given the snapshot + cooldowns (nothing due => no decision)
+pub fn decide(
+    config: &KeeperConfig,
+    addresses: &KeeperAddresses,
+    snapshot: &AccountSnapshot,
+    cooldowns: &Cooldowns,
+) -> Result<Vec<Decision>, KeeperError> {
+    let now = snapshot.now;
+    let market_index = config.market_index;
+    let pdas = &addresses.pdas;
+    let vault_state: VaultState = deserialize_account(snapshot.get(&pdas.vault_state.0)?)?;
+    let state: State = deserialize_account(snapshot.get(&addresses.clearing_house_state)?)?;
+    let markets: Box<Markets> = Box::new(deserialize_zero_copy_account(snapshot.get(&state.markets)?)?);
+    let user_positions: UserPositions = deserialize_zero_copy_account(snapshot.get(&pdas.user_positions.0)?)?;
+    let rebalance_action = match vault_state.uses_orders() {
+        true => Action::UpdatePositionWithOrders,
+        false => Action::UpdatePosition,
+    };
+
+    let mut decisions = vec![];
+    if state.exchange_paused {
+        decisions.push(hold(now, rebalance_action, market_index, "clearing house exchange is paused"));
+        return Ok(decisions);
+    }
+
+    // 1. funding updates which are due
+    let vault_market_indexes = get_vault_market_indexes(&vault_state, market_index, &user_positions);
+    for &vault_market_index in vault_market_indexes.iter() {
+        let amm = &markets.get_market(vault_market_index).amm;
+        let next_funding_ts = calculate_next_funding_ts(amm)?;
+        if now < next_funding_ts {
+            continue;
+        }
+        decisions.push(match state.funding_paused {
+            true => hold(now, Action::UpdateFundingRate, vault_market_index, "clearing house funding is paused"),
+            false => cooldowns.decide(
+                config,
+                now,
+                Action::UpdateFundingRate,
+                vault_market_index,
+                format!("funding update due since {}", next_funding_ts),
+            ),
+        });
+    }
+
+    // 2. rebalance (what update_position would do right now)
+    let mut oracles = vec![];
+    for &vault_market_index in vault_market_indexes.iter() {
+        let oracle = markets.get_market(vault_market_index).amm.oracle;
+        oracles.push((oracle, snapshot.get(&oracle)?));
+    }
+    let accounts = VaultAccountsData {
+        vault_state: snapshot.get(&pdas.vault_state.0)?,
+        vault_mint: snapshot.get(&pdas.vault_mint.0)?,
+        vault_collateral: snapshot.get(&pdas.vault_collateral.0)?,
+        state: snapshot.get(&addresses.clearing_house_state)?,
+        user: snapshot.get(&pdas.user.0)?,
+        user_positions: snapshot.get(&pdas.user_positions.0)?,
+        markets: snapshot.get(&state.markets)?,
+        oracles: &oracles,
+        funding_rate_history: snapshot.accounts.get(&state.funding_rate_history).map(|data| data.as_slice()),
+    };
+    let nav = match calculate_vault_nav(&accounts, market_index, now, snapshot.slot) {
+        Ok(nav) => nav,
+        // update_position would revert, the funding crank (above) refreshes the twaps
+        Err(error) if error == VaultErrorCode::StaleTwaps.into() => {
+            decisions.push(hold(now, rebalance_action, market_index, "twaps older than max_twap_staleness"));
+            return Ok(decisions);
+        }
+        Err(error) => return Err(error.into()),
+    };
+    let trade_value: u128 = nav.trades.iter().map(|trade| trade.amount).sum();
+    let mut rebalancing = false;
+    if !nav.closes.is_empty() || (trade_value > 0 && trade_value >= config.min_trade_value) {
+        let decision = cooldowns.decide(
+            config,
+            now,
+            rebalance_action,
+            market_index,
+            format!("{} closes + {} trades worth {}", nav.closes.len(), nav.trades.len(), trade_value),
+        );
+        rebalancing = decision.send;
+        decisions.push(decision);
+    } else if trade_value > 0 {
+        decisions.push(hold(
+            now,
+            rebalance_action,
+            market_index,
+            &format!("trades worth {} < min_trade_value {}", trade_value, config.min_trade_value),
+        ));
+    }
+
+    // a rebalance settles funding + replaces the orders anyway
+    if rebalancing {
+        return Ok(decisions);
+    }
+
+    // 3. orders which refresh_orders would replace
+    if vault_state.uses_orders() {
+        let user_orders: UserOrders = deserialize_zero_copy_account(snapshot.get(&pdas.user_orders.0)?)?;
+        let position_size = user_positions
+            .positions
+            .iter()
+            .find(|market_position| market_position.is_for(market_index))
+            .map_or(0, |market_position| market_position.base_asset_amount.unsigned_abs());
+        let max_order_age = vault_state.max_order_age;
+        let stale_orders = user_orders
+            .orders
+            .iter()
+            .filter(|order| order.status == OrderStatus::Open)
+            .filter(|order| match order.order_type {
+                OrderType::Limit => now - order.ts >= max_order_age,
+                OrderType::TriggerMarket => order.base_asset_amount != position_size,
+                _ => false,
+            })
+            .count();
+        if stale_orders > 0 {
+            decisions.push(cooldowns.decide(
+                config,
+                now,
+                Action::RefreshOrders,
+                market_index,
+                format!("{} stale orders", stale_orders),
+            ));
+        }
+    }
+
+    // 4. unsettled funding payments
+    let pending_funding: i128 = nav.exposures.iter().map(|exposure| exposure.pending_funding).sum();
+    if nav.exposures.iter().any(|exposure| exposure.pending_funding != 0) {
+        decisions.push(cooldowns.decide(
+            config,
+            now,
+            Action::SettleFundingPayment,
+            market_index,
+            format!("pending funding {}", pending_funding),
+        ));
+    }
+
+    Ok(decisions)
+}
+
+pub fn build_instruction(
+    decision: &Decision,
+    addresses: &KeeperAddresses,
+    snapshot: &AccountSnapshot,
+) -> Result<Instruction, KeeperError> {
+    let pdas = &addresses.pdas;
+    let state: State = deserialize_account(snapshot.get(&addresses.clearing_house_state)?)?;
+    let instructions = VaultInstructions::new(
+        *pdas,
+        ClearingHouseAccounts::from_state(addresses.clearing_house_state, &state),
+    );
+    let markets: Box<Markets> = Box::new(deserialize_zero_copy_account(snapshot.get(&state.markets)?)?);
+    let market_index = decision.market_index;
+    let oracle = markets.get_market(market_index).amm.oracle;
+
+    Ok(match decision.action {
+        Action::UpdateFundingRate => instructions.update_funding_rate(market_index, &oracle),
+        Action::SettleFundingPayment => instructions.settle_funding_payment(),
+        Action::UpdatePosition => {
+            let vault_state: VaultState = deserialize_account(snapshot.get(&pdas.vault_state.0)?)?;
+            let user_positions: UserPositions = deserialize_zero_copy_account(snapshot.get(&pdas.user_positions.0)?)?;
+            let other_oracles: Vec<Pubkey> = get_vault_market_indexes(&vault_state, market_index, &user_positions)
+                .into_iter()
+                .filter(|&vault_market_index| vault_market_index != market_index)
+                .map(|vault_market_index| markets.get_market(vault_market_index).amm.oracle)
+                .collect();
+            instructions.update_position(market_index, &oracle, &other_oracles)
+        }
+        Action::UpdatePositionWithOrders | Action::RefreshOrders => {
+            let order_state: OrderState = deserialize_account(snapshot.get(&state.order_state)?)?;
+            match decision.action {
+                Action::RefreshOrders => instructions.refresh_orders(market_index, &oracle, &order_state.order_history),
+                _ => instructions.update_position_with_orders(market_index, &oracle, &order_state.order_history),
+            }
+        }
+    })
+}
@@ -0,0 +1,29 @@
+use anchor_lang::prelude::{ProgramError, Pubkey};
+use clearing_house::error::ErrorCode;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum KeeperError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("base64 error: {0}")]
+    Base64(#[from] base64::DecodeError),
+    #[error("program error: {0}")]
+    Program(#[from] ProgramError),
+    #[error("clearing house error: {0:?}")]
+    ClearingHouse(ErrorCode),
+    #[error("rpc error: {0}")]
+    Rpc(#[from] solana_client::client_error::ClientError),
+    #[error("account {0} not found")]
+    MissingAccount(Pubkey),
+    #[error("invalid config: {0}")]
+    InvalidConfig(String),
+}
+
+impl From<ErrorCode> for KeeperError {
+    fn from(error: ErrorCode) -> Self {
+        KeeperError::ClearingHouse(error)
+    }
+}
@@ -0,0 +1,87 @@
+use std::thread::sleep;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::config::KeeperConfig;
+use crate::decision::{build_instruction, decide, Cooldowns, Decision};
+use crate::error::KeeperError;
+use crate::snapshot::{fetch_snapshot, KeeperAddresses};
+use crate::source::{AccountSource, InstructionSender};
+
+// one json line per decision (+ the tx signature / send error)
+#[derive(Serialize)]
+struct DecisionLog<'a> {
+    #[serde(flatten)]
+    decision: &'a Decision,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signature: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+pub struct Keeper {
+    pub config: KeeperConfig,
+    pub addresses: KeeperAddresses,
+    pub cooldowns: Cooldowns,
+    source: Box<dyn AccountSource>,
+    sender: Box<dyn InstructionSender>,
+}
+
+impl Keeper {
+    pub fn new(
+        config: KeeperConfig,
+        source: Box<dyn AccountSource>,
+        sender: Box<dyn InstructionSender>,
+    ) -> Result<Self, KeeperError> {
+        let addresses = KeeperAddresses::derive(&config)?;
+        Ok(Keeper {
+            config,
+            addresses,
+            cooldowns: Cooldowns::default(),
+            source,
+            sender,
+        })
+    }
+
+    // fetch => decide => send what's due, a failed send doesnt start the cooldown
+    pub fn tick(&mut self) -> Result<Vec<Decision>, KeeperError> {
+        let snapshot = fetch_snapshot(self.source.as_ref(), &self.addresses, self.config.market_index)?;
+        let decisions = decide(&self.config, &self.addresses, &snapshot, &self.cooldowns)?;
+
+        for decision in decisions.iter() {
+            if !decision.send {
+                log_decision(decision, None, None);
+                continue;
+            }
+            let result = build_instruction(decision, &self.addresses, &snapshot)
+                .and_then(|instruction| self.sender.send(&[instruction]));
+            match result {
+                Ok(signature) => {
+                    self.cooldowns.record(decision);
+                    log_decision(decision, Some(signature), None);
+                }
+                Err(error) => log_decision(decision, None, Some(error.to_string())),
+            }
+        }
+        Ok(decisions)
+    }
+
+    pub fn run(&mut self) {
+        loop {
+            if let Err(error) = self.tick() {
+                log_error(&error);
+            }
+            sleep(Duration::from_secs(self.config.poll_interval));
+        }
+    }
+}
+
+fn log_decision(decision: &Decision, signature: Option<String>, error: Option<String>) {
+    let log = DecisionLog { decision, signature, error };
+    println!("{}", serde_json::to_string(&log).unwrap());
+}
+
+pub fn log_error(error: &KeeperError) {
+    println!("{}", serde_json::json!({ "error": error.to_string() }));
+}
@@ -0,0 +1,16 @@
+// keeper for the vault: polls its accounts through an AccountSource (rpc or an 
+// in-memory snapshot), decides which cranks are due (decide is pure => replayable 
+// on recorded snapshots) and sends them through an InstructionSender 
+pub mod error;
+pub mod config;
+pub mod source;
+pub mod snapshot;
+pub mod decision;
+pub mod keeper;
+
+pub use error::*;
+pub use config::*;
+pub use source::*;
+pub use snapshot::*;
+pub use decision::*;
+pub use keeper::*;
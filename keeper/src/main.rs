@@ -0,0 +1,65 @@
+use std::path::Path;
+use std::process::exit;
+
+use drift_vault_keeper::{
+    log_error, fetch_snapshot, AccountSnapshot, Keeper, KeeperAddresses, KeeperConfig, KeeperError,
+    MemoryAccountSource, RecordingSender, RpcAccountSource, RpcSender,
+};
+
+const USAGE: &str = "usage: drift-vault-keeper <config.json> [--once | --record <snapshot.json> | --replay <snapshot.json>]";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let result = match args.as_slice() {
+        [config_path] => run(config_path, false),
+        [config_path, "--once"] => run(config_path, true),
+        [config_path, "--record", snapshot_path] => record(config_path, snapshot_path),
+        [config_path, "--replay", snapshot_path] => replay(config_path, snapshot_path),
+        _ => {
+            eprintln!("{}", USAGE);
+            exit(1);
+        }
+    };
+    if let Err(error) = result {
+        log_error(&error);
+        exit(1);
+    }
+}
+
+fn run(config_path: &str, once: bool) -> Result<(), KeeperError> {
+    let config = KeeperConfig::load(Path::new(config_path))?;
+    let source = Box::new(RpcAccountSource::new(&config.rpc_url));
+    let mut keeper = match config.dry_run {
+        true => Keeper::new(config, source, Box::new(RecordingSender::default()))?,
+        false => {
+            let sender = Box::new(RpcSender::new(&config.rpc_url, &config.keypair_path)?);
+            Keeper::new(config, source, sender)?
+        }
+    };
+    match once {
+        true => keeper.tick().map(|_| ()),
+        false => {
+            keeper.run();
+            Ok(())
+        }
+    }
+}
+
+// saves the accounts the keeper reads so a decision can be replayed offline
+fn record(config_path: &str, snapshot_path: &str) -> Result<(), KeeperError> {
+    let config = KeeperConfig::load(Path::new(config_path))?;
+    let source = RpcAccountSource::new(&config.rpc_url);
+    let addresses = KeeperAddresses::derive(&config)?;
+    let snapshot = fetch_snapshot(&source, &addresses, config.market_index)?;
+    snapshot.save(Path::new(snapshot_path))
+}
+
+// the decisions on a recorded snapshot (nothing is sent)
+fn replay(config_path: &str, snapshot_path: &str) -> Result<(), KeeperError> {
+    let config = KeeperConfig::load(Path::new(config_path))?;
+    let snapshot = AccountSnapshot::load(Path::new(snapshot_path))?;
+    let source = Box::new(MemoryAccountSource { snapshot });
+    let mut keeper = Keeper::new(config, source, Box::new(RecordingSender::default()))?;
+    keeper.tick().map(|_| ())
+}
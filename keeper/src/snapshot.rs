@@ -0,0 +1,143 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::path::Path;
+use std::str::FromStr;
+
+use anchor_lang::prelude::Pubkey;
+use serde::{Deserialize, Serialize};
+
+use clearing_house::state::market::Markets;
+use clearing_house::state::state::State;
+use clearing_house::state::user::UserPositions;
+
+use drift_vault::state::VaultState;
+use drift_vault_client::{
+    deserialize_account, deserialize_zero_copy_account, get_clearing_house_state_address, 
+    get_vault_market_indexes, VaultPdas,
+};
+
+use crate::config::KeeperConfig;
+use crate::error::KeeperError;
+use crate::source::AccountSource;
+
+// the accounts the keeper looks up 
+#[derive(Clone, Copy, Debug)]
+pub struct KeeperAddresses {
+    pub pdas: VaultPdas,
+    pub clearing_house_state: Pubkey,
+}
+
+impl KeeperAddresses {
+    pub fn derive(config: &KeeperConfig) -> Result<Self, KeeperError> {
+        let (program_id, clearing_house_program_id) = config.program_ids()?;
+        Ok(KeeperAddresses {
+            pdas: VaultPdas::derive(&program_id, &clearing_house_program_id),
+            clearing_house_state: get_clearing_house_state_address(&clearing_house_program_id).0,
+        })
+    }
+}
+
+// account data at one point in time (what decide runs on)
+#[derive(Clone, Debug, Default)]
+pub struct AccountSnapshot {
+    pub now: i64,
+    pub slot: u64,
+    pub accounts: HashMap<Pubkey, Vec<u8>>,
+}
+
+// on-disk format: {now, slot, accounts: {address: base64 data}}
+#[derive(Serialize, Deserialize)]
+struct RecordedSnapshot {
+    now: i64,
+    slot: u64,
+    accounts: BTreeMap<String, String>,
+}
+
+impl AccountSnapshot {
+    pub fn get(&self, address: &Pubkey) -> Result<&[u8], KeeperError> {
+        self.accounts
+            .get(address)
+            .map(|data| data.as_slice())
+            .ok_or(KeeperError::MissingAccount(*address))
+    }
+
+    pub fn fetch(
+        &mut self, 
+        source: &dyn AccountSource, 
+        addresses: &[Pubkey],
+    ) -> Result<(), KeeperError> {
+        let accounts = source.get_multiple_accounts(addresses)?;
+        for (address, data) in addresses.iter().zip(accounts) {
+            if let Some(data) = data {
+                self.accounts.insert(*address, data);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, KeeperError> {
+        let recorded: RecordedSnapshot = serde_json::from_reader(File::open(path)?)?;
+        let mut accounts = HashMap::new();
+        for (address, data) in recorded.accounts {
+            let address = Pubkey::from_str(&address)
+                .map_err(|_| KeeperError::InvalidConfig(format!("invalid pubkey {}", address)))?;
+            accounts.insert(address, base64::decode(data)?);
+        }
+        Ok(AccountSnapshot { now: recorded.now, slot: recorded.slot, accounts })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), KeeperError> {
+        let recorded = RecordedSnapshot {
+            now: self.now,
+            slot: self.slot,
+            accounts: self.accounts
+                .iter()
+                .map(|(address, data)| (address.to_string(), base64::encode(data)))
+                .collect(),
+        };
+        serde_json::to_writer_pretty(File::create(path)?, &recorded)?;
+        Ok(())
+    }
+}
+
+// fetches everything decide + the instructions need: 
+// 1. vault state + clearing house state 
+// 2. the accounts they point to 
+// 3. the oracles of the vault's markets 
+pub fn fetch_snapshot(
+    source: &dyn AccountSource,
+    addresses: &KeeperAddresses,
+    market_index: u64,
+) -> Result<AccountSnapshot, KeeperError> {
+    let (now, slot) = source.get_clock()?;
+    let mut snapshot = AccountSnapshot { now, slot, ..AccountSnapshot::default() };
+    let pdas = &addresses.pdas;
+
+    snapshot.fetch(source, &[pdas.vault_state.0, addresses.clearing_house_state])?;
+    let vault_state: VaultState = deserialize_account(snapshot.get(&pdas.vault_state.0)?)?;
+    let state: State = deserialize_account(snapshot.get(&addresses.clearing_house_state)?)?;
+
+    let mut account_addresses = vec![
+        state.markets,
+        state.order_state,
+        pdas.vault_mint.0,
+        pdas.vault_collateral.0,
+        pdas.user.0,
+        pdas.user_positions.0,
+        pdas.user_orders.0,
+    ];
+    if vault_state.funding_history_length > 0 {
+        account_addresses.push(state.funding_rate_history);
+    }
+    snapshot.fetch(source, &account_addresses)?;
+
+    let markets: Box<Markets> = Box::new(deserialize_zero_copy_account(snapshot.get(&state.markets)?)?);
+    let user_positions: UserPositions = deserialize_zero_copy_account(snapshot.get(&pdas.user_positions.0)?)?;
+    let oracles: Vec<Pubkey> = get_vault_market_indexes(&vault_state, market_index, &user_positions)
+        .into_iter()
+        .map(|vault_market_index| markets.get_market(vault_market_index).amm.oracle)
+        .collect();
+    snapshot.fetch(source, &oracles)?;
+
+    Ok(snapshot)
+}
@@ -0,0 +1,125 @@
+use std::cell::RefCell;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::{clock::Clock, instruction::Instruction, sysvar};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::account::from_account;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::signature::{read_keypair_file, Keypair, Signer};
+use solana_sdk::transaction::Transaction;
+
+use crate::error::KeeperError;
+use crate::snapshot::AccountSnapshot;
+
+// where the keeper reads accounts from 
+pub trait AccountSource {
+    // (unix timestamp, slot)
+    fn get_clock(&self) -> Result<(i64, u64), KeeperError>;
+
+    // None = account doesnt exist 
+    fn get_multiple_accounts(&self, addresses: &[Pubkey]) -> Result<Vec<Option<Vec<u8>>>, KeeperError>;
+}
+
+// where the keeper sends its instructions to, returns the tx signature 
+pub trait InstructionSender {
+    fn send(&self, instructions: &[Instruction]) -> Result<String, KeeperError>;
+}
+
+pub struct RpcAccountSource {
+    client: RpcClient,
+}
+
+impl RpcAccountSource {
+    pub fn new(rpc_url: &str) -> Self {
+        RpcAccountSource {
+            client: RpcClient::new_with_commitment(rpc_url.to_string(), CommitmentConfig::confirmed()),
+        }
+    }
+}
+
+impl AccountSource for RpcAccountSource {
+    fn get_clock(&self) -> Result<(i64, u64), KeeperError> {
+        let account = self.client.get_account(&sysvar::clock::ID)?;
+        let clock: Clock = from_account(&account).ok_or(KeeperError::MissingAccount(sysvar::clock::ID))?;
+        Ok((clock.unix_timestamp, clock.slot))
+    }
+
+    fn get_multiple_accounts(&self, addresses: &[Pubkey]) -> Result<Vec<Option<Vec<u8>>>, KeeperError> {
+        // getMultipleAccounts takes up to 100 addresses 
+        let mut accounts = vec![];
+        for chunk in addresses.chunks(100) {
+            accounts.extend(
+                self.client
+                    .get_multiple_accounts(chunk)?
+                    .into_iter()
+                    .map(|account| account.map(|account| account.data))
+            );
+        }
+        Ok(accounts)
+    }
+}
+
+// serves a (recorded) snapshot, for replays / tests w/o a network 
+pub struct MemoryAccountSource {
+    pub snapshot: AccountSnapshot,
+}
+
+impl AccountSource for MemoryAccountSource {
+    fn get_clock(&self) -> Result<(i64, u64), KeeperError> {
+        Ok((self.snapshot.now, self.snapshot.slot))
+    }
+
+    fn get_multiple_accounts(&self, addresses: &[Pubkey]) -> Result<Vec<Option<Vec<u8>>>, KeeperError> {
+        Ok(addresses
+            .iter()
+            .map(|address| self.snapshot.accounts.get(address).cloned())
+            .collect())
+    }
+}
+
+pub struct RpcSender {
+    client: RpcClient,
+    payer: Keypair,
+}
+
+impl RpcSender {
+    pub fn new(rpc_url: &str, keypair_path: &str) -> Result<Self, KeeperError> {
+        let keypair_path = match (keypair_path.strip_prefix("~/"), std::env::var("HOME")) {
+            (Some(path), Ok(home)) => format!("{}/{}", home, path),
+            _ => keypair_path.to_string(),
+        };
+        let payer = read_keypair_file(&keypair_path)
+            .map_err(|error| KeeperError::InvalidConfig(format!("cant read keypair {}: {}", keypair_path, error)))?;
+        Ok(RpcSender {
+            client: RpcClient::new_with_commitment(rpc_url.to_string(), CommitmentConfig::confirmed()),
+            payer,
+        })
+    }
+}
+
+impl InstructionSender for RpcSender {
+    fn send(&self, instructions: &[Instruction]) -> Result<String, KeeperError> {
+        let blockhash = self.client.get_latest_blockhash()?;
+        let transaction = Transaction::new_signed_with_payer(
+            instructions, 
+            Some(&self.payer.pubkey()), 
+            &[&self.payer], 
+            blockhash,
+        );
+        let signature = self.client.send_and_confirm_transaction(&transaction)?;
+        Ok(signature.to_string())
+    }
+}
+
+// keeps the instructions instead of sending them (dry runs / tests)
+#[derive(Default)]
+pub struct RecordingSender {
+    pub sent: RefCell<Vec<Vec<Instruction>>>,
+}
+
+impl InstructionSender for RecordingSender {
+    fn send(&self, instructions: &[Instruction]) -> Result<String, KeeperError> {
+        self.sent.borrow_mut().push(instructions.to_vec());
+        Ok("dry-run".to_string())
+    }
+}
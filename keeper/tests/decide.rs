@@ -0,0 +1,291 @@
+// decide on the snapshots in fixtures/: one per branch (rebalance, the funding crank
+// which refreshes the twaps, funding settlement, nothing to do, stale twaps + paused
+// markets), the fixtures describe the accounts, build_snapshot encodes them like
+// the clearing house + vault programs do
+use std::fs::File;
+use std::path::Path;
+
+use anchor_lang::prelude::*;
+use anchor_lang::{AccountSerialize, Discriminator};
+use serde::Deserialize;
+use spl_token::solana_program::program_option::COption;
+use spl_token::solana_program::program_pack::Pack;
+
+use clearing_house::math::constants::{AMM_RESERVE_PRECISION, MARK_PRICE_PRECISION, PEG_PRECISION, QUOTE_PRECISION};
+use clearing_house::state::market::{Market, Markets, AMM};
+use clearing_house::state::state::State;
+use clearing_house::state::user::{MarketPosition, User, UserPositions};
+
+use drift_vault::state::VaultState;
+use drift_vault_keeper::{build_instruction, decide, AccountSnapshot, Action, Cooldowns, Decision, KeeperAddresses, KeeperConfig};
+
+const SQRT_K: u128 = 100_000_000 * AMM_RESERVE_PRECISION;
+// pyth Price: expo at 20, valid_slot at 40, agg.price at 208, 3312 bytes
+const PYTH_PRICE_SIZE: usize = 3312;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Fixture {
+    #[allow(dead_code)]
+    description: String,
+    now: i64,
+    slot: u64,
+    #[serde(default)]
+    exchange_paused: bool,
+    #[serde(default)]
+    funding_paused: bool,
+    market: MarketFixture,
+    vault: VaultFixture,
+}
+
+// market 0, prices in usd, ages in seconds before now
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MarketFixture {
+    mark_price: f64,
+    oracle_price: f64,
+    mark_twap: f64,
+    oracle_twap: f64,
+    twap_age: i64,
+    last_funding_age: i64,
+    #[serde(default)]
+    cumulative_funding_rate: i128,
+}
+
+// amounts in usdc, base_asset_amount > 0 = long
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VaultFixture {
+    collateral: f64,
+    #[serde(default)]
+    idle: f64,
+    shares: f64,
+    #[serde(default)]
+    base_asset_amount: f64,
+    #[serde(default)]
+    quote_asset_amount: f64,
+    #[serde(default)]
+    max_twap_staleness: i64,
+}
+
+fn to_quote(amount: f64) -> u128 {
+    (amount * QUOTE_PRECISION as f64).round() as u128
+}
+
+fn to_mark_price(price: f64) -> u128 {
+    (price * MARK_PRICE_PRECISION as f64).round() as u128
+}
+
+fn serialize_account<T: AccountSerialize>(account: &T) -> Vec<u8> {
+    let mut data = vec![];
+    account.try_serialize(&mut data).unwrap();
+    data
+}
+
+fn serialize_zero_copy_account<T: Discriminator + bytemuck::Pod>(account: &T) -> Vec<u8> {
+    let mut data = T::discriminator().to_vec();
+    data.extend_from_slice(bytemuck::bytes_of(account));
+    data
+}
+
+fn pack_token_account<T: Pack>(account: &T) -> Vec<u8> {
+    let mut data = vec![0; T::LEN];
+    account.pack_into_slice(&mut data);
+    data
+}
+
+fn pyth_price_data(price: f64, slot: u64) -> Vec<u8> {
+    let mut data = vec![0; PYTH_PRICE_SIZE];
+    data[20..24].copy_from_slice(&(-10_i32).to_le_bytes());
+    data[40..48].copy_from_slice(&slot.to_le_bytes());
+    data[208..216].copy_from_slice(&(to_mark_price(price) as i64).to_le_bytes());
+    data
+}
+
+fn build_snapshot(fixture: &Fixture, addresses: &KeeperAddresses) -> AccountSnapshot {
+    let pdas = &addresses.pdas;
+    let now = fixture.now;
+    let (market, vault) = (&fixture.market, &fixture.vault);
+    let state = State {
+        exchange_paused: fixture.exchange_paused,
+        funding_paused: fixture.funding_paused,
+        collateral_mint: Pubkey::new_unique(),
+        markets: Pubkey::new_unique(),
+        funding_rate_history: Pubkey::new_unique(),
+        order_state: Pubkey::new_unique(),
+        ..State::default()
+    };
+    let oracle = Pubkey::new_unique();
+
+    let base_asset_amount = (vault.base_asset_amount * AMM_RESERVE_PRECISION as f64) as i128;
+    let mut markets = Box::new(Markets::default());
+    markets.markets[0] = Market {
+        initialized: true,
+        base_asset_amount_long: base_asset_amount.max(0),
+        base_asset_amount_short: base_asset_amount.min(0),
+        base_asset_amount,
+        amm: AMM {
+            oracle,
+            base_asset_reserve: SQRT_K,
+            quote_asset_reserve: SQRT_K,
+            sqrt_k: SQRT_K,
+            peg_multiplier: (market.mark_price * PEG_PRECISION as f64).round() as u128,
+            cumulative_funding_rate_long: market.cumulative_funding_rate,
+            cumulative_funding_rate_short: market.cumulative_funding_rate,
+            funding_period: 3600,
+            last_funding_rate_ts: now - market.last_funding_age,
+            last_mark_price_twap: to_mark_price(market.mark_twap),
+            last_mark_price_twap_ts: now - market.twap_age,
+            last_oracle_price_twap: to_mark_price(market.oracle_twap) as i128,
+            last_oracle_price_twap_ts: now - market.twap_age,
+            last_oracle_price: to_mark_price(market.oracle_price) as i128,
+            ..AMM::default()
+        },
+        ..Market::default()
+    };
+
+    let mut user_positions = UserPositions { user: pdas.user.0, ..UserPositions::default() };
+    user_positions.positions[0] = MarketPosition {
+        market_index: 0,
+        base_asset_amount,
+        quote_asset_amount: to_quote(vault.quote_asset_amount),
+        ..MarketPosition::default()
+    };
+    let user = User {
+        authority: pdas.authority.0,
+        collateral: to_quote(vault.collateral),
+        positions: pdas.user_positions.0,
+        ..User::default()
+    };
+    let vault_state = VaultState {
+        total_amount_minted: to_quote(vault.shares) as u64,
+        max_twap_staleness: vault.max_twap_staleness,
+        ..VaultState::default()
+    };
+
+    let vault_mint = pack_token_account(&spl_token::state::Mint {
+        mint_authority: COption::Some(pdas.authority.0),
+        supply: to_quote(vault.shares) as u64,
+        decimals: 6,
+        is_initialized: true,
+        freeze_authority: COption::None,
+    });
+    let vault_collateral = pack_token_account(&spl_token::state::Account {
+        mint: state.collateral_mint,
+        owner: pdas.authority.0,
+        amount: to_quote(vault.idle) as u64,
+        state: spl_token::state::AccountState::Initialized,
+        ..spl_token::state::Account::default()
+    });
+
+    let mut snapshot = AccountSnapshot { now, slot: fixture.slot, ..AccountSnapshot::default() };
+    let accounts = [
+        (addresses.clearing_house_state, serialize_account(&state)),
+        (state.markets, serialize_zero_copy_account(&*markets)),
+        (oracle, pyth_price_data(market.oracle_price, fixture.slot)),
+        (pdas.vault_state.0, serialize_account(&vault_state)),
+        (pdas.vault_mint.0, vault_mint),
+        (pdas.vault_collateral.0, vault_collateral),
+        (pdas.user.0, serialize_account(&user)),
+        (pdas.user_positions.0, serialize_zero_copy_account(&user_positions)),
+    ];
+    snapshot.accounts.extend(accounts.iter().cloned());
+    snapshot
+}
+
+fn load_fixture(name: &str) -> (KeeperConfig, KeeperAddresses, AccountSnapshot) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(format!("{}.json", name));
+    let fixture: Fixture = serde_json::from_reader(File::open(&path).unwrap()).unwrap();
+    let config = KeeperConfig::default();
+    let addresses = KeeperAddresses::derive(&config).unwrap();
+    let snapshot = build_snapshot(&fixture, &addresses);
+    (config, addresses, snapshot)
+}
+
+// (action, send) of each decision
+fn summarize(decisions: &[Decision]) -> Vec<(Action, bool)> {
+    decisions.iter().map(|decision| (decision.action, decision.send)).collect()
+}
+
+#[test]
+fn rebalances_when_update_position_would_trade() {
+    let (mut config, addresses, snapshot) = load_fixture("rebalance");
+    let mut cooldowns = Cooldowns::default();
+
+    let decisions = decide(&config, &addresses, &snapshot, &cooldowns).unwrap();
+    assert_eq!(summarize(&decisions), vec![(Action::UpdatePosition, true)]);
+    assert!(decisions[0].reason.starts_with("0 closes + 1 trades"), "{}", decisions[0].reason);
+    let instruction = build_instruction(&decisions[0], &addresses, &snapshot).unwrap();
+    assert_eq!(instruction.program_id, drift_vault::ID);
+
+    // sent => held for the cooldown
+    cooldowns.record(&decisions[0]);
+    let decisions = decide(&config, &addresses, &snapshot, &cooldowns).unwrap();
+    assert_eq!(summarize(&decisions), vec![(Action::UpdatePosition, false)]);
+    assert!(decisions[0].reason.contains("cooldown for 60s"), "{}", decisions[0].reason);
+
+    // the 1000 usdc trade is below min_trade_value
+    config.min_trade_value = 2_000 * QUOTE_PRECISION;
+    let decisions = decide(&config, &addresses, &snapshot, &Cooldowns::default()).unwrap();
+    assert_eq!(summarize(&decisions), vec![(Action::UpdatePosition, false)]);
+    assert!(decisions[0].reason.contains("< min_trade_value"), "{}", decisions[0].reason);
+}
+
+#[test]
+fn cranks_funding_which_refreshes_the_twaps() {
+    let (config, addresses, snapshot) = load_fixture("funding_due");
+
+    let decisions = decide(&config, &addresses, &snapshot, &Cooldowns::default()).unwrap();
+    assert_eq!(summarize(&decisions), vec![(Action::UpdateFundingRate, true)]);
+    assert_eq!(decisions[0].reason, format!("funding update due since {}", snapshot.now));
+    let instruction = build_instruction(&decisions[0], &addresses, &snapshot).unwrap();
+    assert_eq!(instruction.program_id, clearing_house::ID);
+}
+
+#[test]
+fn settles_pending_funding() {
+    let (config, addresses, snapshot) = load_fixture("settle_funding");
+
+    let decisions = decide(&config, &addresses, &snapshot, &Cooldowns::default()).unwrap();
+    assert_eq!(summarize(&decisions), vec![(Action::SettleFundingPayment, true)]);
+    // longs are paid: 0.01 * 1000 base = 10 usdc (in AMM_RESERVE_PRECISION)
+    assert_eq!(decisions[0].reason, format!("pending funding {}", 10 * AMM_RESERVE_PRECISION));
+}
+
+#[test]
+fn does_nothing_when_nothing_is_due() {
+    let (config, addresses, snapshot) = load_fixture("no_op");
+
+    let decisions = decide(&config, &addresses, &snapshot, &Cooldowns::default()).unwrap();
+    assert!(decisions.is_empty());
+}
+
+#[test]
+fn holds_the_rebalance_on_stale_twaps() {
+    let (config, addresses, snapshot) = load_fixture("stale_twaps");
+
+    let decisions = decide(&config, &addresses, &snapshot, &Cooldowns::default()).unwrap();
+    assert_eq!(
+        summarize(&decisions),
+        vec![(Action::UpdateFundingRate, true), (Action::UpdatePosition, false)]
+    );
+    assert_eq!(decisions[1].reason, "twaps older than max_twap_staleness");
+}
+
+#[test]
+fn holds_everything_while_the_exchange_is_paused() {
+    let (config, addresses, snapshot) = load_fixture("exchange_paused");
+
+    let decisions = decide(&config, &addresses, &snapshot, &Cooldowns::default()).unwrap();
+    assert_eq!(summarize(&decisions), vec![(Action::UpdatePosition, false)]);
+    assert_eq!(decisions[0].reason, "clearing house exchange is paused");
+}
+
+#[test]
+fn holds_the_funding_crank_while_funding_is_paused() {
+    let (config, addresses, snapshot) = load_fixture("funding_paused");
+
+    let decisions = decide(&config, &addresses, &snapshot, &Cooldowns::default()).unwrap();
+    assert_eq!(summarize(&decisions), vec![(Action::UpdateFundingRate, false)]);
+    assert_eq!(decisions[0].reason, "clearing house funding is paused");
+}
@@ -6,6 +6,54 @@ pub enum VaultErrorCode {
     NotEnoughFunds,
     #[msg("Widthdraw amount too small.")]
     WidthdrawAmountTooSmall,
+    #[msg("Deposit exceeds the vault collateral cap.")]
+    VaultCollateralCapExceeded,
+    #[msg("Deposit exceeds the depositor cap.")]
+    DepositorCapExceeded,
+    #[msg("Deposit exceeds the clearing house max deposit.")]
+    ClearingHouseMaxDepositExceeded,
+    #[msg("Invalid whitelist token.")]
+    InvalidWhitelistToken,
+    #[msg("Whitelist token not found.")]
+    WhitelistTokenNotFound,
+    #[msg("Deposit is still within the minimum holding period.")]
+    DepositLocked,
+    #[msg("Invalid lockup params.")]
+    InvalidLockupParams,
+    #[msg("Invalid idle buffer.")]
+    InvalidIdleBuffer,
+    #[msg("Invalid execution mode params.")]
+    InvalidExecutionParams,
+    #[msg("Vault is using orders (use update_position_with_orders).")]
+    VaultOrdersEnabled,
+    #[msg("Invalid trigger order params.")]
+    InvalidTriggerParams,
+    #[msg("Invalid funding window.")]
+    InvalidFundingWindow,
+    #[msg("Invalid spread markets.")]
+    InvalidSpreadMarkets,
+    #[msg("Oracle not found for market.")]
+    OracleNotFound,
+    #[msg("Orders are not supported with the spread strategy.")]
+    SpreadOrdersNotSupported,
+    #[msg("Invalid volatility sizing params.")]
+    InvalidVolatilitySizing,
+    #[msg("Invalid funding history params.")]
+    InvalidFundingHistoryParams,
+    #[msg("Market twaps are stale.")]
+    StaleTwaps,
+    #[msg("Invalid twap staleness.")]
+    InvalidTwapStaleness,
+    #[msg("Clearing house exchange is paused.")]
+    ExchangePaused,
+    #[msg("Deposit amount too small.")]
+    DepositAmountTooSmall,
+    #[msg("Invalid deposit caps.")]
+    InvalidDepositCaps,
+    #[msg("Deposit exceeds the vault's max share of the market.")]
+    MarketShareExceeded,
+    #[msg("Deploy max slippage must be > 0.")]
+    InvalidSlippage,
 }
 
 // copy pasta from clearing house 
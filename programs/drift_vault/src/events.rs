@@ -0,0 +1,11 @@
+use anchor_lang::prelude::*;
+
+// update_position didnt follow the strategy because the clearing house paused funding 
+// (an exchange pause fails the tx w/ ExchangePaused => nothing to emit)
+#[event]
+pub struct FundingPauseEvent {
+    pub ts: i64,
+    pub market_index: u64,
+    // true = closed the positions, false = froze them 
+    pub went_flat: bool,
+}
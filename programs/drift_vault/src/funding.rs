@@ -0,0 +1,140 @@
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+use std::convert::TryInto;
+
+use clearing_house::state::market::AMM;
+use clearing_house::state::history::funding_rate::{FundingRateHistory, FundingRateRecord};
+use clearing_house::error::ErrorCode;
+
+use crate::state::FUNDING_DECAY_PRECISION;
+use crate::math_error;
+
+pub const FUNDING_RATE_HISTORY_LENGTH: u64 = 1024;
+const FUNDING_RATE_RECORD_SIZE: usize = 112;
+const FUNDING_RATE_RECORDS_OFFSET: usize = 16; // discriminator + head 
+
+// yanked from controller::funding::update_funding_rate: funding can be
+// updated at last_funding_rate_ts + next_update_wait (rounded to the hour)
+pub fn calculate_next_funding_ts(
+    amm: &AMM,
+) -> std::result::Result<i64, ErrorCode> {
+    let mut next_update_wait = amm.funding_period;
+    if amm.funding_period > 1 {
+        let last_update_delay = amm
+            .last_funding_rate_ts
+            .rem_euclid(amm.funding_period);
+        if last_update_delay != 0 {
+            let max_delay_for_next_period = amm
+                .funding_period
+                .checked_div(3)
+                .ok_or_else(math_error!())?;
+            if last_update_delay > max_delay_for_next_period {
+                // too late for on the hour next period, delay to following period
+                next_update_wait = amm
+                    .funding_period
+                    .checked_mul(2)
+                    .ok_or_else(math_error!())?
+                    .checked_sub(last_update_delay)
+                    .ok_or_else(math_error!())?;
+            } else {
+                // allow update on the hour
+                next_update_wait = amm
+                    .funding_period
+                    .checked_sub(last_update_delay)
+                    .ok_or_else(math_error!())?;
+            }
+        }
+    }
+
+    amm.last_funding_rate_ts
+        .checked_add(next_update_wait)
+        .ok_or_else(math_error!())
+}
+
+// FundingRateHistory's fields are private so we read its (packed) account data: 
+// [discriminator][head: u64][FundingRateRecord; 1024] 
+// walks the ring buffer backwards from head => newest record first 
+pub fn read_funding_rate_records(
+    data: &[u8],
+    market_index: u64,
+    max_records: usize,
+) -> std::result::Result<Vec<FundingRateRecord>, ProgramError> {
+    let records_end = FUNDING_RATE_RECORDS_OFFSET + 
+        FUNDING_RATE_HISTORY_LENGTH as usize * FUNDING_RATE_RECORD_SIZE;
+    if data.len() < records_end || data[..8] != FundingRateHistory::discriminator() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let head = u64::from_le_bytes(data[8..16].try_into().unwrap());
+
+    let mut records = vec![];
+    for i in 1..=FUNDING_RATE_HISTORY_LENGTH {
+        if records.len() == max_records {
+            break;
+        }
+        let index = (head + FUNDING_RATE_HISTORY_LENGTH - i) % FUNDING_RATE_HISTORY_LENGTH;
+        let start = FUNDING_RATE_RECORDS_OFFSET + index as usize * FUNDING_RATE_RECORD_SIZE;
+        let record = parse_funding_rate_record(&data[start..start + FUNDING_RATE_RECORD_SIZE]);
+
+        // record ids start at 1 => an empty slot = the buffer hasnt wrapped yet 
+        let record_id = record.record_id;
+        if record_id == 0 {
+            break;
+        }
+        let record_market_index = record.market_index;
+        if record_market_index == market_index {
+            records.push(record);
+        }
+    }
+
+    Ok(records)
+}
+
+fn parse_funding_rate_record(data: &[u8]) -> FundingRateRecord {
+    let read_u64 = |start: usize| u64::from_le_bytes(data[start..start + 8].try_into().unwrap());
+    let read_u128 = |start: usize| u128::from_le_bytes(data[start..start + 16].try_into().unwrap());
+    let read_i128 = |start: usize| i128::from_le_bytes(data[start..start + 16].try_into().unwrap());
+
+    FundingRateRecord {
+        ts: read_u64(0) as i64,
+        record_id: read_u128(8),
+        market_index: read_u64(24),
+        funding_rate: read_i128(32),
+        cumulative_funding_rate_long: read_i128(48),
+        cumulative_funding_rate_short: read_i128(64),
+        oracle_price_twap: read_i128(80),
+        mark_price_twap: read_u128(96),
+    }
+}
+
+// exponentially weighted average of funding rates (newest first): 
+// record i gets weight decay^i (None = no records)
+pub fn calculate_funding_rate_ewma(
+    records: &[FundingRateRecord],
+    decay: u128,
+) -> std::result::Result<Option<i128>, ErrorCode> {
+    let mut weight = FUNDING_DECAY_PRECISION;
+    let mut weighted_funding_rate: i128 = 0;
+    let mut total_weight: u128 = 0;
+    for record in records.iter() {
+        if weight == 0 {
+            break;
+        }
+        let funding_rate = record.funding_rate;
+        weighted_funding_rate = funding_rate
+            .checked_mul(weight as i128).ok_or_else(math_error!())?
+            .checked_add(weighted_funding_rate).ok_or_else(math_error!())?;
+        total_weight = total_weight.checked_add(weight).ok_or_else(math_error!())?;
+        weight = weight
+            .checked_mul(decay).ok_or_else(math_error!())?
+            .checked_div(FUNDING_DECAY_PRECISION).ok_or_else(math_error!())?;
+    }
+
+    if total_weight == 0 {
+        return Ok(None);
+    }
+    Ok(Some(
+        weighted_funding_rate
+            .checked_div(total_weight as i128)
+            .ok_or_else(math_error!())?
+    ))
+}
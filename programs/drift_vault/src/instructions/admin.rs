@@ -0,0 +1,217 @@
+use anchor_lang::prelude::*;
+
+use crate::state::{VaultState, StrategyKind, StrategyParams, FUNDING_DECAY_PRECISION, MARKET_SHARE_PRECISION};
+use crate::funding::FUNDING_RATE_HISTORY_LENGTH;
+use crate::strategy::validate_strategy;
+use crate::error::VaultErrorCode;
+
+pub fn update_deposit_caps(
+    ctx: Context<AdminUpdateVault>, 
+    max_vault_collateral: u128,
+    max_depositor_amount: u64,
+    max_market_share: u128,
+) -> ProgramResult {
+    require!(max_market_share <= MARKET_SHARE_PRECISION, VaultErrorCode::InvalidDepositCaps);
+
+    let vault_state = &mut ctx.accounts.vault_state;
+    vault_state.max_vault_collateral = max_vault_collateral;
+    vault_state.max_depositor_amount = max_depositor_amount;
+    vault_state.max_market_share = max_market_share;
+    Ok(())
+}
+
+pub fn update_whitelist_mint(
+    ctx: Context<AdminUpdateVault>, 
+    whitelist_mint: Pubkey,
+) -> ProgramResult {
+    ctx.accounts.vault_state.whitelist_mint = whitelist_mint;
+    Ok(())
+}
+
+pub fn update_lockup(
+    ctx: Context<AdminUpdateVault>, 
+    min_holding_period: i64,
+    early_withdrawal_fee_numerator: u128,
+    early_withdrawal_fee_denominator: u128,
+) -> ProgramResult {
+    require!(min_holding_period >= 0, VaultErrorCode::InvalidLockupParams);
+    if early_withdrawal_fee_numerator > 0 {
+        require!(
+            early_withdrawal_fee_denominator > 0 && 
+            early_withdrawal_fee_numerator <= early_withdrawal_fee_denominator, 
+            VaultErrorCode::InvalidLockupParams
+        );
+    }
+
+    let vault_state = &mut ctx.accounts.vault_state;
+    vault_state.min_holding_period = min_holding_period;
+    vault_state.early_withdrawal_fee_numerator = early_withdrawal_fee_numerator;
+    vault_state.early_withdrawal_fee_denominator = early_withdrawal_fee_denominator;
+    Ok(())
+}
+
+pub fn update_idle_buffer(
+    ctx: Context<AdminUpdateVault>, 
+    idle_buffer_numerator: u128,
+    idle_buffer_denominator: u128,
+) -> ProgramResult {
+    if idle_buffer_numerator > 0 {
+        require!(
+            idle_buffer_denominator > 0 && 
+            idle_buffer_numerator <= idle_buffer_denominator, 
+            VaultErrorCode::InvalidIdleBuffer
+        );
+    }
+
+    let vault_state = &mut ctx.accounts.vault_state;
+    vault_state.idle_buffer_numerator = idle_buffer_numerator;
+    vault_state.idle_buffer_denominator = idle_buffer_denominator;
+    Ok(())
+}
+
+pub fn update_execution_mode(
+    ctx: Context<AdminUpdateVault>, 
+    use_limit_orders: bool,
+    limit_order_oracle_offset: u128,
+    max_order_age: i64,
+) -> ProgramResult {
+    if use_limit_orders {
+        // offset = 0 => clearing house reads it as a limit order w/o a price 
+        require!(
+            limit_order_oracle_offset > 0 && max_order_age > 0, 
+            VaultErrorCode::InvalidExecutionParams
+        );
+        require!(
+            ctx.accounts.vault_state.strategy != StrategyKind::FundingSpread, 
+            VaultErrorCode::SpreadOrdersNotSupported
+        );
+    }
+
+    let vault_state = &mut ctx.accounts.vault_state;
+    vault_state.use_limit_orders = use_limit_orders;
+    vault_state.limit_order_oracle_offset = limit_order_oracle_offset;
+    vault_state.max_order_age = max_order_age;
+    Ok(())
+}
+
+pub fn update_trigger_orders(
+    ctx: Context<AdminUpdateVault>, 
+    stop_loss_numerator: u128,
+    stop_loss_denominator: u128,
+    take_profit_numerator: u128,
+    take_profit_denominator: u128,
+) -> ProgramResult {
+    // stop loss >= 100% => trigger price <= 0 for longs 
+    if stop_loss_numerator > 0 {
+        require!(
+            stop_loss_denominator > 0 && 
+            stop_loss_numerator < stop_loss_denominator, 
+            VaultErrorCode::InvalidTriggerParams
+        );
+    }
+    if take_profit_numerator > 0 {
+        require!(
+            take_profit_denominator > 0 && 
+            take_profit_numerator < take_profit_denominator, 
+            VaultErrorCode::InvalidTriggerParams
+        );
+    }
+
+    if stop_loss_numerator > 0 || take_profit_numerator > 0 {
+        require!(
+            ctx.accounts.vault_state.strategy != StrategyKind::FundingSpread, 
+            VaultErrorCode::SpreadOrdersNotSupported
+        );
+    }
+
+    let vault_state = &mut ctx.accounts.vault_state;
+    vault_state.stop_loss_numerator = stop_loss_numerator;
+    vault_state.stop_loss_denominator = stop_loss_denominator;
+    vault_state.take_profit_numerator = take_profit_numerator;
+    vault_state.take_profit_denominator = take_profit_denominator;
+    Ok(())
+}
+
+pub fn update_funding_window(
+    ctx: Context<AdminUpdateVault>, 
+    funding_window: i64,
+) -> ProgramResult {
+    require!(funding_window >= 0, VaultErrorCode::InvalidFundingWindow);
+    ctx.accounts.vault_state.funding_window = funding_window;
+    Ok(())
+}
+
+pub fn update_strategy(
+    ctx: Context<AdminUpdateVault>, 
+    strategy: StrategyKind,
+    strategy_params: StrategyParams,
+) -> ProgramResult {
+    validate_strategy(strategy, &strategy_params)?;
+    if strategy == StrategyKind::FundingSpread {
+        require!(!ctx.accounts.vault_state.uses_orders(), VaultErrorCode::SpreadOrdersNotSupported);
+    }
+
+    let vault_state = &mut ctx.accounts.vault_state;
+    vault_state.strategy = strategy;
+    vault_state.strategy_params = strategy_params;
+    Ok(())
+}
+
+pub fn update_volatility_sizing(
+    ctx: Context<AdminUpdateVault>, 
+    min_leverage: u128,
+    max_leverage: u128,
+    max_volatility: u128,
+) -> ProgramResult {
+    require!(min_leverage <= max_leverage, VaultErrorCode::InvalidVolatilitySizing);
+
+    let vault_state = &mut ctx.accounts.vault_state;
+    vault_state.min_leverage = min_leverage;
+    vault_state.max_leverage = max_leverage;
+    vault_state.max_volatility = max_volatility;
+    Ok(())
+}
+
+pub fn update_funding_history(
+    ctx: Context<AdminUpdateVault>, 
+    funding_history_length: u64,
+    funding_history_decay: u128,
+    funding_history_full_size_rate: u128,
+) -> ProgramResult {
+    require!(
+        funding_history_length <= FUNDING_RATE_HISTORY_LENGTH && 
+        funding_history_decay <= FUNDING_DECAY_PRECISION, 
+        VaultErrorCode::InvalidFundingHistoryParams
+    );
+
+    let vault_state = &mut ctx.accounts.vault_state;
+    vault_state.funding_history_length = funding_history_length;
+    vault_state.funding_history_decay = funding_history_decay;
+    vault_state.funding_history_full_size_rate = funding_history_full_size_rate;
+    Ok(())
+}
+
+pub fn update_twap_staleness(
+    ctx: Context<AdminUpdateVault>, 
+    max_twap_staleness: i64,
+) -> ProgramResult {
+    require!(max_twap_staleness >= 0, VaultErrorCode::InvalidTwapStaleness);
+    ctx.accounts.vault_state.max_twap_staleness = max_twap_staleness;
+    Ok(())
+}
+
+pub fn update_funding_pause_mode(
+    ctx: Context<AdminUpdateVault>, 
+    flatten_on_funding_pause: bool,
+) -> ProgramResult {
+    ctx.accounts.vault_state.flatten_on_funding_pause = flatten_on_funding_pause;
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct AdminUpdateVault<'info> {
+    #[account(signer)]
+    pub admin: AccountInfo<'info>,
+    #[account(mut, seeds = [b"vault_state".as_ref()], bump, has_one = admin)] 
+    pub vault_state: Account<'info, VaultState>,
+}
@@ -1,3 +1,4 @@
+use std::convert::TryFrom;
 use anchor_lang::prelude::*;
 
 use anchor_spl::{
@@ -12,37 +13,170 @@ use clearing_house::cpi::accounts::{
     DepositCollateral as ClearingHouseDepositCollateral, 
 };
 use clearing_house::state::state::State;
+use clearing_house::state::market::{Market, Markets};
+use clearing_house::state::user::{User, UserPositions};
 use clearing_house::program::ClearingHouse;
+use clearing_house::math::casting::{cast_to_i128};
 
-use crate::state::VaultState;
+use crate::state::{VaultState, DepositorState, MARKET_SHARE_PRECISION};
+use crate::error::VaultErrorCode;
+use crate::optional_accounts::get_whitelist_token;
+use crate::strategy::get_strategy;
+use crate::instructions::update_position::*;
 
-pub fn deposit(
-    ctx: Context<Deposit>, 
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct DepositOptionalAccounts {
+    pub whitelist_token: bool,
+    pub deploy: Option<DeployParams>, // deploy the new collateral in the same ix 
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, Debug)]
+pub struct DeployParams {
+    pub market_index: u64,
+    // worst fill price vs the oracle (in SLIPPAGE_PRECISION, > 0)
+    pub max_slippage: u128,
+}
+
+pub fn deposit<'info>(
+    ctx: Context<'_, '_, '_, 'info, Deposit<'info>>, 
     deposit_amount: u64,
     authority_nonce: u8,
+    optional_accounts: DepositOptionalAccounts,
 ) -> ProgramResult {
     let vault_state = &mut ctx.accounts.vault_state;
 
+    // permissioned vaults: owner must hold the whitelist token 
+    if !vault_state.whitelist_mint.eq(&Pubkey::default()) {
+        let whitelist_token = get_whitelist_token(
+            optional_accounts.whitelist_token, 
+            ctx.remaining_accounts, 
+            &vault_state.whitelist_mint,
+        )?
+        .ok_or(VaultErrorCode::WhitelistTokenNotFound)?;
+
+        require!(
+            whitelist_token.owner.eq(ctx.accounts.owner.key), 
+            VaultErrorCode::InvalidWhitelistToken
+        );
+        require!(whitelist_token.amount > 0, VaultErrorCode::WhitelistTokenNotFound);
+    }
+
     // 1. mint pool tokens to user
-    // mint amount = same amount as USDC deposited
-    let mint_amount = deposit_amount; 
+    // mint amount = deposit * total_minted / total vault collateral (drift + idle buffer) 
+    // priced at the higher of the amm / oracle valuation, the opposite of withdraw => 
+    // a deposit never dilutes the existing depositors (oracles of the vault's positions 
+    // are remaining accounts)
+    let deposit_collateral_amount = ctx.accounts.get_deposit_collateral(ctx.remaining_accounts)?;
+    let vault_state = &mut ctx.accounts.vault_state;
+    let mint_amount = compute_mint_amount(deposit_amount, deposit_collateral_amount, vault_state);
+    msg!("mint amount: {}", mint_amount);
+    require!(mint_amount > 0, VaultErrorCode::DepositAmountTooSmall);
+
+    // check deposit caps up front (instead of failing in the cpi)
+    let user = &ctx.accounts.user;
+    if vault_state.max_vault_collateral > 0 {
+        // drift collateral + idle buffer 
+        let new_vault_collateral = user.collateral
+            .checked_add(ctx.accounts.vault_collateral_ata.amount as u128)
+            .unwrap()
+            .checked_add(deposit_amount as u128)
+            .unwrap();
+        require!(
+            new_vault_collateral <= vault_state.max_vault_collateral, 
+            VaultErrorCode::VaultCollateralCapExceeded
+        );
+    }
+
+    // tracked in the depositor state (vault tokens can be spread over token accounts)
+    let new_depositor_amount = ctx.accounts.depositor_state.deposited_amount
+        .checked_add(deposit_amount)
+        .unwrap();
+    if vault_state.max_depositor_amount > 0 {
+        require!(
+            new_depositor_amount <= vault_state.max_depositor_amount, 
+            VaultErrorCode::DepositorCapExceeded
+        );
+    }
+
+    // clearing house checks max_deposit against cumulative deposits
+    let max_deposit = ctx.accounts.state.max_deposit;
+    if max_deposit > 0 {
+        let new_cumulative_deposits = user.cumulative_deposits
+            .checked_add(cast_to_i128(deposit_amount)?)
+            .unwrap();
+        require!(
+            new_cumulative_deposits <= cast_to_i128(max_deposit)?, 
+            VaultErrorCode::ClearingHouseMaxDepositExceeded
+        );
+    }
+
+    // a deploy always trades w/ a limit price 
+    if let Some(deploy) = optional_accounts.deploy {
+        require!(deploy.max_slippage > 0, VaultErrorCode::InvalidSlippage);
+    }
+
+    // rebalances never grow a position past max_market_share of its side => 
+    // a deploy into a market the vault already maxed out cant do anything 
+    if let Some(deploy) = optional_accounts.deploy {
+        let max_market_share = vault_state.max_market_share;
+        if max_market_share > 0 {
+            let markets = ctx.accounts.markets.load()?;
+            let base_asset_amounts = get_base_asset_amounts(&ctx.accounts.user_positions.load()?);
+            for market_index in get_strategy(vault_state, deploy.market_index).get_market_indexes() {
+                let base_asset_amount = base_asset_amounts
+                    .iter()
+                    .find(|(position_market_index, _)| *position_market_index == market_index)
+                    .map_or(0, |(_, base_asset_amount)| *base_asset_amount);
+                if base_asset_amount == 0 {
+                    continue;
+                }
+                let market_share = calculate_market_share(base_asset_amount, markets.get_market(market_index));
+                msg!("market {} share: {}", market_index, market_share);
+                require!(market_share < max_market_share, VaultErrorCode::MarketShareExceeded);
+            }
+        }
+    }
 
     // record deposit in state 
     vault_state.total_amount_minted = vault_state.total_amount_minted
         .checked_add(mint_amount)
         .unwrap();
-    
+
     let authority_seeds = [
         b"authority".as_ref(),
         &[authority_nonce][..],
     ];
     let signers = &[&authority_seeds[..]];
+
+    // lockup restarts on every deposit => release the shares which are unlocked first 
+    let now = Clock::get()?.unix_timestamp;
+    let min_holding_period = vault_state.min_holding_period;
+    let depositor_state = &mut ctx.accounts.depositor_state;
+    let locked_amount = ctx.accounts.locked_shares.amount;
+    if locked_amount > 0 && now >= depositor_state.get_unlock_ts(min_holding_period) {
+        msg!("unlocking shares: {}", locked_amount);
+        transfer(CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.locked_shares.to_account_info(),
+                to: ctx.accounts.user_vault_ata.to_account_info(),
+                authority: ctx.accounts.authority.to_account_info(),
+            }
+        ).with_signer(signers), locked_amount)?;
+    }
+    depositor_state.last_deposit_ts = now;
+    depositor_state.deposited_amount = new_depositor_amount;
     
-    // send mint to user 
+    // send mint to user (held in the locked_shares account during the lockup 
+    // so the lock follows the shares, not the wallet)
+    let mint_to_account = match min_holding_period > 0 {
+        true => ctx.accounts.locked_shares.to_account_info(), 
+        false => ctx.accounts.user_vault_ata.to_account_info(), 
+    };
     mint_to(CpiContext::new(
         ctx.accounts.token_program.to_account_info(), 
         MintTo {
-            to: ctx.accounts.user_vault_ata.to_account_info(),
+            to: mint_to_account,
             mint: ctx.accounts.vault_mint.to_account_info(),
             authority: ctx.accounts.authority.to_account_info(),
         }).with_signer(signers), 
@@ -89,9 +223,97 @@ pub fn deposit(
     );
     clearing_house::cpi::deposit_collateral(cpi_ctx, deposit_amount)?;
 
+    // 3. (optional) deploy the new collateral like update_position 
+    // (orders are placed + refreshed by the cranks => dont fail the deposit, leave it to them)
+    let deploy = match optional_accounts.deploy {
+        Some(_) if ctx.accounts.vault_state.uses_orders() => {
+            msg!("vault uses orders, not deploying...");
+            None
+        }
+        deploy => deploy,
+    };
+    if let Some(deploy) = deploy {
+        // persist the deposit before update_position re-loads the vault state 
+        ctx.accounts.vault_state.exit(ctx.program_id)?;
+
+        // update_position accounts come after the whitelist token
+        let mut update_position_accounts = ctx.remaining_accounts
+            .get(optional_accounts.whitelist_token as usize..)
+            .unwrap_or(&[]);
+        let mut update_position = UpdatePosition::try_accounts(
+            ctx.program_id, 
+            &mut update_position_accounts, 
+            &[],
+        )?;
+
+        msg!("deploying deposit...");
+        // oracles of the strategy's other markets come after the update_position accounts
+        // only the new collateral is traded, w/ a limit price on every fill
+        update_position.rebalance(
+            deploy.market_index, 
+            deploy.max_slippage, 
+            Some(deposit_amount as u128), 
+            update_position_accounts, 
+            signers,
+        )?;
+        update_position.exit(ctx.program_id)?;
+
+        // dont overwrite update_position's changes on exit 
+        ctx.accounts.vault_state.reload()?;
+    }
+
     Ok(())
 }
 
+// shares = deposit * total_minted / collateral, 1:1 for the first deposit 
+// (rounded down: the dust stays w/ the existing depositors)
+pub fn compute_mint_amount(
+    deposit_amount: u64, 
+    collateral_amount: u128,
+    state: &VaultState,
+) -> u64 {
+    if state.total_amount_minted == 0 {
+        return deposit_amount;
+    }
+
+    // nothing backs the outstanding shares => no price to mint at 
+    let mint_amount = (deposit_amount as u128)
+        .checked_mul(state.total_amount_minted as u128).unwrap()
+        .checked_div(collateral_amount).unwrap_or(0);
+
+    u64::try_from(mint_amount).unwrap()
+}
+
+// (market_index, base asset amount) of the vault's open positions 
+pub fn get_base_asset_amounts(
+    user_positions: &UserPositions,
+) -> Vec<(u64, i128)> {
+    user_positions
+        .positions
+        .iter()
+        .filter(|market_position| market_position.base_asset_amount != 0)
+        .map(|market_position| (market_position.market_index, market_position.base_asset_amount))
+        .collect()
+}
+
+// the vault's base asset amount / the base asset amount of its side of the market 
+// (in MARKET_SHARE_PRECISION, the vault's own position incl.)
+pub fn calculate_market_share(
+    base_asset_amount: i128, 
+    market: &Market,
+) -> u128 {
+    let side_base_asset_amount = match base_asset_amount > 0 {
+        true => market.base_asset_amount_long, 
+        false => market.base_asset_amount_short, 
+    };
+    match side_base_asset_amount {
+        0 => 0, 
+        side_base_asset_amount => base_asset_amount.unsigned_abs()
+            .checked_mul(MARKET_SHARE_PRECISION).unwrap()
+            .checked_div(side_base_asset_amount.unsigned_abs()).unwrap(),
+    }
+}
+
 #[derive(Accounts)]
 pub struct Deposit<'info> {
     // depositer / owner of ATAs 
@@ -120,27 +342,40 @@ pub struct Deposit<'info> {
         constraint = &user_vault_ata.mint.eq(&vault_mint.key())
     )]
     pub user_vault_ata: Box<Account<'info, TokenAccount>>,  // mint to this 
+    #[account(
+        mut, 
+        seeds = [b"locked_shares".as_ref(), owner.key.as_ref()], 
+        bump, 
+    )]
+    pub locked_shares: Box<Account<'info, TokenAccount>>, // or this (w/ a lockup)
     
     // vault stuff 
     #[account(mut, seeds = [b"vault_state".as_ref()], bump)] 
     pub vault_state: Account<'info, VaultState>,
     #[account(mut, seeds = [b"vault_mint".as_ref()], bump)] 
     pub vault_mint: Account<'info, Mint>,
+    #[account(
+        mut, 
+        seeds = [b"depositor".as_ref(), owner.key.as_ref()], 
+        bump, 
+        has_one = owner
+    )] 
+    pub depositor_state: Account<'info, DepositorState>,
     
     // drift vault stuff
     #[account(mut, seeds = [b"authority".as_ref()], bump)]
     pub authority: AccountInfo<'info>,
     #[account(mut, seeds = [b"user_positions".as_ref()], bump)]
-    pub user_positions: AccountInfo<'info>,
+    pub user_positions: AccountLoader<'info, UserPositions>,
     #[account(mut)]
-    pub user: AccountInfo<'info>,
+    pub user: Box<Account<'info, User>>,
 
     // drift clearing house stuff 
     #[account(mut)]
     pub state: Box<Account<'info, State>>,
     #[account(mut)]
     pub collateral_vault: Box<Account<'info, TokenAccount>>,
-    pub markets: AccountInfo<'info>,
+    pub markets: AccountLoader<'info, Markets>,
     #[account(mut)]
     pub funding_payment_history: AccountInfo<'info>,
     #[account(mut)]
@@ -150,3 +385,22 @@ pub struct Deposit<'info> {
     pub clearing_house_program: Program<'info, ClearingHouse>,
     pub token_program: Program<'info, Token>,
 }
+
+impl<'info> Deposit<'info> {
+    // total vault collateral (drift + idle buffer) used to price new vault tokens: 
+    // the higher of the amm + oracle valuations
+    pub fn get_deposit_collateral(
+        &self, 
+        oracles: &[AccountInfo<'info>],
+    ) -> std::result::Result<u128, ProgramError> {
+        let [amm_collateral_amount, oracle_collateral_amount] = get_amm_oracle_collateral(
+            &self.user, 
+            &self.user_positions.load()?, 
+            &self.markets.load()?, 
+            oracles, 
+            Clock::get()?.slot,
+        )?;
+        let idle_amount = self.vault_collateral_ata.amount as u128;
+        Ok(amm_collateral_amount.max(oracle_collateral_amount) + idle_amount)
+    }
+}
@@ -10,22 +10,36 @@ use clearing_house::context::{
 };
 use clearing_house::cpi::accounts::{
     InitializeUserWithExplicitPayer,
+    InitializeUserOrdersWithExplicitPayer,
 };
 use clearing_house::state::state::State;
 use clearing_house::program::ClearingHouse;
 
-use crate::state::VaultState;
+use crate::state::{VaultState, DepositorState, StrategyKind, StrategyParams};
+use crate::strategy::validate_strategy;
 
 pub fn initialize_vault(
     ctx: Context<InitializeVault>, 
     user_nonce: u8, 
     authority_nonce: u8,
     user_positions_nonce: u8,
+    user_orders_nonce: u8,
+    strategy: StrategyKind,
+    strategy_params: StrategyParams,
 ) -> ProgramResult {
 
     // 1. create pool mint for LPs [done by anchor]
     // 2. create vault collateral ATA [done by anchor]
 
+    // payer administers the vault params 
+    let vault_state = &mut ctx.accounts.vault_state;
+    vault_state.admin = ctx.accounts.payer.key();
+
+    // 5. pick the vault's strategy 
+    validate_strategy(strategy, &strategy_params)?;
+    vault_state.strategy = strategy;
+    vault_state.strategy_params = strategy_params;
+
     let authority_seeds = [
         b"authority".as_ref(),
         &[authority_nonce][..],
@@ -62,6 +76,29 @@ pub fn initialize_vault(
         },
     )?;
 
+    // 4. create drift orders account (for limit order execution)
+    let cpi_program = ctx.accounts.clearing_house_program.to_account_info();
+    let cpi_accounts = InitializeUserOrdersWithExplicitPayer {
+        user: ctx.accounts.user.to_account_info(), 
+        user_orders: ctx.accounts.user_orders.clone(), 
+        state: ctx.accounts.state.to_account_info(), 
+        authority: ctx.accounts.authority.clone(), 
+
+        payer: ctx.accounts.payer.clone(), 
+        rent: ctx.accounts.rent.to_account_info(),
+        system_program: ctx.accounts.system_program.to_account_info(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(
+        cpi_program, 
+        cpi_accounts,
+        &[&authority_seeds[..]],
+    );
+
+    clearing_house::cpi::initialize_user_orders_with_explicit_payer(
+        cpi_ctx,
+        user_orders_nonce,
+    )?;
+
     Ok(())
 }
 
@@ -77,6 +114,9 @@ pub struct InitializeVault<'info> {
     pub user: AccountInfo<'info>,
     #[account(mut, seeds = [b"user_positions".as_ref()], bump)]
     pub user_positions: AccountInfo<'info>,
+    // PDA of the clearing house (["user_orders", user])
+    #[account(mut)]
+    pub user_orders: AccountInfo<'info>,
     // drift clearing house 
     pub state: Box<Account<'info, State>>,
     
@@ -119,3 +159,45 @@ pub struct InitializeVault<'info> {
     pub token_program: Program<'info, Token>,
     pub clearing_house_program: Program<'info, ClearingHouse>,
 }
+
+pub fn initialize_depositor(
+    ctx: Context<InitializeDepositor>, 
+) -> ProgramResult {
+    let depositor_state = &mut ctx.accounts.depositor_state;
+    depositor_state.owner = ctx.accounts.owner.key();
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct InitializeDepositor<'info> {
+    #[account(mut, signer)]
+    pub owner: AccountInfo<'info>,
+    #[account(
+        init, 
+        payer = owner,
+        seeds = [b"depositor".as_ref(), owner.key.as_ref()], 
+        bump, 
+    )] 
+    pub depositor_state: Account<'info, DepositorState>,
+    // vault tokens minted within the lockup 
+    #[account(
+        init,
+        payer = owner,
+        seeds = [b"locked_shares".as_ref(), owner.key.as_ref()],
+        bump,
+        token::mint = vault_mint,
+        token::authority = authority
+    )]
+    pub locked_shares: Box<Account<'info, TokenAccount>>,
+
+    // vault stuff 
+    #[account(seeds = [b"vault_mint".as_ref()], bump)] 
+    pub vault_mint: Account<'info, Mint>,
+    #[account(seeds = [b"authority".as_ref()], bump)]
+    pub authority: AccountInfo<'info>,
+
+    // system stuff 
+    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+}
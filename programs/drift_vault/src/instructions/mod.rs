@@ -7,5 +7,14 @@ pub use deposit::*;
 pub mod withdraw;
 pub use withdraw::*;
 
+pub mod unlock_shares;
+pub use unlock_shares::*;
+
 pub mod update_position;
-pub use update_position::*;
\ No newline at end of file
+pub use update_position::*;
+
+pub mod admin;
+pub use admin::*;
+
+pub mod orders;
+pub use orders::*;
@@ -0,0 +1,355 @@
+use anchor_lang::prelude::*;
+
+use clearing_house::context::{
+    OrderParams as ClearingHouseOrderParams,
+    OrderParamsOptionalAccounts as ClearingHouseOrderParamsOptionalAccounts,
+};
+use clearing_house::controller::position::PositionDirection as ClearingHousePositionDirection;
+use clearing_house::cpi::accounts::{
+    CancelOrder as ClearingHouseCancelOrder,
+    PlaceOrder as ClearingHousePlaceOrder,
+};
+use clearing_house::state::market::Markets;
+use clearing_house::state::order_state::OrderState;
+use clearing_house::state::user_orders::{OrderStatus, OrderTriggerCondition, OrderType, UserOrders};
+use clearing_house::math::constants::{AMM_TO_QUOTE_PRECISION_RATIO, MARK_PRICE_PRECISION};
+use clearing_house::math::casting::{cast_to_i128};
+use clearing_house::error::ErrorCode;
+
+use crate::instructions::update_position::*;
+use crate::state::{Position, StrategyKind};
+use crate::error::VaultErrorCode;
+use crate::math_error;
+
+pub fn update_position_with_orders(
+    ctx: Context<UpdatePositionWithOrders>,
+    market_index: u64,
+    authority_nonce: u8,
+) -> ProgramResult {
+    let authority_seeds = [
+        b"authority".as_ref(),
+        &[authority_nonce][..],
+    ];
+    let signers = &[&authority_seeds[..]];
+
+    ctx.accounts.rebalance(market_index, signers)
+}
+
+// crank: cancel + replace the vault's orders once a limit order is older than
+// max_order_age or the trigger orders dont match the position (resized / flipped)
+pub fn refresh_orders(
+    ctx: Context<UpdatePositionWithOrders>,
+    market_index: u64,
+    authority_nonce: u8,
+) -> ProgramResult {
+    let now = Clock::get()?.unix_timestamp;
+    let max_order_age = ctx.accounts.update_position.vault_state.max_order_age;
+    let position_size = ctx.accounts.get_position_size(market_index);
+    let has_stale_order = ctx.accounts.user_orders.load()?
+        .orders
+        .iter()
+        .filter(|order| order.status == OrderStatus::Open)
+        .any(|order| match order.order_type {
+            OrderType::Limit => now.checked_sub(order.ts).unwrap() >= max_order_age,
+            OrderType::TriggerMarket => order.base_asset_amount != position_size,
+            _ => false,
+        });
+
+    if !has_stale_order {
+        msg!("no stale orders, not doing anything...");
+        return Ok(());
+    }
+
+    let authority_seeds = [
+        b"authority".as_ref(),
+        &[authority_nonce][..],
+    ];
+    let signers = &[&authority_seeds[..]];
+
+    ctx.accounts.rebalance(market_index, signers)
+}
+
+#[derive(Accounts)]
+pub struct UpdatePositionWithOrders<'info> {
+    pub update_position: UpdatePosition<'info>,
+
+    // drift order things
+    pub order_state: Box<Account<'info, OrderState>>,
+    #[account(mut)]
+    pub user_orders: AccountLoader<'info, UserOrders>,
+    #[account(mut)]
+    pub order_history: AccountInfo<'info>,
+}
+
+impl<'info> UpdatePositionWithOrders<'info> {
+
+    // same as UpdatePosition::rebalance but the increases are placed as
+    // post-only limit orders at oracle -/+ offset (if use_limit_orders) and
+    // the position is covered by stop loss / take profit trigger orders
+    pub fn rebalance(
+        &mut self,
+        market_index: u64,
+        signers: &[&[&[u8]]],
+    ) -> ProgramResult {
+        require!(
+            self.update_position.vault_state.strategy != StrategyKind::FundingSpread,
+            VaultErrorCode::SpreadOrdersNotSupported
+        );
+        let frozen = self.update_position.check_pauses(market_index)?;
+
+        // open orders dont count towards the position =>
+        // cancel them first so we dont double up (+ so none stay live while frozen)
+        self.cancel_all_orders(signers)?;
+        if frozen {
+            return Ok(());
+        }
+
+        let trades = self.update_position.prepare_rebalance(market_index, None, &[], signers)?;
+        for trade in trades {
+            if self.update_position.vault_state.use_limit_orders && !trade.is_reduce {
+                self.place_limit_order(
+                    trade.amount,
+                    trade.direction,
+                    signers,
+                    trade.market_index
+                )?;
+            } else {
+                self.update_position.open_position(
+                    trade.amount,
+                    0,
+                    trade.direction,
+                    signers,
+                    trade.market_index
+                )?;
+                self.update_position.user.reload()?;
+            }
+        }
+
+        // protect the (new) position
+        self.place_trigger_orders(signers, market_index)?;
+
+        Ok(())
+    }
+
+    // abs(base asset amount) of the vault's position (0 = no position)
+    pub fn get_position_size(
+        &self,
+        market_index: u64,
+    ) -> u128 {
+        let vault_positions = &self.update_position.user_positions.load().unwrap();
+        vault_positions
+            .positions
+            .iter()
+            .find(|market_position| market_position.is_for(market_index))
+            .map_or(0, |market_position| market_position.base_asset_amount.unsigned_abs())
+    }
+
+    // reduce-only stop loss / take profit at % away from the entry price
+    pub fn place_trigger_orders(
+        &self,
+        signers: &[&[&[u8]]],
+        market_index: u64,
+    ) -> ProgramResult {
+        let vault_state = &self.update_position.vault_state;
+        if vault_state.stop_loss_numerator == 0 && vault_state.take_profit_numerator == 0 {
+            return Ok(());
+        }
+
+        let (base_asset_amount, entry_price, position_direction);
+        {
+            let vault_positions = &self.update_position.user_positions.load()?;
+            let market_position = match vault_positions
+                .positions
+                .iter()
+                .find(|market_position| market_position.is_for(market_index)) {
+                Some(market_position) if market_position.base_asset_amount != 0 => market_position,
+                _ => {
+                    msg!("no position, not placing trigger orders...");
+                    return Ok(());
+                }
+            };
+
+            // entry price = quote / base (in mark price precision)
+            base_asset_amount = market_position.base_asset_amount.unsigned_abs();
+            entry_price = market_position.quote_asset_amount
+                .checked_mul(AMM_TO_QUOTE_PRECISION_RATIO).ok_or_else(math_error!())?
+                .checked_mul(MARK_PRICE_PRECISION).ok_or_else(math_error!())?
+                .checked_div(base_asset_amount).ok_or_else(math_error!())?;
+            position_direction = if market_position.base_asset_amount > 0 {
+                Position::Long
+            } else {
+                Position::Short
+            };
+        }
+        msg!("(entry price, base) amount: {}, {}", entry_price, base_asset_amount);
+
+        // longs lose when the price goes down, shorts when it goes up
+        let (close_direction, stop_loss_condition, take_profit_condition) = match position_direction {
+            Position::Long => (
+                ClearingHousePositionDirection::Short,
+                OrderTriggerCondition::Below,
+                OrderTriggerCondition::Above,
+            ),
+            _ => (
+                ClearingHousePositionDirection::Long,
+                OrderTriggerCondition::Above,
+                OrderTriggerCondition::Below,
+            ),
+        };
+
+        let triggers = [
+            (vault_state.stop_loss_numerator, vault_state.stop_loss_denominator, stop_loss_condition),
+            (vault_state.take_profit_numerator, vault_state.take_profit_denominator, take_profit_condition),
+        ];
+        for (numerator, denominator, trigger_condition) in triggers.iter() {
+            if *numerator == 0 {
+                continue;
+            }
+
+            let price_delta = entry_price
+                .checked_mul(*numerator).ok_or_else(math_error!())?
+                .checked_div(*denominator).ok_or_else(math_error!())?;
+            let trigger_price = match trigger_condition {
+                OrderTriggerCondition::Above => entry_price.checked_add(price_delta),
+                OrderTriggerCondition::Below => entry_price.checked_sub(price_delta),
+            }.ok_or_else(math_error!())?;
+
+            self.place_order(
+                ClearingHouseOrderParams {
+                    order_type: OrderType::TriggerMarket,
+                    direction: close_direction,
+                    base_asset_amount,
+                    market_index,
+                    reduce_only: true,
+                    trigger_price,
+                    trigger_condition: *trigger_condition,
+                    optional_accounts: ClearingHouseOrderParamsOptionalAccounts {
+                        discount_token: false,
+                        referrer: false,
+                    },
+                    ..ClearingHouseOrderParams::default()
+                },
+                signers,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn cancel_all_orders(
+        &self,
+        signers: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let update_position = &self.update_position;
+
+        let cpi_program = update_position.clearing_house_program.to_account_info();
+        let cpi_accounts = ClearingHouseCancelOrder {
+            state: update_position.state.to_account_info(),
+            order_state: self.order_state.to_account_info(),
+            user: update_position.user.to_account_info(),
+            authority: update_position.authority.clone(),
+            markets: update_position.markets.to_account_info(),
+            user_positions: update_position.user_positions.to_account_info(),
+            user_orders: self.user_orders.to_account_info(),
+            funding_payment_history: update_position.funding_payment_history.to_account_info(),
+            order_history: self.order_history.to_account_info(),
+        };
+        // oracle offset orders need the oracle to cancel
+        let cpi_ctx = CpiContext::new_with_signer(
+            cpi_program,
+            cpi_accounts,
+            signers
+        ).with_remaining_accounts(vec![update_position.oracle.clone()]);
+
+        clearing_house::cpi::cancel_all_orders(cpi_ctx)
+    }
+
+    pub fn place_limit_order(
+        &self,
+        amount_in: u128,
+        position_direction: Position,
+        signers: &[&[&[u8]]],
+        market_index: u64,
+    ) -> ProgramResult {
+        let update_position = &self.update_position;
+        let vault_state = &update_position.vault_state;
+
+        // quote amount => base amount @ oracle price
+        let base_asset_amount;
+        {
+            let market = &update_position.markets.load()?
+                .markets[Markets::index_from_u64(market_index)];
+            let clock_slot = Clock::get()?.slot;
+            let oracle_price = market.amm
+                .get_oracle_price(&update_position.oracle, clock_slot)?
+                .price;
+
+            base_asset_amount = amount_in
+                .checked_mul(AMM_TO_QUOTE_PRECISION_RATIO).ok_or_else(math_error!())?
+                .checked_mul(MARK_PRICE_PRECISION).ok_or_else(math_error!())?
+                .checked_div(oracle_price.unsigned_abs()).ok_or_else(math_error!())?;
+
+            if base_asset_amount < market.amm.minimum_base_asset_trade_size ||
+                amount_in < self.order_state.min_order_quote_asset_amount {
+                msg!("order too small, not placing...");
+                return Ok(());
+            }
+        }
+
+        // bid below / ask above the oracle (maker side)
+        let offset = cast_to_i128(vault_state.limit_order_oracle_offset)?;
+        let (clearing_house_direction, oracle_price_offset) = match position_direction {
+            Position::Long => (ClearingHousePositionDirection::Long, -offset),
+            Position::Short => (ClearingHousePositionDirection::Short, offset),
+            _ => panic!("shouldnt be called...")
+        };
+        msg!("placing a {:?} limit order: base {} oracle offset {}",
+            position_direction, base_asset_amount, oracle_price_offset);
+
+        self.place_order(
+            ClearingHouseOrderParams {
+                order_type: OrderType::Limit,
+                direction: clearing_house_direction,
+                base_asset_amount,
+                market_index,
+                post_only: true,
+                oracle_price_offset,
+                optional_accounts: ClearingHouseOrderParamsOptionalAccounts {
+                    discount_token: false,
+                    referrer: false,
+                },
+                ..ClearingHouseOrderParams::default()
+            },
+            signers,
+        )
+    }
+
+    pub fn place_order(
+        &self,
+        params: ClearingHouseOrderParams,
+        signers: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let update_position = &self.update_position;
+
+        let cpi_program = update_position.clearing_house_program.to_account_info();
+        let cpi_accounts = ClearingHousePlaceOrder {
+            state: update_position.state.to_account_info(),
+            order_state: self.order_state.to_account_info(),
+            user: update_position.user.to_account_info(),
+            authority: update_position.authority.clone(),
+            markets: update_position.markets.to_account_info(),
+            user_positions: update_position.user_positions.to_account_info(),
+            user_orders: self.user_orders.to_account_info(),
+            funding_payment_history: update_position.funding_payment_history.to_account_info(),
+            order_history: self.order_history.to_account_info(),
+        };
+        // oracle offset orders need the oracle to place
+        let cpi_ctx = CpiContext::new_with_signer(
+            cpi_program,
+            cpi_accounts,
+            signers
+        ).with_remaining_accounts(vec![update_position.oracle.clone()]);
+
+        clearing_house::cpi::place_order(cpi_ctx, params)
+    }
+}
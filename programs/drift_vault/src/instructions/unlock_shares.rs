@@ -0,0 +1,76 @@
+use anchor_lang::prelude::*;
+
+use anchor_spl::{
+    token::{
+        Token, TokenAccount, 
+        Transfer, transfer, 
+    },
+};
+
+use crate::state::{VaultState, DepositorState};
+use crate::error::VaultErrorCode;
+
+pub fn unlock_shares(
+    ctx: Context<UnlockShares>, 
+    authority_nonce: u8,
+) -> ProgramResult {
+    let now = Clock::get()?.unix_timestamp;
+    let unlock_ts = ctx.accounts.depositor_state
+        .get_unlock_ts(ctx.accounts.vault_state.min_holding_period);
+    msg!("unlocks at {}", unlock_ts);
+    require!(now >= unlock_ts, VaultErrorCode::DepositLocked);
+
+    let locked_amount = ctx.accounts.locked_shares.amount;
+    require!(locked_amount > 0, VaultErrorCode::NotEnoughFunds);
+
+    let authority_seeds = [
+        b"authority".as_ref(),
+        &[authority_nonce][..],
+    ];
+    let signers = &[&authority_seeds[..]];
+
+    // locked shares => user ATA 
+    transfer(CpiContext::new(
+        ctx.accounts.token_program.to_account_info(),
+        Transfer {
+            from: ctx.accounts.locked_shares.to_account_info(),
+            to: ctx.accounts.user_vault_ata.to_account_info(),
+            authority: ctx.accounts.authority.to_account_info(),
+        }
+    ).with_signer(signers), locked_amount)?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct UnlockShares<'info> {
+    #[account(signer)]
+    pub owner: AccountInfo<'info>, 
+
+    #[account(
+        mut, 
+        has_one = owner, 
+        constraint = &user_vault_ata.mint.eq(&locked_shares.mint)
+    )]
+    pub user_vault_ata: Box<Account<'info, TokenAccount>>,  
+    #[account(
+        mut, 
+        seeds = [b"locked_shares".as_ref(), owner.key.as_ref()], 
+        bump, 
+    )]
+    pub locked_shares: Box<Account<'info, TokenAccount>>,
+
+    // vault stuff 
+    #[account(seeds = [b"vault_state".as_ref()], bump)] 
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        seeds = [b"depositor".as_ref(), owner.key.as_ref()], 
+        bump, 
+        has_one = owner
+    )] 
+    pub depositor_state: Account<'info, DepositorState>,
+    #[account(seeds = [b"authority".as_ref()], bump)]
+    pub authority: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
@@ -1,6 +1,10 @@
 use core::panic;
 use anchor_lang::prelude::*;
 
+use anchor_spl::{
+    token::{Token, TokenAccount},
+};
+
 use clearing_house::context::{
     ManagePositionOptionalAccounts as ClearingHouseManagePositionOptionalAccounts,
 };
@@ -8,6 +12,8 @@ use clearing_house::controller::position::PositionDirection as ClearingHousePosi
 use clearing_house::cpi::accounts::{
     ClosePosition as ClearingHouseClosePosition,
     OpenPosition as ClearingHouseOpenPosition,
+    DepositCollateral as ClearingHouseDepositCollateral, 
+    WithdrawCollateral as ClearingHouseWithdrawCollateral,
 };
 use clearing_house::state::state::State;
 use clearing_house::program::ClearingHouse;
@@ -15,58 +21,27 @@ use clearing_house::state::{
     market::Markets,
     user::{User, UserPositions},
 };
-use clearing_house::math::casting::{cast_to_i128};
-use clearing_house::error::ErrorCode;
-use clearing_house::math::position::calculate_base_asset_value_and_pnl;
 
-use crate::state::{Position};
+use crate::state::{VaultState, Position, SLIPPAGE_PRECISION};
+use crate::error::VaultErrorCode;
 use crate::math_error;
+use crate::funding::read_funding_rate_records;
+use crate::twap::{MarketTwaps, calculate_fresh_twaps, validate_twap_staleness};
+use crate::events::FundingPauseEvent;
+use crate::strategy::{
+    get_strategy, StrategySnapshot, 
+    calculate_collateral_liabilities, calculate_position_value,
+    calculate_collateral_with_oracle_prices,
+    get_stale_market_indexes, calculate_trades, Trade,
+    calculate_rebalance_targets, calculate_deploy_trades,
+};
 
-pub fn update_position(
-    ctx: Context<UpdatePosition>, 
+pub fn update_position<'info>(
+    ctx: Context<'_, '_, '_, 'info, UpdatePosition<'info>>, 
     market_index: u64,
     authority_nonce: u8,
 ) -> ProgramResult {
 
-    // 1. compute funding_rate = mark - oracle 
-    let approx_funding;
-    {
-        let market = &ctx.accounts.markets.load()?
-            .markets[Markets::index_from_u64(market_index)];
-        let oracle_price_twap = market.amm.last_oracle_price_twap;
-        let mark_price_twap = market.amm.last_mark_price_twap;
-
-        // negative = shorts pay longs (should go long)
-        // positive = longs pay shorts (should go short)
-        approx_funding = cast_to_i128(mark_price_twap)?
-            .checked_sub(oracle_price_twap)
-            .ok_or_else(math_error!())?;
-
-        msg!("(mark twap, oracle twap): {} {} approx funding: {}", mark_price_twap, oracle_price_twap, approx_funding);
-    }
-
-    if approx_funding == 0 { 
-        msg!("funding = 0, not doing anything...");
-        return Ok(());
-    }
-
-    let funding_direction = if approx_funding < 0 { // funding goes to longs 
-        Position::Long
-    } else { 
-        Position::Short
-    };
-
-    // get current position 
-    let vault_position = ctx.accounts.get_current_position(market_index);
-    msg!("current position: {:?}", vault_position);
-
-    // print the state of the current position of vault before anything
-    ctx.accounts.get_position_state(true);
-        
-    // 2. do:
-    //  if funding = good for longs => *open_long()
-    //  if funding = good for shorts => *open_short()
-    
     // get vault signature 
     let authority_seeds = [
         b"authority".as_ref(),
@@ -74,36 +49,73 @@ pub fn update_position(
     ];
     let signers = &[&authority_seeds[..]];
 
-    /* Note: for now, if we need to reverse (Long=>Short / Short=>Long) 
-    * we use 2 steps (close, new_pos) but 
-    * in future we can do this in a single step for less fees 
-    */
-
-    if vault_position != Position::None && 
-        vault_position != funding_direction {
-        msg!("closing {:?}...", vault_position);
-        ctx.accounts.close_position(
-            signers, 
-            market_index
-        )?;
-        let user = &mut ctx.accounts.user; 
-        user.reload()?; // update underlying account 
-    }
+    // oracles of the strategy's other markets are remaining accounts 
+    ctx.accounts.rebalance(market_index, 0, None, ctx.remaining_accounts, signers)
+}
 
-    // compute how much we can trade for 1:1 
-    let amount_to_trade = ctx.accounts.get_position_state(true)[2];
+// worst average fill price the clearing house accepts for a market order: 
+// oracle price +/- max_slippage (longs pay up to it, shorts sell down to it)
+pub fn calculate_limit_price(
+    oracle_price: i128, 
+    direction: Position, 
+    max_slippage: u128,
+) -> std::result::Result<u128, ProgramError> {
+    let slippage_factor = match direction {
+        Position::Long => SLIPPAGE_PRECISION.checked_add(max_slippage).ok_or_else(math_error!())?,
+        Position::Short => SLIPPAGE_PRECISION.saturating_sub(max_slippage),
+        Position::None => return Ok(0),
+    };
+    Ok(oracle_price.unsigned_abs()
+        .checked_mul(slippage_factor).ok_or_else(math_error!())?
+        .checked_div(SLIPPAGE_PRECISION).ok_or_else(math_error!())?)
+}
 
-    if amount_to_trade > 0 {
-        ctx.accounts.open_position(
-            amount_to_trade, 
-            0, 
-            funding_direction, 
-            signers, 
-            market_index
-        )?;
+// idle buffer % of the total collateral (drift + idle buffer)
+pub fn calculate_idle_buffer_target(
+    total_collateral: u128, 
+    vault_state: &VaultState,
+) -> u128 {
+    if vault_state.idle_buffer_numerator == 0 {
+        return 0;
     }
+    total_collateral
+        .checked_mul(vault_state.idle_buffer_numerator).unwrap()
+        .checked_div(vault_state.idle_buffer_denominator).unwrap()
+}
 
-    Ok(())
+// [amm, oracle] valuation of the drift collateral (+ pnl) 
+// (oracles = the accounts to find the oracles of the vault's positions in)
+pub fn get_amm_oracle_collateral(
+    user: &User, 
+    user_positions: &UserPositions, 
+    markets: &Markets, 
+    oracles: &[AccountInfo],
+    clock_slot: u64,
+) -> std::result::Result<[u128; 2], ProgramError> {
+    let [amm_collateral_amount, ..] = calculate_collateral_liabilities(user, user_positions, markets);
+
+    let mut oracle_prices = vec![];
+    for market_position in user_positions.positions.iter() {
+        if market_position.base_asset_amount == 0 {
+            continue;
+        }
+        let market_index = market_position.market_index;
+        let amm = &markets.get_market(market_index).amm;
+        let oracle = oracles
+            .iter()
+            .find(|oracle| oracle.key.eq(&amm.oracle))
+            .ok_or(VaultErrorCode::OracleNotFound)?;
+        let oracle_price_data = amm.get_oracle_price(oracle, clock_slot)?;
+        oracle_prices.push((market_index, oracle_price_data.price));
+    }
+    let oracle_collateral_amount = calculate_collateral_with_oracle_prices(
+        user, 
+        user_positions, 
+        &oracle_prices,
+    )?;
+    msg!("(amm, oracle) collateral: {}, {}", amm_collateral_amount, oracle_collateral_amount);
+
+    Ok([amm_collateral_amount, oracle_collateral_amount])
 }
 
 #[derive(Accounts)]
@@ -112,6 +124,16 @@ pub struct UpdatePosition<'info> {
     pub authority: AccountInfo<'info>,
     #[account(mut, seeds = [b"user_positions".as_ref()], bump)]
     pub user_positions: AccountLoader<'info, UserPositions>,
+    #[account(mut, seeds = [b"vault_state".as_ref()], bump)] 
+    pub vault_state: Box<Account<'info, VaultState>>,
+    // idle buffer 
+    #[account(
+        mut, 
+        seeds = [b"vault_collateral".as_ref()],
+        bump,
+        constraint = &vault_collateral_ata.mint.eq(&state.collateral_mint)
+    )]
+    pub vault_collateral_ata: Box<Account<'info, TokenAccount>>,  
 
     #[account(mut)]
     pub state: Box<Account<'info, State>>,
@@ -123,14 +145,246 @@ pub struct UpdatePosition<'info> {
     pub trade_history: AccountInfo<'info>,
     #[account(mut)]
     pub funding_payment_history: AccountInfo<'info>,
-    #[account(mut)]
+    #[account(mut, constraint = &state.funding_rate_history.eq(&funding_rate_history.key()))]
     pub funding_rate_history: AccountInfo<'info>,
     pub oracle: AccountInfo<'info>,
+
+    // drift collateral things (to move collateral in/out of the idle buffer)
+    #[account(mut)]
+    pub collateral_vault: Box<Account<'info, TokenAccount>>,
+    pub collateral_vault_authority: AccountInfo<'info>,
+    #[account(mut)]
+    pub deposit_history: AccountInfo<'info>,
+    #[account(mut)]
+    pub insurance_vault: Box<Account<'info, TokenAccount>>,
+    pub insurance_vault_authority: AccountInfo<'info>,
+
     pub clearing_house_program: Program<'info, ClearingHouse>,
+    pub token_program: Program<'info, Token>,
 }
 
 impl<'info> UpdatePosition<'info> {
 
+    // max_slippage = worst fill vs the oracle (in SLIPPAGE_PRECISION, 0 = no limit price), 
+    // deploy_amount = only add up to this much to the target side (see calculate_deploy_trades)
+    pub fn rebalance(
+        &mut self, 
+        market_index: u64,
+        max_slippage: u128,
+        deploy_amount: Option<u128>,
+        oracles: &[AccountInfo<'info>],
+        signers: &[&[&[u8]]],
+    ) -> ProgramResult {
+        // limit / trigger orders are maintained with update_position_with_orders 
+        require!(!self.vault_state.uses_orders(), VaultErrorCode::VaultOrdersEnabled);
+        if self.check_pauses(market_index)? {
+            return Ok(());
+        }
+
+        let trades = self.prepare_rebalance(market_index, deploy_amount, oracles, signers)?;
+        for trade in trades {
+            let oracle = self.get_oracle(trade.market_index, oracles)?;
+            let limit_price = match max_slippage {
+                0 => 0, 
+                max_slippage => {
+                    let oracle_price = self.markets.load()?
+                        .get_market(trade.market_index)
+                        .amm
+                        .get_oracle_price(&oracle, Clock::get()?.slot)?
+                        .price;
+                    calculate_limit_price(oracle_price, trade.direction, max_slippage)?
+                }
+            };
+            self.open_position_with_oracle(
+                trade.amount, 
+                limit_price, 
+                trade.direction, 
+                signers, 
+                trade.market_index, 
+                oracle
+            )?;
+            self.user.reload()?; // update underlying account 
+        }
+
+        Ok(())
+    }
+
+    // clearing house pauses: exchange paused => fail w/ ExchangePaused (the CPIs would 
+    // fail anyway + an event would be reverted w/ the tx), funding paused => go flat 
+    // or freeze (returns true = freeze, dont do anything)
+    pub fn check_pauses(
+        &self, 
+        market_index: u64,
+    ) -> std::result::Result<bool, ProgramError> {
+        if self.state.exchange_paused {
+            msg!("exchange paused, not rebalancing market {}", market_index);
+            return Err(VaultErrorCode::ExchangePaused.into());
+        }
+
+        if self.state.funding_paused {
+            let went_flat = self.vault_state.flatten_on_funding_pause;
+            msg!("funding paused, going flat: {}", went_flat);
+            emit!(FundingPauseEvent {
+                ts: Clock::get()?.unix_timestamp, 
+                market_index, 
+                went_flat, 
+            });
+            return Ok(!went_flat);
+        }
+
+        Ok(false)
+    }
+
+    // steps shared by market + limit order execution: 
+    // returns the trades to get to the strategy's target positions 
+    pub fn prepare_rebalance(
+        &mut self, 
+        market_index: u64,
+        deploy_amount: Option<u128>,
+        oracles: &[AccountInfo<'info>],
+        signers: &[&[&[u8]]],
+    ) -> std::result::Result<Vec<Trade>, ProgramError> {
+
+        // print the state of the current position of vault before anything
+        self.get_position_state(true);
+
+        // 1. keep idle_buffer % of the vault's collateral idle in the vault ATA 
+        // (so small withdrawals dont need to touch the position)
+        self.rebalance_idle_buffer(signers)?;
+
+        // 2. ask the strategy where the vault should be 
+        let funding_rate_records = match self.vault_state.funding_history_length {
+            0 => vec![], 
+            funding_history_length => read_funding_rate_records(
+                &self.funding_rate_history.try_borrow_data()?, 
+                market_index, 
+                funding_history_length as usize,
+            )?,
+        };
+        let strategy = get_strategy(&self.vault_state, market_index);
+        let clock = Clock::get()?;
+        let twaps = match self.state.funding_paused {
+            true => vec![], 
+            false => self.get_fresh_twaps(&strategy.get_market_indexes(), oracles, &clock)?,
+        };
+        let targets = {
+            let markets = self.markets.load()?;
+            let user_positions = self.user_positions.load()?;
+            let snapshot = StrategySnapshot {
+                markets: &markets, 
+                user: &self.user, 
+                user_positions: &user_positions, 
+                vault_state: &self.vault_state, 
+                now: clock.unix_timestamp, 
+                funding_rate_records: &funding_rate_records, 
+                twaps: &twaps, 
+            };
+            // targets => funding window => volatility sizing => max_market_share 
+            let targets = calculate_rebalance_targets(
+                &*strategy, 
+                &snapshot, 
+                self.state.funding_paused, 
+                &self.state.oracle_guard_rails.validity, 
+                |target_market_index| {
+                    let oracle = self.get_oracle(target_market_index, oracles)?;
+                    Ok(markets.get_market(target_market_index).amm.get_oracle_price(&oracle, clock.slot)?)
+                },
+            )?;
+            match targets {
+                Some(targets) => targets, 
+                None => return Ok(vec![]),
+            }
+        };
+
+        /* Note: for now, if we need to reverse (Long=>Short / Short=>Long) 
+        * we use 2 steps (close, new_pos) but 
+        * in future we can do this in a single step for less fees 
+        */
+
+        // 3. close positions which arent a target (or are on the wrong side) 
+        // (closes take no limit price => left to the next update_position on a deploy)
+        let stale_market_indexes = get_stale_market_indexes(&targets, &self.user_positions.load()?);
+        if deploy_amount.is_none() {
+            for market_index in stale_market_indexes.iter() {
+                msg!("closing market {}...", market_index);
+                let oracle = self.get_oracle(*market_index, oracles)?;
+                self.close_position_with_oracle(signers, *market_index, oracle)?;
+                self.user.reload()?; // update underlying account 
+            }
+        }
+
+        // 4. trade the difference to the targets 
+        let mut trades = calculate_trades(
+            &targets, 
+            &self.user_positions.load()?, 
+            &self.markets.load()?,
+        );
+        if let Some(deploy_amount) = deploy_amount {
+            trades = calculate_deploy_trades(trades, &stale_market_indexes, deploy_amount);
+        }
+        msg!("trades: {:?}", trades);
+        Ok(trades)
+    }
+
+    // twaps extended to now (the stored ones only update on trades / funding updates)
+    pub fn get_fresh_twaps(
+        &self, 
+        market_indexes: &[u64], 
+        oracles: &[AccountInfo<'info>],
+        clock: &Clock,
+    ) -> std::result::Result<Vec<MarketTwaps>, ProgramError> {
+        let mut twaps = vec![];
+        for market_index in market_indexes.iter() {
+            let markets = self.markets.load()?;
+            let market = markets.get_market(*market_index);
+            if !market.initialized {
+                continue;
+            }
+            let amm = &market.amm;
+            let oracle = self.get_oracle(*market_index, oracles)?;
+            validate_twap_staleness(amm, clock.unix_timestamp, self.vault_state.max_twap_staleness)?;
+
+            let oracle_price_data = amm.get_oracle_price(&oracle, clock.slot)?;
+            let market_twaps = calculate_fresh_twaps(
+                *market_index, 
+                amm, 
+                oracle_price_data.price, 
+                clock.unix_timestamp,
+            )?;
+            msg!("market {} fresh (mark, oracle) twap: {}, {}", market_index, market_twaps.mark_price_twap, market_twaps.oracle_price_twap);
+            twaps.push(market_twaps);
+        }
+        Ok(twaps)
+    }
+
+    // market oracle = the oracle account or one of the remaining accounts 
+    pub fn get_oracle(
+        &self, 
+        market_index: u64, 
+        oracles: &[AccountInfo<'info>],
+    ) -> std::result::Result<AccountInfo<'info>, ProgramError> {
+        let oracle_key = self.markets.load()?
+            .get_market(market_index)
+            .amm
+            .oracle;
+
+        std::iter::once(&self.oracle)
+            .chain(oracles.iter())
+            .find(|oracle| oracle.key.eq(&oracle_key))
+            .cloned()
+            .ok_or_else(|| VaultErrorCode::OracleNotFound.into())
+    }
+
+    // notional value of the vault's position in a market (0 = no position)
+    pub fn get_position_value(
+        &self, 
+        market_index: u64,
+    ) -> u128 {
+        let vault_positions = &self.user_positions.load().unwrap();
+        let markets = &self.markets.load().unwrap();
+        calculate_position_value(vault_positions, markets, market_index)
+    }
+
     pub fn get_position_state(
         &self,
         log_results: bool,
@@ -153,6 +407,66 @@ impl<'info> UpdatePosition<'info> {
         [collateral_amount, liabilites_amount, amount_to_trade]  
     }
 
+    // drift collateral (+ pnl) used to price vault tokens on withdraw: the lower of 
+    // the amm + oracle valuations (the amm's reserves can be moved in the same tx 
+    // to inflate a refund, the oracle cant)
+    pub fn get_share_collateral(
+        &self, 
+        oracles: &[AccountInfo<'info>],
+    ) -> std::result::Result<u128, ProgramError> {
+        let oracles: Vec<AccountInfo<'info>> = std::iter::once(self.oracle.clone())
+            .chain(oracles.iter().cloned())
+            .collect();
+        let [amm_collateral_amount, oracle_collateral_amount] = get_amm_oracle_collateral(
+            &self.user, 
+            &self.user_positions.load()?, 
+            &self.markets.load()?, 
+            &oracles, 
+            Clock::get()?.slot,
+        )?;
+        Ok(amm_collateral_amount.min(oracle_collateral_amount))
+    }
+
+    // total collateral = drift collateral (+ profits) + idle buffer 
+    pub fn get_total_collateral(
+        &self,
+    ) -> u128 {
+        let [collateral_amount, ..] = self.compute_collateral_liabilities();
+        collateral_amount + self.vault_collateral_ata.amount as u128
+    }
+
+    pub fn get_idle_buffer_target(
+        &self,
+    ) -> u128 {
+        calculate_idle_buffer_target(self.get_total_collateral(), &self.vault_state)
+    }
+
+    pub fn rebalance_idle_buffer(
+        &mut self, 
+        signers: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let idle_amount = self.vault_collateral_ata.amount as u128;
+        let idle_target = self.get_idle_buffer_target();
+        msg!("(idle, idle target) amount: {}, {}", idle_amount, idle_target);
+
+        if idle_amount < idle_target { 
+            // top up: drift => vault ATA 
+            let amount = (idle_target - idle_amount) as u64;
+            self.withdraw_collateral(amount, signers)?;
+        } else if idle_amount > idle_target { 
+            // too much idle: vault ATA => drift 
+            let amount = (idle_amount - idle_target) as u64;
+            self.deposit_collateral(amount, signers)?;
+        } else { 
+            return Ok(());
+        }
+
+        // update underlying accounts 
+        self.user.reload()?;
+        self.vault_collateral_ata.reload()?;
+        Ok(())
+    }
+
     pub fn get_current_position(
         &self, 
         market_index: u64,
@@ -179,30 +493,75 @@ impl<'info> UpdatePosition<'info> {
     pub fn compute_collateral_liabilities(
         &self
     ) -> [u128; 2] {
-        let mut collateral_amount = self.user.collateral;
-
-        // yanked from the protocol-v1 src code 
         let vault_positions = &self.user_positions.load().unwrap();
         let markets = &self.markets.load().unwrap();
-        
-        let mut liabilites_amount = 0; 
-        for market_position in vault_positions.positions.iter() {
-            if market_position.base_asset_amount == 0 {
-                continue;
-            }
-            let market = markets.get_market(market_position.market_index);
-            let amm = &market.amm;
-            let (position_base_asset_value, position_unrealized_pnl) =
-                calculate_base_asset_value_and_pnl(market_position, amm).unwrap();
+        calculate_collateral_liabilities(&self.user, vault_positions, markets)
+    }
+
+    pub fn withdraw_collateral(
+        &self, 
+        amount: u64,
+        signers: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let cpi_program = self.clearing_house_program.to_account_info();
+        let cpi_accounts = ClearingHouseWithdrawCollateral {
+            // user stuff 
+            user: self.user.to_account_info(), 
+            user_collateral_account: self.vault_collateral_ata.to_account_info(), // [!]
+            user_positions: self.user_positions.to_account_info(),
+            authority: self.authority.clone(), 
+
+            // drift stuff 
+            state: self.state.to_account_info(), 
+            markets: self.markets.to_account_info(), 
+            collateral_vault: self.collateral_vault.to_account_info(), 
+            deposit_history: self.deposit_history.to_account_info(),
+            funding_payment_history: self.funding_payment_history.to_account_info(), 
+
+            collateral_vault_authority: self.collateral_vault_authority.to_account_info(),
+            insurance_vault: self.insurance_vault.to_account_info(),
+            insurance_vault_authority: self.insurance_vault_authority.to_account_info(),
             
-            liabilites_amount += position_base_asset_value;
-            // profit pnl = additional collateral 
-            if position_unrealized_pnl > 0 {
-                collateral_amount += position_unrealized_pnl as u128; 
-            }
-        }
+            // other
+            token_program: self.token_program.to_account_info(), 
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            cpi_program, 
+            cpi_accounts,
+            signers
+        );
+        clearing_house::cpi::withdraw_collateral(cpi_ctx, amount)
+    }
+
+    pub fn deposit_collateral(
+        &self, 
+        amount: u64,
+        signers: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let cpi_program = self.clearing_house_program.to_account_info();
+        let cpi_accounts = ClearingHouseDepositCollateral {
+            // user stuff 
+            user: self.user.to_account_info(), 
+            user_collateral_account: self.vault_collateral_ata.to_account_info(), // [!]
+            user_positions: self.user_positions.to_account_info(),
+            authority: self.authority.clone(), 
 
-        [collateral_amount, liabilites_amount]
+            // drift stuff 
+            state: self.state.to_account_info(),
+            markets: self.markets.to_account_info(), 
+            collateral_vault: self.collateral_vault.to_account_info(), 
+            deposit_history: self.deposit_history.to_account_info(),
+            funding_payment_history: self.funding_payment_history.to_account_info(), 
+            
+            // other
+            token_program: self.token_program.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            cpi_program, 
+            cpi_accounts,
+            signers
+        );
+        clearing_house::cpi::deposit_collateral(cpi_ctx, amount)
     }
 
     pub fn close_position(
@@ -210,6 +569,15 @@ impl<'info> UpdatePosition<'info> {
         signers: &[&[&[u8]]],
         market_index: u64,
     ) -> ProgramResult {
+        self.close_position_with_oracle(signers, market_index, self.oracle.clone())
+    }
+
+    pub fn close_position_with_oracle(
+        &self, 
+        signers: &[&[&[u8]]],
+        market_index: u64,
+        oracle: AccountInfo<'info>,
+    ) -> ProgramResult {
 
         let cpi_program = self.clearing_house_program.to_account_info();
         let cpi_accounts = ClearingHouseClosePosition {
@@ -218,7 +586,7 @@ impl<'info> UpdatePosition<'info> {
             user_positions: self.user_positions.to_account_info(),
             authority: self.authority.clone(),
             markets: self.markets.to_account_info(),
-            oracle: self.oracle.clone(),
+            oracle,
             trade_history: self.trade_history.to_account_info(),
             funding_payment_history: self.funding_payment_history.to_account_info(),
             funding_rate_history: self.funding_rate_history.to_account_info(),
@@ -246,6 +614,25 @@ impl<'info> UpdatePosition<'info> {
         position_direction: Position, 
         signers: &[&[&[u8]]],
         market_index: u64,
+    ) -> ProgramResult {
+        self.open_position_with_oracle(
+            amount_in, 
+            limit_price, 
+            position_direction, 
+            signers, 
+            market_index, 
+            self.oracle.clone()
+        )
+    }
+
+    pub fn open_position_with_oracle(
+        &self,
+        amount_in: u128,
+        limit_price: u128, 
+        position_direction: Position, 
+        signers: &[&[&[u8]]],
+        market_index: u64,
+        oracle: AccountInfo<'info>,
     ) -> ProgramResult {
         msg!("opening a {:?}...", position_direction);
 
@@ -262,7 +649,7 @@ impl<'info> UpdatePosition<'info> {
             user_positions: self.user_positions.to_account_info(),
             authority: self.authority.clone(),
             markets: self.markets.to_account_info(),
-            oracle: self.oracle.clone(),
+            oracle,
             trade_history: self.trade_history.to_account_info(),
             funding_payment_history: self.funding_payment_history.to_account_info(),
             funding_rate_history: self.funding_rate_history.to_account_info(),
@@ -2,53 +2,73 @@ use anchor_lang::prelude::*;
 
 use anchor_spl::{
     token::{
-        Mint, Token, TokenAccount, 
+        Mint, TokenAccount, 
         Transfer, transfer, 
         Burn, burn
     },
 };
 
-use clearing_house::cpi::accounts::{
-    WithdrawCollateral as ClearingHouseWithdrawCollateral,
-};
-
-use crate::state::{VaultState, Position};
+use crate::state::{VaultState, DepositorState};
 use crate::error::VaultErrorCode;
 use crate::instructions::update_position::*;
+use crate::strategy::calculate_pro_rata_reductions;
 
-pub fn withdraw(
-    ctx: Context<Withdraw>, 
+pub fn withdraw<'info>(
+    ctx: Context<'_, '_, '_, 'info, Withdraw<'info>>, 
     burn_amount: u128,
     market_index: u64,
     authority_nonce: u8,
 ) -> ProgramResult {
     let update_position_accounts = &mut ctx.accounts.update_position;
 
-    // ensure user has enough to burn 
+    // ensure user has enough to burn: unlocked shares are burnt first, 
+    // then the ones still held in the locked_shares account
     let user_vault_balance = ctx.accounts.user_vault_ata.amount as u128; 
-    require!(user_vault_balance >= burn_amount, VaultErrorCode::NotEnoughFunds);
+    let free_burn_amount = burn_amount.min(user_vault_balance);
+    let locked_burn_amount = burn_amount - free_burn_amount;
+    require!(
+        locked_burn_amount <= ctx.accounts.locked_shares.amount as u128, 
+        VaultErrorCode::NotEnoughFunds
+    );
+
+    // early withdrawals (of locked shares) are rejected or pay a fee (which stays in the vault)
+    let now = Clock::get()?.unix_timestamp;
+    let unlock_ts = ctx.accounts.depositor_state
+        .get_unlock_ts(update_position_accounts.vault_state.min_holding_period);
+    let early_withdrawal = locked_burn_amount > 0 && now < unlock_ts;
+    if early_withdrawal {
+        msg!("early withdrawal (unlocks at {})", unlock_ts);
+        require!(
+            update_position_accounts.vault_state.early_withdrawal_fee_numerator > 0, 
+            VaultErrorCode::DepositLocked
+        );
+    }
+
+    // market_index = the update_position oracle's market (every position is priced 
+    // + reduced w/ its own oracle)
+    update_position_accounts.get_oracle(market_index, ctx.remaining_accounts)?;
 
     // 1. compute relative collateral to burn_pool_tokens
-    // compute total amount of vault collateral 
-    let [collateral_amount, liabilites_amount, ..] = 
+    // compute total amount of vault collateral (drift + idle buffer)
+    // priced at the lower of the amm / oracle valuation (oracles of the 
+    // vault's other markets are passed as remaining accounts)
+    let [collateral_amount, ..] = 
         update_position_accounts.get_position_state(true);
+    let share_collateral_amount = 
+        update_position_accounts.get_share_collateral(ctx.remaining_accounts)?;
+    let idle_amount = update_position_accounts.vault_collateral_ata.amount as u128;
         
     // compute collateral to give = (burn_amount / total_minted) * total_colateral
-    let state = &mut ctx.accounts.vault_state; 
-    let mut refund_collateral_amount = 
-        burn_amount
-            .checked_mul(collateral_amount).unwrap()
-            .checked_div(state.total_amount_minted as u128).unwrap() as u64;
+    let mut refund_collateral_amount = compute_split_refund_amount(
+        free_burn_amount, 
+        locked_burn_amount, 
+        share_collateral_amount + idle_amount, 
+        &update_position_accounts.vault_state, 
+        early_withdrawal
+    );
     msg!("estimated refund amount: {}", refund_collateral_amount);
     require!(refund_collateral_amount > 0, VaultErrorCode::WidthdrawAmountTooSmall);
     
-    // 2. adjust position size:
-    // reduce position (want approx 1:1 collat:liabilities)
-    let new_collateral_amount = collateral_amount - refund_collateral_amount as u128;
-    let amount_to_reduce = liabilites_amount - new_collateral_amount; 
-    let vault_position = update_position_accounts.get_current_position(market_index);
-    msg!("vaults current position: {:?}", vault_position);
-    
     // get signature
     let authority_seeds = [
         b"authority".as_ref(),
@@ -56,110 +76,156 @@ pub fn withdraw(
     ];
     let signers = &[&authority_seeds[..]];
 
-    if vault_position != Position::None {
-        msg!("reducing position...");
-        
-        let reduce_direction = match vault_position {
-            Position::Long => Position::Short,
-            Position::Short => Position::Long,
-            _ => panic!("shouldnt be called...")
-        };
-
-        update_position_accounts.open_position(
-            amount_to_reduce, 
-            0, 
-            reduce_direction, 
-            signers,
-            market_index,
-        )?;
-
-        // re-compute total amount of collateral after reduced position 
-        // (collateral estimate isnt perfect bc slippage + fees)
-        update_position_accounts.user.reload()?;
-        let [collateral_amount, ..] = 
+    // small withdrawals are paid straight from the idle buffer
+    if refund_collateral_amount as u128 > idle_amount {
+        // 2. adjust position size:
+        // reduce every position pro rata (want approx 1:1 collat:liabilities)
+        // the idle buffer is used first, the rest comes from drift 
+        let drift_refund_amount = refund_collateral_amount as u128 - idle_amount;
+        let new_collateral_amount = collateral_amount.saturating_sub(drift_refund_amount);
+        let reductions = calculate_pro_rata_reductions(
+            &update_position_accounts.user_positions.load()?, 
+            &update_position_accounts.markets.load()?, 
+            new_collateral_amount,
+        );
+
+        if !reductions.is_empty() {
+            msg!("reducing positions...");
+            for reduction in reductions.iter() {
+                msg!("reducing market {} by {}", reduction.market_index, reduction.amount);
+                let oracle = update_position_accounts.get_oracle(reduction.market_index, ctx.remaining_accounts)?;
+                match reduction.amount >= update_position_accounts.get_position_value(reduction.market_index) {
+                    // the whole leg => close it (a bigger quote amount would flip it)
+                    true => update_position_accounts.close_position_with_oracle(
+                        signers, 
+                        reduction.market_index, 
+                        oracle,
+                    )?,
+                    false => update_position_accounts.open_position_with_oracle(
+                        reduction.amount, 
+                        0, 
+                        reduction.direction, 
+                        signers,
+                        reduction.market_index,
+                        oracle,
+                    )?,
+                }
+                update_position_accounts.user.reload()?;
+            }
+
+            // re-compute total amount of collateral after reduced positions 
+            // (collateral estimate isnt perfect bc slippage + fees)
             update_position_accounts.get_position_state(true);
+            let share_collateral_amount = 
+                update_position_accounts.get_share_collateral(ctx.remaining_accounts)?;
 
-        // re-compute refund amount after close 
-        refund_collateral_amount = 
-            burn_amount
-            .checked_mul(collateral_amount).unwrap()
-            .checked_div(state.total_amount_minted as u128).unwrap() as u64;
-        msg!("estimated refund amount: {}", refund_collateral_amount);
-        require!(refund_collateral_amount > 0, VaultErrorCode::WidthdrawAmountTooSmall);
-    }
+            // re-compute refund amount after close 
+            refund_collateral_amount = compute_split_refund_amount(
+                free_burn_amount, 
+                locked_burn_amount, 
+                share_collateral_amount + idle_amount, 
+                &update_position_accounts.vault_state, 
+                early_withdrawal
+            );
+            msg!("estimated refund amount: {}", refund_collateral_amount);
+            require!(refund_collateral_amount > 0, VaultErrorCode::WidthdrawAmountTooSmall);
+        }
 
-    // 3. transfer from drift vault => vault ATA
-    let cpi_program = update_position_accounts.clearing_house_program.to_account_info();
-    let cpi_accounts = ClearingHouseWithdrawCollateral {
-        // user stuff 
-        user: update_position_accounts.user.to_account_info(), // PDA
-        user_collateral_account: ctx.accounts.vault_collateral_ata.to_account_info(), // [!]
-        user_positions: update_position_accounts.user_positions.to_account_info(),// KP
-        authority: update_position_accounts.authority.clone(), // KP 
-
-        // drift stuff 
-        state: update_position_accounts.state.to_account_info(), // CH 
-        markets: update_position_accounts.markets.to_account_info(), // CH 
-        collateral_vault: ctx.accounts.collateral_vault.to_account_info(), // CH 
-        deposit_history: ctx.accounts.deposit_history.to_account_info(),// CH 
-        funding_payment_history: update_position_accounts.funding_payment_history.to_account_info(), // CH 
-
-        collateral_vault_authority: ctx.accounts.collateral_vault_authority.to_account_info(),// CH 
-        insurance_vault: ctx.accounts.insurance_vault.to_account_info(),// CH 
-        insurance_vault_authority: ctx.accounts.insurance_vault_authority.to_account_info(),// CH 
-        
-        // other
-        token_program: ctx.accounts.token_program.to_account_info(), // basic
-    };
-    let cpi_ctx = CpiContext::new_with_signer(
-        cpi_program, 
-        cpi_accounts,
-        signers
-    );
-    clearing_house::cpi::withdraw_collateral(cpi_ctx, refund_collateral_amount)?;
+        // 3. transfer from drift vault => vault ATA
+        if refund_collateral_amount as u128 > idle_amount {
+            let drift_refund_amount = refund_collateral_amount - idle_amount as u64;
+            update_position_accounts.withdraw_collateral(drift_refund_amount, signers)?;
+        }
+    } else { 
+        msg!("paying from idle buffer...");
+    }
 
     // 4. vault ATA => user ATA  
     transfer(CpiContext::new(
-        ctx.accounts.token_program.to_account_info(),
+        update_position_accounts.token_program.to_account_info(),
         Transfer {
-            from: ctx.accounts.vault_collateral_ata.to_account_info(),
+            from: update_position_accounts.vault_collateral_ata.to_account_info(),
             to: ctx.accounts.user_collateral_ata.to_account_info(),
             authority: update_position_accounts.authority.to_account_info(),
         }
     ).with_signer(signers), refund_collateral_amount)?;
 
     // 5. burn user pool_tokens 
-    burn(CpiContext::new(
-        ctx.accounts.token_program.to_account_info(), 
-    Burn { 
-            mint: ctx.accounts.vault_mint.to_account_info(), 
-            to: ctx.accounts.user_vault_ata.to_account_info(), 
-            authority: ctx.accounts.owner.to_account_info(),
-        }
-    ), burn_amount as u64)?;
+    if free_burn_amount > 0 {
+        burn(CpiContext::new(
+            update_position_accounts.token_program.to_account_info(), 
+        Burn { 
+                mint: ctx.accounts.vault_mint.to_account_info(), 
+                to: ctx.accounts.user_vault_ata.to_account_info(), 
+                authority: ctx.accounts.owner.to_account_info(),
+            }
+        ), free_burn_amount as u64)?;
+    }
+    if locked_burn_amount > 0 {
+        burn(CpiContext::new(
+            update_position_accounts.token_program.to_account_info(), 
+        Burn { 
+                mint: ctx.accounts.vault_mint.to_account_info(), 
+                to: ctx.accounts.locked_shares.to_account_info(), 
+                authority: update_position_accounts.authority.to_account_info(),
+            }
+        ).with_signer(signers), locked_burn_amount as u64)?;
+    }
     
     // update state 
+    let state = &mut update_position_accounts.vault_state; 
     state.total_amount_minted = state.total_amount_minted
         .checked_sub(burn_amount as u64).unwrap(); 
 
+    // refunds free up the depositor cap (profits dont take it below 0)
+    let depositor_state = &mut ctx.accounts.depositor_state;
+    depositor_state.deposited_amount = depositor_state.deposited_amount
+        .saturating_sub(refund_collateral_amount);
+
     Ok(())
 }
 
+// only the locked shares pay the early withdrawal fee 
+pub fn compute_split_refund_amount(
+    free_burn_amount: u128, 
+    locked_burn_amount: u128, 
+    collateral_amount: u128,
+    state: &VaultState,
+    early_withdrawal: bool,
+) -> u64 {
+    compute_refund_amount(free_burn_amount, collateral_amount, state, false)
+        .checked_add(compute_refund_amount(locked_burn_amount, collateral_amount, state, early_withdrawal))
+        .unwrap()
+}
+
+pub fn compute_refund_amount(
+    burn_amount: u128, 
+    collateral_amount: u128,
+    state: &VaultState,
+    early_withdrawal: bool,
+) -> u64 {
+    let refund_amount = burn_amount
+        .checked_mul(collateral_amount).unwrap()
+        .checked_div(state.total_amount_minted as u128).unwrap();
+
+    // fee is left in the vault for the remaining depositors 
+    let fee = if early_withdrawal { 
+        refund_amount
+            .checked_mul(state.early_withdrawal_fee_numerator).unwrap()
+            .checked_div(state.early_withdrawal_fee_denominator).unwrap()
+    } else { 
+        0
+    };
+
+    refund_amount.checked_sub(fee).unwrap() as u64
+}
+
 #[derive(Accounts)]
 pub struct Withdraw<'info> {
     #[account(signer)]
     pub owner: AccountInfo<'info>, // depositer / owner of ATAs 
 
-    // atas 
-        // vault 
-    #[account(
-        mut, 
-        seeds = [b"vault_collateral".as_ref()],
-        bump,
-        constraint = &vault_collateral_ata.mint.eq(&update_position.state.collateral_mint)
-    )]
-    pub vault_collateral_ata: Box<Account<'info, TokenAccount>>,  
-        // user
+    // user atas 
     #[account(
         mut, 
         has_one = owner, 
@@ -172,26 +238,24 @@ pub struct Withdraw<'info> {
         constraint = &user_vault_ata.mint.eq(&vault_mint.key())
     )]
     pub user_vault_ata: Box<Account<'info, TokenAccount>>,  
+    #[account(
+        mut, 
+        seeds = [b"locked_shares".as_ref(), owner.key.as_ref()], 
+        bump, 
+    )]
+    pub locked_shares: Box<Account<'info, TokenAccount>>,
 
     // vault stuff 
-    #[account(mut, seeds = [b"vault_state".as_ref()], bump)] 
-    pub vault_state: Account<'info, VaultState>,
     #[account(mut, seeds = [b"vault_mint".as_ref()], bump)] 
     pub vault_mint: Account<'info, Mint>,
+    #[account(
+        mut, 
+        seeds = [b"depositor".as_ref(), owner.key.as_ref()], 
+        bump, 
+        has_one = owner
+    )] 
+    pub depositor_state: Account<'info, DepositorState>,
 
-    // additional drift things 
-    #[account(mut)]
-    pub collateral_vault: Box<Account<'info, TokenAccount>>,
-    pub collateral_vault_authority: AccountInfo<'info>,
-    #[account(mut)]
-    pub deposit_history: AccountInfo<'info>,
-    #[account(mut)]
-    pub insurance_vault: Box<Account<'info, TokenAccount>>,
-    pub insurance_vault_authority: AccountInfo<'info>,
-
-    pub update_position: UpdatePosition<'info>, // lots of drift things 
-
-    // other
-    pub token_program: Program<'info, Token>,
+    // vault state, vault collateral ATA + lots of drift things 
+    pub update_position: UpdatePosition<'info>, 
 }
-
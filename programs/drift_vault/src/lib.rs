@@ -4,9 +4,16 @@ use anchor_lang::prelude::*;
 pub mod error;
 pub mod state;
 pub mod instructions;
+pub mod optional_accounts;
+pub mod funding;
+pub mod strategy;
+pub mod volatility;
+pub mod twap;
+pub mod events;
 
 pub use error::*;
 pub use instructions::*;
+pub use state::{StrategyKind, StrategyParams};
 
 declare_id!("FKKbXdAxoX6RK6h2ESspJEgxfN83JHw48CYfh1if142Z");
 
@@ -18,36 +25,62 @@ pub mod drift_vault {
     // 1. create pool mint for LPs 
     // 2. create vault collateral ATA 
     // 3. create drift account 
+    // 4. create drift orders account 
+    // 5. pick the vault's strategy 
     pub fn initialize_vault(
         ctx: Context<InitializeVault>, 
         user_nonce: u8, 
         authority_nonce: u8,
         user_positions_nonce: u8,
+        user_orders_nonce: u8,
+        strategy: StrategyKind,
+        strategy_params: StrategyParams,
     ) -> ProgramResult {
-        instructions::initialize_vault(ctx, user_nonce, authority_nonce, user_positions_nonce)
+        instructions::initialize_vault(
+            ctx, 
+            user_nonce, 
+            authority_nonce, 
+            user_positions_nonce, 
+            user_orders_nonce,
+            strategy,
+            strategy_params
+        )
+    }
+
+    // ** initialize depositor 
+    // tracks the depositor's last deposit for the lockup period + creates the 
+    // account which holds the vault tokens minted within it 
+    pub fn initialize_depositor(
+        ctx: Context<InitializeDepositor>, 
+    ) -> ProgramResult {
+        instructions::initialize_depositor(ctx)
     }
 
     // ** deposit
     // 1. mint pool tokens to user
     // 2. deposit usdc to vault's drift collateral 
-    pub fn deposit(
-        ctx: Context<Deposit>, 
+    // 3. (optional) deploy it like update_position (UpdatePosition accounts are remaining accounts), 
+    //  w/ a slippage bound vs the oracle + skipped when the vault uses orders 
+    pub fn deposit<'info>(
+        ctx: Context<'_, '_, '_, 'info, Deposit<'info>>, 
         deposit_amount: u64,
         authority_nonce: u8,
+        optional_accounts: DepositOptionalAccounts,
     ) -> ProgramResult {
-        instructions::deposit(ctx, deposit_amount, authority_nonce)
+        instructions::deposit(ctx, deposit_amount, authority_nonce, optional_accounts)
     }
 
     // ** widthdraw 
     // 1. compute relative collateral to burn_pool_tokens
-    // 2. adjust position size:
+    // 2. adjust position size (skipped if the idle buffer covers the refund):
     //  compute new_collateral = collateral - withdraw_amount 
-    //  reduce position so approx 1:1 collateral:liabilities after withdraw
-    // 3. transfer from drift vault => vault ATA
+    //  reduce every position pro rata so approx 1:1 collateral:liabilities after withdraw
+    //  (oracles of the vault's other markets are remaining accounts)
+    // 3. transfer from drift vault => vault ATA (idle buffer is used first)
     // 4. vault ATA => user ATA  
-    // 5. burn user pool_tokens 
-    pub fn withdraw(
-        ctx: Context<Withdraw>, 
+    // 5. burn user pool_tokens (unlocked first, then the ones still in the lockup)
+    pub fn withdraw<'info>(
+        ctx: Context<'_, '_, '_, 'info, Withdraw<'info>>, 
         burn_amount: u128,
         market_index: u64,
         authority_nonce: u8,
@@ -55,18 +88,190 @@ pub mod drift_vault {
         instructions::withdraw(ctx, burn_amount, market_index, authority_nonce)
     }
 
+    // ** unlock shares 
+    // vault tokens minted within the lockup => user ATA (once it ends) 
+    pub fn unlock_shares(
+        ctx: Context<UnlockShares>, 
+        authority_nonce: u8,
+    ) -> ProgramResult {
+        instructions::unlock_shares(ctx, authority_nonce)
+    }
+
     // ** update position 
     // 1. compute funding_rate = mark - oracle 
-    // 2. do:
+    // 2. top up / drain the idle buffer to idle_buffer % of collateral
+    //  (if outside the funding window => close the position + stop)
+    // 3. do:
     //  if funding = good for longs => *open_long()
     //  if funding = good for shorts => *open_short()
     // we aim for 1:1 ratio of collateral + positions
-    pub fn update_position(
-        ctx: Context<UpdatePosition>, 
+    // (the vault's strategy (see strategy/) picks the target positions, 
+    //  oracles of its other markets are remaining accounts)
+    pub fn update_position<'info>(
+        ctx: Context<'_, '_, '_, 'info, UpdatePosition<'info>>, 
         market_index: u64,
         authority_nonce: u8,
     ) -> ProgramResult {
         instructions::update_position(ctx, market_index, authority_nonce)
     }
 
+    // ** update position with orders 
+    // same as update_position but:
+    // 1. cancel the vault's open orders 
+    // 2. (if use_limit_orders) place a post-only limit order at oracle -/+ offset for the 1:1 delta 
+    // 3. place reduce-only stop loss / take profit trigger orders for the position 
+    pub fn update_position_with_orders(
+        ctx: Context<UpdatePositionWithOrders>, 
+        market_index: u64,
+        authority_nonce: u8,
+    ) -> ProgramResult {
+        instructions::update_position_with_orders(ctx, market_index, authority_nonce)
+    }
+
+    // ** refresh orders (crank) 
+    // cancel + replace the vault's orders if a limit order is older than max_order_age 
+    // or the trigger orders dont match the position size 
+    pub fn refresh_orders(
+        ctx: Context<UpdatePositionWithOrders>, 
+        market_index: u64,
+        authority_nonce: u8,
+    ) -> ProgramResult {
+        instructions::refresh_orders(ctx, market_index, authority_nonce)
+    }
+
+    // ** admin 
+    // caps on total vault collateral, per depositor collateral + the vault's share of 
+    // a market side after a deploy (MARKET_SHARE_PRECISION) (0 = no cap)
+    pub fn update_deposit_caps(
+        ctx: Context<AdminUpdateVault>, 
+        max_vault_collateral: u128,
+        max_depositor_amount: u64,
+        max_market_share: u128,
+    ) -> ProgramResult {
+        instructions::update_deposit_caps(ctx, max_vault_collateral, max_depositor_amount, max_market_share)
+    }
+
+    // only owners holding the whitelist token can deposit (default pubkey = no whitelist)
+    pub fn update_whitelist_mint(
+        ctx: Context<AdminUpdateVault>, 
+        whitelist_mint: Pubkey,
+    ) -> ProgramResult {
+        instructions::update_whitelist_mint(ctx, whitelist_mint)
+    }
+
+    // min holding period after a deposit + fee for withdrawing before it ends 
+    // (fee numerator = 0 => early withdrawals are rejected)
+    pub fn update_lockup(
+        ctx: Context<AdminUpdateVault>, 
+        min_holding_period: i64,
+        early_withdrawal_fee_numerator: u128,
+        early_withdrawal_fee_denominator: u128,
+    ) -> ProgramResult {
+        instructions::update_lockup(
+            ctx, 
+            min_holding_period, 
+            early_withdrawal_fee_numerator, 
+            early_withdrawal_fee_denominator
+        )
+    }
+
+    // % of collateral kept idle in the vault ATA (topped up in update_position)
+    pub fn update_idle_buffer(
+        ctx: Context<AdminUpdateVault>, 
+        idle_buffer_numerator: u128,
+        idle_buffer_denominator: u128,
+    ) -> ProgramResult {
+        instructions::update_idle_buffer(ctx, idle_buffer_numerator, idle_buffer_denominator)
+    }
+
+    // maker execution w/ oracle offset limit orders (instead of market orders)
+    pub fn update_execution_mode(
+        ctx: Context<AdminUpdateVault>, 
+        use_limit_orders: bool,
+        limit_order_oracle_offset: u128,
+        max_order_age: i64,
+    ) -> ProgramResult {
+        instructions::update_execution_mode(
+            ctx, 
+            use_limit_orders, 
+            limit_order_oracle_offset, 
+            max_order_age
+        )
+    }
+
+    // stop loss / take profit at % from the entry price (numerator = 0 => off)
+    pub fn update_trigger_orders(
+        ctx: Context<AdminUpdateVault>, 
+        stop_loss_numerator: u128,
+        stop_loss_denominator: u128,
+        take_profit_numerator: u128,
+        take_profit_denominator: u128,
+    ) -> ProgramResult {
+        instructions::update_trigger_orders(
+            ctx, 
+            stop_loss_numerator, 
+            stop_loss_denominator, 
+            take_profit_numerator, 
+            take_profit_denominator
+        )
+    }
+
+    // only trade in the window before the next funding update (0 = off)
+    pub fn update_funding_window(
+        ctx: Context<AdminUpdateVault>, 
+        funding_window: i64,
+    ) -> ProgramResult {
+        instructions::update_funding_window(ctx, funding_window)
+    }
+
+    // switch the vault's strategy / its params 
+    pub fn update_strategy(
+        ctx: Context<AdminUpdateVault>, 
+        strategy: StrategyKind,
+        strategy_params: StrategyParams,
+    ) -> ProgramResult {
+        instructions::update_strategy(ctx, strategy, strategy_params)
+    }
+
+    // scale position size between min + max leverage by oracle volatility (max_leverage = 0 = off)
+    pub fn update_volatility_sizing(
+        ctx: Context<AdminUpdateVault>, 
+        min_leverage: u128,
+        max_leverage: u128,
+        max_volatility: u128,
+    ) -> ProgramResult {
+        instructions::update_volatility_sizing(ctx, min_leverage, max_leverage, max_volatility)
+    }
+
+    // trade on an ewma of the market's last funding rates (funding_history_length = 0 = off)
+    pub fn update_funding_history(
+        ctx: Context<AdminUpdateVault>, 
+        funding_history_length: u64,
+        funding_history_decay: u128,
+        funding_history_full_size_rate: u128,
+    ) -> ProgramResult {
+        instructions::update_funding_history(
+            ctx, 
+            funding_history_length, 
+            funding_history_decay, 
+            funding_history_full_size_rate,
+        )
+    }
+
+    // max age of the market twaps the vault trades on (0 = off)
+    pub fn update_twap_staleness(
+        ctx: Context<AdminUpdateVault>, 
+        max_twap_staleness: i64,
+    ) -> ProgramResult {
+        instructions::update_twap_staleness(ctx, max_twap_staleness)
+    }
+
+    // when the clearing house pauses funding: close the positions (true) or freeze them (false)
+    pub fn update_funding_pause_mode(
+        ctx: Context<AdminUpdateVault>, 
+        flatten_on_funding_pause: bool,
+    ) -> ProgramResult {
+        instructions::update_funding_pause_mode(ctx, flatten_on_funding_pause)
+    }
+
 }
@@ -0,0 +1,28 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::TokenAccount;
+
+use crate::error::VaultErrorCode;
+
+// mirrors the clearing house's whitelist token check in initialize_user 
+pub fn get_whitelist_token<'info>(
+    expect_whitelist_token: bool,
+    accounts: &[AccountInfo<'info>],
+    whitelist_mint: &Pubkey,
+) -> std::result::Result<Option<Account<'info, TokenAccount>>, VaultErrorCode> {
+    if !expect_whitelist_token {
+        return Ok(None);
+    }
+
+    let token_account_info = accounts.first()
+        .ok_or(VaultErrorCode::WhitelistTokenNotFound)?;
+
+    // checks the token program owns the account + is initialized 
+    let token_account: Account<TokenAccount> = Account::try_from(token_account_info)
+        .or(Err(VaultErrorCode::InvalidWhitelistToken))?;
+
+    if !token_account.mint.eq(whitelist_mint) {
+        return Err(VaultErrorCode::InvalidWhitelistToken);
+    }
+
+    Ok(Some(token_account))
+}
@@ -1,12 +1,137 @@
 use anchor_lang::prelude::*;
 
+pub const MAX_SPREAD_MARKETS: usize = 4;
+pub const BETA_PRECISION: u128 = 10_000; // expo = -4
+pub const LEVERAGE_PRECISION: u128 = 10_000; // expo = -4
+pub const VOLATILITY_PRECISION: u128 = 10_000; // expo = -4
+// max_volatility = 0 => 5% (perp oracles rarely sit > a few % from their twap), 
+// capped by the clearing house's too_volatile_ratio (see calculate_volatility_leverage)
+pub const DEFAULT_MAX_VOLATILITY: u128 = 500;
+pub const FUNDING_DECAY_PRECISION: u128 = 10_000; // expo = -4
+pub const SLIPPAGE_PRECISION: u128 = 10_000; // expo = -4
+pub const MARKET_SHARE_PRECISION: u128 = 10_000; // expo = -4
+
 #[account]
 #[derive(Default)]
 pub struct VaultState {
     pub total_amount_minted: u64, 
+    pub admin: Pubkey, 
+
+    // deposit caps (0 = no cap): vault collateral (drift + idle buffer), 
+    // collateral deposited by one depositor (net of refunds) + the vault's 
+    // share of a market side's base asset amount, which every rebalance 
+    // sizes its targets within (in MARKET_SHARE_PRECISION)
+    pub max_vault_collateral: u128, 
+    pub max_depositor_amount: u64, 
+    pub max_market_share: u128, 
+
+    // only holders of this token can deposit (default = permissionless)
+    pub whitelist_mint: Pubkey, 
+
+    // vault tokens minted within min_holding_period of a depositor's last deposit 
+    // are held in their locked_shares account, withdrawing them before it ends 
+    // is rejected (fee = 0) or charged a fee which stays in the vault
+    pub min_holding_period: i64, 
+    pub early_withdrawal_fee_numerator: u128, 
+    pub early_withdrawal_fee_denominator: u128, 
+
+    // % of collateral kept idle in the vault ATA for instant withdrawals
+    pub idle_buffer_numerator: u128, 
+    pub idle_buffer_denominator: u128, 
+
+    // maker execution: rebalances place post-only limit orders at 
+    // oracle -/+ limit_order_oracle_offset (instead of market orders) 
+    // which are cancelled + replaced once older than max_order_age 
+    pub use_limit_orders: bool, 
+    pub limit_order_oracle_offset: u128, 
+    pub max_order_age: i64, 
+
+    // reduce-only stop loss / take profit trigger orders at % away from 
+    // the position's entry price (numerator = 0 => no trigger order)
+    pub stop_loss_numerator: u128, 
+    pub stop_loss_denominator: u128, 
+    pub take_profit_numerator: u128, 
+    pub take_profit_denominator: u128, 
+
+    // only hold the targets (of any strategy) within funding_window seconds 
+    // before their markets' next funding update, flat otherwise (0 = always in the market)
+    pub funding_window: i64, 
+
+    // what the vault trades (see strategy/), picked at initialize_vault 
+    pub strategy: StrategyKind, 
+    pub strategy_params: StrategyParams, 
+
+    // volatility sizing: target notional * leverage where leverage goes from 
+    // max_leverage (calm) down to min_leverage as volatility (oracle conf + 
+    // oracle vs twap gap) goes to max_volatility (0 = DEFAULT_MAX_VOLATILITY), 
+    // max_leverage = 0 => off (in LEVERAGE/VOLATILITY_PRECISION)
+    pub min_leverage: u128, 
+    pub max_leverage: u128, 
+    pub max_volatility: u128, 
+
+    // funding history signal (FundingTwap): direction + size from an ewma of the 
+    // market's last funding_history_length funding rates (record i back gets weight 
+    // funding_history_decay^i), the predicted funding breaks ties, size is full 1:1 
+    // at |ewma| >= funding_history_full_size_rate (0 = always full), length = 0 => off 
+    pub funding_history_length: u64, 
+    pub funding_history_decay: u128, 
+    pub funding_history_full_size_rate: u128, 
+
+    // refuse to trade on twaps last updated > max_twap_staleness seconds ago (0 = off) 
+    pub max_twap_staleness: i64, 
+
+    // clearing house funding paused => close the positions (true) or leave them as is (false) 
+    pub flatten_on_funding_pause: bool, 
+}
+
+impl VaultState {
+    // vault orders are only maintained by update_position_with_orders
+    pub fn uses_orders(&self) -> bool {
+        self.use_limit_orders || 
+            self.stop_loss_numerator > 0 || 
+            self.take_profit_numerator > 0
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub enum StrategyKind {
+    // long / short the market_index by its twap funding, sized 1:1 
+    FundingTwap, 
+    // long the spread market w/ the most negative funding + short the one 
+    // w/ the most positive, sized so beta * notional matches 
+    FundingSpread, 
+}
+
+impl Default for StrategyKind {
+    fn default() -> Self {
+        StrategyKind::FundingTwap
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct StrategyParams {
+    // FundingSpread markets (beta = 0 => unused slot, in BETA_PRECISION)
+    // note: idl cant parse consts => len = MAX_SPREAD_MARKETS 
+    pub spread_market_indexes: [u64; 4], 
+    pub spread_market_betas: [u128; 4], 
+}
+
+#[account]
+#[derive(Default)]
+pub struct DepositorState {
+    pub owner: Pubkey, 
+    pub last_deposit_ts: i64, 
+    pub deposited_amount: u64, // collateral deposited - refunded (for max_depositor_amount)
+}
+
+impl DepositorState {
+    // locked shares can be unlocked / withdrawn w/o the fee from then on 
+    pub fn get_unlock_ts(&self, min_holding_period: i64) -> i64 {
+        self.last_deposit_ts.checked_add(min_holding_period).unwrap()
+    }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Position { 
     Long, 
     Short, 
@@ -0,0 +1,134 @@
+use anchor_lang::prelude::*;
+
+use clearing_house::math::casting::{cast_to_i128};
+use clearing_house::math::constants::MARK_PRICE_PRECISION;
+use clearing_house::error::ErrorCode;
+
+use crate::strategy::{Strategy, StrategySnapshot, TargetPosition};
+use crate::state::{Position, StrategyParams};
+use crate::error::VaultErrorCode;
+use crate::math_error;
+
+// a market of the spread strategy
+#[derive(Clone, Copy, Debug)]
+pub struct SpreadLeg {
+    pub market_index: u64,
+    pub beta: u128,
+}
+
+// relative value: long the spread market w/ the most negative funding +
+// short the one w/ the most positive, sized so beta * notional matches
+pub struct FundingSpreadStrategy {
+    pub params: StrategyParams,
+}
+
+impl FundingSpreadStrategy {
+    // the spread markets (beta = 0 => unused slot)
+    pub fn get_spread_market_indexes(&self) -> Vec<u64> {
+        self.params.spread_market_indexes.iter()
+            .zip(self.params.spread_market_betas.iter())
+            .filter(|(_, beta)| **beta > 0)
+            .map(|(market_index, _)| *market_index)
+            .collect()
+    }
+
+    // (long, short) legs = lowest, highest predicted funding over the spread markets
+    pub fn get_spread_legs(
+        &self,
+        snapshot: &StrategySnapshot,
+    ) -> std::result::Result<Option<(SpreadLeg, SpreadLeg)>, ProgramError> {
+        let mut legs: Vec<(SpreadLeg, i128)> = vec![];
+        for (market_index, beta) in self.params.spread_market_indexes.iter()
+            .zip(self.params.spread_market_betas.iter()) {
+            if *beta == 0 {
+                continue;
+            }
+            let market = snapshot.markets.get_market(*market_index);
+            if !market.initialized {
+                continue;
+            }
+
+            // funding % = (mark twap - oracle twap) / oracle twap
+            let twaps = snapshot.get_twaps(*market_index)?;
+            let oracle_price_twap = twaps.oracle_price_twap;
+            let mark_price_twap = cast_to_i128(twaps.mark_price_twap)?;
+            let approx_funding = mark_price_twap
+                .checked_sub(oracle_price_twap).ok_or_else(math_error!())?
+                .checked_mul(cast_to_i128(MARK_PRICE_PRECISION)?).ok_or_else(math_error!())?
+                .checked_div(oracle_price_twap).ok_or_else(math_error!())?;
+            msg!("market {} approx funding: {}", market_index, approx_funding);
+
+            legs.push((SpreadLeg { market_index: *market_index, beta: *beta }, approx_funding));
+        }
+
+        let long_leg = legs.iter().min_by_key(|(_, approx_funding)| *approx_funding);
+        let short_leg = legs.iter().max_by_key(|(_, approx_funding)| *approx_funding);
+        match (long_leg, short_leg) {
+            (Some((long_leg, long_funding)), Some((short_leg, short_funding)))
+                if long_funding < short_funding => Ok(Some((*long_leg, *short_leg))),
+            _ => Ok(None),
+        }
+    }
+}
+
+impl Strategy for FundingSpreadStrategy {
+    fn get_market_indexes(&self) -> Vec<u64> {
+        self.get_spread_market_indexes()
+    }
+
+    fn get_target_positions(
+        &self,
+        snapshot: &StrategySnapshot,
+    ) -> std::result::Result<Option<Vec<TargetPosition>>, ProgramError> {
+        let (long_leg, short_leg) = match self.get_spread_legs(snapshot)? {
+            Some(legs) => legs,
+            None => {
+                msg!("no funding spread, not doing anything...");
+                return Ok(None);
+            }
+        };
+        msg!("(long, short) leg: {:?}, {:?}", long_leg, short_leg);
+
+        // long beta * long notional = short beta * short notional
+        // and long notional + short notional = collateral (1:1)
+        let [collateral_amount, ..] = snapshot.get_collateral_liabilities();
+        let beta_sum = long_leg.beta.checked_add(short_leg.beta).ok_or_else(math_error!())?;
+
+        let mut targets = vec![];
+        for (leg, direction, other_beta) in [
+            (long_leg, Position::Long, short_leg.beta),
+            (short_leg, Position::Short, long_leg.beta),
+        ].iter() {
+            let value = collateral_amount
+                .checked_mul(*other_beta).ok_or_else(math_error!())?
+                .checked_div(beta_sum).ok_or_else(math_error!())?;
+            targets.push(TargetPosition {
+                market_index: leg.market_index,
+                direction: *direction,
+                value,
+            });
+        }
+
+        Ok(Some(targets))
+    }
+}
+
+pub fn validate_spread_params(
+    params: &StrategyParams,
+) -> std::result::Result<(), VaultErrorCode> {
+    // need a long + short leg (in different markets)
+    let market_indexes: Vec<u64> = params.spread_market_indexes.iter()
+        .zip(params.spread_market_betas.iter())
+        .filter(|(_, beta)| **beta > 0)
+        .map(|(market_index, _)| *market_index)
+        .collect();
+    if market_indexes.len() < 2 {
+        return Err(VaultErrorCode::InvalidSpreadMarkets);
+    }
+    for (i, market_index) in market_indexes.iter().enumerate() {
+        if market_indexes[..i].contains(market_index) {
+            return Err(VaultErrorCode::InvalidSpreadMarkets);
+        }
+    }
+    Ok(())
+}
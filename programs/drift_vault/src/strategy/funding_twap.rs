@@ -0,0 +1,98 @@
+use anchor_lang::prelude::*;
+
+use clearing_house::math::casting::{cast_to_i128};
+use clearing_house::error::ErrorCode;
+
+use crate::strategy::{Strategy, StrategySnapshot, TargetPosition};
+use crate::state::Position;
+use crate::funding::calculate_funding_rate_ewma;
+use crate::math_error;
+
+// go long / short market_index depending on who pays funding
+// and aim for a 1:1 ratio of collateral + positions
+pub struct FundingTwapStrategy {
+    pub market_index: u64,
+}
+
+impl Strategy for FundingTwapStrategy {
+    fn get_market_indexes(&self) -> Vec<u64> {
+        vec![self.market_index]
+    }
+
+    fn get_target_positions(
+        &self,
+        snapshot: &StrategySnapshot,
+    ) -> std::result::Result<Option<Vec<TargetPosition>>, ProgramError> {
+        // 1. compute funding_rate = mark - oracle
+        let twaps = snapshot.get_twaps(self.market_index)?;
+        let oracle_price_twap = twaps.oracle_price_twap;
+        let mark_price_twap = twaps.mark_price_twap;
+
+        // negative = shorts pay longs (should go long)
+        // positive = longs pay shorts (should go short)
+        let approx_funding = cast_to_i128(mark_price_twap)?
+            .checked_sub(oracle_price_twap)
+            .ok_or_else(math_error!())?;
+
+        msg!("(mark twap, oracle twap): {} {} approx funding: {}", mark_price_twap, oracle_price_twap, approx_funding);
+
+        // 1b. funding history signal: ewma of the last funding rates 
+        // (the predicted funding above breaks ties)
+        let vault_state = snapshot.vault_state;
+        let funding_rate_ewma = match vault_state.funding_history_length {
+            0 => None, 
+            _ => calculate_funding_rate_ewma(
+                snapshot.funding_rate_records, 
+                vault_state.funding_history_decay,
+            )?,
+        };
+        msg!("funding rate ewma: {:?}", funding_rate_ewma);
+
+        let funding_signal = match funding_rate_ewma {
+            Some(funding_rate_ewma) if funding_rate_ewma != 0 => funding_rate_ewma,
+            _ => approx_funding,
+        };
+
+        if funding_signal == 0 {
+            msg!("funding = 0, not doing anything...");
+            return Ok(None);
+        }
+
+        let funding_direction = if funding_signal < 0 { // funding goes to longs
+            Position::Long
+        } else {
+            Position::Short
+        };
+
+        // 2. size for 1:1 (a flipped position is closed first + w/o the history signal we never reduce)
+        let [collateral_amount, liabilites_amount] = snapshot.get_collateral_liabilities();
+        let position_value = snapshot.get_position_value(self.market_index);
+        let other_liabilities_amount = liabilites_amount - position_value;
+        let current_value = if snapshot.get_position(self.market_index) == funding_direction {
+            position_value
+        } else {
+            0
+        };
+        let value = match collateral_amount > other_liabilities_amount {
+            true => collateral_amount - other_liabilities_amount,
+            false => 0,
+        };
+
+        // with the history signal the size follows the ewma (can reduce), 
+        // full size at |ewma| >= funding_history_full_size_rate 
+        let full_size_rate = vault_state.funding_history_full_size_rate;
+        let value = match funding_rate_ewma {
+            Some(funding_rate_ewma) if full_size_rate > 0 => value
+                .checked_mul(funding_rate_ewma.unsigned_abs().min(full_size_rate)).ok_or_else(math_error!())?
+                .checked_div(full_size_rate).ok_or_else(math_error!())?,
+            Some(_) => value, 
+            None => value.max(current_value),
+        };
+
+        Ok(Some(vec![TargetPosition {
+            market_index: self.market_index,
+            direction: funding_direction,
+            value,
+        }]))
+    }
+}
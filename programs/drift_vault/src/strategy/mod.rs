@@ -0,0 +1,462 @@
+use anchor_lang::prelude::*;
+
+use clearing_house::state::{
+    market::{Markets, OraclePriceData},
+    state::ValidityGuardRails,
+    user::{User, UserPositions},
+    history::funding_rate::FundingRateRecord,
+};
+use clearing_house::math::position::{
+    calculate_base_asset_value_and_pnl, 
+    calculate_base_asset_value_and_pnl_with_oracle_price,
+};
+use clearing_house::math::constants::MARK_PRICE_TIMES_AMM_TO_QUOTE_PRECISION_RATIO;
+use clearing_house::error::ErrorCode;
+
+use crate::state::{VaultState, StrategyKind, StrategyParams, Position, LEVERAGE_PRECISION, MARKET_SHARE_PRECISION};
+use crate::error::VaultErrorCode;
+use crate::twap::MarketTwaps;
+use crate::funding::calculate_next_funding_ts;
+use crate::volatility::calculate_volatility_leverage;
+use crate::math_error;
+
+pub mod funding_twap;
+pub use funding_twap::*;
+
+pub mod funding_spread;
+pub use funding_spread::*;
+
+// read-only view of the vault's drift accounts
+pub struct StrategySnapshot<'a> {
+    pub markets: &'a Markets,
+    pub user: &'a User,
+    pub user_positions: &'a UserPositions,
+    pub vault_state: &'a VaultState,
+    pub now: i64,
+    // the market_index's latest funding rate records, newest first 
+    // (empty when the funding history signal is off)
+    pub funding_rate_records: &'a [FundingRateRecord],
+    // twaps extended to now for the strategy's markets (see get_market_indexes)
+    pub twaps: &'a [MarketTwaps],
+}
+
+impl<'a> StrategySnapshot<'a> {
+    pub fn get_collateral_liabilities(&self) -> [u128; 2] {
+        calculate_collateral_liabilities(self.user, self.user_positions, self.markets)
+    }
+
+    pub fn get_position_value(&self, market_index: u64) -> u128 {
+        calculate_position_value(self.user_positions, self.markets, market_index)
+    }
+
+    pub fn get_twaps(&self, market_index: u64) -> std::result::Result<MarketTwaps, VaultErrorCode> {
+        self.twaps
+            .iter()
+            .find(|twaps| twaps.market_index == market_index)
+            .cloned()
+            .ok_or(VaultErrorCode::OracleNotFound)
+    }
+
+    pub fn get_position(&self, market_index: u64) -> Position {
+        match self.user_positions
+            .positions
+            .iter()
+            .find(|market_position| market_position.is_for(market_index)) {
+            Some(market_position) if market_position.base_asset_amount > 0 => Position::Long,
+            Some(market_position) if market_position.base_asset_amount < 0 => Position::Short,
+            _ => Position::None,
+        }
+    }
+}
+
+// a position the vault should have (value = notional in quote)
+#[derive(Clone, Copy, Debug)]
+pub struct TargetPosition {
+    pub market_index: u64,
+    pub direction: Position,
+    pub value: u128,
+}
+
+pub trait Strategy {
+    // the markets whose twaps get_target_positions reads
+    fn get_market_indexes(&self) -> Vec<u64>;
+
+    // None => keep the current positions
+    // Some(targets) => trade to the targets + close every other position
+    fn get_target_positions(
+        &self,
+        snapshot: &StrategySnapshot,
+    ) -> std::result::Result<Option<Vec<TargetPosition>>, ProgramError>;
+}
+
+pub fn get_strategy(
+    vault_state: &VaultState,
+    market_index: u64,
+) -> Box<dyn Strategy> {
+    match vault_state.strategy {
+        StrategyKind::FundingTwap => Box::new(FundingTwapStrategy {
+            market_index,
+        }),
+        StrategyKind::FundingSpread => Box::new(FundingSpreadStrategy {
+            params: vault_state.strategy_params,
+        }),
+    }
+}
+
+pub fn validate_strategy(
+    strategy: StrategyKind,
+    strategy_params: &StrategyParams,
+) -> std::result::Result<(), VaultErrorCode> {
+    match strategy {
+        StrategyKind::FundingTwap => Ok(()),
+        StrategyKind::FundingSpread => validate_spread_params(strategy_params),
+    }
+}
+
+// the decision half of UpdatePosition::prepare_rebalance w/o the CPIs (the client + 
+// backtest run the same): the strategy's targets => funding window => volatility sizing 
+// => max_market_share (None = keep the current positions, oracle_price_data = the 
+// oracle of a target's market)
+pub fn calculate_rebalance_targets(
+    strategy: &dyn Strategy,
+    snapshot: &StrategySnapshot,
+    funding_paused: bool,
+    validity_guard_rails: &ValidityGuardRails,
+    oracle_price_data: impl Fn(u64) -> std::result::Result<OraclePriceData, ProgramError>,
+) -> std::result::Result<Option<Vec<TargetPosition>>, ProgramError> {
+    // funding paused (+ not frozen) => no funding to earn, close everything 
+    if funding_paused {
+        return Ok(Some(vec![]));
+    }
+    let targets = match strategy.get_target_positions(snapshot)? {
+        Some(targets) => targets, 
+        None => return Ok(None),
+    };
+
+    // every strategy only holds its targets within the funding window 
+    let vault_state = snapshot.vault_state;
+    let mut targets = apply_funding_window(targets, snapshot.markets, snapshot.now, vault_state.funding_window)?;
+    msg!("target positions: {:?}", targets);
+
+    // scale the targets down when the market's oracle is volatile (max_leverage = 0 => off)
+    if vault_state.max_leverage > 0 {
+        for target in targets.iter_mut() {
+            let amm = &snapshot.markets.get_market(target.market_index).amm;
+            let leverage = calculate_volatility_leverage(
+                amm, 
+                &oracle_price_data(target.market_index)?, 
+                vault_state, 
+                validity_guard_rails,
+            )?;
+            target.value = target.value
+                .checked_mul(leverage).ok_or_else(math_error!())?
+                .checked_div(LEVERAGE_PRECISION).ok_or_else(math_error!())?;
+        }
+        msg!("volatility scaled target positions: {:?}", targets);
+    }
+
+    // never hold more than max_market_share of a market side (every rebalance path)
+    if vault_state.max_market_share > 0 {
+        clamp_to_max_market_share(
+            &mut targets, 
+            snapshot.user_positions, 
+            snapshot.markets, 
+            vault_state.max_market_share,
+        )?;
+        msg!("market share capped target positions: {:?}", targets);
+    }
+
+    Ok(Some(targets))
+}
+
+// [collateral (+ unrealized profits), liabilities]
+pub fn calculate_collateral_liabilities(
+    user: &User,
+    user_positions: &UserPositions,
+    markets: &Markets,
+) -> [u128; 2] {
+    // yanked from the protocol-v1 src code
+    let mut liabilites_amount = 0;
+    let mut unrealized_pnl = 0;
+    for market_position in user_positions.positions.iter() {
+        if market_position.base_asset_amount == 0 {
+            continue;
+        }
+        let market = markets.get_market(market_position.market_index);
+        let amm = &market.amm;
+        let (position_base_asset_value, position_unrealized_pnl) =
+            calculate_base_asset_value_and_pnl(market_position, amm).unwrap();
+
+        liabilites_amount += position_base_asset_value;
+        unrealized_pnl += position_unrealized_pnl;
+    }
+
+    [add_unrealized_pnl(user.collateral, unrealized_pnl), liabilites_amount]
+}
+
+// collateral (+ unrealized pnl) w/ the positions valued at the oracle price 
+// (oracle_prices = (market_index, oracle price) for every market w/ a position)
+pub fn calculate_collateral_with_oracle_prices(
+    user: &User,
+    user_positions: &UserPositions,
+    oracle_prices: &[(u64, i128)],
+) -> std::result::Result<u128, ProgramError> {
+    let mut unrealized_pnl = 0;
+    for market_position in user_positions.positions.iter() {
+        if market_position.base_asset_amount == 0 {
+            continue;
+        }
+        let (_, oracle_price) = oracle_prices
+            .iter()
+            .find(|(market_index, _)| market_position.is_for(*market_index))
+            .ok_or(VaultErrorCode::OracleNotFound)?;
+        let (_, position_unrealized_pnl) =
+            calculate_base_asset_value_and_pnl_with_oracle_price(market_position, *oracle_price)?;
+
+        unrealized_pnl += position_unrealized_pnl;
+    }
+
+    Ok(add_unrealized_pnl(user.collateral, unrealized_pnl))
+}
+
+// losses eat into the collateral (floored at 0 = the account is underwater), 
+// profits add to it 
+pub fn add_unrealized_pnl(
+    collateral: u128, 
+    unrealized_pnl: i128,
+) -> u128 {
+    match unrealized_pnl >= 0 {
+        true => collateral.saturating_add(unrealized_pnl.unsigned_abs()),
+        false => collateral.saturating_sub(unrealized_pnl.unsigned_abs()),
+    }
+}
+
+// a market order to get to a strategy's target position 
+#[derive(Clone, Copy, Debug)]
+pub struct Trade {
+    pub market_index: u64, 
+    pub direction: Position, 
+    pub amount: u128, 
+    pub is_reduce: bool, 
+}
+
+// positions which arent a target (or are on the wrong side) => closed 
+pub fn get_stale_market_indexes(
+    targets: &[TargetPosition],
+    user_positions: &UserPositions,
+) -> Vec<u64> {
+    user_positions
+        .positions
+        .iter()
+        .filter(|market_position| market_position.base_asset_amount != 0)
+        .filter(|market_position| !targets.iter().any(|target| 
+            market_position.is_for(target.market_index) && 
+            (market_position.base_asset_amount > 0) == (target.direction == Position::Long)
+        ))
+        .map(|market_position| market_position.market_index)
+        .collect()
+}
+
+// the difference to the targets (once the stale positions are closed)
+// reductions come first to free up margin for the other positions 
+pub fn calculate_trades(
+    targets: &[TargetPosition],
+    user_positions: &UserPositions,
+    markets: &Markets,
+) -> Vec<Trade> {
+    let stale_market_indexes = get_stale_market_indexes(targets, user_positions);
+
+    let mut trades = vec![];
+    for target in targets.iter() {
+        let current_value = match stale_market_indexes.contains(&target.market_index) {
+            true => 0, 
+            false => calculate_position_value(user_positions, markets, target.market_index),
+        };
+
+        if current_value > target.value { 
+            let reduce_direction = match target.direction {
+                Position::Long => Position::Short, 
+                _ => Position::Long, 
+            };
+            trades.push(Trade {
+                market_index: target.market_index, 
+                direction: reduce_direction, 
+                amount: current_value - target.value, 
+                is_reduce: true, 
+            });
+        } else if target.value > current_value { 
+            trades.push(Trade {
+                market_index: target.market_index, 
+                direction: target.direction, 
+                amount: target.value - current_value, 
+                is_reduce: false, 
+            });
+        }
+    }
+
+    trades.sort_by_key(|trade| !trade.is_reduce);
+    trades
+}
+
+// funding timing: only hold the targets within funding_window seconds before their 
+// markets' next funding update, flat otherwise (all legs or none => a spread stays 
+// hedged, 0 = always in the market)
+pub fn apply_funding_window(
+    targets: Vec<TargetPosition>,
+    markets: &Markets,
+    now: i64,
+    funding_window: i64,
+) -> std::result::Result<Vec<TargetPosition>, ProgramError> {
+    if funding_window == 0 {
+        return Ok(targets);
+    }
+
+    for target in targets.iter() {
+        let time_until_funding = calculate_next_funding_ts(&markets.get_market(target.market_index).amm)?
+            .checked_sub(now)
+            .ok_or_else(math_error!())?;
+        msg!("market {} (time until funding, funding window): {}, {}", target.market_index, time_until_funding, funding_window);
+
+        if time_until_funding > funding_window {
+            msg!("outside the funding window, going flat...");
+            return Ok(vec![]);
+        }
+    }
+    Ok(targets)
+}
+
+// a deposit deploy only adds the new collateral to the target side: no reductions + 
+// nothing in markets whose position is on the wrong side (both need a close / reduce 
+// which the slippage bound cant cover), the rest scaled down together to deploy_amount 
+pub fn calculate_deploy_trades(
+    trades: Vec<Trade>,
+    stale_market_indexes: &[u64],
+    deploy_amount: u128,
+) -> Vec<Trade> {
+    let trades: Vec<Trade> = trades
+        .into_iter()
+        .filter(|trade| !trade.is_reduce && !stale_market_indexes.contains(&trade.market_index))
+        .collect();
+    let total_amount: u128 = trades.iter().map(|trade| trade.amount).sum();
+    if total_amount <= deploy_amount {
+        return trades;
+    }
+
+    trades
+        .into_iter()
+        .map(|trade| Trade {
+            amount: trade.amount
+                .checked_mul(deploy_amount).unwrap()
+                .checked_div(total_amount).unwrap(),
+            ..trade
+        })
+        .filter(|trade| trade.amount > 0)
+        .collect()
+}
+
+// cap each target at max_market_share of its side of the market (0 = no cap): 
+// share = vault base / (others' base + vault base) <= m  <=>  vault base <= others' base * m / (1 - m) 
+// (valued at the mark price, the vault's position on the other side gets closed => not the vault's)
+pub fn clamp_to_max_market_share(
+    targets: &mut [TargetPosition],
+    user_positions: &UserPositions,
+    markets: &Markets,
+    max_market_share: u128,
+) -> std::result::Result<(), ProgramError> {
+    if max_market_share == 0 || max_market_share >= MARKET_SHARE_PRECISION {
+        return Ok(());
+    }
+
+    for target in targets.iter_mut() {
+        let market = markets.get_market(target.market_index);
+        let side_base_asset_amount = match target.direction {
+            Position::Long => market.base_asset_amount_long, 
+            _ => market.base_asset_amount_short, 
+        };
+        let vault_base_asset_amount = user_positions
+            .positions
+            .iter()
+            .find(|market_position| market_position.is_for(target.market_index))
+            .map_or(0, |market_position| market_position.base_asset_amount);
+        let vault_side_base_asset_amount = match (vault_base_asset_amount > 0) == (target.direction == Position::Long) {
+            true => vault_base_asset_amount.unsigned_abs(), 
+            false => 0, 
+        };
+        let other_base_asset_amount = side_base_asset_amount
+            .unsigned_abs()
+            .saturating_sub(vault_side_base_asset_amount);
+
+        let max_base_asset_amount = other_base_asset_amount
+            .checked_mul(max_market_share).ok_or_else(math_error!())?
+            .checked_div(MARKET_SHARE_PRECISION - max_market_share).ok_or_else(math_error!())?;
+        let max_value = max_base_asset_amount
+            .checked_mul(market.amm.mark_price()?).ok_or_else(math_error!())?
+            .checked_div(MARK_PRICE_TIMES_AMM_TO_QUOTE_PRECISION_RATIO).ok_or_else(math_error!())?;
+        target.value = target.value.min(max_value);
+    }
+    Ok(())
+}
+
+// scale every open position down by the same % so total liabilities = new_liabilities 
+// (a withdrawal keeps the legs' ratios, eg the spread's betas), each reduction 
+// capped at the position's value so it never flips a side 
+pub fn calculate_pro_rata_reductions(
+    user_positions: &UserPositions,
+    markets: &Markets,
+    new_liabilities: u128,
+) -> Vec<Trade> {
+    let position_values: Vec<(u64, Position, u128)> = user_positions
+        .positions
+        .iter()
+        .filter(|market_position| market_position.base_asset_amount != 0)
+        .map(|market_position| {
+            let direction = match market_position.base_asset_amount > 0 {
+                true => Position::Long, 
+                false => Position::Short, 
+            };
+            let market_index = market_position.market_index;
+            (market_index, direction, calculate_position_value(user_positions, markets, market_index))
+        })
+        .collect();
+    let liabilities: u128 = position_values.iter().map(|(_, _, value)| value).sum();
+    if liabilities <= new_liabilities {
+        return vec![];
+    }
+
+    let reduce_amount = liabilities - new_liabilities;
+    position_values
+        .into_iter()
+        .map(|(market_index, direction, value)| {
+            let amount = value
+                .checked_mul(reduce_amount).unwrap()
+                .checked_div(liabilities).unwrap()
+                .min(value);
+            let reduce_direction = match direction {
+                Position::Long => Position::Short, 
+                _ => Position::Long, 
+            };
+            Trade { market_index, direction: reduce_direction, amount, is_reduce: true }
+        })
+        .filter(|trade| trade.amount > 0)
+        .collect()
+}
+
+// notional value of a position (0 = no position)
+pub fn calculate_position_value(
+    user_positions: &UserPositions,
+    markets: &Markets,
+    market_index: u64,
+) -> u128 {
+    match user_positions
+        .positions
+        .iter()
+        .find(|market_position|
+            market_position.is_for(market_index) && market_position.base_asset_amount != 0
+        ) {
+        None => 0,
+        Some(market_position) => {
+            let amm = &markets.get_market(market_index).amm;
+            calculate_base_asset_value_and_pnl(market_position, amm).unwrap().0
+        }
+    }
+}
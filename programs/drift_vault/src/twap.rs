@@ -0,0 +1,50 @@
+use anchor_lang::prelude::*;
+
+use clearing_house::state::market::AMM;
+use clearing_house::math::amm::{calculate_new_mark_twap, calculate_new_oracle_price_twap};
+use clearing_house::error::ErrorCode;
+
+use crate::error::VaultErrorCode;
+use crate::math_error;
+
+// a market's twaps extended to now with its current mark + oracle price
+#[derive(Clone, Copy, Debug)]
+pub struct MarketTwaps {
+    pub market_index: u64,
+    pub mark_price_twap: u128,
+    pub oracle_price_twap: i128,
+}
+
+// the stored twaps only update when a trade / funding update touches the market 
+pub fn calculate_fresh_twaps(
+    market_index: u64,
+    amm: &AMM,
+    oracle_price: i128,
+    now: i64,
+) -> std::result::Result<MarketTwaps, ErrorCode> {
+    Ok(MarketTwaps {
+        market_index,
+        mark_price_twap: calculate_new_mark_twap(amm, now, None)?,
+        oracle_price_twap: calculate_new_oracle_price_twap(amm, now, oracle_price)?,
+    })
+}
+
+// refuse to extend twaps which havent been updated in max_twap_staleness seconds (0 = off)
+pub fn validate_twap_staleness(
+    amm: &AMM,
+    now: i64,
+    max_twap_staleness: i64,
+) -> std::result::Result<(), ProgramError> {
+    if max_twap_staleness == 0 {
+        return Ok(());
+    }
+    let mark_twap_age = now.checked_sub(amm.last_mark_price_twap_ts).ok_or_else(math_error!())?;
+    let oracle_twap_age = now.checked_sub(amm.last_oracle_price_twap_ts).ok_or_else(math_error!())?;
+    msg!("(mark twap age, oracle twap age): {}, {}", mark_twap_age, oracle_twap_age);
+
+    require!(
+        mark_twap_age <= max_twap_staleness && oracle_twap_age <= max_twap_staleness, 
+        VaultErrorCode::StaleTwaps
+    );
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use anchor_lang::prelude::*;
+use std::cmp::max;
+
+use clearing_house::state::market::{AMM, OraclePriceData};
+use clearing_house::state::state::ValidityGuardRails;
+use clearing_house::error::ErrorCode;
+
+use crate::state::{VaultState, VOLATILITY_PRECISION, DEFAULT_MAX_VOLATILITY};
+use crate::math_error;
+
+// volatility = oracle confidence % + |last oracle price - oracle twap| % 
+pub fn calculate_volatility(
+    amm: &AMM, 
+    oracle_price_data: &OraclePriceData,
+) -> std::result::Result<u128, ErrorCode> {
+    let oracle_price = oracle_price_data.price.unsigned_abs();
+    let confidence_pct = oracle_price_data.confidence
+        .checked_mul(VOLATILITY_PRECISION).ok_or_else(math_error!())?
+        .checked_div(max(1, oracle_price)).ok_or_else(math_error!())?;
+
+    let twap_gap = amm.last_oracle_price
+        .checked_sub(amm.last_oracle_price_twap).ok_or_else(math_error!())?
+        .unsigned_abs();
+    let twap_gap_pct = twap_gap
+        .checked_mul(VOLATILITY_PRECISION).ok_or_else(math_error!())?
+        .checked_div(max(1, amm.last_oracle_price_twap.unsigned_abs())).ok_or_else(math_error!())?;
+
+    confidence_pct
+        .checked_add(twap_gap_pct)
+        .ok_or_else(math_error!())
+}
+
+// the clearing house's oracle validity check: price / twap or twap / price > too_volatile_ratio 
+// (integer division like the clearing house, ratio <= 0 => unset)
+pub fn is_oracle_too_volatile(
+    amm: &AMM, 
+    oracle_price_data: &OraclePriceData,
+    validity_guard_rails: &ValidityGuardRails,
+) -> std::result::Result<bool, ErrorCode> {
+    let too_volatile_ratio = validity_guard_rails.too_volatile_ratio;
+    if too_volatile_ratio <= 0 {
+        return Ok(false);
+    }
+
+    let oracle_price = oracle_price_data.price;
+    let oracle_price_twap = amm.last_oracle_price_twap;
+    let price_twap_ratio = oracle_price
+        .checked_div(max(1, oracle_price_twap)).ok_or_else(math_error!())?;
+    let twap_price_ratio = oracle_price_twap
+        .checked_div(max(1, oracle_price)).ok_or_else(math_error!())?;
+    Ok(price_twap_ratio > too_volatile_ratio || twap_price_ratio > too_volatile_ratio)
+}
+
+// leverage scales down linearly from max_leverage (volatility = 0) 
+// to min_leverage (volatility >= max_volatility), max_volatility is capped at the twap 
+// gap the clearing house deems too volatile (too_volatile_ratio * 100%) + min_leverage 
+// once it does 
+pub fn calculate_volatility_leverage(
+    amm: &AMM, 
+    oracle_price_data: &OraclePriceData,
+    vault_state: &VaultState,
+    validity_guard_rails: &ValidityGuardRails,
+) -> std::result::Result<u128, ErrorCode> {
+    if is_oracle_too_volatile(amm, oracle_price_data, validity_guard_rails)? {
+        msg!("oracle too volatile, leverage: {}", vault_state.min_leverage);
+        return Ok(vault_state.min_leverage);
+    }
+
+    let volatility = calculate_volatility(amm, oracle_price_data)?;
+
+    let max_volatility = match vault_state.max_volatility {
+        0 => DEFAULT_MAX_VOLATILITY,
+        max_volatility => max_volatility,
+    };
+    let max_volatility = match validity_guard_rails.too_volatile_ratio {
+        too_volatile_ratio if too_volatile_ratio > 0 => max_volatility.min(
+            too_volatile_ratio.unsigned_abs()
+                .checked_mul(VOLATILITY_PRECISION).ok_or_else(math_error!())?
+        ),
+        _ => max_volatility,
+    };
+
+    let leverage_range = vault_state.max_leverage - vault_state.min_leverage;
+    let leverage_reduction = leverage_range
+        .checked_mul(volatility.min(max_volatility)).ok_or_else(math_error!())?
+        .checked_div(max_volatility).ok_or_else(math_error!())?;
+    let leverage = vault_state.max_leverage - leverage_reduction;
+    msg!("(volatility, max volatility, leverage): {}, {}, {}", volatility, max_volatility, leverage);
+
+    Ok(leverage)
+}
@@ -0,0 +1,381 @@
+// property tests of the share accounting, driven through the program's own math:
+// deposits mint w/ compute_mint_amount at the higher of the amm / oracle valuation
+// (deposit.rs), withdrawals refund w/ compute_split_refund_amount at the lower one
+// (withdraw.rs), rebalances realize pnl (add_unrealized_pnl) + move collateral in / out
+// of the idle buffer (calculate_idle_buffer_target, update_position.rs)
+// (proptest shrinks a failing sequence of ops down to a minimal one)
+use proptest::prelude::*;
+
+use drift_vault::instructions::deposit::compute_mint_amount;
+use drift_vault::instructions::update_position::calculate_idle_buffer_target;
+use drift_vault::instructions::withdraw::compute_split_refund_amount;
+use drift_vault::state::VaultState;
+use drift_vault::strategy::add_unrealized_pnl;
+
+const DEPOSITORS: usize = 3;
+const QUOTE_PRECISION: u64 = 1_000_000;
+const BPS: u64 = 10_000;
+
+#[derive(Clone, Debug)]
+enum Op {
+    Deposit { depositor: usize, amount: u64 },
+    // burns burn_bps of the depositor's shares, locked_bps of them still in the
+    // lockup (=> the early withdrawal fee), slippage_bps = what reducing the
+    // position costs (of the part paid out of drift)
+    Withdraw { depositor: usize, burn_bps: u64, locked_bps: u64, slippage_bps: u64 },
+    // pnl on the drift collateral (trades, fees, funding) + the oracle
+    // valuation's gap to the amm's, then the idle buffer moves to its target
+    Rebalance { pnl: i64, oracle_gap: i64 },
+}
+
+// the vault before + after a deposit / withdraw
+// (nav = withdraw's valuation, deposit_nav = deposit's)
+#[derive(Clone, Copy, Debug)]
+struct Navs {
+    nav: u128,
+    deposit_nav: u128,
+    supply: u64,
+    oracle_underwater: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Deposit {
+    amount: u64,
+    minted: u64,
+    before: Navs,
+    after: Navs,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Withdrawal {
+    burn: u64,
+    refund: u64,
+    slippage: u128,
+    before: Navs,
+    after: Navs,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Event {
+    Deposit(Deposit),
+    Withdrawal(Withdrawal),
+}
+
+#[derive(Clone, Default)]
+struct VaultModel {
+    state: VaultState,
+    // the vault mint's supply + each depositor's vault tokens
+    supply: u64,
+    shares: [u64; DEPOSITORS],
+    // vault collateral ATA
+    idle: u128,
+    // drift collateral + unrealized pnl at amm prices
+    amm_collateral: u128,
+    // oracle valuation - amm valuation
+    oracle_gap: i128,
+}
+
+impl VaultModel {
+    fn new(idle_buffer_numerator: u128, early_withdrawal_fee_numerator: u128) -> Self {
+        VaultModel {
+            state: VaultState {
+                idle_buffer_numerator,
+                idle_buffer_denominator: BPS as u128,
+                early_withdrawal_fee_numerator,
+                early_withdrawal_fee_denominator: BPS as u128,
+                ..VaultState::default()
+            },
+            ..VaultModel::default()
+        }
+    }
+
+    fn oracle_collateral(&self) -> u128 {
+        add_unrealized_pnl(self.amm_collateral, self.oracle_gap)
+    }
+
+    // get_share_collateral + idle
+    fn nav(&self) -> u128 {
+        self.amm_collateral.min(self.oracle_collateral()) + self.idle
+    }
+
+    // get_deposit_collateral
+    fn deposit_nav(&self) -> u128 {
+        self.amm_collateral.max(self.oracle_collateral()) + self.idle
+    }
+
+    fn navs(&self) -> Navs {
+        Navs {
+            nav: self.nav(),
+            deposit_nav: self.deposit_nav(),
+            supply: self.supply,
+            oracle_underwater: self.amm_collateral as i128 + self.oracle_gap < 0,
+        }
+    }
+
+    // None = rejected (nothing changes)
+    fn deposit(&mut self, depositor: usize, amount: u64) -> Option<Deposit> {
+        let before = self.navs();
+        let deposit_nav = self.deposit_nav();
+        // shares past u64::MAX panic in compute_mint_amount (the tx fails)
+        if deposit_nav > 0 && amount as u128 * self.state.total_amount_minted as u128 / deposit_nav > u64::MAX as u128 {
+            return None;
+        }
+        let minted = compute_mint_amount(amount, deposit_nav, &self.state);
+        if minted == 0 {
+            return None; // DepositAmountTooSmall
+        }
+
+        self.state.total_amount_minted = self.state.total_amount_minted.checked_add(minted).unwrap();
+        self.supply += minted;
+        self.shares[depositor] += minted;
+        self.amm_collateral += amount as u128;
+        Some(Deposit { amount, minted, before, after: self.navs() })
+    }
+
+    // None = rejected (nothing changes)
+    fn withdraw(&mut self, depositor: usize, burn: u64, locked_bps: u64, slippage_bps: u64) -> Option<Withdrawal> {
+        if burn == 0 || self.shares[depositor] < burn {
+            return None; // NotEnoughFunds
+        }
+        let locked_burn = (burn as u128 * locked_bps as u128 / BPS as u128) as u64;
+        let free_burn = burn - locked_burn;
+        let early = locked_burn > 0;
+        if early && self.state.early_withdrawal_fee_numerator == 0 {
+            return None; // DepositLocked
+        }
+        let refund_at = |nav: u128| {
+            compute_split_refund_amount(free_burn as u128, locked_burn as u128, nav, &self.state, early)
+        };
+
+        let before = self.navs();
+        let mut refund = refund_at(before.nav);
+        if refund == 0 {
+            return None; // WidthdrawAmountTooSmall
+        }
+
+        let mut amm_collateral = self.amm_collateral;
+        let mut idle = self.idle;
+        let mut slippage = 0;
+        if refund as u128 > idle {
+            // reduce the positions, then re-price the refund
+            slippage = (refund as u128 - idle) * slippage_bps as u128 / BPS as u128;
+            if slippage > 0 {
+                amm_collateral -= slippage.min(amm_collateral);
+                let reduced = VaultModel { amm_collateral, ..self.clone() };
+                refund = refund_at(reduced.nav());
+                if refund == 0 {
+                    return None;
+                }
+            }
+        }
+        if refund as u128 > idle {
+            amm_collateral = amm_collateral
+                .checked_sub(refund as u128 - idle)
+                .expect("refund > the vault's collateral");
+            idle = 0;
+        } else {
+            idle -= refund as u128;
+        }
+
+        self.amm_collateral = amm_collateral;
+        self.idle = idle;
+        self.state.total_amount_minted = self.state.total_amount_minted.checked_sub(burn).unwrap();
+        self.supply -= burn;
+        self.shares[depositor] -= burn;
+        Some(Withdrawal { burn, refund, slippage, before, after: self.navs() })
+    }
+
+    fn rebalance(&mut self, pnl: i64, oracle_gap: i64) {
+        // collateral cant go below zero (the clearing house liquidates first)
+        self.amm_collateral = add_unrealized_pnl(self.amm_collateral, pnl as i128);
+        self.oracle_gap = oracle_gap as i128;
+
+        // rebalance_idle_buffer
+        let total_collateral = self.amm_collateral + self.idle;
+        let idle_target = calculate_idle_buffer_target(total_collateral, &self.state);
+        self.amm_collateral = total_collateral - idle_target;
+        self.idle = idle_target;
+    }
+
+    fn apply(&mut self, op: &Op) -> Option<Event> {
+        match *op {
+            Op::Deposit { depositor, amount } => self.deposit(depositor, amount).map(Event::Deposit),
+            Op::Withdraw { depositor, burn_bps, locked_bps, slippage_bps } => {
+                let burn = (self.shares[depositor] as u128 * burn_bps as u128 / BPS as u128).max(1) as u64;
+                self.withdraw(depositor, burn, locked_bps, slippage_bps).map(Event::Withdrawal)
+            }
+            Op::Rebalance { pnl, oracle_gap } => {
+                self.rebalance(pnl, oracle_gap);
+                None
+            }
+        }
+    }
+}
+
+// nav_after / supply_after >= nav_before / supply_before (no holders before / after => true)
+fn nav_per_share_kept(nav: impl Fn(&Navs) -> u128, before: &Navs, after: &Navs) -> bool {
+    before.supply == 0 || after.supply == 0
+        || nav(after) * before.supply as u128 >= nav(before) * after.supply as u128
+}
+
+fn usdc() -> impl Strategy<Value = u64> {
+    1..=10_000 * QUOTE_PRECISION
+}
+
+fn signed_usdc(max: i64) -> impl Strategy<Value = i64> {
+    let max = max * QUOTE_PRECISION as i64;
+    -max..=max
+}
+
+fn op(max_slippage_bps: u64) -> impl Strategy<Value = Op> {
+    prop_oneof![
+        // small deposits too (rounding shows up at a high nav per share)
+        (0..DEPOSITORS, prop_oneof![1..=1_000u64, usdc()]).prop_map(|(depositor, amount)| Op::Deposit { depositor, amount }),
+        // small burns too (rounding shows up on the last few shares)
+        (0..DEPOSITORS, prop_oneof![1..=10u64, 1..=BPS], prop_oneof![Just(0u64), 1..=BPS], 0..=max_slippage_bps).prop_map(
+            |(depositor, burn_bps, locked_bps, slippage_bps)| Op::Withdraw { depositor, burn_bps, locked_bps, slippage_bps }
+        ),
+        (signed_usdc(2_000), signed_usdc(500)).prop_map(|(pnl, oracle_gap)| Op::Rebalance { pnl, oracle_gap }),
+    ]
+}
+
+// (idle_buffer_numerator, early_withdrawal_fee_numerator): idle buffer 0 - 50%, early withdrawal fee 0 - 5%
+fn vault_params() -> impl Strategy<Value = (u128, u128)> {
+    (prop_oneof![Just(0u128), 1..=5_000u128], prop_oneof![Just(0u128), 1..=500u128])
+}
+
+proptest! {
+    #[test]
+    fn share_supply_always_equals_total_amount_minted(
+        (idle_buffer, early_withdrawal_fee) in vault_params(),
+        ops in prop::collection::vec(op(100), 1..50),
+    ) {
+        let mut vault = VaultModel::new(idle_buffer, early_withdrawal_fee);
+        for op in ops.iter() {
+            vault.apply(op);
+            prop_assert_eq!(vault.supply, vault.state.total_amount_minted);
+            prop_assert_eq!(vault.supply, vault.shares.iter().sum::<u64>());
+        }
+    }
+
+    #[test]
+    fn withdrawals_never_exceed_pro_rata_nav(
+        (idle_buffer, early_withdrawal_fee) in vault_params(),
+        ops in prop::collection::vec(op(100), 1..50),
+    ) {
+        let mut vault = VaultModel::new(idle_buffer, early_withdrawal_fee);
+        for op in ops.iter() {
+            if let Some(Event::Withdrawal(withdrawal)) = vault.apply(op) {
+                // burn / supply of the nav before the withdraw (rounded down)
+                let pro_rata_nav = withdrawal.burn as u128 * withdrawal.before.nav / withdrawal.before.supply as u128;
+                prop_assert!(
+                    withdrawal.refund as u128 <= pro_rata_nav,
+                    "refund {} > pro rata nav {} ({:?})", withdrawal.refund, pro_rata_nav, withdrawal
+                );
+            }
+        }
+    }
+
+    // w/o slippage (reducing the positions costs every holder their share of it)
+    #[test]
+    fn existing_holders_nav_per_share_never_falls(
+        (idle_buffer, early_withdrawal_fee) in vault_params(),
+        ops in prop::collection::vec(op(0), 1..50),
+    ) {
+        let mut vault = VaultModel::new(idle_buffer, early_withdrawal_fee);
+        for op in ops.iter() {
+            let (before, after, check_nav) = match vault.apply(op) {
+                // a deposit into an account which is underwater at the oracle valuation
+                // fills the hole first (the clearing house's loss) => only at the deposit nav
+                Some(Event::Deposit(deposit)) =>
+                    (deposit.before, deposit.after, !deposit.before.oracle_underwater && !deposit.after.oracle_underwater),
+                Some(Event::Withdrawal(withdrawal)) => {
+                    prop_assert_eq!(withdrawal.slippage, 0);
+                    (withdrawal.before, withdrawal.after, true)
+                }
+                None => continue,
+            };
+            prop_assert!(
+                nav_per_share_kept(|navs| navs.deposit_nav, &before, &after),
+                "deposit nav per share fell: {:?} => {:?}", before, after
+            );
+            if check_nav {
+                prop_assert!(
+                    nav_per_share_kept(|navs| navs.nav, &before, &after),
+                    "nav per share fell: {:?} => {:?}", before, after
+                );
+            }
+        }
+    }
+
+    // the first deposit into an empty vault mints 1:1 (+ takes whatever collateral is
+    // left in it) => once it has holders
+    #[test]
+    fn deposits_withdrawn_right_away_never_profit(
+        (idle_buffer, early_withdrawal_fee) in vault_params(),
+        ops in prop::collection::vec(op(100), 1..50),
+        depositor in 0..DEPOSITORS,
+        amount in prop_oneof![1..=1_000u64, usdc()],
+    ) {
+        let mut vault = VaultModel::new(idle_buffer, early_withdrawal_fee);
+        for op in ops.iter() {
+            vault.apply(op);
+        }
+        prop_assume!(vault.supply > 0);
+
+        if let Some(deposit) = vault.deposit(depositor, amount) {
+            if let Some(withdrawal) = vault.withdraw(depositor, deposit.minted, 0, 0) {
+                prop_assert!(
+                    withdrawal.refund <= deposit.amount,
+                    "deposit {} => refund {} ({:?}, {:?})", deposit.amount, withdrawal.refund, deposit, withdrawal
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rebalances_keep_the_vaults_collateral(
+        (idle_buffer, early_withdrawal_fee) in vault_params(),
+        ops in prop::collection::vec(op(100), 1..50),
+        oracle_gap in signed_usdc(500),
+    ) {
+        let mut vault = VaultModel::new(idle_buffer, early_withdrawal_fee);
+        for op in ops.iter() {
+            vault.apply(op);
+        }
+        // w/o pnl moving collateral in / out of the idle buffer is a transfer
+        let total_collateral = vault.amm_collateral + vault.idle;
+        vault.rebalance(0, oracle_gap);
+        prop_assert_eq!(vault.amm_collateral + vault.idle, total_collateral);
+    }
+}
+
+#[test]
+fn rejects_withdrawals_which_round_down_to_nothing() {
+    // nav per share = 2 / 3 => burning 1 share refunds floor(2 / 3) = 0
+    let mut vault = VaultModel::new(0, 0);
+    vault.deposit(0, 3 * QUOTE_PRECISION).unwrap();
+    vault.rebalance(-(QUOTE_PRECISION as i64), 0);
+    assert!(vault.withdraw(0, 1, 0, 0).is_none());
+    assert_eq!(vault.shares[0], 3 * QUOTE_PRECISION);
+    assert_eq!(vault.state.total_amount_minted, 3 * QUOTE_PRECISION);
+
+    let withdrawal = vault.withdraw(0, 3 * QUOTE_PRECISION, 0, 0).unwrap();
+    assert_eq!(withdrawal.refund, 2 * QUOTE_PRECISION);
+    assert_eq!(vault.state.total_amount_minted, 0);
+}
+
+#[test]
+fn rejects_deposits_which_round_down_to_no_shares() {
+    // nav per share = 2 => a 1 unit deposit mints floor(1 / 2) = 0
+    let mut vault = VaultModel::new(0, 0);
+    vault.deposit(0, QUOTE_PRECISION).unwrap();
+    vault.rebalance(QUOTE_PRECISION as i64, 0);
+    assert!(vault.deposit(1, 1).is_none());
+    assert_eq!(vault.shares[1], 0);
+
+    // the amm / oracle gap: priced at the higher valuation (3)
+    vault.rebalance(0, QUOTE_PRECISION as i64);
+    let deposit = vault.deposit(1, 3 * QUOTE_PRECISION).unwrap();
+    assert_eq!(deposit.minted, QUOTE_PRECISION);
+}
@@ -0,0 +1,423 @@
+// vault instructions against the real clearing house + mock pyth programs
+// (no update_twaps: prices move w/ the oracle + other users' trades, funding w/ the clock)
+use clearing_house::math::constants::MARK_PRICE_PRECISION;
+use drift_vault_test_utils::{
+    usdc, PositionDirection, StrategyKind, StrategyParams, TestExchange, DEFAULT_SQRT_K, ONE_HOUR,
+};
+
+const SOL: u64 = 0;
+const BTC: u64 = 1;
+
+// mark = oracle = 1, FundingTwap vault on SOL
+async fn build_exchange(depositors: &[&str]) -> TestExchange {
+    let mut builder = TestExchange::builder()
+        .market(SOL, 1.0, DEFAULT_SQRT_K, ONE_HOUR)
+        .user("trader", usdc(10_000))
+        .vault(StrategyKind::FundingTwap, StrategyParams::default());
+    for name in depositors {
+        builder = builder.depositor(name, usdc(1_000));
+    }
+    builder.build().await
+}
+
+// oracle > mark for a funding period => the vault goes long
+async fn open_vault_long(exchange: &mut TestExchange) {
+    exchange.set_oracle_price(SOL, 1.04).await;
+    exchange.warp_to_next_funding(SOL).await;
+    exchange.update_funding_rate(SOL).await.unwrap();
+    exchange.update_position(SOL).await.unwrap();
+    assert!(exchange.get_vault_base_asset_amount(SOL).await > 0);
+}
+
+#[tokio::test]
+async fn deposits_and_withdraws_without_trading() {
+    let mut exchange = build_exchange(&["alice"]).await;
+    let alice_usdc = exchange.depositor("alice").usdc;
+    let alice_vault_tokens = exchange.depositor("alice").vault_tokens;
+
+    exchange.deposit("alice", usdc(1_000)).await.unwrap();
+    assert_eq!(exchange.get_token_balance(&alice_vault_tokens).await, usdc(1_000));
+    assert_eq!(exchange.get_vault_state().await.total_amount_minted, usdc(1_000));
+    assert_eq!(exchange.get_vault_user().await.collateral, usdc(1_000) as u128);
+
+    // cant burn more than you have
+    assert!(exchange.withdraw("alice", usdc(1_001), SOL).await.is_err());
+
+    exchange.withdraw("alice", usdc(1_000), SOL).await.unwrap();
+    assert_eq!(exchange.get_token_balance(&alice_usdc).await, usdc(1_000));
+    assert_eq!(exchange.get_token_balance(&alice_vault_tokens).await, 0);
+    assert_eq!(exchange.get_vault_state().await.total_amount_minted, 0);
+    assert_eq!(exchange.get_vault_token_supply().await, 0);
+}
+
+#[tokio::test]
+async fn goes_long_captures_funding_then_flips_short() {
+    let mut exchange = build_exchange(&["alice"]).await;
+    exchange.deposit("alice", usdc(1_000)).await.unwrap();
+
+    open_vault_long(&mut exchange).await;
+
+    // shorts pay longs for the next period
+    exchange.warp_to_next_funding(SOL).await;
+    exchange.update_funding_rate(SOL).await.unwrap();
+    let collateral_before = exchange.get_vault_user().await.collateral;
+    exchange.settle_vault_funding_payment().await.unwrap();
+    assert!(exchange.get_vault_user().await.collateral > collateral_before);
+
+    // oracle < mark => longs pay shorts => close the long + go short
+    exchange.set_oracle_price(SOL, 0.96).await;
+    exchange.warp_to_next_funding(SOL).await;
+    exchange.update_funding_rate(SOL).await.unwrap();
+    exchange.update_position(SOL).await.unwrap();
+    assert!(exchange.get_vault_base_asset_amount(SOL).await < 0);
+
+    // shares dont change w/ the position
+    assert_eq!(exchange.get_vault_state().await.total_amount_minted, usdc(1_000));
+    assert_eq!(exchange.get_vault_token_supply().await, usdc(1_000));
+}
+
+#[tokio::test]
+async fn withdraws_at_a_loss_after_an_adverse_move() {
+    let mut exchange = build_exchange(&["alice"]).await;
+    let alice_usdc = exchange.depositor("alice").usdc;
+    exchange.deposit("alice", usdc(1_000)).await.unwrap();
+    open_vault_long(&mut exchange).await;
+
+    // price falls: oracle drops + a trader sells the amm down
+    exchange.set_oracle_price(SOL, 0.97).await;
+    exchange.open_position("trader", SOL, PositionDirection::Short, usdc(3_000)).await.unwrap();
+
+    let nav = exchange.get_vault_nav(SOL).await;
+    assert!(nav.nav < usdc(1_000) as u128);
+
+    exchange.withdraw("alice", usdc(1_000), SOL).await.unwrap();
+    let refund = exchange.get_token_balance(&alice_usdc).await;
+    assert!(refund < usdc(1_000));
+    assert!(refund > usdc(900));
+    assert_eq!(exchange.get_vault_state().await.total_amount_minted, 0);
+}
+
+#[tokio::test]
+async fn splits_pnl_pro_rata_between_depositors() {
+    let mut exchange = build_exchange(&["alice", "bob"]).await;
+    let alice_usdc = exchange.depositor("alice").usdc;
+    let bob_usdc = exchange.depositor("bob").usdc;
+    exchange.deposit("alice", usdc(1_000)).await.unwrap();
+    exchange.deposit("bob", usdc(1_000)).await.unwrap();
+    open_vault_long(&mut exchange).await;
+
+    // price rises: oracle up + a trader buys the amm up
+    exchange.set_oracle_price(SOL, 1.06).await;
+    exchange.open_position("trader", SOL, PositionDirection::Long, usdc(2_000)).await.unwrap();
+
+    let nav = exchange.get_vault_nav(SOL).await;
+    assert!(nav.nav > usdc(2_000) as u128);
+
+    // alice withdraws in two steps, bob at once
+    exchange.withdraw("alice", usdc(500), SOL).await.unwrap();
+    exchange.warp(1).await;
+    exchange.withdraw("alice", usdc(500), SOL).await.unwrap();
+    exchange.withdraw("bob", usdc(1_000), SOL).await.unwrap();
+
+    let alice_refund = exchange.get_token_balance(&alice_usdc).await;
+    let bob_refund = exchange.get_token_balance(&bob_usdc).await;
+    assert!(alice_refund > usdc(1_000));
+    assert!(bob_refund > usdc(1_000));
+
+    // same shares => same refund (up to the fees + slippage of reducing the position)
+    let refund_difference = (alice_refund as i64 - bob_refund as i64).unsigned_abs();
+    assert!(refund_difference <= usdc(10), "alice {} vs bob {}", alice_refund, bob_refund);
+
+    // never pays out more than the vault is worth at the amm
+    assert!((alice_refund + bob_refund) as u128 <= nav.amm_collateral + nav.idle_amount);
+    assert_eq!(exchange.get_vault_state().await.total_amount_minted, 0);
+    assert_eq!(exchange.get_vault_token_supply().await, 0);
+}
+
+#[tokio::test]
+async fn deploys_deposits_within_the_slippage_bound() {
+    let mut exchange = build_exchange(&["alice", "bob"]).await;
+
+    // the twaps say long (oracle > mark for a funding period) ...
+    exchange.set_oracle_price(SOL, 1.04).await;
+    exchange.warp_to_next_funding(SOL).await;
+    exchange.update_funding_rate(SOL).await.unwrap();
+    // ... but a trader just bought the mark ~6% up, past the oracle
+    exchange.open_position("trader", SOL, PositionDirection::Long, usdc(3_000)).await.unwrap();
+
+    // no limit price => rejected 
+    assert!(exchange.deposit_with_deploy("alice", usdc(1_000), Some((SOL, 0))).await.is_err());
+
+    // 0.1% over the oracle => rejected (deposit included)
+    assert!(exchange.deposit_with_deploy("alice", usdc(1_000), Some((SOL, 10))).await.is_err());
+    assert_eq!(exchange.get_vault_state().await.total_amount_minted, 0);
+
+    // 50% => fills
+    exchange.deposit_with_deploy("alice", usdc(1_000), Some((SOL, 5_000))).await.unwrap();
+    assert!(exchange.get_vault_base_asset_amount(SOL).await > 0);
+
+    // no deploy => no market needed, nothing traded
+    let base_asset_amount = exchange.get_vault_base_asset_amount(SOL).await;
+    exchange.deposit("bob", usdc(1_000)).await.unwrap();
+    assert_eq!(exchange.get_vault_base_asset_amount(SOL).await, base_asset_amount);
+}
+
+#[tokio::test]
+async fn deploys_only_the_new_collateral() {
+    let mut exchange = build_exchange(&["alice", "bob"]).await;
+    exchange.deposit("alice", usdc(1_000)).await.unwrap();
+
+    // the twaps say long, alice's 1000 is still undeployed
+    exchange.set_oracle_price(SOL, 1.04).await;
+    exchange.warp_to_next_funding(SOL).await;
+    exchange.update_funding_rate(SOL).await.unwrap();
+
+    // bob's deploy trades his 100, not the vault's 1100
+    exchange.deposit_with_deploy("bob", usdc(100), Some((SOL, 5_000))).await.unwrap();
+    let liabilities = exchange.get_vault_nav(SOL).await.liabilities;
+    assert!(liabilities > usdc(99) as u128 && liabilities < usdc(101) as u128, "{}", liabilities);
+
+    // update_position takes it the rest of the way
+    exchange.update_position(SOL).await.unwrap();
+    assert!(exchange.get_vault_nav(SOL).await.liabilities > usdc(1_000) as u128);
+}
+
+#[tokio::test]
+async fn skips_the_deploy_when_the_vault_uses_orders() {
+    let mut exchange = build_exchange(&["alice"]).await;
+    let admin = exchange.admin();
+    let ix = exchange.vault().update_execution_mode(&admin, true, MARK_PRICE_PRECISION / 100, ONE_HOUR);
+    exchange.process(&[ix], &[]).await.unwrap();
+
+    exchange.set_oracle_price(SOL, 1.04).await;
+    exchange.warp_to_next_funding(SOL).await;
+    exchange.update_funding_rate(SOL).await.unwrap();
+
+    // the deposit goes through, the orders crank deploys it later
+    exchange.deposit_with_deploy("alice", usdc(1_000), Some((SOL, 100))).await.unwrap();
+    assert_eq!(exchange.get_vault_state().await.total_amount_minted, usdc(1_000));
+    assert_eq!(exchange.get_vault_base_asset_amount(SOL).await, 0);
+}
+
+#[tokio::test]
+async fn caps_every_rebalance_at_the_max_market_share() {
+    let mut exchange = build_exchange(&["alice"]).await;
+    let admin = exchange.admin();
+    // the vault holds at most a fifth of a side
+    let ix = exchange.vault().update_deposit_caps(&admin, 0, 0, 2_000);
+    exchange.process(&[ix], &[]).await.unwrap();
+    exchange.open_position("trader", SOL, PositionDirection::Long, usdc(400)).await.unwrap();
+
+    // no deploy on the deposit, the cap holds on update_position
+    exchange.deposit("alice", usdc(1_000)).await.unwrap();
+    open_vault_long(&mut exchange).await;
+    let vault_base = exchange.get_vault_base_asset_amount(SOL).await;
+    let long_base = exchange.get_markets().await.get_market(SOL).base_asset_amount_long;
+    assert!(vault_base * 5 <= long_base, "vault {} vs longs {}", vault_base, long_base);
+
+    // the 1000 usdc would've been ~2.5x the trader's long
+    let nav = exchange.get_vault_nav(SOL).await;
+    assert!(nav.liabilities < usdc(200) as u128);
+}
+
+#[tokio::test]
+async fn cancels_the_orders_when_funding_pauses() {
+    let mut exchange = build_exchange(&["alice"]).await;
+    let admin = exchange.admin();
+    // 10% stop loss + take profit, freeze on a funding pause (the default)
+    let ix = exchange.vault().update_trigger_orders(&admin, 1, 10, 1, 10);
+    exchange.process(&[ix], &[]).await.unwrap();
+    exchange.deposit("alice", usdc(1_000)).await.unwrap();
+
+    exchange.set_oracle_price(SOL, 1.04).await;
+    exchange.warp_to_next_funding(SOL).await;
+    exchange.update_funding_rate(SOL).await.unwrap();
+    exchange.update_position_with_orders(SOL).await.unwrap();
+    let base_asset_amount = exchange.get_vault_base_asset_amount(SOL).await;
+    assert!(base_asset_amount > 0);
+    assert_eq!(exchange.get_vault_open_orders().await.len(), 2);
+
+    // frozen: the position stays, the orders dont
+    exchange.update_funding_paused(true).await.unwrap();
+    exchange.warp(1).await;
+    exchange.update_position_with_orders(SOL).await.unwrap();
+    assert_eq!(exchange.get_vault_base_asset_amount(SOL).await, base_asset_amount);
+    assert!(exchange.get_vault_open_orders().await.is_empty());
+}
+
+#[tokio::test]
+async fn prices_shares_w_the_losses_of_a_losing_position() {
+    let mut exchange = build_exchange(&["alice", "bob"]).await;
+    let bob_vault_tokens = exchange.depositor("bob").vault_tokens;
+    exchange.deposit("alice", usdc(1_000)).await.unwrap();
+    open_vault_long(&mut exchange).await;
+
+    // the oracle drops 10%, the amm ~5% (a trader sells it down)
+    exchange.set_oracle_price(SOL, 0.90).await;
+    exchange.open_position("trader", SOL, PositionDirection::Short, usdc(3_000)).await.unwrap();
+
+    // both valuations take the loss off the collateral
+    let collateral = exchange.get_vault_user().await.collateral;
+    let nav = exchange.get_vault_nav(SOL).await;
+    assert!(nav.oracle_collateral < nav.amm_collateral);
+    assert!(nav.amm_collateral < collateral);
+    assert_eq!(nav.nav, nav.oracle_collateral + nav.idle_amount);
+    assert_eq!(nav.deposit_nav, nav.amm_collateral + nav.idle_amount);
+
+    // bob buys in at the higher (amm) valuation: more shares than usdc, 
+    // but fewer than the oracle valuation would give
+    exchange.deposit("bob", usdc(1_000)).await.unwrap();
+    let bob_shares = exchange.get_token_balance(&bob_vault_tokens).await as u128;
+    let total_minted = usdc(1_000) as u128;
+    assert_eq!(bob_shares, usdc(1_000) as u128 * total_minted / nav.deposit_nav);
+    assert!(bob_shares > usdc(1_000) as u128);
+    assert!(bob_shares < usdc(1_000) as u128 * total_minted / nav.nav);
+}
+
+#[tokio::test]
+async fn caps_deposits_by_collateral_incl_the_idle_buffer() {
+    let mut exchange = build_exchange(&["alice", "bob"]).await;
+    let admin = exchange.admin();
+    let ixs = [
+        exchange.vault().update_idle_buffer(&admin, 1, 2),
+        exchange.vault().update_deposit_caps(&admin, usdc(1_000) as u128, usdc(700), 0),
+    ];
+    exchange.process(&ixs, &[]).await.unwrap();
+
+    // half of alice's deposit moves to the idle buffer
+    exchange.deposit("alice", usdc(600)).await.unwrap();
+    exchange.update_position(SOL).await.unwrap();
+    assert_eq!(exchange.get_vault_user().await.collateral, usdc(300) as u128);
+
+    // 300 in drift + 300 idle + 500 > the vault cap
+    assert!(exchange.deposit("bob", usdc(500)).await.is_err());
+    exchange.deposit("bob", usdc(400)).await.unwrap();
+
+    // alice is capped by what she put in (600 + 200 > 700), refunds free it up again
+    let ix = exchange.vault().update_deposit_caps(&admin, 0, usdc(700), 0);
+    exchange.process(&[ix], &[]).await.unwrap();
+    assert!(exchange.deposit("alice", usdc(200)).await.is_err());
+    exchange.withdraw("alice", usdc(300), SOL).await.unwrap();
+    assert_eq!(exchange.get_depositor_state("alice").await.deposited_amount, usdc(300));
+    exchange.deposit("alice", usdc(200)).await.unwrap();
+    assert_eq!(exchange.get_depositor_state("alice").await.deposited_amount, usdc(500));
+}
+
+#[tokio::test]
+async fn locks_the_shares_not_the_wallet() {
+    let mut exchange = build_exchange(&["alice", "bob"]).await;
+    let alice_vault_tokens = exchange.depositor("alice").vault_tokens;
+    let bob_usdc = exchange.depositor("bob").usdc;
+    let admin = exchange.admin();
+    let ix = exchange.vault().update_lockup(&admin, ONE_HOUR, 0, 1);
+    exchange.process(&[ix], &[]).await.unwrap();
+
+    // the shares are held by the vault until the lockup ends
+    exchange.deposit("alice", usdc(1_000)).await.unwrap();
+    assert_eq!(exchange.get_token_balance(&alice_vault_tokens).await, 0);
+    assert_eq!(exchange.get_locked_shares("alice").await, usdc(1_000));
+    assert!(exchange.transfer_shares("alice", "bob", usdc(1_000)).await.is_err());
+    assert!(exchange.withdraw("alice", usdc(1_000), SOL).await.is_err());
+    assert!(exchange.unlock_shares("alice").await.is_err());
+
+    exchange.warp(ONE_HOUR).await;
+    exchange.unlock_shares("alice").await.unwrap();
+    assert_eq!(exchange.get_locked_shares("alice").await, 0);
+    assert_eq!(exchange.get_token_balance(&alice_vault_tokens).await, usdc(1_000));
+
+    // unlocked shares withdraw from any wallet, bob never deposited
+    exchange.transfer_shares("alice", "bob", usdc(1_000)).await.unwrap();
+    exchange.withdraw("bob", usdc(1_000), SOL).await.unwrap();
+    assert_eq!(exchange.get_token_balance(&bob_usdc).await, usdc(2_000));
+    assert_eq!(exchange.get_vault_state().await.total_amount_minted, 0);
+}
+
+#[tokio::test]
+async fn charges_the_early_withdrawal_fee_on_locked_shares_only() {
+    let mut exchange = build_exchange(&["alice"]).await;
+    let alice_usdc = exchange.depositor("alice").usdc;
+    let admin = exchange.admin();
+
+    // unlocked shares from a deposit before the lockup
+    exchange.deposit("alice", usdc(500)).await.unwrap();
+    let ix = exchange.vault().update_lockup(&admin, ONE_HOUR, 1, 100);
+    exchange.process(&[ix], &[]).await.unwrap();
+    exchange.deposit("alice", usdc(500)).await.unwrap();
+    assert_eq!(exchange.get_locked_shares("alice").await, usdc(500));
+
+    // 500 unlocked for free + 100 locked w/ a 1% fee
+    exchange.withdraw("alice", usdc(600), SOL).await.unwrap();
+    assert_eq!(exchange.get_token_balance(&alice_usdc).await, usdc(599));
+    assert_eq!(exchange.get_locked_shares("alice").await, usdc(400));
+}
+
+#[tokio::test]
+async fn unwinds_every_spread_leg_pro_rata_on_withdraw() {
+    let strategy_params = StrategyParams {
+        spread_market_indexes: [SOL, BTC, 0, 0],
+        spread_market_betas: [10_000, 10_000, 0, 0],
+    };
+    let mut exchange = TestExchange::builder()
+        .market(SOL, 1.0, DEFAULT_SQRT_K, ONE_HOUR)
+        .market(BTC, 1.0, DEFAULT_SQRT_K, ONE_HOUR)
+        .vault(StrategyKind::FundingSpread, strategy_params)
+        .depositor("alice", usdc(1_000))
+        .build()
+        .await;
+    exchange.deposit("alice", usdc(1_000)).await.unwrap();
+
+    // SOL: mark < oracle => long, BTC: mark > oracle => short
+    exchange.set_oracle_price(SOL, 1.04).await;
+    exchange.set_oracle_price(BTC, 0.96).await;
+    exchange.warp_to_next_funding(SOL).await;
+    exchange.update_funding_rate(SOL).await.unwrap();
+    exchange.update_funding_rate(BTC).await.unwrap();
+    exchange.update_position(SOL).await.unwrap();
+    let sol_base = exchange.get_vault_base_asset_amount(SOL).await;
+    let btc_base = exchange.get_vault_base_asset_amount(BTC).await;
+    assert!(sol_base > 0 && btc_base < 0);
+
+    // half the shares => both legs ~halved (not just the withdraw's market)
+    exchange.withdraw("alice", usdc(500), SOL).await.unwrap();
+    let sol_ratio = exchange.get_vault_base_asset_amount(SOL).await as f64 / sol_base as f64;
+    let btc_ratio = exchange.get_vault_base_asset_amount(BTC).await as f64 / btc_base as f64;
+    assert!((0.45..0.55).contains(&sol_ratio), "SOL leg ratio {}", sol_ratio);
+    assert!((0.45..0.55).contains(&btc_ratio), "BTC leg ratio {}", btc_ratio);
+    assert!((sol_ratio - btc_ratio).abs() < 0.02, "{} vs {}", sol_ratio, btc_ratio);
+}
+
+#[tokio::test]
+async fn holds_the_spread_only_within_the_funding_window() {
+    let strategy_params = StrategyParams {
+        spread_market_indexes: [SOL, BTC, 0, 0],
+        spread_market_betas: [10_000, 10_000, 0, 0],
+    };
+    let mut exchange = TestExchange::builder()
+        .market(SOL, 1.0, DEFAULT_SQRT_K, ONE_HOUR)
+        .market(BTC, 1.0, DEFAULT_SQRT_K, ONE_HOUR)
+        .vault(StrategyKind::FundingSpread, strategy_params)
+        .depositor("alice", usdc(1_000))
+        .build()
+        .await;
+    let admin = exchange.admin();
+    let ix = exchange.vault().update_funding_window(&admin, 600);
+    exchange.process(&[ix], &[]).await.unwrap();
+    exchange.deposit("alice", usdc(1_000)).await.unwrap();
+
+    exchange.set_oracle_price(SOL, 1.04).await;
+    exchange.set_oracle_price(BTC, 0.96).await;
+    exchange.warp_to_next_funding(SOL).await;
+    exchange.update_funding_rate(SOL).await.unwrap();
+    exchange.update_funding_rate(BTC).await.unwrap();
+
+    // an hour to the next funding => flat on both legs
+    exchange.update_position(SOL).await.unwrap();
+    assert_eq!(exchange.get_vault_base_asset_amount(SOL).await, 0);
+    assert_eq!(exchange.get_vault_base_asset_amount(BTC).await, 0);
+
+    // a minute to go => in on both
+    exchange.warp(ONE_HOUR - 60).await;
+    exchange.update_position(SOL).await.unwrap();
+    assert!(exchange.get_vault_base_asset_amount(SOL).await > 0);
+    assert!(exchange.get_vault_base_asset_amount(BTC).await < 0);
+}
@@ -0,0 +1,118 @@
+// volatility sizing at realistic perp volatility: the default max_volatility (5%)
+// scales the leverage down within a few % of oracle confidence + twap gap, 
+// + the clearing house's too_volatile_ratio bound
+use clearing_house::math::constants::MARK_PRICE_PRECISION;
+use clearing_house::state::market::{OraclePriceData, AMM};
+use clearing_house::state::state::ValidityGuardRails;
+
+use drift_vault::state::{VaultState, DEFAULT_MAX_VOLATILITY, LEVERAGE_PRECISION, VOLATILITY_PRECISION};
+use drift_vault::volatility::{calculate_volatility, calculate_volatility_leverage, is_oracle_too_volatile};
+
+// oracle twap at 1, the oracle twap_gap above it w/ confidence (in VOLATILITY_PRECISION)
+fn oracle(twap_gap: u128, confidence: u128) -> (AMM, OraclePriceData) {
+    let twap = MARK_PRICE_PRECISION;
+    let price = twap + twap * twap_gap / VOLATILITY_PRECISION;
+    let amm = AMM {
+        last_oracle_price: price as i128,
+        last_oracle_price_twap: twap as i128,
+        ..AMM::default()
+    };
+    let oracle_price_data = OraclePriceData {
+        price: price as i128,
+        confidence: price * confidence / VOLATILITY_PRECISION,
+        ..OraclePriceData::default()
+    };
+    (amm, oracle_price_data)
+}
+
+// 0.5x - 2x w/ the default max volatility
+fn vault_state() -> VaultState {
+    VaultState {
+        min_leverage: LEVERAGE_PRECISION / 2,
+        max_leverage: 2 * LEVERAGE_PRECISION,
+        ..VaultState::default()
+    }
+}
+
+// the clearing house's default: price / twap > 5 => too volatile 
+fn guard_rails() -> ValidityGuardRails {
+    ValidityGuardRails {
+        too_volatile_ratio: 5,
+        ..ValidityGuardRails::default()
+    }
+}
+
+fn leverage(twap_gap: u128, confidence: u128) -> u128 {
+    let (amm, oracle_price_data) = oracle(twap_gap, confidence);
+    calculate_volatility_leverage(&amm, &oracle_price_data, &vault_state(), &guard_rails()).unwrap()
+}
+
+#[test]
+fn defaults_the_max_volatility_to_5_pct() {
+    assert_eq!(DEFAULT_MAX_VOLATILITY, 500);
+
+    let (amm, oracle_price_data) = oracle(200, 50);
+    assert_eq!(calculate_volatility(&amm, &oracle_price_data).unwrap(), 250);
+}
+
+#[test]
+fn scales_leverage_down_at_plausible_volatility() {
+    // calm => max leverage
+    assert_eq!(leverage(0, 0), 2 * LEVERAGE_PRECISION);
+
+    // 1% twap gap + 0.25% confidence = 1.25% => a quarter of the way down
+    assert_eq!(leverage(100, 25), 16_250);
+
+    // 2% + 0.5% = 2.5% => half way
+    assert_eq!(leverage(200, 50), 12_500);
+
+    // >= 5% => min leverage
+    assert_eq!(leverage(400, 100), LEVERAGE_PRECISION / 2);
+    assert_eq!(leverage(2_000, 100), LEVERAGE_PRECISION / 2);
+}
+
+#[test]
+fn uses_the_configured_max_volatility() {
+    // 2.5% volatility vs a 10% max => a quarter of the way down
+    let (amm, oracle_price_data) = oracle(200, 50);
+    let vault_state = VaultState { max_volatility: 1_000, ..vault_state() };
+    let leverage = calculate_volatility_leverage(&amm, &oracle_price_data, &vault_state, &guard_rails()).unwrap();
+    assert_eq!(leverage, 16_250);
+}
+
+#[test]
+fn caps_the_max_volatility_at_the_too_volatile_ratio() {
+    // 1000% max volatility > the 500% twap gap the clearing house flags => 500%
+    let vault_state = VaultState { max_volatility: 100_000, ..vault_state() };
+    let leverage = |twap_gap, guard_rails: &ValidityGuardRails| {
+        let (amm, oracle_price_data) = oracle(twap_gap, 0);
+        calculate_volatility_leverage(&amm, &oracle_price_data, &vault_state, guard_rails).unwrap()
+    };
+
+    // 250% => half way (a quarter w/o the cap)
+    assert_eq!(leverage(25_000, &guard_rails()), 12_500);
+    assert_eq!(leverage(25_000, &ValidityGuardRails::default()), 16_250);
+
+    // just inside the clearing house's bound (price / twap = 5.9999 => 5)
+    assert_eq!(leverage(49_999, &guard_rails()), 5_001);
+    assert!(!is_oracle_too_volatile(&oracle(49_999, 0).0, &oracle(49_999, 0).1, &guard_rails()).unwrap());
+
+    // price / twap = 6 > 5 => too volatile => min leverage
+    assert!(is_oracle_too_volatile(&oracle(50_000, 0).0, &oracle(50_000, 0).1, &guard_rails()).unwrap());
+    assert_eq!(leverage(50_000, &guard_rails()), LEVERAGE_PRECISION / 2);
+}
+
+#[test]
+fn goes_to_min_leverage_when_the_oracle_crashes_below_the_twap() {
+    // twap / price = 6 > 5, the same check the other way around
+    let (mut amm, mut oracle_price_data) = oracle(0, 0);
+    oracle_price_data.price /= 6;
+    amm.last_oracle_price = oracle_price_data.price;
+    assert!(is_oracle_too_volatile(&amm, &oracle_price_data, &guard_rails()).unwrap());
+    let leverage = calculate_volatility_leverage(&amm, &oracle_price_data, &vault_state(), &guard_rails()).unwrap();
+    assert_eq!(leverage, LEVERAGE_PRECISION / 2);
+
+    // twap / price = 5 => not flagged, the twap gap (> 5%) already maxes it out
+    oracle_price_data.price = amm.last_oracle_price_twap / 5;
+    assert!(!is_oracle_too_volatile(&amm, &oracle_price_data, &guard_rails()).unwrap());
+}
@@ -0,0 +1,173 @@
+use std::collections::BTreeMap;
+
+use anchor_lang::prelude::Pubkey;
+
+use solana_program_test::{processor, ProgramTest};
+use solana_sdk::signature::{Keypair, Signer};
+
+use drift_vault::{StrategyKind, StrategyParams};
+use drift_vault_client::{VaultInstructions, VaultPdas};
+
+use crate::exchange::{usdc, TestExchange};
+use crate::setup::{
+    create_mint, initialize_clearing_house, initialize_market, initialize_oracle, mint_to, process,
+};
+
+pub const ONE_HOUR: i64 = 60 * 60;
+
+// same amm depth as the ts tests (sqrt_k = 1e8 => ~$100k each side at $1)
+pub const DEFAULT_SQRT_K: u128 = 100_000_000;
+// pays the funding the amm owes (the clearing house caps it by the fee pool otherwise)
+pub const DEFAULT_COLLATERAL_VAULT_FUNDING: u64 = 100_000;
+
+#[derive(Clone, Copy, Debug)]
+pub struct MarketConfig {
+    // the market's own mock pyth oracle starts here + so does the mark (peg)
+    pub oracle_price: f64,
+    // base = quote reserves (AMM_RESERVE_PRECISION = sqrt_k * MARK_PRICE_PRECISION)
+    pub sqrt_k: u128,
+    pub funding_period: i64,
+    pub margin_ratio_initial: u32,
+    pub margin_ratio_partial: u32,
+    pub margin_ratio_maintenance: u32,
+}
+
+impl Default for MarketConfig {
+    fn default() -> Self {
+        MarketConfig {
+            oracle_price: 1.0,
+            sqrt_k: DEFAULT_SQRT_K,
+            funding_period: ONE_HOUR,
+            margin_ratio_initial: 2000,
+            margin_ratio_partial: 625,
+            margin_ratio_maintenance: 500,
+        }
+    }
+}
+
+// TestExchange::builder()
+//     .market(SOL, 1.0, DEFAULT_SQRT_K, ONE_HOUR)
+//     .oracle("backup", 1.0)
+//     .user("whale", usdc(10_000))
+//     .vault(StrategyKind::FundingTwap, StrategyParams::default())
+//     .depositor("alice", usdc(1_000))
+//     .build().await
+#[derive(Default)]
+pub struct TestExchangeBuilder {
+    markets: BTreeMap<u64, MarketConfig>,
+    oracles: Vec<(String, f64)>,
+    users: Vec<(String, u64)>,
+    vault: Option<(StrategyKind, StrategyParams)>,
+    depositors: Vec<(String, u64)>,
+    collateral_vault_funding: Option<u64>,
+}
+
+impl TestExchangeBuilder {
+    pub fn market(
+        self,
+        market_index: u64,
+        oracle_price: f64,
+        sqrt_k: u128,
+        funding_period: i64,
+    ) -> Self {
+        self.market_with_config(market_index, MarketConfig {
+            oracle_price,
+            sqrt_k,
+            funding_period,
+            ..MarketConfig::default()
+        })
+    }
+
+    pub fn market_with_config(mut self, market_index: u64, config: MarketConfig) -> Self {
+        self.markets.insert(market_index, config);
+        self
+    }
+
+    // a mock pyth oracle which isnt a market's (e.g. for update_market_oracle)
+    pub fn oracle(mut self, name: &str, price: f64) -> Self {
+        self.oracles.push((name.to_string(), price));
+        self
+    }
+
+    // a clearing house user w/ usdc_amount of collateral deposited
+    pub fn user(mut self, name: &str, usdc_amount: u64) -> Self {
+        self.users.push((name.to_string(), usdc_amount));
+        self
+    }
+
+    pub fn vault(mut self, strategy: StrategyKind, strategy_params: StrategyParams) -> Self {
+        self.vault = Some((strategy, strategy_params));
+        self
+    }
+
+    // a vault depositor w/ usdc_amount in their usdc account (nothing deposited yet)
+    pub fn depositor(mut self, name: &str, usdc_amount: u64) -> Self {
+        self.depositors.push((name.to_string(), usdc_amount));
+        self
+    }
+
+    pub fn collateral_vault_funding(mut self, usdc_amount: u64) -> Self {
+        self.collateral_vault_funding = Some(usdc_amount);
+        self
+    }
+
+    pub async fn build(self) -> TestExchange {
+        assert!(
+            self.depositors.is_empty() || self.vault.is_some(),
+            "depositors need a vault (TestExchangeBuilder::vault)"
+        );
+
+        let mut program_test = ProgramTest::new("drift_vault", drift_vault::ID, processor!(drift_vault::entry));
+        program_test.add_program("clearing_house", clearing_house::ID, processor!(clearing_house::entry));
+        program_test.add_program("pyth", pyth::ID, processor!(pyth::entry));
+        let mut context = program_test.start_with_context().await;
+
+        let usdc_mint = Keypair::new();
+        create_mint(&mut context, &usdc_mint, 6).await;
+        let clearing_house = initialize_clearing_house(&mut context, &usdc_mint.pubkey()).await;
+        let collateral_vault_funding = self.collateral_vault_funding
+            .unwrap_or_else(|| usdc(DEFAULT_COLLATERAL_VAULT_FUNDING));
+        if collateral_vault_funding > 0 {
+            mint_to(&mut context, &usdc_mint.pubkey(), &clearing_house.collateral_vault, collateral_vault_funding).await;
+        }
+
+        let mut market_oracles = BTreeMap::new();
+        for (&market_index, config) in self.markets.iter() {
+            let oracle = initialize_oracle(&mut context, config.oracle_price).await;
+            initialize_market(&mut context, &clearing_house, market_index, &oracle, config).await;
+            market_oracles.insert(market_index, oracle);
+        }
+        let mut oracles: BTreeMap<String, Pubkey> = BTreeMap::new();
+        for (name, price) in self.oracles.iter() {
+            oracles.insert(name.clone(), initialize_oracle(&mut context, *price).await);
+        }
+
+        let vault = VaultInstructions::new(
+            VaultPdas::derive(&drift_vault::ID, &clearing_house::ID),
+            clearing_house,
+        );
+        if let Some((strategy, strategy_params)) = self.vault {
+            let payer = context.payer.pubkey();
+            let ix = vault.initialize_vault(&payer, strategy, strategy_params);
+            process(&mut context, &[ix], &[]).await.unwrap();
+        }
+
+        let mut exchange = TestExchange {
+            context,
+            usdc_mint: usdc_mint.pubkey(),
+            clearing_house,
+            market_oracles,
+            oracles,
+            users: BTreeMap::new(),
+            vault: self.vault.map(|_| vault),
+            depositors: BTreeMap::new(),
+        };
+        for (name, usdc_amount) in self.users.iter() {
+            exchange.add_user(name, *usdc_amount).await;
+        }
+        for (name, usdc_amount) in self.depositors.iter() {
+            exchange.add_depositor(name, *usdc_amount).await;
+        }
+        exchange
+    }
+}
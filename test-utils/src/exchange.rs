@@ -0,0 +1,524 @@
+use std::collections::BTreeMap;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::{clock::Clock, instruction::Instruction, system_program, sysvar};
+use anchor_lang::ToAccountMetas;
+use anchor_spl::token::{Mint, TokenAccount};
+
+use solana_program_test::{BanksClientError, ProgramTestContext};
+use solana_sdk::signature::{Keypair, Signer};
+
+use clearing_house::context::{InitializeUserOptionalAccounts, ManagePositionOptionalAccounts};
+use clearing_house::controller::position::PositionDirection;
+use clearing_house::math::constants::QUOTE_PRECISION;
+use clearing_house::state::market::Markets;
+use clearing_house::state::order_state::OrderState;
+use clearing_house::state::state::State;
+use clearing_house::state::user::{User, UserPositions};
+use clearing_house::state::user_orders::{Order, OrderStatus, UserOrders};
+
+use drift_vault::funding::calculate_next_funding_ts;
+use drift_vault::state::{DepositorState, VaultState};
+use drift_vault_client::{
+    calculate_vault_nav, deserialize_account, deserialize_zero_copy_account, get_user_address,
+    ClearingHouseAccounts, DeployAccounts, VaultAccountsData, VaultInstructions, VaultNav,
+};
+
+use crate::builder::TestExchangeBuilder;
+use crate::setup::{
+    clearing_house_instruction, create_token_account, fund, get_account_data, mint_to, process,
+    set_oracle_price_instruction,
+};
+
+pub fn usdc(amount: u64) -> u64 {
+    amount * QUOTE_PRECISION as u64
+}
+
+// a clearing house user (trades against the amm => moves the mark price)
+pub struct TestUser {
+    pub authority: Keypair,
+    pub user: Pubkey,
+    pub user_positions: Pubkey,
+    pub usdc: Pubkey,
+}
+
+// a vault depositor w/ a usdc + vault token account
+pub struct Depositor {
+    pub owner: Keypair,
+    pub usdc: Pubkey,
+    pub vault_tokens: Pubkey,
+}
+
+// an in-memory clearing house (+ markets, mock pyth oracles, users) and
+// optionally the vault, see TestExchangeBuilder
+pub struct TestExchange {
+    pub context: ProgramTestContext,
+    pub usdc_mint: Pubkey,
+    pub clearing_house: ClearingHouseAccounts,
+    pub market_oracles: BTreeMap<u64, Pubkey>,
+    pub oracles: BTreeMap<String, Pubkey>,
+    pub users: BTreeMap<String, TestUser>,
+    pub vault: Option<VaultInstructions>,
+    pub depositors: BTreeMap<String, Depositor>,
+}
+
+impl TestExchange {
+    pub fn builder() -> TestExchangeBuilder {
+        TestExchangeBuilder::default()
+    }
+
+    pub async fn process(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<(), BanksClientError> {
+        process(&mut self.context, instructions, signers).await
+    }
+
+    pub fn market_oracle(&self, market_index: u64) -> Pubkey {
+        *self.market_oracles
+            .get(&market_index)
+            .unwrap_or_else(|| panic!("market {} not found", market_index))
+    }
+
+    pub fn oracle(&self, name: &str) -> Pubkey {
+        *self.oracles
+            .get(name)
+            .unwrap_or_else(|| panic!("oracle {} not found", name))
+    }
+
+    pub fn user(&self, name: &str) -> &TestUser {
+        self.users
+            .get(name)
+            .unwrap_or_else(|| panic!("user {} not found", name))
+    }
+
+    pub fn depositor(&self, name: &str) -> &Depositor {
+        self.depositors
+            .get(name)
+            .unwrap_or_else(|| panic!("depositor {} not found", name))
+    }
+
+    // the payer initializes the vault => signs the admin instructions
+    pub fn admin(&self) -> Pubkey {
+        self.context.payer.pubkey()
+    }
+
+    pub fn vault(&self) -> &VaultInstructions {
+        self.vault
+            .as_ref()
+            .expect("no vault (TestExchangeBuilder::vault)")
+    }
+
+    // oracles of every market but market_index (the vault's remaining accounts)
+    fn other_market_oracles(&self, market_index: u64) -> Vec<Pubkey> {
+        self.market_oracles
+            .iter()
+            .filter(|(&other_market_index, _)| other_market_index != market_index)
+            .map(|(_, oracle)| *oracle)
+            .collect()
+    }
+
+    // ** clearing house users
+    pub async fn add_user(&mut self, name: &str, usdc_amount: u64) {
+        let authority = Keypair::new();
+        fund(&mut self.context, &authority.pubkey()).await;
+        let clearing_house = self.clearing_house;
+        let (user, user_nonce) = get_user_address(&authority.pubkey(), &clearing_house::ID);
+        let user_positions = Keypair::new();
+        let accounts = clearing_house::accounts::InitializeUser {
+            user,
+            state: clearing_house.state,
+            user_positions: user_positions.pubkey(),
+            authority: authority.pubkey(),
+            rent: sysvar::rent::ID,
+            system_program: system_program::ID,
+        };
+        let data = clearing_house::instruction::InitializeUser {
+            _user_nonce: user_nonce,
+            optional_accounts: InitializeUserOptionalAccounts { whitelist_token: false },
+        };
+        let ix = clearing_house_instruction(accounts.to_account_metas(None), data);
+        self.process(&[ix], &[&authority, &user_positions]).await.unwrap();
+
+        let usdc = create_token_account(&mut self.context, &self.usdc_mint, &authority.pubkey()).await;
+        let test_user = TestUser {
+            authority,
+            user,
+            user_positions: user_positions.pubkey(),
+            usdc,
+        };
+        self.users.insert(name.to_string(), test_user);
+        if usdc_amount > 0 {
+            mint_to(&mut self.context, &self.usdc_mint, &usdc, usdc_amount).await;
+            self.deposit_collateral(name, usdc_amount).await.unwrap();
+        }
+    }
+
+    pub async fn deposit_collateral(&mut self, name: &str, amount: u64) -> Result<(), BanksClientError> {
+        let clearing_house = self.clearing_house;
+        let test_user = &self.users[name];
+        let accounts = clearing_house::accounts::DepositCollateral {
+            state: clearing_house.state,
+            user: test_user.user,
+            authority: test_user.authority.pubkey(),
+            collateral_vault: clearing_house.collateral_vault,
+            user_collateral_account: test_user.usdc,
+            token_program: spl_token::ID,
+            markets: clearing_house.markets,
+            user_positions: test_user.user_positions,
+            funding_payment_history: clearing_house.funding_payment_history,
+            deposit_history: clearing_house.deposit_history,
+        };
+        let data = clearing_house::instruction::DepositCollateral { amount };
+        let ix = clearing_house_instruction(accounts.to_account_metas(None), data);
+        process(&mut self.context, &[ix], &[&test_user.authority]).await
+    }
+
+    // quote_amount in QUOTE_PRECISION
+    pub async fn open_position(
+        &mut self,
+        name: &str,
+        market_index: u64,
+        direction: PositionDirection,
+        quote_amount: u64,
+    ) -> Result<(), BanksClientError> {
+        let clearing_house = self.clearing_house;
+        let oracle = self.market_oracle(market_index);
+        let test_user = &self.users[name];
+        let accounts = clearing_house::accounts::OpenPosition {
+            state: clearing_house.state,
+            user: test_user.user,
+            authority: test_user.authority.pubkey(),
+            markets: clearing_house.markets,
+            user_positions: test_user.user_positions,
+            trade_history: clearing_house.trade_history,
+            funding_payment_history: clearing_house.funding_payment_history,
+            funding_rate_history: clearing_house.funding_rate_history,
+            oracle,
+        };
+        let data = clearing_house::instruction::OpenPosition {
+            direction,
+            quote_asset_amount: quote_amount as u128,
+            market_index,
+            limit_price: 0,
+            optional_accounts: ManagePositionOptionalAccounts { discount_token: false, referrer: false },
+        };
+        let ix = clearing_house_instruction(accounts.to_account_metas(None), data);
+        process(&mut self.context, &[ix], &[&test_user.authority]).await
+    }
+
+    pub async fn close_position(&mut self, name: &str, market_index: u64) -> Result<(), BanksClientError> {
+        let clearing_house = self.clearing_house;
+        let oracle = self.market_oracle(market_index);
+        let test_user = &self.users[name];
+        let accounts = clearing_house::accounts::ClosePosition {
+            state: clearing_house.state,
+            user: test_user.user,
+            authority: test_user.authority.pubkey(),
+            markets: clearing_house.markets,
+            user_positions: test_user.user_positions,
+            trade_history: clearing_house.trade_history,
+            funding_payment_history: clearing_house.funding_payment_history,
+            funding_rate_history: clearing_house.funding_rate_history,
+            oracle,
+        };
+        let data = clearing_house::instruction::ClosePosition {
+            market_index,
+            optional_accounts: ManagePositionOptionalAccounts { discount_token: false, referrer: false },
+        };
+        let ix = clearing_house_instruction(accounts.to_account_metas(None), data);
+        process(&mut self.context, &[ix], &[&test_user.authority]).await
+    }
+
+    // ** clearing house cranks
+    pub async fn update_funding_rate(&mut self, market_index: u64) -> Result<(), BanksClientError> {
+        let clearing_house = self.clearing_house;
+        let accounts = clearing_house::accounts::UpdateFundingRate {
+            state: clearing_house.state,
+            markets: clearing_house.markets,
+            oracle: self.market_oracle(market_index),
+            funding_rate_history: clearing_house.funding_rate_history,
+        };
+        let data = clearing_house::instruction::UpdateFundingRate { market_index };
+        let ix = clearing_house_instruction(accounts.to_account_metas(None), data);
+        self.process(&[ix], &[]).await
+    }
+
+    // ** clearing house admin (the admin = the context payer)
+    pub async fn update_exchange_paused(&mut self, exchange_paused: bool) -> Result<(), BanksClientError> {
+        let accounts = clearing_house::accounts::AdminUpdateState {
+            admin: self.admin(),
+            state: self.clearing_house.state,
+        };
+        let data = clearing_house::instruction::UpdateExchangePaused { exchange_paused };
+        let ix = clearing_house_instruction(accounts.to_account_metas(None), data);
+        self.process(&[ix], &[]).await
+    }
+
+    pub async fn update_funding_paused(&mut self, funding_paused: bool) -> Result<(), BanksClientError> {
+        let accounts = clearing_house::accounts::AdminUpdateState {
+            admin: self.admin(),
+            state: self.clearing_house.state,
+        };
+        let data = clearing_house::instruction::UpdateFundingPaused { funding_paused };
+        let ix = clearing_house_instruction(accounts.to_account_metas(None), data);
+        self.process(&[ix], &[]).await
+    }
+
+    // ** vault
+    pub async fn add_depositor(&mut self, name: &str, usdc_amount: u64) {
+        let owner = Keypair::new();
+        fund(&mut self.context, &owner.pubkey()).await;
+        let usdc = create_token_account(&mut self.context, &self.usdc_mint, &owner.pubkey()).await;
+        if usdc_amount > 0 {
+            mint_to(&mut self.context, &self.usdc_mint, &usdc, usdc_amount).await;
+        }
+        let vault_mint = self.vault().pdas.vault_mint.0;
+        let vault_tokens = create_token_account(&mut self.context, &vault_mint, &owner.pubkey()).await;
+
+        let ix = self.vault().initialize_depositor(&owner.pubkey());
+        self.process(&[ix], &[&owner]).await.unwrap();
+        self.depositors.insert(name.to_string(), Depositor { owner, usdc, vault_tokens });
+    }
+
+    pub async fn deposit(&mut self, name: &str, amount: u64) -> Result<(), BanksClientError> {
+        self.deposit_with_deploy(name, amount, None).await
+    }
+
+    // deploy = (market_index, max_slippage)
+    pub async fn deposit_with_deploy(
+        &mut self,
+        name: &str,
+        amount: u64,
+        deploy: Option<(u64, u128)>,
+    ) -> Result<(), BanksClientError> {
+        let deploy = deploy.map(|(market_index, max_slippage)| DeployAccounts {
+            market_index,
+            max_slippage,
+            oracle: self.market_oracle(market_index),
+            other_oracles: self.other_market_oracles(market_index),
+        });
+        let depositor = &self.depositors[name];
+        let ix = self.vault().deposit(
+            &depositor.owner.pubkey(),
+            &depositor.usdc,
+            &depositor.vault_tokens,
+            amount,
+            None,
+            &self.market_oracles.values().copied().collect::<Vec<_>>(),
+            deploy.as_ref(),
+        );
+        process(&mut self.context, &[ix], &[&depositor.owner]).await
+    }
+
+    pub async fn withdraw(&mut self, name: &str, burn_amount: u64, market_index: u64) -> Result<(), BanksClientError> {
+        let depositor = &self.depositors[name];
+        let ix = self.vault().withdraw(
+            &depositor.owner.pubkey(),
+            &depositor.usdc,
+            &depositor.vault_tokens,
+            burn_amount as u128,
+            market_index,
+            &self.market_oracle(market_index),
+            &self.other_market_oracles(market_index),
+        );
+        process(&mut self.context, &[ix], &[&depositor.owner]).await
+    }
+
+    pub async fn unlock_shares(&mut self, name: &str) -> Result<(), BanksClientError> {
+        let depositor = &self.depositors[name];
+        let ix = self.vault().unlock_shares(&depositor.owner.pubkey(), &depositor.vault_tokens);
+        process(&mut self.context, &[ix], &[&depositor.owner]).await
+    }
+
+    // vault tokens: from's token account => to's token account
+    pub async fn transfer_shares(&mut self, from: &str, to: &str, amount: u64) -> Result<(), BanksClientError> {
+        let (from, to) = (&self.depositors[from], &self.depositors[to]);
+        let ix = spl_token::instruction::transfer(
+            &spl_token::ID,
+            &from.vault_tokens,
+            &to.vault_tokens,
+            &from.owner.pubkey(),
+            &[],
+            amount,
+        ).unwrap();
+        process(&mut self.context, &[ix], &[&from.owner]).await
+    }
+
+    pub async fn update_position(&mut self, market_index: u64) -> Result<(), BanksClientError> {
+        let ix = self.vault().update_position(
+            market_index,
+            &self.market_oracle(market_index),
+            &self.other_market_oracles(market_index),
+        );
+        self.process(&[ix], &[]).await
+    }
+
+    pub async fn update_position_with_orders(&mut self, market_index: u64) -> Result<(), BanksClientError> {
+        let address = self.clearing_house.order_state;
+        let order_state: OrderState = deserialize_account(&self.get_account_data(&address).await).unwrap();
+        let ix = self.vault().update_position_with_orders(
+            market_index,
+            &self.market_oracle(market_index),
+            &order_state.order_history,
+        );
+        self.process(&[ix], &[]).await
+    }
+
+    pub async fn settle_vault_funding_payment(&mut self) -> Result<(), BanksClientError> {
+        let ix = self.vault().settle_funding_payment();
+        self.process(&[ix], &[]).await
+    }
+
+    // ** oracles + clock
+    pub async fn set_oracle_price(&mut self, market_index: u64, price: f64) {
+        let ix = set_oracle_price_instruction(&self.market_oracle(market_index), price);
+        self.process(&[ix], &[]).await.unwrap();
+    }
+
+    // moves the clock forward by one slot + seconds
+    // (the mock oracle goes stale after slots_before_stale slots => warp slot by slot,
+    // identical transactions in the same slot are duplicates => warp between them)
+    pub async fn warp(&mut self, seconds: i64) {
+        let clock = self.get_clock().await;
+        let slot = clock.slot + 1;
+        self.context.warp_to_slot(slot).unwrap();
+        self.context.set_sysvar(&Clock {
+            slot,
+            unix_timestamp: clock.unix_timestamp + seconds,
+            ..clock
+        });
+    }
+
+    // to when update_funding_rate will next update the market's funding
+    pub async fn warp_to_next_funding(&mut self, market_index: u64) {
+        let markets = self.get_markets().await;
+        let next_funding_ts = calculate_next_funding_ts(&markets.get_market(market_index).amm).unwrap();
+        let now = self.get_clock().await.unix_timestamp;
+        self.warp((next_funding_ts - now).max(0)).await;
+    }
+
+    // ** accounts
+    pub async fn get_clock(&mut self) -> Clock {
+        self.context.banks_client.get_sysvar::<Clock>().await.unwrap()
+    }
+
+    pub async fn get_account_data(&mut self, address: &Pubkey) -> Vec<u8> {
+        get_account_data(&mut self.context, address).await
+    }
+
+    pub async fn get_state(&mut self) -> State {
+        let address = self.clearing_house.state;
+        deserialize_account(&self.get_account_data(&address).await).unwrap()
+    }
+
+    pub async fn get_markets(&mut self) -> Box<Markets> {
+        let address = self.clearing_house.markets;
+        Box::new(deserialize_zero_copy_account(&self.get_account_data(&address).await).unwrap())
+    }
+
+    pub async fn get_user(&mut self, address: &Pubkey) -> User {
+        deserialize_account(&self.get_account_data(address).await).unwrap()
+    }
+
+    // > 0 = long, < 0 = short, 0 = no position
+    pub async fn get_base_asset_amount(&mut self, user_positions: &Pubkey, market_index: u64) -> i128 {
+        let user_positions: UserPositions = deserialize_zero_copy_account(&self.get_account_data(user_positions).await).unwrap();
+        user_positions
+            .positions
+            .iter()
+            .find(|market_position| market_position.is_for(market_index))
+            .map_or(0, |market_position| market_position.base_asset_amount)
+    }
+
+    pub async fn get_token_balance(&mut self, address: &Pubkey) -> u64 {
+        let token_account: TokenAccount = deserialize_account(&self.get_account_data(address).await).unwrap();
+        token_account.amount
+    }
+
+    pub async fn get_vault_state(&mut self) -> VaultState {
+        let address = self.vault().pdas.vault_state.0;
+        deserialize_account(&self.get_account_data(&address).await).unwrap()
+    }
+
+    pub async fn get_depositor_state(&mut self, name: &str) -> DepositorState {
+        let address = self.vault().pdas.depositor(&self.depositors[name].owner.pubkey()).0;
+        deserialize_account(&self.get_account_data(&address).await).unwrap()
+    }
+
+    // vault tokens minted within the lockup
+    pub async fn get_locked_shares(&mut self, name: &str) -> u64 {
+        let locked_shares = self.vault().pdas.locked_shares(&self.depositors[name].owner.pubkey()).0;
+        self.get_token_balance(&locked_shares).await
+    }
+
+    // the vault collateral ATA (idle buffer)
+    pub async fn get_vault_idle_amount(&mut self) -> u64 {
+        let vault_collateral = self.vault().pdas.vault_collateral.0;
+        self.get_token_balance(&vault_collateral).await
+    }
+
+    pub async fn get_vault_user(&mut self) -> User {
+        let address = self.vault().pdas.user.0;
+        self.get_user(&address).await
+    }
+
+    pub async fn get_vault_base_asset_amount(&mut self, market_index: u64) -> i128 {
+        let address = self.vault().pdas.user_positions.0;
+        self.get_base_asset_amount(&address, market_index).await
+    }
+
+    // limit + trigger orders the vault has open on the clearing house
+    pub async fn get_vault_open_orders(&mut self) -> Vec<Order> {
+        let address = self.vault().pdas.user_orders.0;
+        let user_orders: UserOrders = deserialize_zero_copy_account(&self.get_account_data(&address).await).unwrap();
+        user_orders
+            .orders
+            .iter()
+            .filter(|order| order.status == OrderStatus::Open)
+            .copied()
+            .collect()
+    }
+
+    pub async fn get_vault_token_supply(&mut self) -> u64 {
+        let address = self.vault().pdas.vault_mint.0;
+        let vault_mint: Mint = deserialize_account(&self.get_account_data(&address).await).unwrap();
+        vault_mint.supply
+    }
+
+    // what withdraw prices the vault tokens with
+    pub async fn get_vault_nav(&mut self, market_index: u64) -> VaultNav {
+        let pdas = self.vault().pdas;
+        let clearing_house = self.clearing_house;
+        let clock = self.get_clock().await;
+        let vault_state = self.get_account_data(&pdas.vault_state.0).await;
+        let vault_mint = self.get_account_data(&pdas.vault_mint.0).await;
+        let vault_collateral = self.get_account_data(&pdas.vault_collateral.0).await;
+        let state = self.get_account_data(&clearing_house.state).await;
+        let user = self.get_account_data(&pdas.user.0).await;
+        let user_positions = self.get_account_data(&pdas.user_positions.0).await;
+        let markets = self.get_account_data(&clearing_house.markets).await;
+        let funding_rate_history = self.get_account_data(&clearing_house.funding_rate_history).await;
+        let mut oracles_data = vec![];
+        for oracle in self.market_oracles.values().copied().collect::<Vec<_>>() {
+            oracles_data.push((oracle, self.get_account_data(&oracle).await));
+        }
+        let oracles: Vec<(Pubkey, &[u8])> = oracles_data
+            .iter()
+            .map(|(oracle, data)| (*oracle, data.as_slice()))
+            .collect();
+        let accounts = VaultAccountsData {
+            vault_state: &vault_state,
+            vault_mint: &vault_mint,
+            vault_collateral: &vault_collateral,
+            state: &state,
+            user: &user,
+            user_positions: &user_positions,
+            markets: &markets,
+            oracles: &oracles,
+            funding_rate_history: Some(&funding_rate_history),
+        };
+        calculate_vault_nav(&accounts, market_index, clock.unix_timestamp, clock.slot).unwrap()
+    }
+}
@@ -0,0 +1,14 @@
+// in-memory clearing house (markets, mock pyth oracles, users) + vault for 
+// solana-program-test tests of the vault and of the clearing house itself 
+// (the rust equivalent of tests/testHelpers.ts)
+pub mod builder;
+pub mod exchange;
+pub mod scenario;
+pub mod setup;
+
+pub use builder::*;
+pub use exchange::*;
+pub use scenario::{load_scenario, scenario_paths, Scenario, ScenarioError};
+
+pub use clearing_house::controller::position::PositionDirection;
+pub use drift_vault::{StrategyKind, StrategyParams};
@@ -0,0 +1,50 @@
+use std::path::Path;
+use std::process::exit;
+
+use drift_vault_test_utils::{load_scenario, scenario_paths, ScenarioError};
+
+// drift-vault-scenario <scenario.json|dir>...
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() {
+        eprintln!("usage: drift-vault-scenario <scenario.json|dir>...");
+        exit(1);
+    }
+    let mut failed = 0;
+    for arg in args.iter() {
+        let paths = match expand(Path::new(arg)) {
+            Ok(paths) => paths,
+            Err(error) => {
+                eprintln!("{}: {}", arg, error);
+                exit(1);
+            }
+        };
+        for path in paths.iter() {
+            match run(path).await {
+                Ok(name) => println!("ok   {} ({})", path.display(), name),
+                Err(error) => {
+                    println!("FAIL {}: {}", path.display(), error);
+                    failed += 1;
+                }
+            }
+        }
+    }
+    if failed > 0 {
+        exit(1);
+    }
+}
+
+fn expand(path: &Path) -> Result<Vec<std::path::PathBuf>, ScenarioError> {
+    if path.is_dir() {
+        scenario_paths(path)
+    } else {
+        Ok(vec![path.to_path_buf()])
+    }
+}
+
+async fn run(path: &Path) -> Result<String, ScenarioError> {
+    let scenario = load_scenario(path)?;
+    scenario.run().await?;
+    Ok(scenario.name)
+}
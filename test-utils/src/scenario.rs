@@ -0,0 +1,630 @@
+// declarative end-to-end scenarios (json) run on a TestExchange: markets, users,
+// the vault + depositors, then steps (clock, oracle moves, other users' trades,
+// funding cranks, exchange pauses, vault admin, deposits / withdrawals) + expectations 
+// on nav, shares + positions
+// amounts are in usdc (1.5 = 1_500_000 QUOTE_PRECISION), vault shares too
+// (the first deposit mints them 1:1), prices in quote per base, fees + buffers in %
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use solana_program_test::BanksClientError;
+
+use clearing_house::controller::position::PositionDirection;
+use clearing_house::math::constants::QUOTE_PRECISION;
+
+use drift_vault::state::{BETA_PRECISION, MAX_SPREAD_MARKETS, SLIPPAGE_PRECISION};
+use drift_vault::{StrategyKind, StrategyParams};
+
+use crate::builder::{MarketConfig, DEFAULT_SQRT_K, ONE_HOUR};
+use crate::exchange::TestExchange;
+
+#[derive(Debug, Error)]
+pub enum ScenarioError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("invalid scenario: {0}")]
+    InvalidScenario(String),
+    // step is 1-based (the nth entry of steps)
+    #[error("step {step} ({action}): {message}")]
+    StepFailed { step: usize, action: String, message: String },
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub markets: Vec<ScenarioMarket>,
+    // clearing house users which trade against the amm (move the mark)
+    #[serde(default)]
+    pub users: Vec<ScenarioAccount>,
+    #[serde(default)]
+    pub vault: Option<ScenarioVault>,
+    // usdc = their usdc balance (nothing deposited in the vault yet)
+    #[serde(default)]
+    pub depositors: Vec<ScenarioAccount>,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioMarket {
+    pub market_index: u64,
+    pub oracle_price: f64,
+    #[serde(default = "default_sqrt_k")]
+    pub sqrt_k: u128,
+    #[serde(default = "default_funding_period")]
+    pub funding_period: i64,
+}
+
+fn default_sqrt_k() -> u128 {
+    DEFAULT_SQRT_K
+}
+
+fn default_funding_period() -> i64 {
+    ONE_HOUR
+}
+
+fn default_max_slippage() -> f64 {
+    1.
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioAccount {
+    pub name: String,
+    pub usdc: f64,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScenarioStrategy {
+    FundingTwap,
+    FundingSpread,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioVault {
+    pub strategy: ScenarioStrategy,
+    // FundingSpread only (at most MAX_SPREAD_MARKETS)
+    #[serde(default)]
+    pub spread_markets: Vec<SpreadMarket>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpreadMarket {
+    pub market_index: u64,
+    pub beta: f64,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Long,
+    Short,
+}
+
+// fails = true => the step's transaction is expected to be rejected
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Step {
+    // one slot + seconds
+    Warp { seconds: i64 },
+    WarpToNextFunding {
+        #[serde(default)]
+        market_index: u64,
+    },
+    SetOraclePrice {
+        #[serde(default)]
+        market_index: u64,
+        price: f64,
+    },
+    OpenPosition {
+        user: String,
+        #[serde(default)]
+        market_index: u64,
+        direction: Direction,
+        quote_amount: f64,
+        #[serde(default)]
+        fails: bool,
+    },
+    ClosePosition {
+        user: String,
+        #[serde(default)]
+        market_index: u64,
+        #[serde(default)]
+        fails: bool,
+    },
+    UpdateFundingRate {
+        #[serde(default)]
+        market_index: u64,
+        #[serde(default)]
+        fails: bool,
+    },
+    SettleFundingPayment {
+        #[serde(default)]
+        fails: bool,
+    },
+    UpdatePosition {
+        #[serde(default)]
+        market_index: u64,
+        #[serde(default)]
+        fails: bool,
+    },
+    // deploy = the market to deploy the deposit into (+ max slippage vs the oracle in %)
+    Deposit {
+        depositor: String,
+        amount: f64,
+        #[serde(default)]
+        deploy: Option<u64>,
+        #[serde(default = "default_max_slippage")]
+        max_slippage: f64,
+        #[serde(default)]
+        fails: bool,
+    },
+    Withdraw {
+        depositor: String,
+        shares: f64,
+        #[serde(default)]
+        market_index: u64,
+        #[serde(default)]
+        fails: bool,
+    },
+    // the shares minted within the lockup => the depositor's vault tokens
+    UnlockShares {
+        depositor: String,
+        #[serde(default)]
+        fails: bool,
+    },
+    // clearing house admin
+    PauseExchange { paused: bool },
+    // vault admin
+    UpdateIdleBuffer { percent: f64 },
+    UpdateLockup {
+        seconds: i64,
+        #[serde(default)]
+        early_withdrawal_fee: f64,
+    },
+    Expect(Expectation),
+}
+
+impl Step {
+    fn name(&self) -> &'static str {
+        match self {
+            Step::Warp { .. } => "warp",
+            Step::WarpToNextFunding { .. } => "warp_to_next_funding",
+            Step::SetOraclePrice { .. } => "set_oracle_price",
+            Step::OpenPosition { .. } => "open_position",
+            Step::ClosePosition { .. } => "close_position",
+            Step::UpdateFundingRate { .. } => "update_funding_rate",
+            Step::SettleFundingPayment { .. } => "settle_funding_payment",
+            Step::UpdatePosition { .. } => "update_position",
+            Step::Deposit { .. } => "deposit",
+            Step::Withdraw { .. } => "withdraw",
+            Step::UnlockShares { .. } => "unlock_shares",
+            Step::PauseExchange { .. } => "pause_exchange",
+            Step::UpdateIdleBuffer { .. } => "update_idle_buffer",
+            Step::UpdateLockup { .. } => "update_lockup",
+            Step::Expect(_) => "expect",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PositionSide {
+    Long,
+    Short,
+    Flat,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FundingSide {
+    // last_funding_rate > 0
+    LongsPay,
+    // last_funding_rate < 0
+    ShortsPay,
+    None,
+}
+
+// in usdc, any of eq / min / max (inclusive)
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Bounds {
+    pub eq: Option<f64>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+impl Bounds {
+    // amount in QUOTE_PRECISION
+    fn check(&self, what: &str, amount: u128) -> Result<(), String> {
+        if let Some(eq) = self.eq {
+            if amount != to_quote(eq) as u128 {
+                return Err(format!("{} = {}, expected {}", what, from_quote(amount), eq));
+            }
+        }
+        if let Some(min) = self.min {
+            if amount < to_quote(min) as u128 {
+                return Err(format!("{} = {}, expected >= {}", what, from_quote(amount), min));
+            }
+        }
+        if let Some(max) = self.max {
+            if amount > to_quote(max) as u128 {
+                return Err(format!("{} = {}, expected <= {}", what, from_quote(amount), max));
+            }
+        }
+        Ok(())
+    }
+}
+
+// checked after the steps before it (nav / positions are for market_index)
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Expectation {
+    pub market_index: u64,
+    pub vault_position: Option<PositionSide>,
+    // users' positions
+    pub positions: BTreeMap<String, PositionSide>,
+    pub funding: Option<FundingSide>,
+    pub nav: Option<Bounds>,
+    pub nav_per_share: Option<Bounds>,
+    // VaultState::total_amount_minted
+    pub total_shares: Option<Bounds>,
+    // depositors' vault token balances
+    pub shares: BTreeMap<String, Bounds>,
+    // depositors' shares held until the lockup ends
+    pub locked_shares: BTreeMap<String, Bounds>,
+    // the vault collateral ATA
+    pub idle: Option<Bounds>,
+    // depositors' usdc balances
+    pub usdc: BTreeMap<String, Bounds>,
+}
+
+fn to_quote(amount: f64) -> u64 {
+    (amount * QUOTE_PRECISION as f64).round() as u64
+}
+
+// % => SLIPPAGE_PRECISION
+fn to_slippage(percent: f64) -> u128 {
+    (percent / 100. * SLIPPAGE_PRECISION as f64).round() as u128
+}
+
+// % => numerator / PERCENT_DENOMINATOR
+const PERCENT_DENOMINATOR: u128 = 10_000;
+
+fn to_numerator(percent: f64) -> u128 {
+    (percent / 100. * PERCENT_DENOMINATOR as f64).round() as u128
+}
+
+fn from_quote(amount: u128) -> f64 {
+    amount as f64 / QUOTE_PRECISION as f64
+}
+
+pub fn load_scenario(path: &Path) -> Result<Scenario, ScenarioError> {
+    let scenario: Scenario = serde_json::from_reader(File::open(path)?)?;
+    scenario.validate()?;
+    Ok(scenario)
+}
+
+// the *.json files in dir (sorted => runs in a stable order)
+pub fn scenario_paths(dir: &Path) -> Result<Vec<PathBuf>, ScenarioError> {
+    let mut paths = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().map_or(false, |extension| extension == "json") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+impl Scenario {
+    // unknown names / markets would panic in TestExchange => reject them up front
+    pub fn validate(&self) -> Result<(), ScenarioError> {
+        let invalid = |message: String| Err(ScenarioError::InvalidScenario(message));
+        let has_market = |market_index: &u64| self.markets.iter().any(|market| market.market_index == *market_index);
+        let has_user = |name: &String| self.users.iter().any(|user| user.name == *name);
+        let has_depositor = |name: &String| self.depositors.iter().any(|depositor| depositor.name == *name);
+
+        if self.markets.is_empty() {
+            return invalid("no markets".to_string());
+        }
+        if !self.depositors.is_empty() && self.vault.is_none() {
+            return invalid("depositors need a vault".to_string());
+        }
+        if let Some(vault) = &self.vault {
+            if vault.spread_markets.len() > MAX_SPREAD_MARKETS {
+                return invalid(format!("at most {} spread markets", MAX_SPREAD_MARKETS));
+            }
+            if let Some(spread_market) = vault.spread_markets.iter().find(|spread_market| !has_market(&spread_market.market_index)) {
+                return invalid(format!("unknown spread market {}", spread_market.market_index));
+            }
+        }
+
+        for (i, step) in self.steps.iter().enumerate() {
+            let (market_index, user, depositor) = match step {
+                Step::Warp { .. }
+                | Step::SettleFundingPayment { .. }
+                | Step::PauseExchange { .. }
+                | Step::UpdateIdleBuffer { .. }
+                | Step::UpdateLockup { .. } => (None, None, None),
+                Step::WarpToNextFunding { market_index }
+                | Step::SetOraclePrice { market_index, .. }
+                | Step::UpdateFundingRate { market_index, .. }
+                | Step::UpdatePosition { market_index, .. } => (Some(market_index), None, None),
+                Step::OpenPosition { user, market_index, .. } | Step::ClosePosition { user, market_index, .. } => {
+                    (Some(market_index), Some(user), None)
+                }
+                Step::Deposit { depositor, deploy, .. } => (deploy.as_ref(), None, Some(depositor)),
+                Step::Withdraw { depositor, market_index, .. } => (Some(market_index), None, Some(depositor)),
+                Step::UnlockShares { depositor, .. } => (None, None, Some(depositor)),
+                Step::Expect(expectation) => {
+                    if let Some(name) = expectation.positions.keys().find(|name| !has_user(name)) {
+                        return invalid(format!("step {}: unknown user {}", i + 1, name));
+                    }
+                    let mut depositors = expectation.shares.keys()
+                        .chain(expectation.locked_shares.keys())
+                        .chain(expectation.usdc.keys());
+                    if let Some(name) = depositors.find(|name| !has_depositor(name)) {
+                        return invalid(format!("step {}: unknown depositor {}", i + 1, name));
+                    }
+                    (Some(&expectation.market_index), None, None)
+                }
+            };
+            let needs_vault = matches!(
+                step,
+                Step::SettleFundingPayment { .. }
+                    | Step::UpdatePosition { .. }
+                    | Step::Deposit { .. }
+                    | Step::Withdraw { .. }
+                    | Step::UnlockShares { .. }
+                    | Step::UpdateIdleBuffer { .. }
+                    | Step::UpdateLockup { .. }
+            ) || matches!(
+                step,
+                Step::Expect(expectation) if expectation.vault_position.is_some()
+                    || expectation.nav.is_some()
+                    || expectation.nav_per_share.is_some()
+                    || expectation.total_shares.is_some()
+                    || expectation.idle.is_some()
+            );
+            if needs_vault && self.vault.is_none() {
+                return invalid(format!("step {}: {} needs a vault", i + 1, step.name()));
+            }
+            if let Some(market_index) = market_index.filter(|market_index| !has_market(market_index)) {
+                return invalid(format!("step {}: unknown market {}", i + 1, market_index));
+            }
+            if let Some(user) = user.filter(|user| !has_user(user)) {
+                return invalid(format!("step {}: unknown user {}", i + 1, user));
+            }
+            if let Some(depositor) = depositor.filter(|depositor| !has_depositor(depositor)) {
+                return invalid(format!("step {}: unknown depositor {}", i + 1, depositor));
+            }
+        }
+        Ok(())
+    }
+
+    fn strategy(&self) -> Option<(StrategyKind, StrategyParams)> {
+        self.vault.as_ref().map(|vault| {
+            let strategy = match vault.strategy {
+                ScenarioStrategy::FundingTwap => StrategyKind::FundingTwap,
+                ScenarioStrategy::FundingSpread => StrategyKind::FundingSpread,
+            };
+            let mut strategy_params = StrategyParams::default();
+            for (i, spread_market) in vault.spread_markets.iter().enumerate() {
+                strategy_params.spread_market_indexes[i] = spread_market.market_index;
+                strategy_params.spread_market_betas[i] = (spread_market.beta * BETA_PRECISION as f64).round() as u128;
+            }
+            (strategy, strategy_params)
+        })
+    }
+
+    pub async fn build(&self) -> TestExchange {
+        let mut builder = TestExchange::builder();
+        for market in self.markets.iter() {
+            builder = builder.market_with_config(market.market_index, MarketConfig {
+                oracle_price: market.oracle_price,
+                sqrt_k: market.sqrt_k,
+                funding_period: market.funding_period,
+                ..MarketConfig::default()
+            });
+        }
+        for user in self.users.iter() {
+            builder = builder.user(&user.name, to_quote(user.usdc));
+        }
+        if let Some((strategy, strategy_params)) = self.strategy() {
+            builder = builder.vault(strategy, strategy_params);
+        }
+        for depositor in self.depositors.iter() {
+            builder = builder.depositor(&depositor.name, to_quote(depositor.usdc));
+        }
+        builder.build().await
+    }
+
+    // runs the steps in order + stops at the first one which doesnt go as expected
+    pub async fn run(&self) -> Result<(), ScenarioError> {
+        self.validate()?;
+        let mut exchange = self.build().await;
+        for (i, step) in self.steps.iter().enumerate() {
+            let step_failed = |message: String| ScenarioError::StepFailed {
+                step: i + 1,
+                action: step.name().to_string(),
+                message,
+            };
+            run_step(&mut exchange, step).await.map_err(step_failed)?;
+            if exchange.vault.is_some() {
+                check_share_supply(&mut exchange).await.map_err(step_failed)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn run_step(exchange: &mut TestExchange, step: &Step) -> Result<(), String> {
+    let (result, fails) = match step {
+        Step::Warp { seconds } => {
+            exchange.warp(*seconds).await;
+            return Ok(());
+        }
+        Step::WarpToNextFunding { market_index } => {
+            exchange.warp_to_next_funding(*market_index).await;
+            return Ok(());
+        }
+        Step::SetOraclePrice { market_index, price } => {
+            exchange.set_oracle_price(*market_index, *price).await;
+            return Ok(());
+        }
+        Step::OpenPosition { user, market_index, direction, quote_amount, fails } => {
+            let direction = match direction {
+                Direction::Long => PositionDirection::Long,
+                Direction::Short => PositionDirection::Short,
+            };
+            let result = exchange.open_position(user, *market_index, direction, to_quote(*quote_amount)).await;
+            (result, *fails)
+        }
+        Step::ClosePosition { user, market_index, fails } => {
+            (exchange.close_position(user, *market_index).await, *fails)
+        }
+        Step::UpdateFundingRate { market_index, fails } => {
+            (exchange.update_funding_rate(*market_index).await, *fails)
+        }
+        Step::SettleFundingPayment { fails } => (exchange.settle_vault_funding_payment().await, *fails),
+        Step::UpdatePosition { market_index, fails } => (exchange.update_position(*market_index).await, *fails),
+        Step::Deposit { depositor, amount, deploy, max_slippage, fails } => {
+            let deploy = deploy.map(|market_index| (market_index, to_slippage(*max_slippage)));
+            (exchange.deposit_with_deploy(depositor, to_quote(*amount), deploy).await, *fails)
+        }
+        Step::Withdraw { depositor, shares, market_index, fails } => {
+            (exchange.withdraw(depositor, to_quote(*shares), *market_index).await, *fails)
+        }
+        Step::UnlockShares { depositor, fails } => (exchange.unlock_shares(depositor).await, *fails),
+        Step::PauseExchange { paused } => (exchange.update_exchange_paused(*paused).await, false),
+        Step::UpdateIdleBuffer { percent } => {
+            let admin = exchange.admin();
+            let ix = exchange.vault().update_idle_buffer(&admin, to_numerator(*percent), PERCENT_DENOMINATOR);
+            (exchange.process(&[ix], &[]).await, false)
+        }
+        Step::UpdateLockup { seconds, early_withdrawal_fee } => {
+            let admin = exchange.admin();
+            let ix = exchange.vault().update_lockup(
+                &admin,
+                *seconds,
+                to_numerator(*early_withdrawal_fee),
+                PERCENT_DENOMINATOR,
+            );
+            (exchange.process(&[ix], &[]).await, false)
+        }
+        Step::Expect(expectation) => return check_expectation(exchange, expectation).await,
+    };
+    check_result(result, fails)
+}
+
+fn check_result(result: Result<(), BanksClientError>, fails: bool) -> Result<(), String> {
+    match (result, fails) {
+        (Ok(()), false) | (Err(_), true) => Ok(()),
+        (Ok(()), true) => Err("succeeded, expected it to fail".to_string()),
+        (Err(error), false) => Err(format!("transaction failed: {}", error)),
+    }
+}
+
+fn position_side(base_asset_amount: i128) -> PositionSide {
+    match base_asset_amount {
+        amount if amount > 0 => PositionSide::Long,
+        amount if amount < 0 => PositionSide::Short,
+        _ => PositionSide::Flat,
+    }
+}
+
+async fn check_expectation(exchange: &mut TestExchange, expectation: &Expectation) -> Result<(), String> {
+    let market_index = expectation.market_index;
+
+    if let Some(expected) = expectation.vault_position {
+        let side = position_side(exchange.get_vault_base_asset_amount(market_index).await);
+        if side != expected {
+            return Err(format!("vault position is {:?}, expected {:?}", side, expected));
+        }
+    }
+    for (name, expected) in expectation.positions.iter() {
+        let user_positions = exchange.user(name).user_positions;
+        let side = position_side(exchange.get_base_asset_amount(&user_positions, market_index).await);
+        if side != *expected {
+            return Err(format!("{}'s position is {:?}, expected {:?}", name, side, expected));
+        }
+    }
+    if let Some(expected) = expectation.funding {
+        let last_funding_rate = exchange.get_markets().await.get_market(market_index).amm.last_funding_rate;
+        let side = match last_funding_rate {
+            rate if rate > 0 => FundingSide::LongsPay,
+            rate if rate < 0 => FundingSide::ShortsPay,
+            _ => FundingSide::None,
+        };
+        if side != expected {
+            return Err(format!("funding is {:?}, expected {:?}", side, expected));
+        }
+    }
+
+    if expectation.nav.is_some() || expectation.nav_per_share.is_some() {
+        let nav = exchange.get_vault_nav(market_index).await;
+        if let Some(bounds) = expectation.nav {
+            bounds.check("nav", nav.nav)?;
+        }
+        if let Some(bounds) = expectation.nav_per_share {
+            bounds.check("nav_per_share", nav.nav_per_share)?;
+        }
+    }
+    if let Some(bounds) = expectation.total_shares {
+        let total_amount_minted = exchange.get_vault_state().await.total_amount_minted;
+        bounds.check("total_shares", total_amount_minted as u128)?;
+    }
+    for (name, bounds) in expectation.shares.iter() {
+        let vault_tokens = exchange.depositor(name).vault_tokens;
+        let balance = exchange.get_token_balance(&vault_tokens).await;
+        bounds.check(&format!("{}'s shares", name), balance as u128)?;
+    }
+    for (name, bounds) in expectation.locked_shares.iter() {
+        let balance = exchange.get_locked_shares(name).await;
+        bounds.check(&format!("{}'s locked shares", name), balance as u128)?;
+    }
+    if let Some(bounds) = expectation.idle {
+        let idle_amount = exchange.get_vault_idle_amount().await;
+        bounds.check("idle", idle_amount as u128)?;
+    }
+    for (name, bounds) in expectation.usdc.iter() {
+        let usdc = exchange.depositor(name).usdc;
+        let balance = exchange.get_token_balance(&usdc).await;
+        bounds.check(&format!("{}'s usdc", name), balance as u128)?;
+    }
+    Ok(())
+}
+
+// after every step: the vault's share count = the vault mint's supply = what the depositors hold
+// (incl. their locked shares)
+async fn check_share_supply(exchange: &mut TestExchange) -> Result<(), String> {
+    let total_amount_minted = exchange.get_vault_state().await.total_amount_minted;
+    let supply = exchange.get_vault_token_supply().await;
+    let names: Vec<_> = exchange.depositors.keys().cloned().collect();
+    let mut held = 0;
+    for name in names.iter() {
+        let vault_tokens = exchange.depositor(name).vault_tokens;
+        held += exchange.get_token_balance(&vault_tokens).await + exchange.get_locked_shares(name).await;
+    }
+    if total_amount_minted != supply || supply != held {
+        return Err(format!(
+            "share supply out of sync: total_amount_minted {}, mint supply {}, held by depositors {}",
+            total_amount_minted, supply, held
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,260 @@
+use std::mem::size_of;
+
+use anchor_lang::prelude::{AccountMeta, Pubkey};
+use anchor_lang::solana_program::{
+    instruction::Instruction, program_pack::Pack, system_instruction, system_program, sysvar,
+};
+use anchor_lang::{InstructionData, ToAccountMetas};
+
+use solana_program_test::{BanksClientError, ProgramTestContext};
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::transaction::Transaction;
+
+use clearing_house::math::constants::{MARK_PRICE_PRECISION, PEG_PRECISION};
+use clearing_house::state::history::curve::ExtendedCurveHistory;
+use clearing_house::state::history::deposit::DepositHistory;
+use clearing_house::state::history::funding_payment::FundingPaymentHistory;
+use clearing_house::state::history::funding_rate::FundingRateHistory;
+use clearing_house::state::history::liquidation::LiquidationHistory;
+use clearing_house::state::history::order_history::OrderHistory;
+use clearing_house::state::history::trade::TradeHistory;
+use clearing_house::state::market::{Markets, OracleSource};
+use clearing_house::state::state::State;
+
+use drift_vault_client::{
+    deserialize_account, get_clearing_house_state_address, get_order_state_address, ClearingHouseAccounts,
+};
+
+use crate::builder::MarketConfig;
+
+pub const ORACLE_EXPO: i32 = -6;
+const PYTH_PRICE_ACCOUNT_SIZE: usize = 3312;
+// sol for the rent of the accounts a user inits
+const USER_LAMPORTS: u64 = 1_000_000_000;
+
+// mock pyth price (ORACLE_EXPO decimals)
+pub fn to_oracle_price(price: f64) -> i64 {
+    (price * 10_f64.powi(-ORACLE_EXPO)).round() as i64
+}
+
+// ** clearing house
+pub async fn initialize_clearing_house(
+    context: &mut ProgramTestContext,
+    collateral_mint: &Pubkey,
+) -> ClearingHouseAccounts {
+    let admin = context.payer.pubkey();
+    let program_id = clearing_house::ID;
+    let (state, clearing_house_nonce) = get_clearing_house_state_address(&program_id);
+    let (collateral_vault, collateral_vault_nonce) = Pubkey::find_program_address(&[b"collateral_vault"], &program_id);
+    let (collateral_vault_authority, _) = Pubkey::find_program_address(&[collateral_vault.as_ref()], &program_id);
+    let (insurance_vault, insurance_vault_nonce) = Pubkey::find_program_address(&[b"insurance_vault"], &program_id);
+    let (insurance_vault_authority, _) = Pubkey::find_program_address(&[insurance_vault.as_ref()], &program_id);
+
+    let markets = create_zero_account(context, size_of::<Markets>()).await;
+    let accounts = clearing_house::accounts::Initialize {
+        admin,
+        state,
+        collateral_mint: *collateral_mint,
+        collateral_vault,
+        collateral_vault_authority,
+        insurance_vault,
+        insurance_vault_authority,
+        markets,
+        rent: sysvar::rent::ID,
+        system_program: system_program::ID,
+        token_program: spl_token::ID,
+    };
+    let data = clearing_house::instruction::Initialize {
+        _clearing_house_nonce: clearing_house_nonce,
+        _collateral_vault_nonce: collateral_vault_nonce,
+        _insurance_vault_nonce: insurance_vault_nonce,
+        admin_controls_prices: true,
+    };
+    let ix = clearing_house_instruction(accounts.to_account_metas(None), data);
+    process(context, &[ix], &[]).await.unwrap();
+
+    // histories are big => pre-allocated (zero) accounts
+    let accounts = clearing_house::accounts::InitializeHistory {
+        admin,
+        state,
+        funding_payment_history: create_zero_account(context, size_of::<FundingPaymentHistory>()).await,
+        trade_history: create_zero_account(context, size_of::<TradeHistory>()).await,
+        liquidation_history: create_zero_account(context, size_of::<LiquidationHistory>()).await,
+        deposit_history: create_zero_account(context, size_of::<DepositHistory>()).await,
+        funding_rate_history: create_zero_account(context, size_of::<FundingRateHistory>()).await,
+        curve_history: create_zero_account(context, size_of::<ExtendedCurveHistory>()).await,
+    };
+    let ix = clearing_house_instruction(accounts.to_account_metas(None), clearing_house::instruction::InitializeHistory {});
+    process(context, &[ix], &[]).await.unwrap();
+
+    let (order_state, order_house_nonce) = get_order_state_address(&program_id);
+    let accounts = clearing_house::accounts::InitializeOrderState {
+        admin,
+        state,
+        order_state,
+        order_history: create_zero_account(context, size_of::<OrderHistory>()).await,
+        rent: sysvar::rent::ID,
+        system_program: system_program::ID,
+    };
+    let data = clearing_house::instruction::InitializeOrderState { _order_house_nonce: order_house_nonce };
+    let ix = clearing_house_instruction(accounts.to_account_metas(None), data);
+    process(context, &[ix], &[]).await.unwrap();
+
+    let state_data: State = deserialize_account(&get_account_data(context, &state).await).unwrap();
+    ClearingHouseAccounts::from_state(state, &state_data)
+}
+
+// base = quote reserves + peg = oracle price => the mark starts at the oracle price
+pub async fn initialize_market(
+    context: &mut ProgramTestContext,
+    clearing_house: &ClearingHouseAccounts,
+    market_index: u64,
+    oracle: &Pubkey,
+    config: &MarketConfig,
+) {
+    let reserves = config.sqrt_k * MARK_PRICE_PRECISION;
+    let accounts = clearing_house::accounts::InitializeMarket {
+        admin: context.payer.pubkey(),
+        state: clearing_house.state,
+        markets: clearing_house.markets,
+        oracle: *oracle,
+    };
+    let data = clearing_house::instruction::InitializeMarket {
+        market_index,
+        amm_base_asset_reserve: reserves,
+        amm_quote_asset_reserve: reserves,
+        amm_periodicity: config.funding_period,
+        amm_peg_multiplier: (config.oracle_price * PEG_PRECISION as f64).round() as u128,
+        oracle_source: OracleSource::Pyth,
+        margin_ratio_initial: config.margin_ratio_initial,
+        margin_ratio_partial: config.margin_ratio_partial,
+        margin_ratio_maintenance: config.margin_ratio_maintenance,
+    };
+    let ix = clearing_house_instruction(accounts.to_account_metas(None), data);
+    process(context, &[ix], &[]).await.unwrap();
+}
+
+pub fn clearing_house_instruction(accounts: Vec<AccountMeta>, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: clearing_house::ID,
+        accounts,
+        data: data.data(),
+    }
+}
+
+// clearing house #[account(zero)] account (discriminator + size)
+async fn create_zero_account(context: &mut ProgramTestContext, size: usize) -> Pubkey {
+    let account = Keypair::new();
+    create_account(context, &account, 8 + size, &clearing_house::ID).await;
+    account.pubkey()
+}
+
+// ** mock pyth
+pub async fn initialize_oracle(context: &mut ProgramTestContext, price: f64) -> Pubkey {
+    let oracle = Keypair::new();
+    create_account(context, &oracle, PYTH_PRICE_ACCOUNT_SIZE, &pyth::ID).await;
+    let accounts = pyth::accounts::Initialize { price: oracle.pubkey() };
+    let data = pyth::instruction::Initialize {
+        price: to_oracle_price(price),
+        expo: ORACLE_EXPO,
+        _conf: 0,
+    };
+    let ix = Instruction {
+        program_id: pyth::ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    };
+    process(context, &[ix], &[]).await.unwrap();
+    oracle.pubkey()
+}
+
+pub fn set_oracle_price_instruction(oracle: &Pubkey, price: f64) -> Instruction {
+    let accounts = pyth::accounts::SetPrice { price: *oracle };
+    Instruction {
+        program_id: pyth::ID,
+        accounts: accounts.to_account_metas(None),
+        data: pyth::instruction::SetPrice { price: to_oracle_price(price) }.data(),
+    }
+}
+
+// ** transactions + accounts (the payer signs + pays for everything)
+pub async fn process(
+    context: &mut ProgramTestContext,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), BanksClientError> {
+    let mut all_signers = vec![&context.payer];
+    all_signers.extend_from_slice(signers);
+    let blockhash = context.banks_client.get_latest_blockhash().await?;
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&context.payer.pubkey()),
+        &all_signers,
+        blockhash,
+    );
+    context.banks_client.process_transaction(transaction).await
+}
+
+pub async fn get_account_data(context: &mut ProgramTestContext, address: &Pubkey) -> Vec<u8> {
+    context.banks_client
+        .get_account(*address)
+        .await
+        .unwrap()
+        .unwrap_or_else(|| panic!("account {} not found", address))
+        .data
+}
+
+pub async fn create_account(
+    context: &mut ProgramTestContext,
+    account: &Keypair,
+    space: usize,
+    owner: &Pubkey,
+) {
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let ix = system_instruction::create_account(
+        &context.payer.pubkey(),
+        &account.pubkey(),
+        rent.minimum_balance(space),
+        space as u64,
+        owner,
+    );
+    process(context, &[ix], &[account]).await.unwrap();
+}
+
+pub async fn fund(context: &mut ProgramTestContext, address: &Pubkey) {
+    let ix = system_instruction::transfer(&context.payer.pubkey(), address, USER_LAMPORTS);
+    process(context, &[ix], &[]).await.unwrap();
+}
+
+// ** spl token (the payer is the mint authority)
+pub async fn create_mint(context: &mut ProgramTestContext, mint: &Keypair, decimals: u8) {
+    create_account(context, mint, spl_token::state::Mint::LEN, &spl_token::ID).await;
+    let ix = spl_token::instruction::initialize_mint(
+        &spl_token::ID,
+        &mint.pubkey(),
+        &context.payer.pubkey(),
+        None,
+        decimals,
+    ).unwrap();
+    process(context, &[ix], &[]).await.unwrap();
+}
+
+pub async fn create_token_account(context: &mut ProgramTestContext, mint: &Pubkey, owner: &Pubkey) -> Pubkey {
+    let account = Keypair::new();
+    create_account(context, &account, spl_token::state::Account::LEN, &spl_token::ID).await;
+    let ix = spl_token::instruction::initialize_account(&spl_token::ID, &account.pubkey(), mint, owner).unwrap();
+    process(context, &[ix], &[]).await.unwrap();
+    account.pubkey()
+}
+
+pub async fn mint_to(context: &mut ProgramTestContext, mint: &Pubkey, account: &Pubkey, amount: u64) {
+    let ix = spl_token::instruction::mint_to(
+        &spl_token::ID,
+        mint,
+        account,
+        &context.payer.pubkey(),
+        &[],
+        amount,
+    ).unwrap();
+    process(context, &[ix], &[]).await.unwrap();
+}
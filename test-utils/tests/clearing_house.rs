@@ -0,0 +1,56 @@
+// the fixture on its own: clearing house tests w/o the vault
+use clearing_house::math::constants::MARK_PRICE_PRECISION;
+
+use drift_vault_test_utils::{usdc, PositionDirection, TestExchange, DEFAULT_SQRT_K, ONE_HOUR};
+
+const SOL: u64 = 0;
+const BTC: u64 = 1;
+
+#[tokio::test]
+async fn builds_markets_at_their_oracle_prices() {
+    let mut exchange = TestExchange::builder()
+        .market(SOL, 100.0, DEFAULT_SQRT_K, ONE_HOUR)
+        .market(BTC, 40_000.0, DEFAULT_SQRT_K, ONE_HOUR)
+        .oracle("backup", 1.0)
+        .user("alice", usdc(1_000))
+        .build()
+        .await;
+
+    let markets = exchange.get_markets().await;
+    let sol_amm = markets.get_market(SOL).amm;
+    let btc_amm = markets.get_market(BTC).amm;
+    assert_eq!(sol_amm.mark_price().unwrap(), 100 * MARK_PRICE_PRECISION);
+    assert_eq!(btc_amm.mark_price().unwrap(), 40_000 * MARK_PRICE_PRECISION);
+    let sol_oracle = sol_amm.oracle;
+    assert_eq!(sol_oracle, exchange.market_oracle(SOL));
+    assert_ne!(exchange.oracle("backup"), exchange.market_oracle(SOL));
+
+    let alice = exchange.user("alice").user;
+    assert_eq!(exchange.get_user(&alice).await.collateral, usdc(1_000) as u128);
+    assert!(exchange.vault.is_none());
+}
+
+#[tokio::test]
+async fn longs_pay_shorts_when_mark_is_above_oracle() {
+    let mut exchange = TestExchange::builder()
+        .market(SOL, 1.0, DEFAULT_SQRT_K, ONE_HOUR)
+        .user("long", usdc(10_000))
+        .user("short", usdc(10_000))
+        .build()
+        .await;
+
+    // net long => mark > oracle
+    exchange.open_position("long", SOL, PositionDirection::Long, usdc(3_000)).await.unwrap();
+    exchange.open_position("short", SOL, PositionDirection::Short, usdc(1_000)).await.unwrap();
+    let long_positions = exchange.user("long").user_positions;
+    assert!(exchange.get_base_asset_amount(&long_positions, SOL).await > 0);
+
+    exchange.warp_to_next_funding(SOL).await;
+    exchange.update_funding_rate(SOL).await.unwrap();
+    let amm = exchange.get_markets().await.get_market(SOL).amm;
+    assert!(amm.last_funding_rate > 0);
+    assert!(amm.cumulative_funding_rate_long > 0);
+
+    exchange.close_position("long", SOL).await.unwrap();
+    assert_eq!(exchange.get_base_asset_amount(&long_positions, SOL).await, 0);
+}
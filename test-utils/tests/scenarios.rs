@@ -0,0 +1,23 @@
+// runs every scenarios/*.json (add a file there to add a case, see the README)
+use std::path::Path;
+
+use drift_vault_test_utils::{load_scenario, scenario_paths};
+
+#[tokio::test]
+async fn runs_every_scenario() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenarios");
+    let paths = scenario_paths(&dir).unwrap();
+    assert!(!paths.is_empty(), "no scenarios in {}", dir.display());
+
+    let mut failures = vec![];
+    for path in paths.iter() {
+        let result = match load_scenario(path) {
+            Ok(scenario) => scenario.run().await,
+            Err(error) => Err(error),
+        };
+        if let Err(error) = result {
+            failures.push(format!("{}: {}", path.display(), error));
+        }
+    }
+    assert!(failures.is_empty(), "failed scenarios:\n{}", failures.join("\n"));
+}
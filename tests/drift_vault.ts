@@ -113,6 +113,9 @@ describe('drift_vault', () => {
   let vault_collateral; let vault_collateral_b;
   let authority; let authority_b;
   let user_positions; let user_positions_b;
+  let user_orders; let user_orders_b;
+  let depositor_state;
+  let locked_shares;
   let user_account; let user_account_b;
   let clearingHouseStatePk;
   let clearingHouseState;
@@ -145,6 +148,10 @@ describe('drift_vault', () => {
         CH_program.programId,
         authority,
     );
+    [user_orders, user_orders_b] = await drift.getUserOrdersAccountPublicKeyAndNonce(
+        CH_program.programId,
+        user_account,
+    );
     clearingHouseStatePk = await clearingHouse.getStatePublicKey();
     clearingHouseState = clearingHouse.getStateAccount();
 
@@ -152,6 +159,12 @@ describe('drift_vault', () => {
         user_account_b,
         authority_b,
         user_positions_b,
+        user_orders_b,
+        {fundingTwap: {}},
+        {
+          spreadMarketIndexes: [drift.ZERO, drift.ZERO, drift.ZERO, drift.ZERO],
+          spreadMarketBetas: [drift.ZERO, drift.ZERO, drift.ZERO, drift.ZERO],
+        },
         {
           accounts: {
             payer: provider.wallet.publicKey,
@@ -160,6 +173,7 @@ describe('drift_vault', () => {
             state: clearingHouseStatePk,
             user: user_account,
             userPositions: user_positions,
+            userOrders: user_orders,
 
             vaultMint: vault_mint,
             vaultState: vault_state,
@@ -174,6 +188,27 @@ describe('drift_vault', () => {
           },
         },
     );
+
+    [depositor_state] = await web3.PublicKey.findProgramAddress(
+        [Buffer.from('depositor'), provider.wallet.publicKey.toBuffer()],
+        vault_program.programId,
+    );
+    [locked_shares] = await web3.PublicKey.findProgramAddress(
+        [Buffer.from('locked_shares'), provider.wallet.publicKey.toBuffer()],
+        vault_program.programId,
+    );
+    await vault_program.rpc.initializeDepositor({
+      accounts: {
+        owner: provider.wallet.publicKey,
+        depositorState: depositor_state,
+        lockedShares: locked_shares,
+        vaultMint: vault_mint,
+        authority: authority,
+        rent: web3.SYSVAR_RENT_PUBKEY,
+        systemProgram: web3.SystemProgram.programId,
+        tokenProgram: token.TOKEN_PROGRAM_ID,
+      },
+    });
   });
 
   // helper fcn
@@ -208,15 +243,18 @@ describe('drift_vault', () => {
     const deposit_ix = await vault_program.instruction.deposit(
         depositAmount,
         authority_b,
+        {whitelistToken: false, deploy: null},
         {
           accounts: {
             owner: provider.wallet.publicKey,
             userVaultAta: user_vault_ata,
+            lockedShares: locked_shares,
             userCollateralAta: userUSDCAccount.publicKey,
             vaultCollateralAta: vault_collateral,
 
             vaultMint: vault_mint,
             vaultState: vault_state,
+            depositorState: depositor_state,
 
             authority: authority,
             userPositions: user_positions,
@@ -255,6 +293,167 @@ describe('drift_vault', () => {
     assert(userAccount_start.collateral.lt(userAccount.collateral));
   });
 
+  function deposit_accounts() {
+    return {
+      owner: provider.wallet.publicKey,
+      userVaultAta: user_vault_ata,
+      lockedShares: locked_shares,
+      userCollateralAta: userUSDCAccount.publicKey,
+      vaultCollateralAta: vault_collateral,
+
+      vaultMint: vault_mint,
+      vaultState: vault_state,
+      depositorState: depositor_state,
+
+      authority: authority,
+      userPositions: user_positions,
+      user: user_account,
+
+      state: clearingHouseStatePk,
+      collateralVault: clearingHouseState.collateralVault,
+      markets: clearingHouseState.markets,
+      fundingPaymentHistory: clearingHouseState.fundingPaymentHistory,
+      depositHistory: clearingHouseState.depositHistory,
+
+      clearingHouseProgram: CH_program.programId,
+      tokenProgram: token.TOKEN_PROGRAM_ID,
+    };
+  }
+
+  function update_position_accounts(oracle: web3.PublicKey) {
+    return {
+      authority: authority,
+      userPositions: user_positions,
+      vaultState: vault_state,
+      vaultCollateralAta: vault_collateral,
+
+      state: clearingHouseStatePk,
+      user: user_account,
+      markets: clearingHouseState.markets,
+      tradeHistory: clearingHouseState.tradeHistory,
+      fundingPaymentHistory: clearingHouseState.fundingPaymentHistory,
+      fundingRateHistory: clearingHouseState.fundingRateHistory,
+      oracle: oracle,
+
+      collateralVault: clearingHouseState.collateralVault,
+      collateralVaultAuthority: clearingHouseState.collateralVaultAuthority,
+      depositHistory: clearingHouseState.depositHistory,
+      insuranceVault: clearingHouseState.insuranceVault,
+      insuranceVaultAuthority: clearingHouseState.insuranceVaultAuthority,
+
+      clearingHouseProgram: CH_program.programId,
+      tokenProgram: token.TOKEN_PROGRAM_ID,
+    };
+  }
+
+  it('rejects deposits over the depositor cap', async () => {
+    const user_vault_balance = await get_token_balance(user_vault_ata);
+    const depositor = await vault_program.account.depositorState.fetch(depositor_state);
+
+    // cap depositor at what they deposited so far
+    await vault_program.rpc.updateDepositCaps(
+        drift.ZERO,
+        depositor.depositedAmount,
+        drift.ZERO,
+        {
+          accounts: {
+            admin: provider.wallet.publicKey,
+            vaultState: vault_state,
+          },
+        },
+    );
+
+    const ix = vault_program.instruction.deposit(
+        new BN(1 * 10 ** 6),
+        authority_b,
+        {whitelistToken: false, deploy: null},
+        {accounts: deposit_accounts()},
+    );
+
+    let failed = false;
+    try {
+      await provider.send(new web3.Transaction().add(ix));
+    } catch (e) {
+      failed = true;
+    }
+    assert(failed);
+
+    const user_vault_balance_end = await get_token_balance(user_vault_ata);
+    assert(user_vault_balance_end.eq(user_vault_balance));
+
+    // remove caps
+    await vault_program.rpc.updateDepositCaps(
+        drift.ZERO,
+        drift.ZERO,
+        drift.ZERO,
+        {
+          accounts: {
+            admin: provider.wallet.publicKey,
+            vaultState: vault_state,
+          },
+        },
+    );
+  });
+
+  it('rejects deposits without the whitelist token', async () => {
+    const whitelistMint = await mockUSDCMint(provider);
+    await vault_program.rpc.updateWhitelistMint(
+        whitelistMint.publicKey,
+        {
+          accounts: {
+            admin: provider.wallet.publicKey,
+            vaultState: vault_state,
+          },
+        },
+    );
+
+    // no whitelist token => rejected
+    const ix = vault_program.instruction.deposit(
+        new BN(1 * 10 ** 6),
+        authority_b,
+        {whitelistToken: false, deploy: null},
+        {accounts: deposit_accounts()},
+    );
+    let failed = false;
+    try {
+      await provider.send(new web3.Transaction().add(ix));
+    } catch (e) {
+      failed = true;
+    }
+    assert(failed);
+
+    // holds whitelist token => accepted
+    const whitelistToken = await mockUserUSDCAccount(whitelistMint, new BN(1), provider);
+    const user_vault_balance = await get_token_balance(user_vault_ata);
+    const whitelisted_ix = vault_program.instruction.deposit(
+        new BN(1 * 10 ** 6),
+        authority_b,
+        {whitelistToken: true, deploy: null},
+        {
+          accounts: deposit_accounts(),
+          remainingAccounts: [{
+            pubkey: whitelistToken.publicKey,
+            isWritable: false,
+            isSigner: false,
+          }],
+        },
+    );
+    await provider.send(new web3.Transaction().add(whitelisted_ix));
+    const user_vault_balance_end = await get_token_balance(user_vault_ata);
+    assert(user_vault_balance_end.gt(user_vault_balance));
+
+    // remove whitelist
+    await vault_program.rpc.updateWhitelistMint(
+        web3.PublicKey.default,
+        {
+          accounts: {
+            admin: provider.wallet.publicKey,
+            vaultState: vault_state,
+          },
+        },
+    );
+  });
+
   // helper fcns
   async function view_market_state() {
     const pythClient = new drift.PythClient(connection);
@@ -277,8 +476,8 @@ describe('drift_vault', () => {
     console.log('estimated funding:', estimated_funding.toString());
   }
 
-  async function update_twaps(oracle_increase, mark_increase) {
-    const market = clearingHouse.getMarket(marketIndex);
+  async function update_twaps(oracle_increase, mark_increase, market_index = marketIndex) {
+    const market = clearingHouse.getMarket(market_index);
     const solUsd = market.amm.oracle;
 
     // update oracle
@@ -291,7 +490,7 @@ describe('drift_vault', () => {
 
     // hacky hacky hack hack
     await CH_program.rpc.updateTwaps(
-        marketIndex,
+        market_index,
         new_mark_price,
         new BN(new_oracle_price * 10 ** 10), // mark percision
         {
@@ -318,19 +517,7 @@ describe('drift_vault', () => {
         marketIndex,
         authority_b,
         {
-          accounts: {
-            authority: authority,
-            userPositions: user_positions,
-
-            state: clearingHouseStatePk,
-            user: user_account,
-            markets: clearingHouseState.markets,
-            tradeHistory: clearingHouseState.tradeHistory,
-            fundingPaymentHistory: clearingHouseState.fundingPaymentHistory,
-            fundingRateHistory: clearingHouseState.fundingRateHistory,
-            oracle: solUsd,
-            clearingHouseProgram: CH_program.programId,
-          },
+          accounts: update_position_accounts(solUsd),
         },
     );
     const tx = new web3.Transaction().add(ix);
@@ -362,19 +549,7 @@ describe('drift_vault', () => {
         marketIndex,
         authority_b,
         {
-          accounts: {
-            authority: authority,
-            userPositions: user_positions,
-
-            state: clearingHouseStatePk,
-            user: user_account,
-            markets: clearingHouseState.markets,
-            tradeHistory: clearingHouseState.tradeHistory,
-            fundingPaymentHistory: clearingHouseState.fundingPaymentHistory,
-            fundingRateHistory: clearingHouseState.fundingRateHistory,
-            oracle: solUsd,
-            clearingHouseProgram: CH_program.programId,
-          },
+          accounts: update_position_accounts(solUsd),
         },
     );
 
@@ -394,6 +569,623 @@ describe('drift_vault', () => {
     assert(position.baseAssetAmount.lt(drift.ZERO));
   });
 
+  it('keeps an idle buffer and pays small withdrawals from it', async () => {
+    const market = clearingHouse.getMarket(marketIndex);
+    const solUsd = market.amm.oracle;
+
+    // keep 10% idle
+    await vault_program.rpc.updateIdleBuffer(
+        new BN(10),
+        new BN(100),
+        {
+          accounts: {
+            admin: provider.wallet.publicKey,
+            vaultState: vault_state,
+          },
+        },
+    );
+
+    const ix = vault_program.instruction.updatePosition(
+        marketIndex,
+        authority_b,
+        {
+          accounts: update_position_accounts(solUsd),
+        },
+    );
+    await provider.send(new web3.Transaction().add(ix));
+
+    const idle_balance = await get_token_balance(vault_collateral);
+    assert(idle_balance.gt(drift.ZERO));
+
+    const userAccount = await CH_program.account.user.fetch(user_account);
+    const positions = await CH_program.account.userPositions.fetch(
+      userAccount.positions as web3.PublicKey,
+    );
+    const base_asset_amount = positions.positions[0].baseAssetAmount;
+
+    // 1% withdraw < 10% buffer => paid from the buffer
+    const user_vault_balance = await get_token_balance(user_vault_ata);
+    const withdraw_ix = vault_program.instruction.withdraw(
+        user_vault_balance.div(new BN(100)),
+        marketIndex,
+        authority_b,
+        {
+          accounts: {
+            owner: provider.wallet.publicKey,
+            userVaultAta: user_vault_ata,
+            lockedShares: locked_shares,
+            userCollateralAta: userUSDCAccount.publicKey,
+
+            vaultMint: vault_mint,
+            depositorState: depositor_state,
+
+            updatePosition: update_position_accounts(solUsd),
+          },
+        },
+    );
+    await provider.send(new web3.Transaction().add(withdraw_ix));
+
+    const idle_balance_end = await get_token_balance(vault_collateral);
+    assert(idle_balance_end.lt(idle_balance));
+
+    // position is untouched
+    const positions_end = await CH_program.account.userPositions.fetch(
+      userAccount.positions as web3.PublicKey,
+    );
+    assert(positions_end.positions[0].baseAssetAmount.eq(base_asset_amount));
+
+    // remove buffer
+    await vault_program.rpc.updateIdleBuffer(
+        drift.ZERO,
+        drift.ZERO,
+        {
+          accounts: {
+            admin: provider.wallet.publicKey,
+            vaultState: vault_state,
+          },
+        },
+    );
+  });
+
+  it('deposits and deploys the collateral in one instruction', async () => {
+    const market = clearingHouse.getMarket(marketIndex);
+    const solUsd = market.amm.oracle;
+
+    const userAccount = await CH_program.account.user.fetch(user_account);
+    const positions = await CH_program.account.userPositions.fetch(
+      userAccount.positions as web3.PublicKey,
+    );
+    const base_asset_amount = positions.positions[0].baseAssetAmount;
+
+    const ix = vault_program.instruction.deposit(
+        new BN(100 * 10 ** 6),
+        authority_b,
+        {whitelistToken: false, deploy: {marketIndex, maxSlippage: new BN(1000)}},
+        {
+          accounts: deposit_accounts(),
+          remainingAccounts: vault_program.instruction.updatePosition.accounts(
+              update_position_accounts(solUsd),
+          ),
+        },
+    );
+    await provider.send(new web3.Transaction().add(ix));
+
+    // position grew with the new collateral
+    const positions_end = await CH_program.account.userPositions.fetch(
+      userAccount.positions as web3.PublicKey,
+    );
+    assert(positions_end.positions[0].baseAssetAmount.abs().gt(base_asset_amount.abs()));
+  });
+
+  it('places and refreshes oracle offset limit orders', async () => {
+    const market = clearingHouse.getMarket(marketIndex);
+    const solUsd = market.amm.oracle;
+    const orderState = clearingHouse.getOrderStateAccount();
+
+    // maker mode: bid/ask $0.01 away from the oracle, orders go stale after 1s
+    await vault_program.rpc.updateExecutionMode(
+        true,
+        drift.MARK_PRICE_PRECISION.div(new BN(100)),
+        new BN(1),
+        {
+          accounts: {
+            admin: provider.wallet.publicKey,
+            vaultState: vault_state,
+          },
+        },
+    );
+
+    // market orders are rejected in maker mode
+    let failed = false;
+    try {
+      await vault_program.rpc.updatePosition(
+          marketIndex,
+          authority_b,
+          {
+            accounts: update_position_accounts(solUsd),
+          },
+      );
+    } catch (e) {
+      failed = true;
+    }
+    assert(failed);
+
+    const orders_accounts = {
+      updatePosition: update_position_accounts(solUsd),
+      orderState: clearingHouseState.orderState,
+      userOrders: user_orders,
+      orderHistory: orderState.orderHistory,
+    };
+
+    // deposit without deploying => collateral to trade
+    await vault_program.rpc.deposit(
+        new BN(100 * 10 ** 6),
+        authority_b,
+        {whitelistToken: false, deploy: null},
+        {
+          accounts: deposit_accounts(),
+          remainingAccounts: [{pubkey: solUsd, isWritable: false, isSigner: false}], // share pricing
+        },
+    );
+
+    const ix = vault_program.instruction.updatePositionWithOrders(
+        marketIndex,
+        authority_b,
+        {
+          accounts: orders_accounts,
+        },
+    );
+    await provider.send(new web3.Transaction().add(ix));
+
+    const userOrders = await CH_program.account.userOrders.fetch(user_orders);
+    const order = userOrders.orders.find((o) => 'open' in o.status);
+    assert(order != undefined);
+    assert(order.postOnly);
+    assert(!order.oraclePriceOffset.eq(drift.ZERO));
+
+    // crank replaces the stale order
+    await new Promise((r) => setTimeout(r, 2000));
+    const crank_ix = vault_program.instruction.refreshOrders(
+        marketIndex,
+        authority_b,
+        {
+          accounts: orders_accounts,
+        },
+    );
+    await provider.send(new web3.Transaction().add(crank_ix));
+
+    const userOrders_end = await CH_program.account.userOrders.fetch(user_orders);
+    const open_orders = userOrders_end.orders.filter((o) => 'open' in o.status);
+    assert(open_orders.length == 1);
+    assert(!open_orders[0].orderId.eq(order.orderId));
+
+    // back to market orders
+    await vault_program.rpc.updateExecutionMode(
+        false,
+        drift.ZERO,
+        drift.ZERO,
+        {
+          accounts: {
+            admin: provider.wallet.publicKey,
+            vaultState: vault_state,
+          },
+        },
+    );
+  });
+
+  it('places stop loss and take profit trigger orders', async () => {
+    const market = clearingHouse.getMarket(marketIndex);
+    const solUsd = market.amm.oracle;
+    const orderState = clearingHouse.getOrderStateAccount();
+    const orders_accounts = {
+      updatePosition: update_position_accounts(solUsd),
+      orderState: clearingHouseState.orderState,
+      userOrders: user_orders,
+      orderHistory: orderState.orderHistory,
+    };
+
+    // 10% stop loss / 10% take profit
+    await vault_program.rpc.updateTriggerOrders(
+        new BN(1),
+        new BN(10),
+        new BN(1),
+        new BN(10),
+        {
+          accounts: {
+            admin: provider.wallet.publicKey,
+            vaultState: vault_state,
+          },
+        },
+    );
+
+    const ix = vault_program.instruction.updatePositionWithOrders(
+        marketIndex,
+        authority_b,
+        {
+          accounts: orders_accounts,
+        },
+    );
+    await provider.send(new web3.Transaction().add(ix));
+
+    const userAccount = await CH_program.account.user.fetch(user_account);
+    const positions = await CH_program.account.userPositions.fetch(
+      userAccount.positions as web3.PublicKey,
+    );
+    const base_asset_amount = positions.positions[0].baseAssetAmount.abs();
+
+    const userOrders = await CH_program.account.userOrders.fetch(user_orders);
+    const trigger_orders = userOrders.orders.filter((o) =>
+      'open' in o.status && 'triggerMarket' in o.orderType,
+    );
+    assert(trigger_orders.length == 2);
+    trigger_orders.forEach((o) => {
+      assert(o.reduceOnly);
+      assert(o.baseAssetAmount.eq(base_asset_amount));
+    });
+
+    // nothing changed => crank is a no-op
+    const crank_ix = vault_program.instruction.refreshOrders(
+        marketIndex,
+        authority_b,
+        {
+          accounts: orders_accounts,
+        },
+    );
+    await provider.send(new web3.Transaction().add(crank_ix));
+    const userOrders_crank = await CH_program.account.userOrders.fetch(user_orders);
+    assert(userOrders_crank.orders.filter((o) => 'open' in o.status).length == 2);
+
+    // turn off + cancel the trigger orders
+    await vault_program.rpc.updateTriggerOrders(
+        drift.ZERO,
+        drift.ZERO,
+        drift.ZERO,
+        drift.ZERO,
+        {
+          accounts: {
+            admin: provider.wallet.publicKey,
+            vaultState: vault_state,
+          },
+        },
+    );
+    const cancel_ix = vault_program.instruction.updatePositionWithOrders(
+        marketIndex,
+        authority_b,
+        {
+          accounts: orders_accounts,
+        },
+    );
+    await provider.send(new web3.Transaction().add(cancel_ix));
+
+    const userOrders_end = await CH_program.account.userOrders.fetch(user_orders);
+    assert(userOrders_end.orders.filter((o) => 'open' in o.status).length == 0);
+  });
+
+  it('trades inside the funding window', async () => {
+    const market = clearingHouse.getMarket(marketIndex);
+    const solUsd = market.amm.oracle;
+
+    // funding period = 1s => next funding update is always within 60s
+    await vault_program.rpc.updateFundingWindow(
+        new BN(60),
+        {
+          accounts: {
+            admin: provider.wallet.publicKey,
+            vaultState: vault_state,
+          },
+        },
+    );
+
+    await vault_program.rpc.deposit(
+        new BN(100 * 10 ** 6),
+        authority_b,
+        {whitelistToken: false, deploy: null},
+        {
+          accounts: deposit_accounts(),
+          remainingAccounts: [{pubkey: solUsd, isWritable: false, isSigner: false}], // share pricing
+        },
+    );
+
+    const userAccount = await CH_program.account.user.fetch(user_account);
+    const positions = await CH_program.account.userPositions.fetch(
+      userAccount.positions as web3.PublicKey,
+    );
+    const base_asset_amount = positions.positions[0].baseAssetAmount;
+
+    const ix = vault_program.instruction.updatePosition(
+        marketIndex,
+        authority_b,
+        {
+          accounts: update_position_accounts(solUsd),
+        },
+    );
+    await provider.send(new web3.Transaction().add(ix));
+
+    const positions_end = await CH_program.account.userPositions.fetch(
+      userAccount.positions as web3.PublicKey,
+    );
+    assert(positions_end.positions[0].baseAssetAmount.abs().gt(base_asset_amount.abs()));
+
+    await vault_program.rpc.updateFundingWindow(
+        drift.ZERO,
+        {
+          accounts: {
+            admin: provider.wallet.publicKey,
+            vaultState: vault_state,
+          },
+        },
+    );
+  });
+
+  it('scales the position down with volatility sizing', async () => {
+    const market = clearingHouse.getMarket(marketIndex);
+    const solUsd = market.amm.oracle;
+
+    // floor = ceiling = 0.5x => half the 1:1 target whatever the volatility
+    await vault_program.rpc.updateVolatilitySizing(
+        new BN(5000),
+        new BN(5000),
+        drift.ZERO,
+        {
+          accounts: {
+            admin: provider.wallet.publicKey,
+            vaultState: vault_state,
+          },
+        },
+    );
+
+    const userAccount = await CH_program.account.user.fetch(user_account);
+    const positions = await CH_program.account.userPositions.fetch(
+      userAccount.positions as web3.PublicKey,
+    );
+    const base_asset_amount = positions.positions[0].baseAssetAmount;
+
+    const ix = vault_program.instruction.updatePosition(
+        marketIndex,
+        authority_b,
+        {
+          accounts: update_position_accounts(solUsd),
+        },
+    );
+    await provider.send(new web3.Transaction().add(ix));
+
+    const positions_end = await CH_program.account.userPositions.fetch(
+      userAccount.positions as web3.PublicKey,
+    );
+    assert(positions_end.positions[0].baseAssetAmount.abs().lt(base_asset_amount.abs()));
+
+    await vault_program.rpc.updateVolatilitySizing(
+        drift.ZERO,
+        drift.ZERO,
+        drift.ZERO,
+        {
+          accounts: {
+            admin: provider.wallet.publicKey,
+            vaultState: vault_state,
+          },
+        },
+    );
+  });
+
+  it('trades on the funding history signal', async () => {
+    const market = clearingHouse.getMarket(marketIndex);
+    const solUsd = market.amm.oracle;
+
+    // ewma of the last 24 funding rates (each record back = half the weight)
+    await vault_program.rpc.updateFundingHistory(
+        new BN(24),
+        new BN(5000),
+        drift.ZERO,
+        {
+          accounts: {
+            admin: provider.wallet.publicKey,
+            vaultState: vault_state,
+          },
+        },
+    );
+
+    const ix = vault_program.instruction.updatePosition(
+        marketIndex,
+        authority_b,
+        {
+          accounts: update_position_accounts(solUsd),
+        },
+    );
+    await provider.send(new web3.Transaction().add(ix));
+
+    const userAccount = await CH_program.account.user.fetch(user_account);
+    const positions = await CH_program.account.userPositions.fetch(
+      userAccount.positions as web3.PublicKey,
+    );
+    assert(!positions.positions[0].baseAssetAmount.eq(drift.ZERO));
+
+    await vault_program.rpc.updateFundingHistory(
+        drift.ZERO,
+        drift.ZERO,
+        drift.ZERO,
+        {
+          accounts: {
+            admin: provider.wallet.publicKey,
+            vaultState: vault_state,
+          },
+        },
+    );
+  });
+
+  it('refuses to trade on stale twaps', async () => {
+    const market = clearingHouse.getMarket(marketIndex);
+    const solUsd = market.amm.oracle;
+
+    await vault_program.rpc.updateTwapStaleness(
+        new BN(1),
+        {
+          accounts: {
+            admin: provider.wallet.publicKey,
+            vaultState: vault_state,
+          },
+        },
+    );
+    await new Promise((r) => setTimeout(r, 3000));
+
+    const ix = vault_program.instruction.updatePosition(
+        marketIndex,
+        authority_b,
+        {
+          accounts: update_position_accounts(solUsd),
+        },
+    );
+    let failed = false;
+    try {
+      await provider.send(new web3.Transaction().add(ix));
+    } catch (e) {
+      failed = true;
+    }
+    assert(failed);
+
+    await vault_program.rpc.updateTwapStaleness(
+        drift.ZERO,
+        {
+          accounts: {
+            admin: provider.wallet.publicKey,
+            vaultState: vault_state,
+          },
+        },
+    );
+  });
+
+  it('exits on exchange pause and freezes on funding pause', async () => {
+    const market = clearingHouse.getMarket(marketIndex);
+    const solUsd = market.amm.oracle;
+
+    const ix = vault_program.instruction.updatePosition(
+        marketIndex,
+        authority_b,
+        {
+          accounts: update_position_accounts(solUsd),
+        },
+    );
+
+    // exchange paused => rejected w/ ExchangePaused
+    await clearingHouse.updateExchangePaused(true);
+    let failed = false;
+    try {
+      await provider.send(new web3.Transaction().add(ix));
+    } catch (e) {
+      failed = true;
+      assert(e.toString().includes('custom program error: 0x1785'));
+    }
+    assert(failed);
+    await clearingHouse.updateExchangePaused(false);
+
+    // funding paused (default = freeze) => position is left as is
+    const userAccount = await CH_program.account.user.fetch(user_account);
+    const positions = await CH_program.account.userPositions.fetch(
+      userAccount.positions as web3.PublicKey,
+    );
+    const base_asset_amount = positions.positions[0].baseAssetAmount;
+
+    await clearingHouse.updateFundingPaused(true);
+    await provider.send(new web3.Transaction().add(ix));
+    await clearingHouse.updateFundingPaused(false);
+
+    const positions_end = await CH_program.account.userPositions.fetch(
+      userAccount.positions as web3.PublicKey,
+    );
+    assert(positions_end.positions[0].baseAssetAmount.eq(base_asset_amount));
+  });
+
+  it('rejects withdrawals of locked shares within the lockup period', async () => {
+    const market = clearingHouse.getMarket(marketIndex);
+    const solUsd = market.amm.oracle;
+
+    // 1 hour lockup, no early withdrawal fee => reject
+    await vault_program.rpc.updateLockup(
+        new BN(60 * 60),
+        drift.ZERO,
+        drift.ZERO,
+        {
+          accounts: {
+            admin: provider.wallet.publicKey,
+            vaultState: vault_state,
+          },
+        },
+    );
+
+    // shares minted within the lockup are held by the vault
+    const user_vault_balance = await get_token_balance(user_vault_ata);
+    await vault_program.rpc.deposit(
+        new BN(10 * 10 ** 6),
+        authority_b,
+        {whitelistToken: false, deploy: null},
+        {
+          accounts: deposit_accounts(),
+          remainingAccounts: [{pubkey: solUsd, isWritable: false, isSigner: false}], // share pricing
+        },
+    );
+    const locked_balance = await get_token_balance(locked_shares);
+    assert(locked_balance.gt(drift.ZERO));
+    assert((await get_token_balance(user_vault_ata)).eq(user_vault_balance));
+
+    const ix = vault_program.instruction.withdraw(
+        user_vault_balance.add(locked_balance),
+        marketIndex,
+        authority_b,
+        {
+          accounts: {
+            owner: provider.wallet.publicKey,
+            userVaultAta: user_vault_ata,
+            lockedShares: locked_shares,
+            userCollateralAta: userUSDCAccount.publicKey,
+
+            vaultMint: vault_mint,
+            depositorState: depositor_state,
+
+            updatePosition: update_position_accounts(solUsd),
+          },
+        },
+    );
+
+    let failed = false;
+    try {
+      await provider.send(new web3.Transaction().add(ix));
+    } catch (e) {
+      failed = true;
+    }
+    assert(failed);
+
+    const locked_balance_end = await get_token_balance(locked_shares);
+    assert(locked_balance_end.eq(locked_balance));
+
+    // remove lockup + release the shares
+    await vault_program.rpc.updateLockup(
+        drift.ZERO,
+        drift.ZERO,
+        drift.ZERO,
+        {
+          accounts: {
+            admin: provider.wallet.publicKey,
+            vaultState: vault_state,
+          },
+        },
+    );
+    await vault_program.rpc.unlockShares(
+        authority_b,
+        {
+          accounts: {
+            owner: provider.wallet.publicKey,
+            userVaultAta: user_vault_ata,
+            lockedShares: locked_shares,
+            vaultState: vault_state,
+            depositorState: depositor_state,
+            authority: authority,
+            tokenProgram: token.TOKEN_PROGRAM_ID,
+          },
+        },
+    );
+    const user_vault_balance_end = await get_token_balance(user_vault_ata);
+    assert(user_vault_balance_end.eq(user_vault_balance.add(locked_balance)));
+  });
+
   it('withdraws from the vault', async () => {
     const market = clearingHouse.getMarket(marketIndex);
     const solUsd = market.amm.oracle;
@@ -411,30 +1203,13 @@ describe('drift_vault', () => {
           accounts: {
             owner: provider.wallet.publicKey,
             userVaultAta: user_vault_ata,
+            lockedShares: locked_shares,
             userCollateralAta: userUSDCAccount.publicKey,
-            vaultCollateralAta: vault_collateral,
 
             vaultMint: vault_mint,
-            vaultState: vault_state,
+            depositorState: depositor_state,
 
-            collateralVault: clearingHouseState.collateralVault,
-            collateralVaultAuthority: clearingHouseState.collateralVaultAuthority,
-            depositHistory: clearingHouseState.depositHistory,
-            insuranceVault: clearingHouseState.insuranceVault,
-            insuranceVaultAuthority: clearingHouseState.insuranceVaultAuthority,
-            tokenProgram: token.TOKEN_PROGRAM_ID,
-            updatePosition: {
-              authority: authority,
-              userPositions: user_positions,
-              state: clearingHouseStatePk,
-              user: user_account,
-              markets: clearingHouseState.markets,
-              tradeHistory: clearingHouseState.tradeHistory,
-              fundingPaymentHistory: clearingHouseState.fundingPaymentHistory,
-              fundingRateHistory: clearingHouseState.fundingRateHistory,
-              oracle: solUsd,
-              clearingHouseProgram: CH_program.programId,
-            },
+            updatePosition: update_position_accounts(solUsd),
           },
         },
     );
@@ -474,15 +1249,18 @@ describe('drift_vault', () => {
     var ix = vault_program.instruction.deposit(
         deposit_amount,
         authority_b,
+        {whitelistToken: false, deploy: null},
         {
           accounts: {
             owner: provider.wallet.publicKey,
             userVaultAta: user_vault_ata,
+            lockedShares: locked_shares,
             userCollateralAta: userUSDCAccount.publicKey,
             vaultCollateralAta: vault_collateral,
 
             vaultMint: vault_mint,
             vaultState: vault_state,
+            depositorState: depositor_state,
 
             authority: authority,
             userPositions: user_positions,
@@ -497,6 +1275,7 @@ describe('drift_vault', () => {
             clearingHouseProgram: CH_program.programId,
             tokenProgram: token.TOKEN_PROGRAM_ID,
           },
+          remainingAccounts: [{pubkey: solUsd, isWritable: false, isSigner: false}], // share pricing
         },
     );
     var tx = new web3.Transaction().add(ix);
@@ -511,19 +1290,7 @@ describe('drift_vault', () => {
         marketIndex,
         authority_b,
         {
-          accounts: {
-            authority: authority,
-            userPositions: user_positions,
-
-            state: clearingHouseStatePk,
-            user: user_account,
-            markets: clearingHouseState.markets,
-            tradeHistory: clearingHouseState.tradeHistory,
-            fundingPaymentHistory: clearingHouseState.fundingPaymentHistory,
-            fundingRateHistory: clearingHouseState.fundingRateHistory,
-            oracle: solUsd,
-            clearingHouseProgram: CH_program.programId,
-          },
+          accounts: update_position_accounts(solUsd),
         },
     );
     var tx = new web3.Transaction().add(ix);
@@ -560,30 +1327,13 @@ describe('drift_vault', () => {
           accounts: {
             owner: provider.wallet.publicKey,
             userVaultAta: user_vault_ata,
+            lockedShares: locked_shares,
             userCollateralAta: userUSDCAccount.publicKey,
-            vaultCollateralAta: vault_collateral,
 
             vaultMint: vault_mint,
-            vaultState: vault_state,
+            depositorState: depositor_state,
 
-            collateralVault: clearingHouseState.collateralVault,
-            collateralVaultAuthority: clearingHouseState.collateralVaultAuthority,
-            depositHistory: clearingHouseState.depositHistory,
-            insuranceVault: clearingHouseState.insuranceVault,
-            insuranceVaultAuthority: clearingHouseState.insuranceVaultAuthority,
-            tokenProgram: token.TOKEN_PROGRAM_ID,
-            updatePosition: {
-              authority: authority,
-              userPositions: user_positions,
-              state: clearingHouseStatePk,
-              user: user_account,
-              markets: clearingHouseState.markets,
-              tradeHistory: clearingHouseState.tradeHistory,
-              fundingPaymentHistory: clearingHouseState.fundingPaymentHistory,
-              fundingRateHistory: clearingHouseState.fundingRateHistory,
-              oracle: solUsd,
-              clearingHouseProgram: CH_program.programId,
-            },
+            updatePosition: update_position_accounts(solUsd),
           },
         },
     );
@@ -598,4 +1348,72 @@ describe('drift_vault', () => {
     const user_usdc_balance_end = await get_token_balance(userUSDCAccount.publicKey);
     assert(user_usdc_balance_end.gt(user_usdc_balance));
   });
+
+  it('goes long / short the funding spread across two markets', async () => {
+    const market = clearingHouse.getMarket(marketIndex);
+    const solUsd = market.amm.oracle;
+
+    // second market
+    const marketIndex2 = new BN(1);
+    const solUsd2 = await mockOracle(usdcSolPrice, -6);
+    await clearingHouse.initializeMarket(
+        marketIndex2,
+        solUsd2,
+        ammInitialBaseAssetAmount,
+        ammInitialQuoteAssetAmount,
+        new BN(1),
+        drift.PEG_PRECISION,
+    );
+    await clearingHouse.fetchAccounts();
+
+    // market 0: mark > oracle => short, market 1: mark < oracle => long
+    await update_twaps(1, 1.01);
+    await update_twaps(1.01, 1, marketIndex2);
+
+    // market 1 moves 2x as much => half the notional
+    await vault_program.rpc.updateStrategy(
+        {fundingSpread: {}},
+        {
+          spreadMarketIndexes: [marketIndex, marketIndex2, drift.ZERO, drift.ZERO],
+          spreadMarketBetas: [new BN(10_000), new BN(20_000), drift.ZERO, drift.ZERO],
+        },
+        {
+          accounts: {
+            admin: provider.wallet.publicKey,
+            vaultState: vault_state,
+          },
+        },
+    );
+
+    await vault_program.rpc.deposit(
+        new BN(100 * 10 ** 6),
+        authority_b,
+        {whitelistToken: false, deploy: null},
+        {
+          accounts: deposit_accounts(),
+          remainingAccounts: [{pubkey: solUsd, isWritable: false, isSigner: false}], // share pricing
+        },
+    );
+
+    const ix = vault_program.instruction.updatePosition(
+        marketIndex,
+        authority_b,
+        {
+          accounts: update_position_accounts(solUsd),
+          remainingAccounts: [{pubkey: solUsd2, isWritable: false, isSigner: false}],
+        },
+    );
+    await provider.send(new web3.Transaction().add(ix));
+
+    const userAccount = await CH_program.account.user.fetch(user_account);
+    const positions = await CH_program.account.userPositions.fetch(
+      userAccount.positions as web3.PublicKey,
+    );
+    const short_leg = positions.positions.find((p) => p.marketIndex.eq(marketIndex));
+    const long_leg = positions.positions.find((p) => p.marketIndex.eq(marketIndex2));
+    assert(short_leg.baseAssetAmount.lt(drift.ZERO));
+    assert(long_leg.baseAssetAmount.gt(drift.ZERO));
+    // beta weighted => short leg ~2x the long leg
+    assert(short_leg.quoteAssetAmount.gt(long_leg.quoteAssetAmount));
+  });
 });